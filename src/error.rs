@@ -17,6 +17,7 @@ pub enum Error {
     BitcoinEncode(bitcoin::consensus::encode::Error),
     Blind(String),
     ConfidentialTx(elements::ConfidentialTxOutError),
+    ConfidentialProof(String),
     BIP32(bitcoin::bip32::Error),
     BIP39(bip39::Error),
     Hash(bitcoin::hashes::FromSliceError),
@@ -25,6 +26,17 @@ pub enum Error {
     WebSocket(tungstenite::Error),
     Taproot(String),
     Musig2(String),
+    LimitExceeded(String),
+    AddressRejected(String),
+    NonStandard(String),
+    DustOutput(String),
+    FeeOutOfRange(String),
+    /// A non-cooperative refund was attempted before the refund timelock expired.
+    LocktimeNotReached {
+        current: u32,
+        required: u32,
+    },
+    AmountMismatch(String),
     Generic(String),
 }
 
@@ -238,6 +250,43 @@ impl From<bitcoin::consensus::encode::Error> for Error {
     }
 }
 
+/// Names of all error variants, as returned by [`Error::name`].
+pub const ERROR_NAMES: &[&str] = &[
+    "Electrum",
+    "Hex",
+    "Protocol",
+    "Key",
+    "Address",
+    "Sighash",
+    "Elements-Sighash",
+    "Secp",
+    "HTTP",
+    "JSON",
+    "IO",
+    "Bolt11",
+    "LiquidEncode",
+    "BitcoinEncode",
+    "Blind",
+    "ConfidentialTx",
+    "ConfidentialProof",
+    "BIP32",
+    "BIP39",
+    "Hash",
+    "Locktime",
+    "Url",
+    "WebSocket",
+    "Taproot",
+    "Musig2",
+    "LimitExceeded",
+    "AddressRejected",
+    "NonStandard",
+    "DustOutput",
+    "FeeOutOfRange",
+    "LocktimeNotReached",
+    "AmountMismatch",
+    "Generic",
+];
+
 impl Error {
     // Returns the name of the enum variant as a string
     pub fn name(&self) -> String {
@@ -258,6 +307,7 @@ impl Error {
             Error::BitcoinEncode(_) => "BitcoinEncode",
             Error::Blind(_) => "Blind",
             Error::ConfidentialTx(_) => "ConfidentialTx",
+            Error::ConfidentialProof(_) => "ConfidentialProof",
             Error::BIP32(_) => "BIP32",
             Error::BIP39(_) => "BIP39",
             Error::Hash(_) => "Hash",
@@ -266,6 +316,13 @@ impl Error {
             Error::WebSocket(_) => "WebSocket",
             Error::Taproot(_) => "Taproot",
             Error::Musig2(_) => "Musig2",
+            Error::LimitExceeded(_) => "LimitExceeded",
+            Error::AddressRejected(_) => "AddressRejected",
+            Error::NonStandard(_) => "NonStandard",
+            Error::DustOutput(_) => "DustOutput",
+            Error::FeeOutOfRange(_) => "FeeOutOfRange",
+            Error::LocktimeNotReached { .. } => "LocktimeNotReached",
+            Error::AmountMismatch(_) => "AmountMismatch",
             Error::Generic(_) => "Generic",
         }
         .to_string()
@@ -290,6 +347,7 @@ impl Error {
             Error::BitcoinEncode(e) => e.to_string(),
             Error::Blind(e) => e.clone(),
             Error::ConfidentialTx(e) => e.to_string(),
+            Error::ConfidentialProof(e) => e.clone(),
             Error::BIP32(e) => e.to_string(),
             Error::BIP39(e) => e.to_string(),
             Error::Hash(e) => e.to_string(),
@@ -298,7 +356,29 @@ impl Error {
             Error::WebSocket(e) => e.to_string(),
             Error::Taproot(e) => e.clone(),
             Error::Musig2(e) => e.clone(),
+            Error::LimitExceeded(e) => e.clone(),
+            Error::AddressRejected(e) => e.clone(),
+            Error::NonStandard(e) => e.clone(),
+            Error::DustOutput(e) => e.clone(),
+            Error::FeeOutOfRange(e) => e.clone(),
+            Error::LocktimeNotReached { current, required } => format!(
+                "Refund locktime {} not reached, current height is {}",
+                required, current
+            ),
+            Error::AmountMismatch(e) => e.clone(),
             Error::Generic(e) => e.clone(),
         }
     }
+    /// Whether the operation that failed with this error may succeed when retried later,
+    /// e.g. after a connection problem or once a timelock expired.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Error::Electrum(_)
+                | Error::HTTP(_)
+                | Error::IO(_)
+                | Error::WebSocket(_)
+                | Error::LocktimeNotReached { .. }
+        )
+    }
 }
@@ -37,8 +37,9 @@ pub use elements::{
 pub use lightning_invoice::Bolt11Invoice;
 
 pub use swaps::{
-    bitcoin::{BtcSwapScript, BtcSwapTx},
+    bitcoin::{BatchRefundTx, BtcSwapScript, BtcSwapTx, RefundLocktime},
     boltz,
     liquid::{LBtcSwapScript, LBtcSwapTx},
 };
+pub use util::capabilities::{capabilities, Capabilities};
 pub use util::fees;
@@ -0,0 +1,346 @@
+//! Redundant transaction broadcast.
+//!
+//! Time sensitive claims and refunds should not be delayed by a single flaky or censoring
+//! server. [`broadcast_all`] submits a signed transaction to every configured Electrum server
+//! and optionally the Boltz broadcast endpoint at the same time, and returns as soon as one
+//! of them accepts it.
+//!
+//! When several instances of an app handle the same swaps, [`broadcast_once`] records the intent
+//! to broadcast in a shared [`BroadcastIntentStore`] first. Only the first instance broadcasts,
+//! the others adopt its txid instead of broadcasting a conflicting transaction. Intents are
+//! leased, so another instance takes over if the first one dies before broadcasting.
+
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use bitcoin::hex::DisplayHex;
+use electrum_client::ElectrumApi;
+
+use crate::error::Error;
+use crate::swaps::boltz::{BoltzApiClientV2, SwapTxKind};
+use crate::util::unix_now;
+
+use super::electrum::ElectrumConfig;
+use super::Chain;
+
+/// Seconds a recorded [`BroadcastIntent`] blocks other instances. Once expired, the recording
+/// instance is assumed to have died before broadcasting and another instance may take over.
+pub const BROADCAST_INTENT_LEASE_SECS: u64 = 600;
+
+type BroadcastJob = Box<dyn FnOnce() -> Result<String, Error> + Send>;
+
+/// Broadcast a serialized transaction to all Electrum servers of `network_config` (primary and
+/// fallbacks) and, if given, through Boltz. All backends are tried concurrently.
+///
+/// Returns the txid reported by the first backend accepting the transaction.
+/// Fails with the last error if every backend rejected it.
+pub fn broadcast_all(
+    raw_tx: &[u8],
+    network_config: &ElectrumConfig,
+    boltz_api: Option<(&BoltzApiClientV2, Chain)>,
+) -> Result<String, Error> {
+    let mut jobs: Vec<(String, BroadcastJob)> = vec![];
+    for (i, server) in network_config.servers().into_iter().enumerate() {
+        let raw_tx = raw_tx.to_vec();
+        jobs.push((
+            format!("electrum server {}", i),
+            Box::new(move || {
+                Ok(server
+                    .build_broadcast_client()?
+                    .transaction_broadcast_raw(&raw_tx)?
+                    .to_string())
+            }),
+        ));
+    }
+    if let Some((boltz_api, chain)) = boltz_api {
+        let boltz_api = boltz_api.clone();
+        let tx_hex = raw_tx.to_lower_hex_string();
+        jobs.push((
+            "Boltz".to_string(),
+            Box::new(move || boltz_broadcast(&boltz_api, chain, &tx_hex)),
+        ));
+    }
+    first_success(jobs)
+}
+
+/// Broadcast through the Boltz API and extract the txid from the reply.
+pub(crate) fn boltz_broadcast(
+    boltz_api: &BoltzApiClientV2,
+    chain: Chain,
+    tx_hex: &String,
+) -> Result<String, Error> {
+    let response = boltz_api.broadcast_tx(chain, tx_hex)?;
+    match response.as_object() {
+        None => Err(Error::Protocol("Invalid broadcast reply".to_string())),
+        Some(response_map) => match response_map.get("id") {
+            None => Err(Error::Protocol(
+                "No txid found in broadcast reply".to_string(),
+            )),
+            Some(txid_val) => match txid_val.as_str() {
+                None => Err(Error::Protocol("Returned txid is not a string".to_string())),
+                Some(txid_str) => Ok(txid_str.to_string()),
+            },
+        },
+    }
+}
+
+/// Intent of an app instance to broadcast the claim or refund of a swap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BroadcastIntent {
+    pub swap_id: String,
+    pub kind: SwapTxKind,
+    /// Identifier of the app instance, unique among the instances sharing a store.
+    pub instance_id: String,
+    /// Txid of the transaction about to be broadcast.
+    pub txid: String,
+    /// Unix time the intent was recorded at, in seconds.
+    pub recorded_at: u64,
+}
+
+impl BroadcastIntent {
+    /// Intent recorded right now.
+    pub fn new(
+        swap_id: &str,
+        kind: SwapTxKind,
+        instance_id: &str,
+        txid: &str,
+    ) -> Result<Self, Error> {
+        Ok(BroadcastIntent {
+            swap_id: swap_id.to_string(),
+            kind,
+            instance_id: instance_id.to_string(),
+            txid: txid.to_string(),
+            recorded_at: unix_now()?,
+        })
+    }
+
+    /// Whether the lease of the intent has expired at unix time `now`.
+    pub fn is_expired_at(&self, now: u64) -> bool {
+        now >= self.recorded_at.saturating_add(BROADCAST_INTENT_LEASE_SECS)
+    }
+
+    /// Whether `intent` may replace this intent in the store: it is a retry of the same
+    /// instance, e.g. with a replacement transaction, or this intent's lease expired.
+    pub fn is_replaced_by(&self, intent: &BroadcastIntent) -> bool {
+        self.instance_id == intent.instance_id || self.is_expired_at(intent.recorded_at)
+    }
+}
+
+/// Broadcast intents shared by all app instances, e.g. a table with a unique key on swap id and
+/// kind in the app's database.
+pub trait BroadcastIntentStore {
+    /// Record `intent`, unless an intent for the same swap and kind was recorded before that is
+    /// not replaced by it (see [`BroadcastIntent::is_replaced_by`]).
+    /// Returns the intent in the store: `intent` itself, or the earlier one.
+    /// Has to be atomic across instances.
+    fn record_intent(&self, intent: &BroadcastIntent) -> Result<BroadcastIntent, Error>;
+}
+
+/// Non persistent store, for instances sharing a process.
+#[derive(Debug, Default)]
+pub struct MemoryBroadcastIntentStore {
+    intents: Mutex<Vec<BroadcastIntent>>,
+}
+
+impl BroadcastIntentStore for MemoryBroadcastIntentStore {
+    fn record_intent(&self, intent: &BroadcastIntent) -> Result<BroadcastIntent, Error> {
+        let mut intents = self
+            .intents
+            .lock()
+            .map_err(|_| Error::Generic("Broadcast intent store lock poisoned".to_string()))?;
+        match intents
+            .iter_mut()
+            .find(|i| i.swap_id == intent.swap_id && i.kind == intent.kind)
+        {
+            Some(existing) if !existing.is_replaced_by(intent) => return Ok(existing.clone()),
+            Some(existing) => *existing = intent.clone(),
+            None => intents.push(intent.clone()),
+        }
+        Ok(intent.clone())
+    }
+}
+
+/// Result of [`broadcast_once`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BroadcastOutcome {
+    /// This instance broadcast the transaction, with the returned txid.
+    Broadcast(String),
+    /// Another instance recorded its intent first. Its txid should be tracked instead.
+    Adopted(BroadcastIntent),
+}
+
+/// Record `intent` in `store` and run `broadcast` only if no other instance holds an unexpired
+/// intent for the same swap and kind. An instance retrying its own intent, e.g. after a restart
+/// or with a replacement transaction, broadcasts again and updates the recorded txid.
+pub fn broadcast_once<F>(
+    store: &dyn BroadcastIntentStore,
+    intent: BroadcastIntent,
+    broadcast: F,
+) -> Result<BroadcastOutcome, Error>
+where
+    F: FnOnce() -> Result<String, Error>,
+{
+    let recorded = store.record_intent(&intent)?;
+    if recorded.instance_id != intent.instance_id {
+        log::info!(
+            "Instance {} intends to broadcast the {:?} of swap {} as {}, adopting it",
+            recorded.instance_id,
+            recorded.kind,
+            recorded.swap_id,
+            recorded.txid
+        );
+        return Ok(BroadcastOutcome::Adopted(recorded));
+    }
+    Ok(BroadcastOutcome::Broadcast(broadcast()?))
+}
+
+/// Run all jobs on their own thread and return the first successful result.
+/// Jobs still running after that are left to finish in the background.
+fn first_success(jobs: Vec<(String, BroadcastJob)>) -> Result<String, Error> {
+    let count = jobs.len();
+    let (sender, receiver) = mpsc::channel();
+    for (name, job) in jobs {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let result = job();
+            if let Err(e) = &result {
+                log::warn!("Broadcast via {} failed: {:?}", name, e);
+            }
+            let _ = sender.send(result);
+        });
+    }
+    drop(sender);
+
+    let mut last_error = Error::Protocol("No broadcast backend configured".to_string());
+    for _ in 0..count {
+        match receiver.recv() {
+            Ok(Ok(txid)) => return Ok(txid),
+            Ok(Err(e)) => last_error = e,
+            Err(_) => break,
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_first_success() {
+        let slow: BroadcastJob = Box::new(|| {
+            thread::sleep(Duration::from_secs(5));
+            Ok("slow".to_string())
+        });
+        let failing: BroadcastJob = Box::new(|| Err(Error::Protocol("rejected".to_string())));
+        let fast: BroadcastJob = Box::new(|| Ok("fast".to_string()));
+        let txid = first_success(vec![
+            ("slow".to_string(), slow),
+            ("failing".to_string(), failing),
+            ("fast".to_string(), fast),
+        ])
+        .unwrap();
+        assert_eq!(txid, "fast");
+
+        let failing: BroadcastJob = Box::new(|| Err(Error::Protocol("rejected".to_string())));
+        assert!(first_success(vec![("failing".to_string(), failing)]).is_err());
+        assert!(first_success(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_broadcast_once() {
+        let store = MemoryBroadcastIntentStore::default();
+        let intent = |instance_id: &str, txid: &str| BroadcastIntent {
+            swap_id: "abc".to_string(),
+            kind: SwapTxKind::Claim,
+            instance_id: instance_id.to_string(),
+            txid: txid.to_string(),
+            recorded_at: 1_000,
+        };
+
+        let first = broadcast_once(&store, intent("a", "tx1"), || Ok("tx1".to_string())).unwrap();
+        assert_eq!(first, BroadcastOutcome::Broadcast("tx1".to_string()));
+
+        let second = broadcast_once(&store, intent("b", "tx2"), || {
+            panic!("the losing instance must not broadcast")
+        })
+        .unwrap();
+        assert_eq!(second, BroadcastOutcome::Adopted(intent("a", "tx1")));
+
+        let retry = broadcast_once(&store, intent("a", "tx1"), || Ok("tx1".to_string())).unwrap();
+        assert_eq!(retry, BroadcastOutcome::Broadcast("tx1".to_string()));
+
+        let refund = BroadcastIntent {
+            kind: SwapTxKind::Refund,
+            ..intent("b", "tx3")
+        };
+        assert!(matches!(
+            broadcast_once(&store, refund, || Ok("tx3".to_string())).unwrap(),
+            BroadcastOutcome::Broadcast(_)
+        ));
+    }
+
+    #[test]
+    fn test_broadcast_once_takeover() {
+        let store = MemoryBroadcastIntentStore::default();
+        let intent = |instance_id: &str, txid: &str, recorded_at: u64| BroadcastIntent {
+            swap_id: "abc".to_string(),
+            kind: SwapTxKind::Claim,
+            instance_id: instance_id.to_string(),
+            txid: txid.to_string(),
+            recorded_at,
+        };
+
+        // Instance a dies between recording its intent and broadcasting
+        store.record_intent(&intent("a", "tx1", 1_000)).unwrap();
+        let adopted = broadcast_once(&store, intent("b", "tx2", 1_100), || {
+            panic!("the lease of a has not expired")
+        })
+        .unwrap();
+        assert_eq!(
+            adopted,
+            BroadcastOutcome::Adopted(intent("a", "tx1", 1_000))
+        );
+
+        let takeover_at = 1_000 + BROADCAST_INTENT_LEASE_SECS;
+        let takeover = broadcast_once(&store, intent("b", "tx2", takeover_at), || {
+            Ok("tx2".to_string())
+        })
+        .unwrap();
+        assert_eq!(takeover, BroadcastOutcome::Broadcast("tx2".to_string()));
+
+        // a comes back and has to adopt the broadcast of b
+        let late = broadcast_once(&store, intent("a", "tx1", takeover_at + 1), || {
+            panic!("b took over")
+        })
+        .unwrap();
+        assert_eq!(
+            late,
+            BroadcastOutcome::Adopted(intent("b", "tx2", takeover_at))
+        );
+    }
+
+    #[test]
+    fn test_broadcast_once_txid_change() {
+        let store = MemoryBroadcastIntentStore::default();
+        let intent = |instance_id: &str, txid: &str| BroadcastIntent {
+            swap_id: "abc".to_string(),
+            kind: SwapTxKind::Refund,
+            instance_id: instance_id.to_string(),
+            txid: txid.to_string(),
+            recorded_at: 1_000,
+        };
+
+        broadcast_once(&store, intent("a", "tx1"), || Ok("tx1".to_string())).unwrap();
+        // Fee bump of a: the replacement is broadcast and recorded
+        let bump = broadcast_once(&store, intent("a", "tx2"), || Ok("tx2".to_string())).unwrap();
+        assert_eq!(bump, BroadcastOutcome::Broadcast("tx2".to_string()));
+
+        let other = broadcast_once(&store, intent("b", "tx3"), || {
+            panic!("the losing instance must not broadcast")
+        })
+        .unwrap();
+        assert_eq!(other, BroadcastOutcome::Adopted(intent("a", "tx2")));
+    }
+}
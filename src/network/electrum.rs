@@ -1,8 +1,16 @@
 // use electrum_client::raw_client::RawClient;
 
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bitcoin::hashes::Hash;
+use electrum_client::ElectrumApi;
+
 use crate::error::Error;
 
-use super::Chain;
+use super::{liquid_chain_from_genesis, Chain, OperationTimeouts};
 
 pub const DEFAULT_TESTNET_NODE: &str = "electrum.blockstream.info:60002";
 pub const DEFAULT_MAINNET_NODE: &str = "wes.bullbitcoin.com:50002";
@@ -10,6 +18,19 @@ pub const DEFAULT_LIQUID_TESTNET_NODE: &str = "blockstream.info:465";
 pub const DEFAULT_LIQUID_MAINNET_NODE: &str = "blockstream.info:995";
 pub const DEFAULT_ELECTRUM_TIMEOUT: u8 = 10;
 
+/// Confirmation targets in blocks tried by [`ElectrumConfig::estimate_confirmation_time`]
+/// when the server has no fee histogram.
+const CONFIRMATION_TARGETS: &[usize] = &[1, 2, 3, 6, 12, 24, 48, 144, 504, 1008];
+
+/// Expected confirmation of a transaction at a given fee rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmationEstimate {
+    /// Number of blocks until confirmation.
+    pub blocks: u32,
+    /// Expected time until confirmation, from the chain's block interval.
+    pub time: Duration,
+}
+
 #[derive(Debug, Clone)]
 enum ElectrumUrl {
     Tls(String, bool), // the bool value indicates if the domain name should be validated
@@ -17,9 +38,16 @@ enum ElectrumUrl {
 }
 
 impl ElectrumUrl {
-    pub fn build_client(&self, timeout: u8) -> Result<electrum_client::Client, Error> {
+    pub fn build_client(
+        &self,
+        timeout: Duration,
+        proxy: Option<&str>,
+    ) -> Result<electrum_client::Client, Error> {
+        // electrum_client takes whole seconds, and applies them to connecting as well
+        let timeout = timeout.as_secs().clamp(1, u8::MAX as u64) as u8;
         let builder = electrum_client::ConfigBuilder::new();
         let builder = builder.timeout(Some(timeout));
+        let builder = builder.socks5(proxy.map(electrum_client::Socks5Config::new));
         let (url, builder) = match self {
             ElectrumUrl::Tls(url, validate) => {
                 (format!("ssl://{}", url), builder.validate_domain(*validate))
@@ -30,15 +58,121 @@ impl ElectrumUrl {
     }
 }
 
+/// Confirmation status of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxStatus {
+    /// Height of the block including the transaction, None while in the mempool.
+    pub block_height: Option<u32>,
+    /// Number of confirmations, 0 while in the mempool.
+    pub confirmations: u32,
+}
+
+/// Health and capabilities of an Electrum server, see [`ElectrumConfig::probe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeReport {
+    /// Chain detected from the genesis block. None for networks this crate does not support,
+    /// e.g. signet.
+    pub chain: Option<Chain>,
+    pub is_liquid: bool,
+    /// Hash of the genesis block of the server's chain.
+    pub genesis_hash: String,
+    pub tip_height: u32,
+    /// Round trip time of a ping.
+    pub latency: Duration,
+    pub server_version: String,
+}
+
+impl ProbeReport {
+    /// Whether the server is on the given chain.
+    pub fn is_chain(&self, chain: Chain) -> bool {
+        self.chain == Some(chain)
+    }
+}
+
+/// Whether an Electrum error is the server's response for an unknown transaction. Other
+/// protocol errors, e.g. internal server errors or rate limits, are real failures.
+fn is_tx_not_found(e: &electrum_client::Error) -> bool {
+    match e {
+        electrum_client::Error::Protocol(response) => {
+            let response = response.to_string().to_lowercase();
+            // electrs, and ElectrumX / Fulcrum passing on the bitcoind error
+            [
+                "not found",
+                "missing transaction",
+                "no such mempool or blockchain transaction",
+            ]
+            .iter()
+            .any(|message| response.contains(message))
+        }
+        _ => false,
+    }
+}
+
+/// Detect the chain from the raw genesis block header.
+/// Liquid regtest genesis blocks depend on the node config, so any unknown Liquid genesis is
+/// reported as regtest.
+/// [`ElectrumConfig::deadline_fee_rate`] with the fee estimates of `estimate_fee`.
+fn deadline_fee_rate_from(
+    chain: Chain,
+    blocks_remaining: u32,
+    estimate_fee: impl Fn(usize) -> Result<f64, Error>,
+) -> Result<f64, Error> {
+    let min_rate = match chain {
+        Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => {
+            crate::fees::BITCOIN_MIN_RELAY_FEE_RATE
+        }
+        Chain::Liquid | Chain::LiquidTestnet | Chain::LiquidRegtest => {
+            crate::fees::LIQUID_MIN_RELAY_FEE_RATE
+        }
+    };
+    let target = CONFIRMATION_TARGETS
+        .iter()
+        .rev()
+        .find(|target| **target as u32 <= blocks_remaining)
+        .ok_or(Error::Protocol(format!(
+            "Deadline of {} blocks has already passed",
+            blocks_remaining
+        )))?;
+    Ok(estimate_fee(*target)?.max(min_rate))
+}
+
+fn detect_chain(raw_header: &[u8]) -> Result<(bool, String, Option<Chain>), Error> {
+    if raw_header.len() == 80 {
+        let header: bitcoin::block::Header = bitcoin::consensus::deserialize(raw_header)?;
+        let hash = header.block_hash();
+        let chain = [
+            (bitcoin::Network::Bitcoin, Chain::Bitcoin),
+            (bitcoin::Network::Testnet, Chain::BitcoinTestnet),
+            (bitcoin::Network::Regtest, Chain::BitcoinRegtest),
+        ]
+        .into_iter()
+        .find(|(network, _)| bitcoin::constants::genesis_block(*network).block_hash() == hash)
+        .map(|(_, chain)| chain);
+        return Ok((false, hash.to_string(), chain));
+    }
+
+    let header: elements::BlockHeader = elements::encode::deserialize(raw_header)?;
+    let hash = header.block_hash().to_string();
+    let chain = liquid_chain_from_genesis(&hash);
+    Ok((true, hash, Some(chain)))
+}
+
 /// Electrum client configuration.
+///
+/// Clones share the cached connection returned by [`ElectrumConfig::client`].
 #[derive(Debug, Clone)]
 pub struct ElectrumConfig {
     network: Chain,
     url: ElectrumUrl,
-    timeout: u8,
+    /// Tried in order when the primary url can't be connected to.
+    fallbacks: Vec<ElectrumUrl>,
+    timeouts: OperationTimeouts,
+    proxy: Option<String>,
+    client: Arc<Mutex<Option<Arc<electrum_client::Client>>>>,
 }
 
 impl ElectrumConfig {
+    /// Default servers of the chain. Regtest chains need `regtest_url`, a local plaintext server.
     pub fn default(chain: Chain, regtest_url: Option<String>) -> Result<Self, Error> {
         if (chain == Chain::LiquidRegtest || chain == Chain::BitcoinRegtest)
             && regtest_url.is_none()
@@ -63,10 +197,10 @@ impl ElectrumConfig {
                 DEFAULT_ELECTRUM_TIMEOUT,
             )),
             Chain::BitcoinRegtest => Ok(ElectrumConfig::new(
-                Chain::BitcoinTestnet,
+                Chain::BitcoinRegtest,
                 &regtest_url.unwrap(),
-                true,
-                true,
+                false,
+                false,
                 DEFAULT_ELECTRUM_TIMEOUT,
             )),
             Chain::Liquid => Ok(ElectrumConfig::new(
@@ -84,10 +218,10 @@ impl ElectrumConfig {
                 DEFAULT_ELECTRUM_TIMEOUT,
             )),
             Chain::LiquidRegtest => Ok(ElectrumConfig::new(
-                Chain::BitcoinTestnet,
+                Chain::LiquidRegtest,
                 &regtest_url.unwrap(),
-                true,
-                true,
+                false,
+                false,
                 DEFAULT_ELECTRUM_TIMEOUT,
             )),
         }
@@ -123,18 +257,372 @@ impl ElectrumConfig {
             false => ElectrumUrl::Plaintext(electrum_url.into()),
         };
         ElectrumConfig {
-            timeout,
+            timeouts: OperationTimeouts {
+                read: Duration::from_secs(timeout as u64),
+                ..OperationTimeouts::default()
+            },
             network,
             url: electrum_url,
+            fallbacks: vec![],
+            proxy: None,
+            client: Arc::new(Mutex::new(None)),
         }
     }
+    /// Route connections through a SOCKS5 proxy, e.g. `socks5://127.0.0.1:9050` for Tor.
+    /// The `socks5://` scheme is optional.
+    pub fn with_proxy(mut self, proxy: &str) -> Self {
+        let proxy = proxy
+            .trim_start_matches("socks5h://")
+            .trim_start_matches("socks5://");
+        self.proxy = Some(proxy.to_string());
+        self.client = Arc::new(Mutex::new(None));
+        self
+    }
+    /// Add a fallback server, used when the primary and all previously added fallbacks
+    /// can't be connected to.
+    pub fn with_fallback(mut self, electrum_url: &str, tls: bool, validate_domain: bool) -> Self {
+        self.fallbacks.push(match tls {
+            true => ElectrumUrl::Tls(electrum_url.into(), validate_domain),
+            false => ElectrumUrl::Plaintext(electrum_url.into()),
+        });
+        self.client = Arc::new(Mutex::new(None));
+        self
+    }
+    /// Set the timeouts per operation class, replacing the single timeout given to `new`.
+    /// Electrum connections use `read`, or `broadcast` for the clients of
+    /// [`ElectrumConfig::build_broadcast_client`], as socket timeout including the connect.
+    pub fn with_timeouts(mut self, timeouts: OperationTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self.client = Arc::new(Mutex::new(None));
+        self
+    }
+    pub fn timeouts(&self) -> &OperationTimeouts {
+        &self.timeouts
+    }
+    /// Address of the SOCKS5 proxy, if any.
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
     // Get a copy of the network (Chain) field.
     pub fn network(&self) -> Chain {
         self.network
     }
-    /// Builds an electrum_client::Client which can be used to make calls to electrum api
+    /// Builds an electrum_client::Client which can be used to make calls to electrum api.
+    /// Falls back to the next configured server if a server can't be connected to.
     pub fn build_client(&self) -> Result<electrum_client::Client, Error> {
-        self.url.clone().build_client(self.timeout)
+        self.build_client_with_timeout(self.timeouts.read)
+    }
+    /// Like [`ElectrumConfig::build_client`], with the `broadcast` timeout.
+    pub fn build_broadcast_client(&self) -> Result<electrum_client::Client, Error> {
+        self.build_client_with_timeout(self.timeouts.broadcast)
+    }
+    fn build_client_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<electrum_client::Client, Error> {
+        let mut result = self.url.build_client(timeout, self.proxy.as_deref());
+        for fallback in &self.fallbacks {
+            match result {
+                Ok(_) => break,
+                Err(e) => {
+                    log::warn!(
+                        "Electrum server unreachable, trying {:?}: {:?}",
+                        fallback,
+                        e
+                    );
+                    result = fallback.build_client(timeout, self.proxy.as_deref());
+                }
+            }
+        }
+        result
+    }
+    /// One config per configured server (primary first, then fallbacks), without fallbacks.
+    /// Used to talk to every server independently, e.g. to broadcast redundantly.
+    pub fn servers(&self) -> Vec<ElectrumConfig> {
+        std::iter::once(&self.url)
+            .chain(self.fallbacks.iter())
+            .map(|url| ElectrumConfig {
+                network: self.network,
+                url: url.clone(),
+                fallbacks: vec![],
+                timeouts: self.timeouts,
+                proxy: self.proxy.clone(),
+                client: Arc::new(Mutex::new(None)),
+            })
+            .collect()
+    }
+    /// Shared client, connected on first use and reused by all later calls.
+    /// The connection is re-established by electrum_client itself if it drops.
+    pub fn client(&self) -> Result<Arc<electrum_client::Client>, Error> {
+        let mut cached = self
+            .client
+            .lock()
+            .map_err(|_| Error::Generic("Electrum client lock poisoned".to_string()))?;
+        if let Some(client) = cached.as_ref() {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(self.build_client()?);
+        *cached = Some(client.clone());
+        Ok(client)
+    }
+    /// Drop the shared client, so the next call to [`ElectrumConfig::client`] reconnects.
+    /// Call this after a failed request to fail over to another server.
+    pub fn reset_client(&self) {
+        if let Ok(mut cached) = self.client.lock() {
+            *cached = None;
+        }
+    }
+    /// Check that the primary server is reachable, and detect its chain, tip and latency.
+    /// Use this to validate user provided servers before running swaps against them.
+    /// Uses a dedicated connection, without fallbacks.
+    pub fn probe(&self) -> Result<ProbeReport, Error> {
+        let client = self
+            .url
+            .build_client(self.timeouts.read, self.proxy.as_deref())?;
+        let start = Instant::now();
+        client.ping()?;
+        let latency = start.elapsed();
+        let server_version = client.server_features()?.server_version;
+        let (is_liquid, genesis_hash, chain) = detect_chain(&client.block_header_raw(0)?)?;
+        let tip_height = client.block_headers_subscribe_raw()?.height as u32;
+        Ok(ProbeReport {
+            chain,
+            is_liquid,
+            genesis_hash,
+            tip_height,
+            latency,
+            server_version,
+        })
+    }
+    /// Height of the current chain tip.
+    /// Uses raw headers, so it works the same for Bitcoin and Liquid servers.
+    pub fn get_tip_height(&self) -> Result<u32, Error> {
+        let notification = self.client()?.block_headers_subscribe_raw()?;
+        Ok(notification.height as u32)
+    }
+    /// Estimated fee rate in sat/vbyte for confirmation within `target_blocks`.
+    /// Use the result with `Fee::Relative`.
+    pub fn estimate_fee(&self, target_blocks: usize) -> Result<f64, Error> {
+        // Electrum returns BTC/kvB, or -1 if the server has no estimate
+        let btc_per_kvb = self.client()?.estimate_fee(target_blocks)?;
+        if btc_per_kvb < 0.0 {
+            return Err(Error::Electrum(electrum_client::Error::Message(format!(
+                "No fee estimate available for {} blocks",
+                target_blocks
+            ))));
+        }
+        Ok(btc_per_kvb * 100_000.0)
+    }
+    /// Mempool fee histogram of the server, as `(fee_rate, vsize)` entries with fee rates in
+    /// sat/vbyte, highest fee rate first.
+    pub fn get_fee_histogram(&self) -> Result<Vec<(f64, u64)>, Error> {
+        let histogram = self.client()?.raw_call("mempool.get_fee_histogram", [])?;
+        Ok(serde_json::from_value(histogram)?)
+    }
+    /// Expected confirmation time of a transaction paying `fee_rate` sat/vbyte.
+    /// Uses the mempool fee histogram where the server provides one, otherwise the first
+    /// confirmation target whose fee estimate is at most `fee_rate`.
+    /// Fails if the fee rate is below the estimate of every target.
+    pub fn estimate_confirmation_time(&self, fee_rate: f64) -> Result<ConfirmationEstimate, Error> {
+        let blocks = match self.get_fee_histogram() {
+            Ok(histogram) if !histogram.is_empty() => {
+                crate::fees::blocks_from_fee_histogram(&histogram, fee_rate)
+            }
+            _ => {
+                let mut blocks = None;
+                for target in CONFIRMATION_TARGETS {
+                    if let Ok(estimate) = self.estimate_fee(*target) {
+                        if estimate <= fee_rate {
+                            blocks = Some(*target as u32);
+                            break;
+                        }
+                    }
+                }
+                blocks.ok_or(Error::Electrum(electrum_client::Error::Message(format!(
+                    "{} sat/vbyte is not expected to confirm within {} blocks",
+                    fee_rate,
+                    CONFIRMATION_TARGETS[CONFIRMATION_TARGETS.len() - 1]
+                ))))?
+            }
+        };
+        Ok(ConfirmationEstimate {
+            blocks,
+            time: self.network().block_interval() * blocks,
+        })
+    }
+    /// Cheapest fee rate in sat/vbyte expected to confirm within `blocks_remaining` blocks.
+    /// The estimate of the largest confirmation target not exceeding the deadline is used,
+    /// and never less than the minimum relay fee rate of the chain.
+    pub fn deadline_fee_rate(&self, blocks_remaining: u32) -> Result<f64, Error> {
+        deadline_fee_rate_from(self.network(), blocks_remaining, |target| {
+            self.estimate_fee(target)
+        })
+    }
+    /// Turn a [`Fee::Deadline`](crate::fees::Fee::Deadline) into a relative fee with
+    /// [`Self::deadline_fee_rate`]. Other fees are returned unchanged.
+    pub fn resolve_fee(&self, fee: crate::fees::Fee) -> Result<crate::fees::Fee, Error> {
+        match fee {
+            crate::fees::Fee::Deadline { blocks_remaining } => Ok(crate::fees::Fee::Relative(
+                self.deadline_fee_rate(blocks_remaining)?,
+            )),
+            fee => Ok(fee),
+        }
+    }
+    /// Fetch a Bitcoin transaction by txid.
+    pub fn get_bitcoin_tx(&self, txid: &bitcoin::Txid) -> Result<bitcoin::Transaction, Error> {
+        Ok(self.client()?.transaction_get(txid)?)
+    }
+    /// Fetch a Liquid transaction by txid.
+    pub fn get_liquid_tx(&self, txid: &elements::Txid) -> Result<elements::Transaction, Error> {
+        let txid = bitcoin::Txid::from_byte_array(txid.to_byte_array());
+        let raw_tx = self.client()?.transaction_get_raw(&txid)?;
+        Ok(elements::encode::deserialize(&raw_tx)?)
+    }
+    /// Confirmation status of a transaction, for both Bitcoin and Liquid txids.
+    /// Returns None if the server does not know the transaction.
+    pub fn get_tx_status(&self, txid: &str) -> Result<Option<TxStatus>, Error> {
+        let client = self.client()?;
+        let txid = bitcoin::Txid::from_str(txid)?;
+        let raw_tx = match client.transaction_get_raw(&txid) {
+            Ok(raw_tx) => raw_tx,
+            Err(e) if is_tx_not_found(&e) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        // Electrum indexes history by script, so look the tx up in the history of one of its outputs
+        let script_pubkey = match self.network {
+            Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => {
+                let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&raw_tx)?;
+                tx.output.first().map(|output| output.script_pubkey.clone())
+            }
+            Chain::Liquid | Chain::LiquidTestnet | Chain::LiquidRegtest => {
+                let tx: elements::Transaction = elements::encode::deserialize(&raw_tx)?;
+                tx.output
+                    .iter()
+                    .find(|output| !output.is_fee())
+                    .map(|output| bitcoin::ScriptBuf::from_bytes(output.script_pubkey.to_bytes()))
+            }
+        }
+        .ok_or(Error::Protocol("Transaction has no outputs".to_string()))?;
+
+        let history = client.script_get_history(&script_pubkey)?;
+        let entry = match history.iter().find(|h| h.tx_hash == txid) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if entry.height <= 0 {
+            return Ok(Some(TxStatus {
+                block_height: None,
+                confirmations: 0,
+            }));
+        }
+        let block_height = entry.height as u32;
+        let tip = client.block_headers_subscribe_raw()?.height as u32;
+        Ok(Some(TxStatus {
+            block_height: Some(block_height),
+            confirmations: tip.saturating_sub(block_height) + 1,
+        }))
+    }
+    /// Block until the transaction has at least `confirmations` confirmations, polling every
+    /// `poll_interval`. Fails once `timeout` has elapsed.
+    pub fn wait_for_confirmation(
+        &self,
+        txid: &str,
+        confirmations: u32,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<TxStatus, Error> {
+        let start = Instant::now();
+        loop {
+            match self.get_tx_status(txid)? {
+                Some(status) if status.confirmations >= confirmations => return Ok(status),
+                status => log::debug!("Waiting for confirmation of {}: {:?}", txid, status),
+            }
+            if start.elapsed() + poll_interval > timeout {
+                return Err(Error::Generic(format!(
+                    "Transaction {} not confirmed after {:?}",
+                    txid, timeout
+                )));
+            }
+            thread::sleep(poll_interval);
+        }
+    }
+    /// Subscribe to new headers on an existing client, returning the current tip height.
+    /// Use [`ElectrumConfig::pop_tip_height`] on the same client to receive later tips.
+    pub fn subscribe_headers(client: &electrum_client::Client) -> Result<u32, Error> {
+        Ok(client.block_headers_subscribe_raw()?.height as u32)
+    }
+    /// Latest tip height notified since the last call, if any. Requires [`ElectrumConfig::subscribe_headers`].
+    /// Call `client.ping()` first to process pending notifications.
+    pub fn pop_tip_height(client: &electrum_client::Client) -> Result<Option<u32>, Error> {
+        let mut height = None;
+        while let Some(notification) = client.block_headers_pop_raw()? {
+            height = Some(notification.height as u32);
+        }
+        Ok(height)
+    }
+    /// Subscribe to status changes of a script (`blockchain.scripthash.subscribe`), e.g. to react
+    /// to a lockup utxo appearing instead of polling `fetch_utxos`.
+    /// For Liquid, pass the script bytes with `bitcoin::Script::from_bytes(spk.as_bytes())`.
+    ///
+    /// Uses a dedicated connection. Returns the subscription and the current status, which is
+    /// None if the script has no history.
+    pub fn subscribe_script(
+        &self,
+        script: &bitcoin::Script,
+        poll_interval: Duration,
+    ) -> Result<(ScriptSubscription, Option<electrum_client::ScriptStatus>), Error> {
+        let client = self.build_client()?;
+        let status = client.script_subscribe(script)?;
+        Ok((
+            ScriptSubscription {
+                client,
+                script: script.to_owned(),
+                poll_interval,
+            },
+            status,
+        ))
+    }
+}
+
+/// Blocking stream of status changes of a subscribed script.
+/// Each item is the new status hash, which changes whenever a tx touching the script is seen
+/// or confirmed. Unsubscribes when dropped.
+pub struct ScriptSubscription {
+    client: electrum_client::Client,
+    script: bitcoin::ScriptBuf,
+    poll_interval: Duration,
+}
+
+impl ScriptSubscription {
+    /// Next status change, if one was notified since the last call. Does not block.
+    pub fn try_next(&self) -> Result<Option<electrum_client::ScriptStatus>, Error> {
+        // Notifications are only read from the socket while making a request
+        self.client.ping()?;
+        Ok(self.client.script_pop(&self.script)?)
+    }
+}
+
+impl Iterator for ScriptSubscription {
+    type Item = Result<electrum_client::ScriptStatus, Error>;
+
+    /// Blocks until the next status change.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.try_next() {
+                Ok(Some(status)) => return Some(Ok(status)),
+                Ok(None) => thread::sleep(self.poll_interval),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl Drop for ScriptSubscription {
+    fn drop(&mut self) {
+        if let Err(e) = self.client.script_unsubscribe(&self.script) {
+            log::debug!("Failed to unsubscribe script: {:?}", e);
+        }
     }
 }
 
@@ -142,7 +630,19 @@ impl ElectrumConfig {
 mod tests {
 
     use super::*;
-    use electrum_client::ElectrumApi;
+
+    #[test]
+    fn test_is_tx_not_found() {
+        let protocol = |message: &str| {
+            electrum_client::Error::Protocol(serde_json::json!({ "code": 2, "message": message }))
+        };
+        assert!(is_tx_not_found(&protocol("Transaction not found")));
+        assert!(is_tx_not_found(&protocol(
+            "daemon error: DaemonError({'code': -5, 'message': 'No such mempool or blockchain transaction. Use gettransaction for wallet transactions.'})"
+        )));
+        assert!(!is_tx_not_found(&protocol("excessive resource usage")));
+        assert!(!is_tx_not_found(&protocol("internal error")));
+    }
 
     #[test]
     fn test_electrum_default_clients() {
@@ -156,6 +656,121 @@ mod tests {
         assert!(electrum_client.ping().is_ok());
     }
 
+    #[test]
+    fn test_electrum_regtest_config() {
+        assert!(ElectrumConfig::default(Chain::BitcoinRegtest, None).is_err());
+        let network_config =
+            ElectrumConfig::default(Chain::BitcoinRegtest, Some("localhost:19001".to_string()))
+                .unwrap();
+        assert_eq!(network_config.network(), Chain::BitcoinRegtest);
+        let network_config =
+            ElectrumConfig::default(Chain::LiquidRegtest, Some("localhost:19002".to_string()))
+                .unwrap();
+        assert_eq!(network_config.network(), Chain::LiquidRegtest);
+    }
+
+    #[test]
+    fn test_electrum_proxy() {
+        let network_config = ElectrumConfig::default_bitcoin();
+        assert_eq!(network_config.proxy(), None);
+        let network_config = network_config.with_proxy("socks5://127.0.0.1:9050");
+        assert_eq!(network_config.proxy(), Some("127.0.0.1:9050"));
+    }
+
+    #[test]
+    fn test_electrum_timeouts() {
+        let network_config = ElectrumConfig::default_bitcoin();
+        assert_eq!(
+            network_config.timeouts().read,
+            Duration::from_secs(DEFAULT_ELECTRUM_TIMEOUT as u64)
+        );
+        let timeouts = OperationTimeouts {
+            broadcast: Duration::from_secs(3),
+            ..OperationTimeouts::default()
+        };
+        let network_config = network_config.with_timeouts(timeouts);
+        assert_eq!(network_config.timeouts(), &timeouts);
+        assert!(network_config
+            .servers()
+            .iter()
+            .all(|server| server.timeouts() == &timeouts));
+    }
+
+    #[test]
+    fn test_deadline_fee_rate() {
+        let estimate_fee = |target: usize| Ok(60.0 / target as f64);
+        assert_eq!(
+            deadline_fee_rate_from(Chain::Bitcoin, 1, estimate_fee).unwrap(),
+            60.0
+        );
+        // The largest target within the deadline
+        assert_eq!(
+            deadline_fee_rate_from(Chain::Bitcoin, 5, estimate_fee).unwrap(),
+            20.0
+        );
+        // Never below the minimum relay fee rate of the chain
+        assert_eq!(
+            deadline_fee_rate_from(Chain::Bitcoin, 200, estimate_fee).unwrap(),
+            crate::fees::BITCOIN_MIN_RELAY_FEE_RATE
+        );
+        assert_eq!(
+            deadline_fee_rate_from(Chain::Liquid, 200, estimate_fee).unwrap(),
+            60.0 / 144.0
+        );
+        assert!(matches!(
+            deadline_fee_rate_from(Chain::Bitcoin, 0, estimate_fee),
+            Err(Error::Protocol(_))
+        ));
+        assert!(
+            deadline_fee_rate_from(Chain::Bitcoin, 6, |_| Err(Error::Generic(
+                "no estimate".to_string()
+            )))
+            .is_err()
+        );
+
+        // Resolving other fees needs no server
+        let network_config = ElectrumConfig::default_bitcoin();
+        assert!(matches!(
+            network_config.resolve_fee(crate::fees::Fee::Absolute(1_000)),
+            Ok(crate::fees::Fee::Absolute(1_000))
+        ));
+        assert!(network_config
+            .resolve_fee(crate::fees::Fee::Deadline {
+                blocks_remaining: 0
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_detect_chain() {
+        let header = bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin).header;
+        let (is_liquid, hash, chain) =
+            detect_chain(&bitcoin::consensus::serialize(&header)).unwrap();
+        assert!(!is_liquid);
+        assert_eq!(
+            hash,
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(chain, Some(Chain::Bitcoin));
+
+        let header = bitcoin::constants::genesis_block(bitcoin::Network::Regtest).header;
+        let (_, _, chain) = detect_chain(&bitcoin::consensus::serialize(&header)).unwrap();
+        assert_eq!(chain, Some(Chain::BitcoinRegtest));
+
+        let header = bitcoin::constants::genesis_block(bitcoin::Network::Signet).header;
+        let (_, _, chain) = detect_chain(&bitcoin::consensus::serialize(&header)).unwrap();
+        assert_eq!(chain, None);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_probe() {
+        let report = ElectrumConfig::default_liquid().probe().unwrap();
+        assert!(report.is_liquid);
+        assert!(report.is_chain(Chain::LiquidTestnet));
+        assert!(report.tip_height > 0);
+    }
+
     #[test]
     #[ignore]
     fn test_blockstream_electrum() {
@@ -170,6 +785,80 @@ mod tests {
         let electrum_client = network_config.build_client().unwrap();
         assert!(electrum_client.ping().is_ok());
     }
+    #[test]
+    #[ignore]
+    fn test_electrum_fallback() {
+        let network_config = ElectrumConfig::new(
+            Chain::BitcoinTestnet,
+            "127.0.0.1:1",
+            false,
+            false,
+            DEFAULT_ELECTRUM_TIMEOUT,
+        )
+        .with_fallback(DEFAULT_TESTNET_NODE, true, true);
+        assert!(network_config.client().unwrap().ping().is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_client_reuse() {
+        let network_config = ElectrumConfig::default_bitcoin();
+        let client = network_config.client().unwrap();
+        assert!(Arc::ptr_eq(
+            &client,
+            &network_config.clone().client().unwrap()
+        ));
+        network_config.reset_client();
+        assert!(!Arc::ptr_eq(&client, &network_config.client().unwrap()));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_tip_height() {
+        let network_config = ElectrumConfig::default_bitcoin();
+        let height = network_config.get_tip_height().unwrap();
+        assert!(height > 2_500_000);
+
+        let client = network_config.build_client().unwrap();
+        assert!(ElectrumConfig::subscribe_headers(&client).unwrap() >= height);
+
+        let network_config = ElectrumConfig::default_liquid();
+        assert!(network_config.get_tip_height().unwrap() > 0);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_subscribe_script() {
+        let network_config = ElectrumConfig::default_bitcoin();
+        // Testnet faucet address, which always has history
+        let script = bitcoin::Address::from_str("tb1qerzrlxcfu24davlur5sqmgzzgsal6wusda40er")
+            .unwrap()
+            .assume_checked()
+            .script_pubkey();
+        let (subscription, status) = network_config
+            .subscribe_script(&script, Duration::from_secs(1))
+            .unwrap();
+        assert!(status.is_some());
+        assert!(subscription.try_next().is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_estimate_fee() {
+        let network_config = ElectrumConfig::default_bitcoin();
+        assert!(network_config.estimate_fee(6).unwrap() > 0.0);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_estimate_confirmation_time() {
+        let network_config = ElectrumConfig::default_bitcoin();
+        let fee_rate = network_config.estimate_fee(6).unwrap();
+        let estimate = network_config.estimate_confirmation_time(fee_rate).unwrap();
+        assert!(estimate.blocks > 0);
+        assert_eq!(estimate.time, Duration::from_secs(600) * estimate.blocks);
+    }
+
     #[test]
     #[ignore]
     fn test_raw_electrum_calls() {
@@ -1,6 +1,44 @@
+pub mod broadcast;
 pub mod electrum;
 
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Timeouts per class of network operation.
+///
+/// Broadcasts and cooperative signing are time sensitive and should fail fast, so the caller can
+/// retry or fall back to another backend, while history scans may legitimately take a while.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperationTimeouts {
+    /// Establishing a connection.
+    pub connect: Duration,
+    /// Regular requests, e.g. history, fee estimation and swap status.
+    pub read: Duration,
+    /// Transaction broadcasts.
+    pub broadcast: Duration,
+    /// Requests of Boltz partial signatures for cooperative claims and refunds.
+    pub cooperative_sign: Duration,
+    /// Time without any websocket message after which the connection is considered stale.
+    pub ws_idle: Duration,
+}
+
+impl Default for OperationTimeouts {
+    fn default() -> Self {
+        OperationTimeouts {
+            connect: Duration::from_secs(10),
+            read: Duration::from_secs(30),
+            broadcast: Duration::from_secs(10),
+            cooperative_sign: Duration::from_secs(10),
+            ws_idle: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Chain {
     Bitcoin,
     BitcoinTestnet,
@@ -9,3 +47,123 @@ pub enum Chain {
     LiquidTestnet,
     LiquidRegtest,
 }
+
+/// L-BTC policy asset id on Liquid mainnet.
+pub const LBTC_MAINNET_ASSET_ID: &str =
+    "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
+/// L-BTC policy asset id on Liquid testnet.
+pub const LBTC_TESTNET_ASSET_ID: &str =
+    "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49";
+/// L-BTC policy asset id of elementsd regtest nodes with the default chain parameters.
+pub const LBTC_REGTEST_ASSET_ID: &str =
+    "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+
+const LIQUID_MAINNET_GENESIS: &str =
+    "1466275836220db2944ca059a3a10ef6fd2ea684b0688d2c379296888a206003";
+const LIQUID_TESTNET_GENESIS: &str =
+    "a771da8e52ee6ad581ed1e9a99825e5b3b7992225534eaa2ae23244fe26ab1c1";
+
+/// Liquid chain of a genesis block hash.
+/// Regtest genesis blocks depend on the node config, so any unknown genesis is regtest.
+pub(crate) fn liquid_chain_from_genesis(genesis_hash: &str) -> Chain {
+    match genesis_hash {
+        LIQUID_MAINNET_GENESIS => Chain::Liquid,
+        LIQUID_TESTNET_GENESIS => Chain::LiquidTestnet,
+        _ => Chain::LiquidRegtest,
+    }
+}
+
+impl Chain {
+    /// L-BTC policy asset id, None for Bitcoin chains.
+    pub fn lbtc_asset_id(&self) -> Option<elements::AssetId> {
+        let asset_id = match self {
+            Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => return None,
+            Chain::Liquid => LBTC_MAINNET_ASSET_ID,
+            Chain::LiquidTestnet => LBTC_TESTNET_ASSET_ID,
+            Chain::LiquidRegtest => LBTC_REGTEST_ASSET_ID,
+        };
+        Some(elements::AssetId::from_str(asset_id).expect("valid asset id constant"))
+    }
+
+    /// Fail unless `asset` is the L-BTC policy asset of this chain.
+    pub fn check_lbtc_asset(&self, asset: elements::AssetId) -> Result<(), Error> {
+        match self.lbtc_asset_id() {
+            Some(lbtc) if lbtc == asset => Ok(()),
+            _ => Err(Error::Protocol(format!(
+                "Asset {} is not L-BTC on {:?}",
+                asset, self
+            ))),
+        }
+    }
+
+    /// Bitcoin network of the chain, for address derivation and validation.
+    pub fn bitcoin_network(&self) -> Result<bitcoin::Network, Error> {
+        match self {
+            Chain::Bitcoin => Ok(bitcoin::Network::Bitcoin),
+            Chain::BitcoinTestnet => Ok(bitcoin::Network::Testnet),
+            Chain::BitcoinRegtest => Ok(bitcoin::Network::Regtest),
+            _ => Err(Error::Protocol(
+                "Liquid chain used for Bitcoin operations".to_string(),
+            )),
+        }
+    }
+
+    /// Liquid address parameters of the chain.
+    pub fn liquid_address_params(&self) -> Result<&'static elements::AddressParams, Error> {
+        match self {
+            Chain::Liquid => Ok(&elements::AddressParams::LIQUID),
+            Chain::LiquidTestnet => Ok(&elements::AddressParams::LIQUID_TESTNET),
+            Chain::LiquidRegtest => Ok(&elements::AddressParams::ELEMENTS),
+            _ => Err(Error::Address(
+                "Cannot derive Liquid address for Bitcoin network".to_string(),
+            )),
+        }
+    }
+
+    /// Target time between blocks.
+    pub fn block_interval(&self) -> std::time::Duration {
+        match self {
+            Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => {
+                std::time::Duration::from_secs(600)
+            }
+            Chain::Liquid | Chain::LiquidTestnet | Chain::LiquidRegtest => {
+                std::time::Duration::from_secs(60)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lbtc_asset_ids() {
+        assert_eq!(Chain::Bitcoin.lbtc_asset_id(), None);
+        let lbtc = Chain::Liquid.lbtc_asset_id().unwrap();
+        assert_eq!(lbtc.to_string(), LBTC_MAINNET_ASSET_ID);
+        assert!(Chain::Liquid.check_lbtc_asset(lbtc).is_ok());
+        assert!(Chain::LiquidTestnet.check_lbtc_asset(lbtc).is_err());
+        assert!(Chain::Bitcoin.check_lbtc_asset(lbtc).is_err());
+
+        assert_eq!(
+            Chain::BitcoinRegtest.bitcoin_network().unwrap(),
+            bitcoin::Network::Regtest
+        );
+        assert!(Chain::LiquidRegtest.bitcoin_network().is_err());
+        assert_eq!(
+            Chain::LiquidRegtest.liquid_address_params().unwrap(),
+            &elements::AddressParams::ELEMENTS
+        );
+        assert!(Chain::BitcoinRegtest.liquid_address_params().is_err());
+
+        assert_eq!(
+            liquid_chain_from_genesis(LIQUID_TESTNET_GENESIS),
+            Chain::LiquidTestnet
+        );
+        assert_eq!(
+            liquid_chain_from_genesis(&"00".repeat(32)),
+            Chain::LiquidRegtest
+        );
+    }
+}
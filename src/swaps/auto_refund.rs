@@ -0,0 +1,454 @@
+//! Unattended non-cooperative refunds of Bitcoin swaps.
+//!
+//! [`auto_refund`] waits for the refund timelock of a swap, signs and broadcasts the refund, and
+//! keeps replacing it (RBF) at the next fee rate of a [`RefundSchedule`] until it confirms. It
+//! blocks the calling thread, so run it on a thread of its own and stop it with a
+//! [`CancellationToken`]. It gives up once the swap utxos were spent by another transaction,
+//! e.g. a claim of Boltz or a cooperative refund.
+
+use std::time::{Duration, Instant};
+
+use bitcoin::secp256k1::Keypair;
+use bitcoin::{OutPoint, Transaction, Txid};
+use electrum_client::ElectrumApi;
+
+use crate::error::Error;
+use crate::network::electrum::{ElectrumConfig, TxStatus};
+use crate::util::backoff::{sleep_unless_cancelled, CancellationToken};
+use crate::util::fees::{Fee, TxLimits};
+
+use super::bitcoin::{checked_refund_address, BtcSwapScript, BtcSwapTx};
+use super::boltz::{SwapTxKind, SwapType};
+use super::privacy::PrivacyPolicy;
+
+/// Fee escalation of [`auto_refund`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefundSchedule {
+    /// Fee rates in sat/vbyte, in increasing order. The refund is broadcast at the first rate,
+    /// then replaced at the next one every `bump_interval` until it confirms.
+    pub fee_rates: Vec<f64>,
+    /// Time to wait for a confirmation before bumping the fee.
+    pub bump_interval: Duration,
+    /// Interval between checks of the chain tip and the refund status.
+    pub poll_interval: Duration,
+    /// Confirmations after which the refund is done.
+    pub confirmations: u32,
+}
+
+impl Default for RefundSchedule {
+    fn default() -> Self {
+        RefundSchedule {
+            fee_rates: vec![2.0, 5.0, 10.0, 20.0, 50.0],
+            bump_interval: Duration::from_secs(30 * 60),
+            poll_interval: Duration::from_secs(30),
+            confirmations: 1,
+        }
+    }
+}
+
+impl RefundSchedule {
+    /// Fee rate after `bumps` fee bumps. Stays at the last rate once all were used.
+    pub fn fee_rate(&self, bumps: usize) -> Option<f64> {
+        let last = self.fee_rates.len().checked_sub(1)?;
+        Some(self.fee_rates[bumps.min(last)])
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.fee_rates.is_empty() {
+            return Err(Error::Protocol(
+                "Refund schedule without fee rates".to_string(),
+            ));
+        }
+        if self.fee_rates.windows(2).any(|rates| rates[1] <= rates[0]) {
+            return Err(Error::Protocol(
+                "Refund schedule fee rates have to increase".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Refund `swap_script` to `refund_address` without Boltz, once its timelock expired, and bump
+/// the fee along `schedule` until the refund has the scheduled confirmations.
+/// Returns the txid of the confirmed refund, which may be any of the broadcast replacements.
+pub fn auto_refund(
+    swap_script: BtcSwapScript,
+    keys: &Keypair,
+    refund_address: &str,
+    network_config: &ElectrumConfig,
+    schedule: &RefundSchedule,
+    cancel: Option<&CancellationToken>,
+) -> Result<Txid, Error> {
+    schedule.validate()?;
+    if swap_script.swap_type == SwapType::ReverseSubmarine {
+        return Err(Error::Protocol(
+            "Refund Txs cannot be constructed for Reverse Submarine Swaps.".to_string(),
+        ));
+    }
+    let output_address = checked_refund_address(refund_address, network_config)?;
+
+    loop {
+        // Relative timelocks start with the lockup confirmation, so check again every round
+        match swap_script.check_refund_locktime(network_config) {
+            Ok(()) => break,
+            Err(Error::LocktimeNotReached { current, required }) => log::debug!(
+                "Waiting for refund locktime {}, current height is {}",
+                required,
+                current
+            ),
+            Err(e) if e.is_retryable() => {
+                log::warn!("Failed to check refund locktime: {}", e.message())
+            }
+            Err(e) => return Err(e),
+        }
+        sleep(schedule.poll_interval, cancel)?;
+    }
+
+    let utxos = swap_script.fetch_utxos(network_config)?;
+    if utxos.is_empty() {
+        return Err(Error::Protocol(
+            "No Bitcoin UTXO detected for this script".to_string(),
+        ));
+    }
+    let refund_tx = BtcSwapTx {
+        kind: SwapTxKind::Refund,
+        swap_script,
+        output_address,
+        utxos,
+        signal_rbf: true,
+        memo: None,
+        limits: TxLimits::default(),
+        refund_outputs: vec![],
+        claim_splits: vec![],
+        privacy: PrivacyPolicy::default(),
+        network_config: Some(network_config.clone()),
+        anchor: None,
+    };
+
+    let tx = refund_tx.sign_refund(keys, Fee::Relative(schedule.fee_rates[0]), None)?;
+    broadcast_until_confirmed(&refund_tx, tx, keys, network_config, schedule, cancel)
+}
+
+/// Chain access of the broadcast loop of [`auto_refund`].
+trait RefundChain {
+    fn broadcast(&self, tx: &Transaction) -> Result<(), Error>;
+    /// Status of `txid`, None if unknown.
+    fn tx_status(&self, txid: &Txid) -> Result<Option<TxStatus>, Error>;
+    /// Txids of the transactions in the history of `swap_script` spending any of `outpoints`.
+    fn spending_txids(
+        &self,
+        swap_script: &BtcSwapScript,
+        outpoints: &[OutPoint],
+    ) -> Result<Vec<Txid>, Error>;
+}
+
+impl RefundChain for ElectrumConfig {
+    fn broadcast(&self, tx: &Transaction) -> Result<(), Error> {
+        self.build_broadcast_client()?.transaction_broadcast(tx)?;
+        Ok(())
+    }
+
+    fn tx_status(&self, txid: &Txid) -> Result<Option<TxStatus>, Error> {
+        self.get_tx_status(&txid.to_string())
+    }
+
+    fn spending_txids(
+        &self,
+        swap_script: &BtcSwapScript,
+        outpoints: &[OutPoint],
+    ) -> Result<Vec<Txid>, Error> {
+        let electrum_client = self.client()?;
+        let spk = swap_script.to_address(self.network())?.script_pubkey();
+        let history = electrum_client.script_get_history(spk.as_script())?;
+        let txs = electrum_client
+            .batch_transaction_get(&history.iter().map(|h| h.tx_hash).collect::<Vec<_>>())?;
+        Ok(txs
+            .iter()
+            .filter(|tx| {
+                tx.input
+                    .iter()
+                    .any(|input| outpoints.contains(&input.previous_output))
+            })
+            .map(|tx| tx.compute_txid())
+            .collect())
+    }
+}
+
+/// Broadcast the signed refund `tx` of `refund_tx`, and replace it along `schedule` until one of
+/// the broadcast refunds has the scheduled confirmations.
+fn broadcast_until_confirmed(
+    refund_tx: &BtcSwapTx,
+    mut tx: Transaction,
+    keys: &Keypair,
+    chain: &impl RefundChain,
+    schedule: &RefundSchedule,
+    cancel: Option<&CancellationToken>,
+) -> Result<Txid, Error> {
+    let outpoints: Vec<OutPoint> = refund_tx
+        .utxos
+        .iter()
+        .map(|(outpoint, _)| *outpoint)
+        .collect();
+    let mut bumps = 0;
+    let mut broadcast = vec![tx.compute_txid()];
+    let mut bumped_at = Instant::now();
+    let mut missing_inputs = 0;
+    loop {
+        // Also rebroadcasts the latest refund, in case it was evicted from the mempool
+        match chain.broadcast(&tx) {
+            Ok(_) => missing_inputs = 0,
+            Err(e) => {
+                log::debug!(
+                    "Broadcast of refund {} failed: {}",
+                    tx.compute_txid(),
+                    e.message()
+                );
+                if is_missing_inputs(&e) {
+                    missing_inputs += 1;
+                }
+            }
+        }
+        sleep(schedule.poll_interval, cancel)?;
+
+        // An earlier refund may confirm if its replacement didn't propagate
+        let mut refund_mined = false;
+        for txid in &broadcast {
+            match chain.tx_status(txid) {
+                Ok(Some(status)) if status.confirmations >= schedule.confirmations => {
+                    log::info!("Refund {} confirmed", txid);
+                    return Ok(*txid);
+                }
+                Ok(Some(status)) => refund_mined |= status.confirmations > 0,
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to get status of refund {}: {}", txid, e.message()),
+            }
+        }
+
+        // Without one of our refunds in a block, check what spent the swap utxos. One of our
+        // refunds may have confirmed since its status was checked, or its status check failed,
+        // so only spends by other transactions end the refund.
+        if !refund_mined {
+            match chain.spending_txids(&refund_tx.swap_script, &outpoints) {
+                Ok(spenders) => {
+                    if let Some(spender) = spenders.iter().find(|txid| !broadcast.contains(txid)) {
+                        return Err(Error::Protocol(format!(
+                            "Swap utxos were spent by another transaction {}",
+                            spender
+                        )));
+                    }
+                }
+                Err(e) => log::warn!("Failed to fetch spends of swap utxos: {}", e.message()),
+            }
+            if missing_inputs >= MAX_MISSING_INPUTS {
+                return Err(Error::Protocol(format!(
+                    "Refund rejected {} times for missing or spent inputs",
+                    missing_inputs
+                )));
+            }
+        }
+
+        if bumps + 1 >= schedule.fee_rates.len() || bumped_at.elapsed() < schedule.bump_interval {
+            continue;
+        }
+        bumps += 1;
+        bumped_at = Instant::now();
+        let fee_rate = schedule.fee_rates[bumps];
+        match refund_tx.bump_fee(keys, &tx, Fee::Relative(fee_rate), None, None) {
+            Ok(replacement) => {
+                log::info!(
+                    "Replacing refund {} at {} sat/vbyte with {}",
+                    tx.compute_txid(),
+                    fee_rate,
+                    replacement.compute_txid()
+                );
+                tx = replacement;
+                broadcast.push(tx.compute_txid());
+            }
+            Err(e) => log::warn!(
+                "Failed to bump refund {} to {} sat/vbyte: {}",
+                tx.compute_txid(),
+                fee_rate,
+                e.message()
+            ),
+        }
+    }
+}
+
+/// Consecutive broadcasts rejected for missing or spent inputs after which the refund is given up.
+const MAX_MISSING_INPUTS: u32 = 3;
+
+/// Whether a broadcast was rejected because an input is missing or already spent, as reported by
+/// Bitcoin Core (`bad-txns-inputs-missingorspent`, `missing-inputs`).
+fn is_missing_inputs(e: &Error) -> bool {
+    let message = e.message();
+    message.contains("missingorspent") || message.contains("missing-inputs")
+}
+
+fn sleep(duration: Duration, cancel: Option<&CancellationToken>) -> Result<(), Error> {
+    if sleep_unless_cancelled(duration, cancel) {
+        Ok(())
+    } else {
+        Err(Error::Generic("Refund cancelled".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use bitcoin::hashes::{hash160, Hash};
+    use bitcoin::key::rand::thread_rng;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::{absolute::LockTime, Amount, PublicKey, TxOut};
+
+    use crate::network::Chain;
+
+    /// Chain reporting the broadcast refunds as spending the swap utxos, or else `other_spender`.
+    #[derive(Default)]
+    struct StubChain {
+        broadcast: Mutex<Vec<Txid>>,
+        /// Status requests failing before the broadcast refunds are reported confirmed.
+        failing_status: Mutex<u32>,
+        other_spender: Option<Txid>,
+    }
+
+    impl RefundChain for StubChain {
+        fn broadcast(&self, tx: &Transaction) -> Result<(), Error> {
+            self.broadcast.lock().unwrap().push(tx.compute_txid());
+            Ok(())
+        }
+
+        fn tx_status(&self, txid: &Txid) -> Result<Option<TxStatus>, Error> {
+            let mut failing_status = self.failing_status.lock().unwrap();
+            if *failing_status > 0 {
+                *failing_status -= 1;
+                return Err(Error::Generic("timeout".to_string()));
+            }
+            if self.other_spender.is_some() || !self.broadcast.lock().unwrap().contains(txid) {
+                return Ok(None);
+            }
+            Ok(Some(TxStatus {
+                block_height: Some(850_001),
+                confirmations: 1,
+            }))
+        }
+
+        fn spending_txids(
+            &self,
+            _swap_script: &BtcSwapScript,
+            _outpoints: &[OutPoint],
+        ) -> Result<Vec<Txid>, Error> {
+            Ok(match self.other_spender {
+                Some(txid) => vec![txid],
+                None => self.broadcast.lock().unwrap().clone(),
+            })
+        }
+    }
+
+    fn test_refund(keys: &Keypair) -> (BtcSwapTx, Transaction) {
+        let secp = Secp256k1::new();
+        let swap_script = BtcSwapScript {
+            swap_type: SwapType::Submarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+            locktime: LockTime::from_height(850_000).unwrap(),
+            sender_pubkey: PublicKey::new(keys.public_key()),
+            relative_timelock: None,
+        };
+        let address = swap_script.to_address(Chain::Bitcoin).unwrap();
+        let refund_tx = BtcSwapTx {
+            kind: SwapTxKind::Refund,
+            swap_script,
+            output_address: address.clone(),
+            utxos: vec![(
+                OutPoint::null(),
+                TxOut {
+                    value: Amount::from_sat(100_000),
+                    script_pubkey: address.script_pubkey(),
+                },
+            )],
+            signal_rbf: true,
+            memo: None,
+            limits: TxLimits::default(),
+            refund_outputs: vec![],
+            claim_splits: vec![],
+            privacy: PrivacyPolicy::default(),
+            network_config: None,
+            anchor: None,
+        };
+        let tx = refund_tx
+            .sign_refund(keys, Fee::Absolute(1_000), None)
+            .unwrap();
+        (refund_tx, tx)
+    }
+
+    #[test]
+    fn test_broadcast_until_confirmed() {
+        let keys = Keypair::new(&Secp256k1::new(), &mut thread_rng());
+        let (refund_tx, tx) = test_refund(&keys);
+        let txid = tx.compute_txid();
+        let schedule = RefundSchedule {
+            poll_interval: Duration::ZERO,
+            ..RefundSchedule::default()
+        };
+
+        let chain = StubChain::default();
+        let confirmed =
+            broadcast_until_confirmed(&refund_tx, tx.clone(), &keys, &chain, &schedule, None);
+        assert_eq!(confirmed.unwrap(), txid);
+
+        // Our refund spent the utxos, but its status is not known yet
+        let chain = StubChain {
+            failing_status: Mutex::new(1),
+            ..StubChain::default()
+        };
+        let confirmed =
+            broadcast_until_confirmed(&refund_tx, tx.clone(), &keys, &chain, &schedule, None);
+        assert_eq!(confirmed.unwrap(), txid);
+        assert_eq!(chain.broadcast.lock().unwrap().len(), 2);
+
+        let other_spender = Txid::from_byte_array([1; 32]);
+        let chain = StubChain {
+            other_spender: Some(other_spender),
+            ..StubChain::default()
+        };
+        let err =
+            broadcast_until_confirmed(&refund_tx, tx, &keys, &chain, &schedule, None).unwrap_err();
+        assert!(err.message().contains(&other_spender.to_string()));
+    }
+
+    #[test]
+    fn test_refund_schedule() {
+        let schedule = RefundSchedule::default();
+        assert!(schedule.validate().is_ok());
+        assert_eq!(schedule.fee_rate(0), Some(2.0));
+        assert_eq!(schedule.fee_rate(100), Some(50.0));
+
+        let schedule = RefundSchedule {
+            fee_rates: vec![],
+            ..RefundSchedule::default()
+        };
+        assert!(schedule.validate().is_err());
+        assert_eq!(schedule.fee_rate(0), None);
+
+        let schedule = RefundSchedule {
+            fee_rates: vec![5.0, 5.0],
+            ..RefundSchedule::default()
+        };
+        assert!(schedule.validate().is_err());
+    }
+
+    #[test]
+    fn test_is_missing_inputs() {
+        assert!(is_missing_inputs(&Error::Protocol(
+            "sendrawtransaction RPC error: {\"code\":-25,\"message\":\"bad-txns-inputs-missingorspent\"}"
+                .to_string()
+        )));
+        assert!(!is_missing_inputs(&Error::Protocol(
+            "insufficient fee, rejecting replacement".to_string()
+        )));
+    }
+}
@@ -0,0 +1,113 @@
+//! Per item outcomes of batch operations.
+//!
+//! Operations on many swaps, like claiming all pending swaps or assembling a batch refund, don't
+//! stop at the first failing swap. They return a [`BatchResult`] with the outcome of every item
+//! instead, and flag which failures are worth retrying, so a large recovery job can be resumed
+//! with just the items that are left.
+
+use crate::error::Error;
+
+/// Outcome of a single item of a batch.
+#[derive(Debug)]
+pub struct BatchItem<T> {
+    /// Id of the item, usually the swap id.
+    pub id: String,
+    pub result: Result<T, Error>,
+}
+
+impl<T> BatchItem<T> {
+    /// Whether the item failed with an error that may go away when retried.
+    pub fn is_retryable(&self) -> bool {
+        match &self.result {
+            Ok(_) => false,
+            Err(e) => e.is_retryable(),
+        }
+    }
+}
+
+/// Outcomes of all items of a batch, in the order they were processed.
+#[derive(Debug)]
+pub struct BatchResult<T> {
+    pub items: Vec<BatchItem<T>>,
+}
+
+impl<T> Default for BatchResult<T> {
+    fn default() -> Self {
+        BatchResult { items: vec![] }
+    }
+}
+
+impl<T> BatchResult<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the outcome of item `id`.
+    pub fn push(&mut self, id: impl Into<String>, result: Result<T, Error>) {
+        self.items.push(BatchItem {
+            id: id.into(),
+            result,
+        });
+    }
+
+    /// Ids and values of the items that succeeded.
+    pub fn succeeded(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.items.iter().filter_map(|item| match &item.result {
+            Ok(value) => Some((item.id.as_str(), value)),
+            Err(_) => None,
+        })
+    }
+
+    /// Ids and errors of the items that failed.
+    pub fn failed(&self) -> impl Iterator<Item = (&str, &Error)> {
+        self.items.iter().filter_map(|item| match &item.result {
+            Ok(_) => None,
+            Err(e) => Some((item.id.as_str(), e)),
+        })
+    }
+
+    /// Ids of the failed items that are safe to retry.
+    pub fn retryable_ids(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .filter(|item| item.is_retryable())
+            .map(|item| item.id.clone())
+            .collect()
+    }
+
+    /// Whether every item succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.items.iter().all(|item| item.result.is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_result() {
+        let mut batch = BatchResult::new();
+        assert!(batch.is_complete());
+
+        batch.push("a", Ok("txid".to_string()));
+        batch.push(
+            "b",
+            Err(Error::LocktimeNotReached {
+                current: 10,
+                required: 20,
+            }),
+        );
+        batch.push("c", Err(Error::Protocol("Invalid swap".to_string())));
+        assert!(!batch.is_complete());
+        assert_eq!(
+            batch.succeeded().collect::<Vec<_>>(),
+            vec![("a", &"txid".to_string())]
+        );
+        assert_eq!(
+            batch.failed().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec!["b", "c"]
+        );
+        assert_eq!(batch.retryable_ids(), vec!["b".to_string()]);
+    }
+}
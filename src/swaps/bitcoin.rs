@@ -1,48 +1,101 @@
+use bitcoin::bip32::KeySource;
 use bitcoin::consensus::{deserialize, Decodable};
 use bitcoin::hashes::Hash;
 use bitcoin::hex::{DisplayHex, FromHex};
-use bitcoin::key::rand::rngs::OsRng;
-use bitcoin::key::rand::{thread_rng, RngCore};
+use bitcoin::key::TapTweak;
+use bitcoin::psbt::Psbt;
 use bitcoin::script::{PushBytes, PushBytesBuf};
-use bitcoin::secp256k1::{All, Keypair, Message, Secp256k1, SecretKey};
+use bitcoin::secp256k1::{schnorr, All, Keypair, Message, Secp256k1, SecretKey};
 use bitcoin::sighash::Prevouts;
-use bitcoin::taproot::{LeafVersion, Signature, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::taproot::{ControlBlock, LeafVersion, Signature, TaprootBuilder, TaprootSpendInfo};
 use bitcoin::transaction::Version;
 use bitcoin::{
     blockdata::script::{Builder, Instruction, Script, ScriptBuf},
     opcodes::{all::*, OP_0},
     Address, OutPoint, PublicKey,
 };
-use bitcoin::{sighash::SighashCache, Network, Sequence, Transaction, TxIn, TxOut, Witness};
-use bitcoin::{Amount, EcdsaSighashType, TapLeafHash, TapSighashType, Txid, XOnlyPublicKey};
+use bitcoin::{
+    relative, Amount, CompressedPublicKey, EcdsaSighashType, TapLeafHash, TapSighashType, Txid,
+    XOnlyPublicKey,
+};
+use bitcoin::{sighash::SighashCache, Sequence, Transaction, TxIn, TxOut, Witness};
 use electrum_client::{ElectrumApi, GetHistoryRes};
 use elements::encode::serialize;
 use elements::pset::serialize::Serialize;
 use std::collections::HashMap;
 use std::ops::{Add, Index};
 use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::{
     error::Error,
-    network::{electrum::ElectrumConfig, Chain},
-    util::secrets::Preimage,
+    network::{broadcast::broadcast_all, electrum::ElectrumConfig, Chain},
+    util::{
+        descriptor::addr_descriptor,
+        entropy::SecureRng,
+        screening::{screen_address, AddressPurpose},
+        script_fingerprint,
+        secrets::Preimage,
+        standardness::StandardnessPolicy,
+    },
 };
 use crate::{LBtcSwapScript, LBtcSwapTx};
 
-use bitcoin::{blockdata::locktime::absolute::LockTime, hashes::hash160};
+use bitcoin::{
+    blockdata::locktime::absolute::LockTime,
+    hashes::{hash160, sha256},
+};
 
+use super::batch::BatchResult;
 use super::boltz::{
     BoltzApiClientV2, ChainClaimTxResponse, ChainSwapDetails, Cooperative, CreateChainResponse,
-    CreateReverseResponse, CreateSubmarineResponse, PartialSig, Side, SubmarineClaimTxResponse,
-    SwapTxKind, SwapType, ToSign,
+    CreateReverseResponse, CreateSubmarineResponse, PartialSig, Side, SpendPath,
+    SubmarineClaimTxResponse, SwapTree, SwapTxKind, SwapType, ToSign,
+};
+use super::crosscheck::check_boltz_lockup;
+
+use super::musig::{partial_sig_from_hex, pub_nonce_from_hex, SwapMusigContext};
+use super::output::{ScriptType, SwapOutput};
+use super::privacy::{split_non_round, PrivacyPolicy};
+use super::signer::{KeypairSigner, SwapSigner};
+use crate::util::fees::{
+    create_tx_with_fee, drain_output_value, min_replacement_fee, split_value, unresolved_deadline,
+    Fee, TxLimits, BITCOIN_MIN_RELAY_FEE_RATE, MAX_MEMO_SIZE,
 };
-
-use crate::util::fees::{create_tx_with_fee, Fee};
 use elements::secp256k1_zkp::{
     musig, MusigAggNonce, MusigKeyAggCache, MusigPartialSignature, MusigPubNonce, MusigSession,
-    MusigSessionId,
 };
 
+/// When a non-cooperative refund of a swap becomes valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefundLocktime {
+    /// Chain tip height from which the refund can be broadcast.
+    Absolute(u32),
+    /// Delay after the confirmation of the lockup (BIP68), for scripts with a
+    /// `relative_timelock`.
+    Relative(Sequence),
+}
+
+impl RefundLocktime {
+    /// Chain tip height from which the refund can be broadcast, given the confirmation heights
+    /// of the lockup utxos it spends. None for time based relative timelocks, or without
+    /// lockup heights for a relative timelock.
+    pub fn spendable_height(&self, lockup_heights: &[u32]) -> Option<u32> {
+        match self {
+            RefundLocktime::Absolute(height) => Some(*height),
+            RefundLocktime::Relative(sequence) => match sequence.to_relative_lock_time()? {
+                // The refund is valid in the block at lockup height + delay, so it can be
+                // broadcast one block earlier
+                relative::LockTime::Blocks(delay) => {
+                    Some(lockup_heights.iter().max()? + u32::from(delay.value()).max(1) - 1)
+                }
+                relative::LockTime::Time(_) => None,
+            },
+        }
+    }
+}
+
 /// Bitcoin v2 swap script helper.
 // TODO: This should encode the network at global level.
 #[derive(Debug, PartialEq, Clone)]
@@ -54,8 +107,12 @@ pub struct BtcSwapScript {
     // if we are using it just to recognize regtest, we should consider another strategy
     pub hashlock: hash160::Hash,
     pub receiver_pubkey: PublicKey,
+    /// Absolute refund timelock. Zero for scripts with a `relative_timelock`.
     pub locktime: LockTime,
     pub sender_pubkey: PublicKey,
+    /// Relative refund timelock, for refund leaves using OP_CHECKSEQUENCEVERIFY
+    /// instead of OP_CHECKLOCKTIMEVERIFY.
+    pub relative_timelock: Option<Sequence>,
 }
 
 impl BtcSwapScript {
@@ -107,12 +164,15 @@ impl BtcSwapScript {
         let hashlock =
             hashlock.ok_or_else(|| Error::Protocol("No hashlock provided".to_string()))?;
 
-        let timelock =
-            timelock.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?;
+        let relative_timelock = relative_refund_timelock(&refund_script);
+        let timelock = match relative_timelock {
+            Some(_) => LockTime::ZERO,
+            None => timelock.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?,
+        };
 
         let funding_addrs = Address::from_str(&create_swap_response.address)?.assume_checked();
 
-        Ok(BtcSwapScript {
+        let script = BtcSwapScript {
             swap_type: SwapType::Submarine,
             // swap_id: create_swap_response.id.clone(),
             side: None,
@@ -120,8 +180,12 @@ impl BtcSwapScript {
             hashlock,
             receiver_pubkey: create_swap_response.claim_public_key,
             locktime: timelock,
+            relative_timelock,
             sender_pubkey: our_pubkey,
-        })
+        };
+        script.check_swap_tree(&create_swap_response.swap_tree)?;
+
+        Ok(script)
     }
 
     pub fn musig_keyagg_cache(&self) -> MusigKeyAggCache {
@@ -138,6 +202,12 @@ impl BtcSwapScript {
         }
     }
 
+    /// MuSig2 context for cooperative key path spends of the swap output.
+    pub fn musig_context(&self) -> Result<SwapMusigContext, Error> {
+        let tweak = self.taproot_spendinfo()?.tap_tweak();
+        SwapMusigContext::new(self.musig_keyagg_cache(), *tweak.as_byte_array())
+    }
+
     /// Create the struct for a reverse swap from a boltz create response.
     pub fn reverse_from_swap_resp(
         reverse_response: &CreateReverseResponse,
@@ -185,12 +255,15 @@ impl BtcSwapScript {
         let hashlock =
             hashlock.ok_or_else(|| Error::Protocol("No hashlock provided".to_string()))?;
 
-        let timelock =
-            timelock.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?;
+        let relative_timelock = relative_refund_timelock(&refund_script);
+        let timelock = match relative_timelock {
+            Some(_) => LockTime::ZERO,
+            None => timelock.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?,
+        };
 
         let funding_addrs = Address::from_str(&reverse_response.lockup_address)?.assume_checked();
 
-        Ok(BtcSwapScript {
+        let script = BtcSwapScript {
             swap_type: SwapType::ReverseSubmarine,
             // swap_id: reverse_response.id.clone(),
             side: None,
@@ -198,8 +271,12 @@ impl BtcSwapScript {
             hashlock,
             receiver_pubkey: our_pubkey,
             locktime: timelock,
+            relative_timelock,
             sender_pubkey: reverse_response.refund_public_key,
-        })
+        };
+        script.check_swap_tree(&reverse_response.swap_tree)?;
+
+        Ok(script)
     }
 
     /// Create the struct for a chain swap from a boltz create response.
@@ -250,8 +327,11 @@ impl BtcSwapScript {
         let hashlock =
             hashlock.ok_or_else(|| Error::Protocol("No hashlock provided".to_string()))?;
 
-        let timelock =
-            timelock.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?;
+        let relative_timelock = relative_refund_timelock(&refund_script);
+        let timelock = match relative_timelock {
+            Some(_) => LockTime::ZERO,
+            None => timelock.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?,
+        };
 
         let funding_addrs = Address::from_str(&chain_swap_details.lockup_address)?.assume_checked();
 
@@ -260,7 +340,7 @@ impl BtcSwapScript {
             Side::Claim => (chain_swap_details.server_public_key, our_pubkey),
         };
 
-        Ok(BtcSwapScript {
+        let script = BtcSwapScript {
             swap_type: SwapType::Chain,
             // swap_id: reverse_response.id.clone(),
             side: Some(side),
@@ -268,8 +348,39 @@ impl BtcSwapScript {
             hashlock,
             receiver_pubkey,
             locktime: timelock,
+            relative_timelock,
             sender_pubkey,
-        })
+        };
+        script.check_swap_tree(&chain_swap_details.swap_tree)?;
+
+        Ok(script)
+    }
+
+    /// Check that the swap tree sent by Boltz is byte for byte the one rebuilt from the parsed
+    /// parameters. The constructors only pick the hashlock and timelock out of the leaves, so any
+    /// extra opcode changing the spending conditions has to be caught here.
+    fn check_swap_tree(&self, swap_tree: &SwapTree) -> Result<(), Error> {
+        let leaves = [
+            ("claim", &swap_tree.claim_leaf, self.claim_script()),
+            ("refund", &swap_tree.refund_leaf, self.refund_script()),
+        ];
+        for (name, leaf, expected) in leaves {
+            if leaf.version != LeafVersion::TapScript.to_consensus() {
+                return Err(Error::Protocol(format!(
+                    "Unexpected {} leaf version: {}",
+                    name, leaf.version
+                )));
+            }
+            if ScriptBuf::from_hex(&leaf.output)? != expected {
+                return Err(Error::Protocol(format!(
+                    "{} leaf does not match the swap parameters. Received: {}, Expected: {}",
+                    name,
+                    leaf.output,
+                    expected.to_hex_string()
+                )));
+            }
+        }
+        Ok(())
     }
 
     fn claim_script(&self) -> ScriptBuf {
@@ -297,12 +408,19 @@ impl BtcSwapScript {
 
     fn refund_script(&self) -> ScriptBuf {
         // Refund scripts are same for all swap types
-        Builder::new()
+        let builder = Builder::new()
             .push_x_only_key(&self.sender_pubkey.inner.x_only_public_key().0)
-            .push_opcode(OP_CHECKSIGVERIFY)
-            .push_lock_time(self.locktime)
-            .push_opcode(OP_CLTV)
-            .into_script()
+            .push_opcode(OP_CHECKSIGVERIFY);
+        match self.relative_timelock {
+            Some(sequence) => builder
+                .push_int(sequence.to_consensus_u32() as i64)
+                .push_opcode(OP_CSV)
+                .into_script(),
+            None => builder
+                .push_lock_time(self.locktime)
+                .push_opcode(OP_CLTV)
+                .into_script(),
+        }
     }
 
     /// Internally used to convert struct into a bitcoin::Script type
@@ -337,21 +455,7 @@ impl BtcSwapScript {
         // Which will be None only for regtest integration tests, so verification will be skipped for them.
         if let Some(funding_address) = &self.funding_addrs {
             let claim_key = taproot_spend_info.output_key();
-
-            let lockup_spk = funding_address.script_pubkey();
-
-            let pubkey_instruction = lockup_spk
-                .instructions()
-                .last()
-                .expect("should contain value")
-                .expect("should not fail");
-
-            let lockup_xonly_pubkey_bytes = pubkey_instruction
-                .push_bytes()
-                .expect("pubkey bytes expected");
-
-            let lockup_xonly_pubkey =
-                XOnlyPublicKey::from_slice(lockup_xonly_pubkey_bytes.as_bytes())?;
+            let lockup_xonly_pubkey = lockup_output_key(&funding_address.script_pubkey())?;
 
             if lockup_xonly_pubkey != claim_key.to_inner() {
                 return Err(Error::Protocol(format!(
@@ -366,23 +470,243 @@ impl BtcSwapScript {
         Ok(taproot_spend_info)
     }
 
+    /// Check that input `input_index` of a signed `tx` is a valid spend of this swap script,
+    /// without broadcasting it. `prevouts` are the outputs spent by all inputs of `tx`, in order.
+    ///
+    /// For script path spends, the leaf and control block must match this script, the preimage
+    /// must match the hashlock and the refund timelock must be satisfied. The signature is
+    /// checked against the aggregated key, the receiver key or the sender key respectively.
+    pub fn verify_spend(
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+        prevouts: &[TxOut],
+    ) -> Result<SpendPath, Error> {
+        let secp = Secp256k1::new();
+        let input = tx.input.get(input_index).ok_or(Error::Protocol(format!(
+            "No input at index {}",
+            input_index
+        )))?;
+        let output_key = self.taproot_spendinfo()?.output_key();
+        let witness: Vec<&[u8]> = input.witness.iter().collect();
+        let mut sighash_cache = SighashCache::new(tx);
+        let prevouts = Prevouts::All(prevouts);
+
+        let signature = Signature::from_slice(
+            witness
+                .first()
+                .ok_or(Error::Protocol("Empty witness".to_string()))?,
+        )
+        .map_err(|e| Error::Protocol(format!("Invalid signature encoding: {}", e)))?;
+
+        let (path, leaf, pubkey) = match witness.len() {
+            1 => {
+                let sighash = sighash_cache.taproot_key_spend_signature_hash(
+                    input_index,
+                    &prevouts,
+                    signature.sighash_type,
+                )?;
+                let msg = Message::from_digest_slice(sighash.as_byte_array())?;
+                secp.verify_schnorr(&signature.signature, &msg, &output_key.to_inner())
+                    .map_err(|e| Error::Protocol(format!("Invalid key path signature: {}", e)))?;
+                return Ok(SpendPath::KeyPath);
+            }
+            4 => {
+                let preimage = witness[1];
+                if preimage.len() != 32 || hash160::Hash::hash(preimage) != self.hashlock {
+                    return Err(Error::Protocol(
+                        "Preimage does not match the hashlock".to_string(),
+                    ));
+                }
+                (
+                    SpendPath::ClaimLeaf,
+                    self.claim_script(),
+                    self.receiver_pubkey,
+                )
+            }
+            3 => {
+                if let Some(required) = self.relative_timelock {
+                    let satisfied = match (
+                        required.to_relative_lock_time(),
+                        input.sequence.to_relative_lock_time(),
+                    ) {
+                        (Some(required), Some(actual)) => required.is_implied_by(actual),
+                        _ => false,
+                    };
+                    if !satisfied || tx.version < Version::TWO {
+                        return Err(Error::Protocol(format!(
+                            "Relative refund timelock {} not satisfied by input sequence {}",
+                            required, input.sequence
+                        )));
+                    }
+                } else if !self.locktime.is_implied_by(tx.lock_time)
+                    || !input.sequence.enables_absolute_lock_time()
+                {
+                    return Err(Error::Protocol(format!(
+                        "Refund locktime {} not satisfied by transaction locktime {}",
+                        self.locktime, tx.lock_time
+                    )));
+                }
+                (
+                    SpendPath::RefundLeaf,
+                    self.refund_script(),
+                    self.sender_pubkey,
+                )
+            }
+            n => {
+                return Err(Error::Protocol(format!(
+                    "Unexpected witness with {} elements",
+                    n
+                )))
+            }
+        };
+
+        let script = witness[witness.len() - 2];
+        if script != leaf.as_bytes() {
+            return Err(Error::Protocol(format!(
+                "Witness script does not match the {:?} script",
+                path
+            )));
+        }
+        let control_block = ControlBlock::decode(witness[witness.len() - 1])?;
+        if !control_block.verify_taproot_commitment(&secp, output_key.to_inner(), &leaf) {
+            return Err(Error::Protocol(
+                "Control block does not commit to the swap script".to_string(),
+            ));
+        }
+
+        let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::TapScript);
+        let sighash = sighash_cache.taproot_script_spend_signature_hash(
+            input_index,
+            &prevouts,
+            leaf_hash,
+            signature.sighash_type,
+        )?;
+        let msg = Message::from_digest_slice(sighash.as_byte_array())?;
+        secp.verify_schnorr(
+            &signature.signature,
+            &msg,
+            &pubkey.inner.x_only_public_key().0,
+        )
+        .map_err(|e| Error::Protocol(format!("Invalid {:?} signature: {}", path, e)))?;
+
+        Ok(path)
+    }
+
     /// Get taproot address for the swap script.
     pub fn to_address(&self, network: Chain) -> Result<Address, Error> {
         let spend_info = self.taproot_spendinfo()?;
         let output_key = spend_info.output_key();
 
-        let mut network = match network {
-            Chain::Bitcoin => Network::Bitcoin,
-            Chain::BitcoinRegtest => Network::Regtest,
-            Chain::BitcoinTestnet => Network::Testnet,
-            _ => {
-                return Err(Error::Protocol(
-                    "Liquid chain used for Bitcoin operations".to_string(),
-                ))
-            }
+        Ok(Address::p2tr_tweaked(
+            output_key,
+            network.bitcoin_network()?,
+        ))
+    }
+
+    /// Get the `addr()` output descriptor of the swap script, with checksum.
+    /// Can be imported into a Core watch-only wallet (e.g. for EPS) via `importdescriptors`.
+    pub fn to_descriptor(&self, network: Chain) -> Result<String, Error> {
+        addr_descriptor(&self.to_address(network)?.to_string())
+    }
+
+    /// Stable identifier of the swap script, usable as a deduplication key in persistence
+    /// and to match recovered scripts with Boltz records.
+    pub fn fingerprint(&self) -> sha256::Hash {
+        script_fingerprint(
+            self.swap_type,
+            self.hashlock.as_byte_array(),
+            &self.receiver_pubkey,
+            &self.sender_pubkey,
+            self.locktime.to_consensus_u32(),
+            self.relative_timelock
+                .map(|sequence| sequence.to_consensus_u32()),
+        )
+    }
+
+    /// Timelock of a non-cooperative refund. An absolute refund spends with the script locktime
+    /// as transaction locktime, so it is final in the block after the locktime height.
+    pub fn refund_spendable_at(&self) -> RefundLocktime {
+        match self.relative_timelock {
+            Some(sequence) => RefundLocktime::Relative(sequence),
+            None => RefundLocktime::Absolute(self.locktime.to_consensus_u32()),
+        }
+    }
+
+    /// Chain tip height from which a non-cooperative refund can be broadcast.
+    /// For a `relative_timelock` this depends on the confirmation of the lockup utxos. Unconfirmed
+    /// ones are assumed to confirm in the next block, so the height is a lower bound until then.
+    pub fn refund_spendable_height(&self, network_config: &ElectrumConfig) -> Result<u32, Error> {
+        if let RefundLocktime::Absolute(height) = self.refund_spendable_at() {
+            return Ok(height);
+        }
+        let current = network_config.get_tip_height()?;
+        let lockup_heights = self.fetch_lockup_heights(network_config, current)?;
+        self.refund_spendable_height_at(&lockup_heights)
+    }
+
+    /// Confirmation heights of the lockup utxos of the script. Unconfirmed ones are assumed to
+    /// confirm in the block after `current`.
+    fn fetch_lockup_heights(
+        &self,
+        network_config: &ElectrumConfig,
+        current: u32,
+    ) -> Result<Vec<u32>, Error> {
+        let electrum_client = network_config.client()?;
+        let spk = self.to_address(network_config.network())?.script_pubkey();
+        let history: Vec<_> = electrum_client.script_get_history(spk.as_script())?;
+        let txs = electrum_client
+            .batch_transaction_get(&history.iter().map(|h| h.tx_hash).collect::<Vec<_>>())?;
+        let utxos = Self::fetch_utxos_core(&txs, &history, &spk);
+        if utxos.is_empty() {
+            return Err(Error::Protocol(
+                "No Bitcoin UTXO detected for this script".to_string(),
+            ));
+        }
+
+        Ok(utxos
+            .iter()
+            .map(|(outpoint, _)| {
+                history
+                    .iter()
+                    .find(|h| h.tx_hash == outpoint.txid && h.height > 0)
+                    .map_or(current + 1, |h| h.height as u32)
+            })
+            .collect())
+    }
+
+    /// [`Self::refund_spendable_height`] for lockup utxos confirmed at `lockup_heights`.
+    fn refund_spendable_height_at(&self, lockup_heights: &[u32]) -> Result<u32, Error> {
+        self.refund_spendable_at()
+            .spendable_height(lockup_heights)
+            .ok_or(Error::Protocol(
+                "Time based relative refund timelocks are not supported".to_string(),
+            ))
+    }
+
+    /// Fail with [`Error::LocktimeNotReached`] if the chain tip is below
+    /// [`Self::refund_spendable_height`].
+    pub fn check_refund_locktime(&self, network_config: &ElectrumConfig) -> Result<(), Error> {
+        let current = network_config.get_tip_height()?;
+        let lockup_heights = match self.relative_timelock {
+            Some(_) => self.fetch_lockup_heights(network_config, current)?,
+            None => vec![],
         };
+        self.check_refund_locktime_at(current, &lockup_heights)
+    }
 
-        Ok(Address::p2tr_tweaked(output_key, network))
+    /// [`Self::check_refund_locktime`] at chain tip `current`, for lockup utxos confirmed at
+    /// `lockup_heights`. The heights are only used by a `relative_timelock`.
+    pub fn check_refund_locktime_at(
+        &self,
+        current: u32,
+        lockup_heights: &[u32],
+    ) -> Result<(), Error> {
+        let required = self.refund_spendable_height_at(lockup_heights)?;
+        if current < required {
+            return Err(Error::LocktimeNotReached { current, required });
+        }
+        Ok(())
     }
 
     pub fn validate_address(&self, chain: Chain, address: String) -> Result<(), Error> {
@@ -396,7 +720,7 @@ impl BtcSwapScript {
 
     /// Get the balance of the script
     pub fn get_balance(&self, network_config: &ElectrumConfig) -> Result<(u64, i64), Error> {
-        let electrum_client = network_config.build_client()?;
+        let electrum_client = network_config.client()?;
         let spk = self.to_address(network_config.network())?.script_pubkey();
         let script_balance = electrum_client.script_get_balance(spk.as_script())?;
         Ok((script_balance.confirmed, script_balance.unconfirmed))
@@ -407,7 +731,7 @@ impl BtcSwapScript {
         &self,
         network_config: &ElectrumConfig,
     ) -> Result<Vec<(OutPoint, TxOut)>, Error> {
-        let electrum_client = network_config.build_client()?;
+        let electrum_client = network_config.client()?;
         let spk = self.to_address(network_config.network())?.script_pubkey();
         let history: Vec<_> = electrum_client.script_get_history(spk.as_script())?;
 
@@ -417,6 +741,62 @@ impl BtcSwapScript {
         Ok(Self::fetch_utxos_core(&txs, &history, &spk))
     }
 
+    /// Poll until the script has at least one utxo known to the Electrum server, e.g. after Boltz
+    /// reported its lockup tx, and return the utxos. Fails once `timeout` has elapsed.
+    pub fn wait_for_utxos(
+        &self,
+        network_config: &ElectrumConfig,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<Vec<(OutPoint, TxOut)>, Error> {
+        let start = Instant::now();
+        loop {
+            let utxos = self.fetch_utxos(network_config)?;
+            if !utxos.is_empty() {
+                return Ok(utxos);
+            }
+            if start.elapsed() + poll_interval > timeout {
+                return Err(Error::Protocol(format!(
+                    "No utxo found for the swap script after {:?}",
+                    timeout
+                )));
+            }
+            thread::sleep(poll_interval);
+        }
+    }
+
+    /// Fetch (utxo,amount) pairs for many script_pubkeys, in two batched round trips.
+    /// Returns the utxos of each script, in the order of `scripts`.
+    pub fn get_scripts_utxos(
+        network_config: &ElectrumConfig,
+        scripts: &[ScriptBuf],
+    ) -> Result<Vec<Vec<(OutPoint, TxOut)>>, Error> {
+        let electrum_client = network_config.client()?;
+        let histories =
+            electrum_client.batch_script_get_history(scripts.iter().map(|s| s.as_script()))?;
+
+        let mut txids: Vec<Txid> = histories.iter().flatten().map(|h| h.tx_hash).collect();
+        txids.sort();
+        txids.dedup();
+        let txs: HashMap<Txid, Transaction> = electrum_client
+            .batch_transaction_get(&txids)?
+            .into_iter()
+            .map(|tx| (tx.compute_txid(), tx))
+            .collect();
+
+        Ok(scripts
+            .iter()
+            .zip(histories.iter())
+            .map(|(spk, history)| {
+                let script_txs: Vec<Transaction> = history
+                    .iter()
+                    .filter_map(|h| txs.get(&h.tx_hash).cloned())
+                    .collect();
+                Self::fetch_utxos_core(&script_txs, history, spk)
+            })
+            .collect())
+    }
+
     fn fetch_utxos_core(
         txs: &[Transaction],
         history: &[GetHistoryRes],
@@ -461,7 +841,8 @@ impl BtcSwapScript {
             .collect()
     }
 
-    /// Fetch utxo for script from BoltzApi
+    /// Fetch utxo for script from BoltzApi.
+    /// If the lockup tx pays the script more than once, the utxo is picked with [`select_claim_utxo`].
     pub fn fetch_lockup_utxo_boltz(
         &self,
         network_config: &ElectrumConfig,
@@ -469,6 +850,19 @@ impl BtcSwapScript {
         swap_id: &str,
         tx_kind: SwapTxKind,
     ) -> Result<Option<(OutPoint, TxOut)>, Error> {
+        let utxos = self.fetch_lockup_utxos_boltz(network_config, boltz_url, swap_id, tx_kind)?;
+        Ok(select_claim_utxo(&utxos))
+    }
+
+    /// Fetch all outputs of the lockup tx reported by BoltzApi that pay to the script.
+    /// Every output is cross-checked against chain data when the chain client is available.
+    pub fn fetch_lockup_utxos_boltz(
+        &self,
+        network_config: &ElectrumConfig,
+        boltz_url: &str,
+        swap_id: &str,
+        tx_kind: SwapTxKind,
+    ) -> Result<Vec<(OutPoint, TxOut)>, Error> {
         let boltz_client: BoltzApiClientV2 = BoltzApiClientV2::new(boltz_url);
         let hex = match self.swap_type {
             SwapType::Chain => match tx_kind {
@@ -503,16 +897,93 @@ impl BtcSwapScript {
         }
         let address = self.to_address(network_config.network())?;
         let tx: Transaction = bitcoin::consensus::deserialize(&hex::decode(hex.unwrap())?)?;
-        for (vout, output) in tx.clone().output.into_iter().enumerate() {
+        let txid = tx.compute_txid();
+        let chain_tx = network_config.get_bitcoin_tx(&txid);
+        let mut utxos = vec![];
+        for (vout, output) in tx.output.into_iter().enumerate() {
             if output.script_pubkey == address.script_pubkey() {
-                let outpoint_0 = OutPoint::new(tx.compute_txid(), vout as u32);
-                return Ok(Some((outpoint_0, output)));
+                let chain_output = match &chain_tx {
+                    Ok(chain_tx) => Ok(chain_tx.output.get(vout).cloned()),
+                    Err(e) => Err(Error::Generic(format!("{:?}", e))),
+                };
+                check_boltz_lockup(
+                    swap_id,
+                    &txid.to_string(),
+                    vout as u32,
+                    &output,
+                    chain_output,
+                )?;
+                utxos.push((OutPoint::new(txid, vout as u32), output));
             }
         }
-        Ok(None)
+        Ok(utxos)
+    }
+}
+
+/// Absolute fee paid by a transaction spending `prevouts`.
+fn tx_fee(tx: &Transaction, prevouts: &[(OutPoint, TxOut)]) -> Result<u64, Error> {
+    let input_value: u64 = prevouts.iter().map(|(_, txo)| txo.value.to_sat()).sum();
+    let output_value: u64 = tx.output.iter().map(|txo| txo.value.to_sat()).sum();
+    input_value.checked_sub(output_value).ok_or(Error::Protocol(
+        "Transaction spends more than its inputs".to_string(),
+    ))
+}
+
+/// Output key of a taproot lockup script. Errors instead of trusting the shape of the script,
+/// as the lockup address comes from Boltz.
+fn lockup_output_key(lockup_spk: &Script) -> Result<XOnlyPublicKey, Error> {
+    if !lockup_spk.is_p2tr() {
+        return Err(Error::Protocol(
+            "Lockup address is not a taproot address".to_string(),
+        ));
+    }
+    // OP_1 OP_PUSHBYTES_32 <output key>
+    XOnlyPublicKey::from_slice(&lockup_spk.as_bytes()[2..])
+        .map_err(|e| Error::Protocol(format!("Invalid lockup output key: {}", e)))
+}
+
+/// Parse and screen a refund address, checking it belongs to the chain of `network_config`.
+pub(crate) fn checked_refund_address(
+    refund_address: &str,
+    network_config: &ElectrumConfig,
+) -> Result<Address, Error> {
+    let network = network_config.network().bitcoin_network()?;
+    let address = Address::from_str(refund_address)?;
+    screen_address(refund_address, AddressPurpose::Refund)?;
+    if !address.is_valid_for_network(network) {
+        return Err(Error::Address("Address validation failed".to_string()));
+    };
+    Ok(address.assume_checked())
+}
+
+/// Relative timelock of a refund leaf ending in `<sequence> OP_CHECKSEQUENCEVERIFY`, if any.
+fn relative_refund_timelock(refund_script: &Script) -> Option<Sequence> {
+    let instructions: Vec<Instruction> = refund_script
+        .instructions()
+        .filter_map(Result::ok)
+        .collect();
+    match instructions.as_slice() {
+        // Small delays are pushed as OP_1..OP_16 instead of data
+        [.., delay, Instruction::Op(op)] if *op == OP_CSV => Some(Sequence::from_consensus(
+            u32::try_from(delay.script_num()?).ok()?,
+        )),
+        _ => None,
     }
 }
 
+/// Deterministic choice of the utxo to claim when a script was paid more than once:
+/// the largest output, ties broken by the lowest outpoint.
+pub fn select_claim_utxo(utxos: &[(OutPoint, TxOut)]) -> Option<(OutPoint, TxOut)> {
+    utxos
+        .iter()
+        .max_by(|(a_outpoint, a), (b_outpoint, b)| {
+            a.value
+                .cmp(&b.value)
+                .then_with(|| b_outpoint.cmp(a_outpoint))
+        })
+        .cloned()
+}
+
 pub fn bytes_to_u32_little_endian(bytes: &[u8]) -> u32 {
     let mut result = 0u32;
     for (i, &byte) in bytes.iter().enumerate() {
@@ -521,6 +992,44 @@ pub fn bytes_to_u32_little_endian(bytes: &[u8]) -> u32 {
     result
 }
 
+/// Pay-to-anchor output script, `OP_1 <0x4e73>`.
+const P2A_SCRIPT: [u8; 4] = [0x51, 0x02, 0x4e, 0x73];
+
+/// Small output added to claims and refunds, so their fee can later be bumped by any wallet
+/// spending it in a child transaction (CPFP). Useful after a cooperative spend, which can't be
+/// replaced without another signing round with Boltz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    /// Keyless pay-to-anchor output (P2A), spendable by anyone with an empty witness.
+    /// Relayed by Bitcoin Core 28 and later.
+    PayToAnchor,
+    /// Taproot output of the given key (BIP-86), only spendable by its owner.
+    Keyed(XOnlyPublicKey),
+}
+
+impl Anchor {
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        match self {
+            Anchor::PayToAnchor => ScriptBuf::from_bytes(P2A_SCRIPT.to_vec()),
+            Anchor::Keyed(key) => ScriptBuf::new_p2tr(&Secp256k1::new(), *key, None),
+        }
+    }
+
+    /// Value of the anchor output, the dust limit of its script:
+    /// 240 sats for P2A, 330 sats for keyed anchors.
+    pub fn value(&self) -> Amount {
+        self.script_pubkey().minimal_non_dust()
+    }
+
+    pub fn txout(&self) -> TxOut {
+        let script_pubkey = self.script_pubkey();
+        TxOut {
+            value: script_pubkey.minimal_non_dust(),
+            script_pubkey,
+        }
+    }
+}
+
 /// A structure representing either a Claim or a Refund Tx.
 /// This Tx spends from the HTLC.
 #[derive(Debug, Clone)]
@@ -532,10 +1041,36 @@ pub struct BtcSwapTx {
     /// - the initial lockup utxo, if not yet spent (claimed or refunded)
     /// - any further utxos, if not yet spent
     pub utxos: Vec<(OutPoint, TxOut)>,
+    /// Whether claim transactions signal replaceability (BIP-125).
+    /// Defaults to true, set to false if the receiver only credits non-replaceable zero-conf claims.
+    pub signal_rbf: bool,
+    /// Data added as an OP_RETURN output to claim and refund transactions, e.g. an order reference.
+    /// At most [`MAX_MEMO_SIZE`] bytes. The output is included in size and fee calculations.
+    pub memo: Option<Vec<u8>>,
+    /// Sanity limits checked before a signed claim or refund is returned.
+    pub limits: TxLimits,
+    /// Outputs of a fixed value paid by refunds, e.g. to route parts of a refund to different
+    /// wallets. What remains after these and the fee is drained to `output_address`.
+    pub refund_outputs: Vec<(Address, Amount)>,
+    /// Further claim destinations, with their share of the claimed value in percent,
+    /// e.g. 90% to cold storage. Each output pays its share of the fee,
+    /// the rest goes to `output_address`.
+    pub claim_splits: Vec<(Address, f64)>,
+    /// Privacy policy of claims. If the amount left for `output_address` after fees is round
+    /// and the policy avoids round amounts, it is split into several non-round outputs to
+    /// `output_address`, whose extra size is paid for by the fee.
+    pub privacy: PrivacyPolicy,
+    /// Used by non-cooperative refunds to check the refund locktime against the chain tip before
+    /// signing. Set by [`Self::new_refund`].
+    pub network_config: Option<ElectrumConfig>,
+    /// Anchor output added to claims and refunds. Its value is taken from the output to
+    /// `output_address`. Find it with [`Self::anchor_outpoint`] to spend it in a CPFP child.
+    pub anchor: Option<Anchor>,
 }
 
 impl BtcSwapTx {
     /// Craft a new ClaimTx. Only works for Reverse and Chain Swaps.
+    /// The claim spends all utxos of the HTLC, in case the lockup address was paid more than once.
     /// Returns None, if the HTLC utxo doesn't exist for the swap.
     pub fn new_claim(
         swap_script: BtcSwapScript,
@@ -550,36 +1085,85 @@ impl BtcSwapTx {
             ));
         }
 
-        let network = match network_config.network() {
-            Chain::Bitcoin => Network::Bitcoin,
-            Chain::BitcoinTestnet => Network::Testnet,
-            _ => Network::Regtest,
-        };
+        let network = network_config.network().bitcoin_network()?;
         let address = Address::from_str(&claim_address)?;
+        screen_address(&claim_address, AddressPurpose::Claim)?;
 
         address.is_valid_for_network(network);
 
-        let utxo_info = match swap_script.fetch_utxos(network_config) {
-            Ok(v) => v.first().cloned(),
-            Err(_) => swap_script.fetch_lockup_utxo_boltz(
+        let mut utxos = match swap_script.fetch_utxos(network_config) {
+            Ok(v) => v,
+            Err(_) => swap_script.fetch_lockup_utxos_boltz(
                 network_config,
                 &boltz_url,
                 &swap_id,
                 SwapTxKind::Claim,
             )?,
         };
-        if let Some(utxo) = utxo_info {
-            Ok(BtcSwapTx {
-                kind: SwapTxKind::Claim,
-                swap_script,
-                output_address: address.assume_checked(),
-                utxos: vec![utxo], // When claiming, we only consider the first utxo
-            })
-        } else {
-            Err(Error::Protocol(
+        // All utxos are claimed, the preferred one goes first
+        if let Some(selected) = select_claim_utxo(&utxos) {
+            utxos.retain(|(outpoint, _)| *outpoint != selected.0);
+            utxos.insert(0, selected);
+        }
+        if utxos.is_empty() {
+            return Err(Error::Protocol(
                 "No Bitcoin UTXO detected for this script".to_string(),
-            ))
+            ));
+        }
+
+        Ok(BtcSwapTx {
+            kind: SwapTxKind::Claim,
+            swap_script,
+            output_address: address.assume_checked(),
+            utxos,
+            signal_rbf: true,
+            memo: None,
+            limits: TxLimits::default(),
+            refund_outputs: vec![],
+            claim_splits: vec![],
+            privacy: PrivacyPolicy::default(),
+            network_config: None,
+            anchor: None,
+        })
+    }
+
+    /// Craft a new ClaimTx paying to several `outputs`, given as address and share of the claimed
+    /// value in percent. The shares have to sum to 100%, the fee is taken proportionally.
+    /// The first output becomes `output_address` and receives any rounding remainder.
+    pub fn new_claim_split(
+        swap_script: BtcSwapScript,
+        outputs: Vec<(String, f64)>,
+        network_config: &ElectrumConfig,
+        boltz_url: String,
+        swap_id: String,
+    ) -> Result<BtcSwapTx, Error> {
+        let total: f64 = outputs.iter().map(|(_, share)| share).sum();
+        if outputs.is_empty() || (total - 100.0).abs() > 1e-9 {
+            return Err(Error::Protocol(format!(
+                "Claim output shares have to sum to 100%, got {}%",
+                total
+            )));
+        }
+        let network = network_config.network().bitcoin_network()?;
+        let mut claim_splits = vec![];
+        for (address, share) in &outputs[1..] {
+            screen_address(address, AddressPurpose::Claim)?;
+            let address = Address::from_str(address)?;
+            if !address.is_valid_for_network(network) {
+                return Err(Error::Address("Address validation failed".to_string()));
+            }
+            claim_splits.push((address.assume_checked(), *share));
         }
+
+        let mut claim_tx = Self::new_claim(
+            swap_script,
+            outputs[0].0.clone(),
+            network_config,
+            boltz_url,
+            swap_id,
+        )?;
+        claim_tx.claim_splits = claim_splits;
+        Ok(claim_tx)
     }
 
     /// Construct a RefundTX corresponding to the swap_script. Only works for Submarine and Chain Swaps.
@@ -597,31 +1181,18 @@ impl BtcSwapTx {
             ));
         }
 
-        let network = match network_config.network() {
-            Chain::Bitcoin => Network::Bitcoin,
-            Chain::BitcoinTestnet => Network::Testnet,
-            _ => Network::Regtest,
-        };
-
-        let address = Address::from_str(refund_address)?;
-        if !address.is_valid_for_network(network) {
-            return Err(Error::Address("Address validation failed".to_string()));
-        };
+        let address = checked_refund_address(refund_address, network_config)?;
 
         let utxos = match swap_script.fetch_utxos(network_config) {
             Ok(r) => r,
             Err(_) => {
-                let lockup_utxo_info = swap_script.fetch_lockup_utxo_boltz(
+                // Refund every output paying to the script
+                swap_script.fetch_lockup_utxos_boltz(
                     network_config,
                     &boltz_url,
                     &swap_id,
                     SwapTxKind::Refund,
-                )?;
-
-                match lockup_utxo_info {
-                    Some(r) => vec![r],
-                    None => vec![],
-                }
+                )?
             }
         };
 
@@ -632,12 +1203,34 @@ impl BtcSwapTx {
             false => Ok(BtcSwapTx {
                 kind: SwapTxKind::Refund,
                 swap_script,
-                output_address: address.assume_checked(),
+                output_address: address,
                 utxos,
+                signal_rbf: true,
+                memo: None,
+                limits: TxLimits::default(),
+                refund_outputs: vec![],
+                claim_splits: vec![],
+                privacy: PrivacyPolicy::default(),
+                network_config: Some(network_config.clone()),
+                anchor: None,
             }),
         }
     }
 
+    /// Check that the swap utxos hold at least `expected_amount` sats in total, less `tolerance`.
+    /// Call before claiming a reverse or chain swap, so the preimage is not revealed for a
+    /// lockup short of the quoted amount.
+    pub fn verify_lockup(&self, expected_amount: u64, tolerance: u64) -> Result<(), Error> {
+        let locked: u64 = self.utxos.iter().map(|(_, txo)| txo.value.to_sat()).sum();
+        if locked + tolerance < expected_amount {
+            return Err(Error::AmountMismatch(format!(
+                "Lockup of {} sats is below the expected {} sats",
+                locked, expected_amount
+            )));
+        }
+        Ok(())
+    }
+
     /// Compute the Musig partial signature.
     /// This is used to cooperatively settle a Submarine or Chain Swap.
     pub fn partial_sign(
@@ -646,45 +1239,55 @@ impl BtcSwapTx {
         pub_nonce: &str,
         transaction_hash: &str,
     ) -> Result<(MusigPartialSignature, MusigPubNonce), Error> {
-        // Step 1: Start with a Musig KeyAgg Cache
-        let secp = Secp256k1::new();
-
-        let pubkeys = [
-            self.swap_script.receiver_pubkey.inner,
-            self.swap_script.sender_pubkey.inner,
-        ];
-
-        let mut key_agg_cache = self.swap_script.musig_keyagg_cache();
-
-        let tweak = SecretKey::from_slice(
-            self.swap_script
-                .taproot_spendinfo()?
-                .tap_tweak()
-                .as_byte_array(),
-        )?;
-
-        let _ = key_agg_cache.pubkey_xonly_tweak_add(&secp, tweak)?;
-
-        let session_id = MusigSessionId::new(&mut thread_rng());
+        self.partial_sign_with_signer(&KeypairSigner::new(*keys), pub_nonce, transaction_hash)
+    }
 
+    /// [`Self::partial_sign`] with an external signer.
+    pub fn partial_sign_with_signer(
+        &self,
+        signer: &dyn SwapSigner,
+        pub_nonce: &str,
+        transaction_hash: &str,
+    ) -> Result<(MusigPartialSignature, MusigPubNonce), Error> {
+        let musig = self.swap_script.musig_context()?;
         let msg = Message::from_digest_slice(&Vec::from_hex(transaction_hash)?)?;
 
-        // Step 4: Start the Musig2 Signing session
-        let mut extra_rand = [0u8; 32];
-        OsRng.fill_bytes(&mut extra_rand);
-
-        let (gen_sec_nonce, gen_pub_nonce) =
-            key_agg_cache.nonce_gen(&secp, session_id, keys.public_key(), msg, Some(extra_rand))?;
+        let gen_pub_nonce = musig.nonce(signer, msg)?;
+        let boltz_nonce = pub_nonce_from_hex(pub_nonce)?;
+        let musig_session = musig.session(gen_pub_nonce, boltz_nonce, msg);
+        let partial_sig = musig.partial_sign(signer, &musig_session, &gen_pub_nonce)?;
 
-        let boltz_nonce = MusigPubNonce::from_slice(&Vec::from_hex(pub_nonce)?)?;
+        Ok((partial_sig, gen_pub_nonce))
+    }
 
-        let agg_nonce = MusigAggNonce::new(&secp, &[boltz_nonce, gen_pub_nonce]);
+    /// Handle the `transaction.claim.pending` status of a Submarine Swap.
+    /// Fetches the claim details from Boltz, checks that the preimage matches the invoice
+    /// and posts our partial signature, so Boltz can claim the lockup cooperatively.
+    /// Returns the preimage as proof of payment.
+    pub fn cooperative_submarine_claim(
+        &self,
+        keys: &Keypair,
+        boltz_api: &BoltzApiClientV2,
+        swap_id: &String,
+        invoice: &str,
+    ) -> Result<Preimage, Error> {
+        if self.swap_script.swap_type != SwapType::Submarine {
+            return Err(Error::Protocol(
+                "Cooperative submarine claim called on a non-submarine swap".to_string(),
+            ));
+        }
 
-        let musig_session = MusigSession::new(&secp, &key_agg_cache, agg_nonce, msg);
+        let claim_tx_response = boltz_api.get_submarine_claim_tx_details(swap_id)?;
+        let preimage = claim_tx_response.validate_preimage(invoice)?;
 
-        let partial_sig = musig_session.partial_sign(&secp, gen_sec_nonce, keys, &key_agg_cache)?;
+        let (partial_sig, pub_nonce) = self.partial_sign(
+            keys,
+            &claim_tx_response.pub_nonce,
+            &claim_tx_response.transaction_hash,
+        )?;
+        boltz_api.post_submarine_claim_tx_details(swap_id, pub_nonce, partial_sig)?;
 
-        Ok((partial_sig, gen_pub_nonce))
+        Ok(preimage)
     }
 
     /// Sign a claim transaction.
@@ -697,6 +1300,17 @@ impl BtcSwapTx {
         preimage: &Preimage,
         fee: Fee,
         is_cooperative: Option<Cooperative>,
+    ) -> Result<Transaction, Error> {
+        self.sign_claim_with_signer(&KeypairSigner::new(*keys), preimage, fee, is_cooperative)
+    }
+
+    /// [`Self::sign_claim`] with an external signer.
+    pub fn sign_claim_with_signer(
+        &self,
+        signer: &dyn SwapSigner,
+        preimage: &Preimage,
+        fee: Fee,
+        is_cooperative: Option<Cooperative>,
     ) -> Result<Transaction, Error> {
         if self.swap_script.swap_type == SwapType::Submarine {
             return Err(Error::Protocol(
@@ -710,145 +1324,108 @@ impl BtcSwapTx {
             ));
         }
 
-        let mut claim_tx = create_tx_with_fee(
-            fee,
-            |fee| self.create_claim(keys, preimage, fee, is_cooperative.is_some()),
-            |tx| tx.vsize(),
-        )?;
+        if let Some(cooperative) = is_cooperative {
+            return self
+                .musig_session(signer, Some(preimage), fee)?
+                .resume(&cooperative);
+        }
+        self.screen_outputs()?;
 
-        // If it's a cooperative claim, compute the Musig2 Aggregate Signature and use Keypath spending
-        if let Some(Cooperative {
-            boltz_api,
-            swap_id,
-            pub_nonce,
-            partial_sig,
-        }) = is_cooperative
-        {
-            let secp = Secp256k1::new();
+        let claim_tx = self.build_claim(fee, |fee, outputs| {
+            self.create_claim(signer, preimage, fee, false, outputs)
+        })?;
 
-            // Start the Musig session
-            // Step 1: Get the sighash
-            let claim_tx_taproot_hash = SighashCache::new(claim_tx.clone())
-                .taproot_key_spend_signature_hash(
-                    0,
-                    &Prevouts::All(&[&self.utxos.first().unwrap().1]),
-                    bitcoin::TapSighashType::Default,
-                )?;
+        // Safety net against witness construction regressions in debug builds
+        #[cfg(all(feature = "consensus-verify", debug_assertions))]
+        self.verify_witness(&claim_tx)?;
 
-            let msg = Message::from_digest_slice(claim_tx_taproot_hash.as_byte_array())?;
+        StandardnessPolicy::default().check_bitcoin_tx(&claim_tx)?;
+        self.check_limits(&claim_tx)?;
 
-            // Step 2: Get the Public and Secret nonces
-            let mut key_agg_cache = self.swap_script.musig_keyagg_cache();
+        Ok(claim_tx)
+    }
 
-            let tweak = SecretKey::from_slice(
-                self.swap_script
-                    .taproot_spendinfo()?
-                    .tap_tweak()
-                    .as_byte_array(),
-            )?;
+    /// The OP_RETURN output carrying the memo, if any.
+    fn memo_output(&self) -> Result<Vec<TxOut>, Error> {
+        let memo = match &self.memo {
+            Some(memo) => memo,
+            None => return Ok(vec![]),
+        };
+        if memo.len() > MAX_MEMO_SIZE {
+            return Err(Error::Protocol(format!(
+                "Memo of {} bytes exceeds the standard limit of {} bytes",
+                memo.len(),
+                MAX_MEMO_SIZE
+            )));
+        }
+        let data = PushBytesBuf::try_from(memo.clone())
+            .map_err(|e| Error::Protocol(format!("Invalid memo: {}", e)))?;
+        Ok(vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_op_return(data),
+        }])
+    }
 
-            let _ = key_agg_cache.pubkey_xonly_tweak_add(&secp, tweak)?;
+    /// The anchor output, if any.
+    fn anchor_output(&self) -> Vec<TxOut> {
+        self.anchor.iter().map(Anchor::txout).collect()
+    }
 
-            let session_id = MusigSessionId::new(&mut thread_rng());
+    fn anchor_value(&self) -> u64 {
+        self.anchor.map_or(0, |anchor| anchor.value().to_sat())
+    }
 
-            let mut extra_rand = [0u8; 32];
-            OsRng.fill_bytes(&mut extra_rand);
+    /// The anchor output of a claim or refund `tx` built from this swap, to be spent by a
+    /// CPFP child. None if no anchor is configured or `tx` doesn't contain it.
+    pub fn anchor_outpoint(&self, tx: &Transaction) -> Option<(OutPoint, TxOut)> {
+        let spk = self.anchor?.script_pubkey();
+        let txid = tx.compute_txid();
+        tx.output
+            .iter()
+            .enumerate()
+            .find(|(_, output)| output.script_pubkey == spk)
+            .map(|(vout, output)| (OutPoint::new(txid, vout as u32), output.clone()))
+    }
 
-            let (claim_sec_nonce, claim_pub_nonce) = key_agg_cache.nonce_gen(
-                &secp,
-                session_id,
-                keys.public_key(),
-                msg,
-                Some(extra_rand),
-            )?;
+    /// Sequence of claim inputs, depending on RBF signaling and spending path.
+    fn claim_sequence(&self, is_cooperative: bool) -> Sequence {
+        match (self.signal_rbf, is_cooperative) {
+            (true, true) => Sequence::ENABLE_RBF_NO_LOCKTIME,
+            (true, false) => Sequence::ZERO,
+            (false, _) => Sequence::MAX,
+        }
+    }
 
-            // Step 7: Get boltz's partial sig
-            let claim_tx_hex = claim_tx.serialize().to_lower_hex_string();
-            let partial_sig_resp = match self.swap_script.swap_type {
-                SwapType::Chain => match (pub_nonce, partial_sig) {
-                    (Some(pub_nonce), Some(partial_sig)) => boltz_api.post_chain_claim_tx_details(
-                        &swap_id,
-                        preimage,
-                        pub_nonce,
-                        partial_sig,
-                        ToSign {
-                            pub_nonce: claim_pub_nonce.serialize().to_lower_hex_string(),
-                            transaction: claim_tx_hex,
-                            index: 0,
-                        },
-                    ),
-                    _ => Err(Error::Protocol(
-                        "Chain swap claim needs a partial_sig".to_string(),
-                    )),
-                },
-                SwapType::ReverseSubmarine => boltz_api.get_reverse_partial_sig(
-                    &swap_id,
-                    preimage,
-                    &claim_pub_nonce,
-                    &claim_tx_hex,
-                ),
-                _ => Err(Error::Protocol(format!(
-                    "Cannot get partial sig for {:?} Swap",
-                    self.swap_script.swap_type
-                ))),
-            }?;
-
-            let boltz_public_nonce =
-                MusigPubNonce::from_slice(&Vec::from_hex(&partial_sig_resp.pub_nonce)?)?;
-
-            let boltz_partial_sig = MusigPartialSignature::from_slice(&Vec::from_hex(
-                &partial_sig_resp.partial_signature,
-            )?)?;
-
-            // Aggregate Our's and Other's Nonce and start the Musig session.
-            let agg_nonce = MusigAggNonce::new(&secp, &[boltz_public_nonce, claim_pub_nonce]);
-
-            let musig_session = MusigSession::new(&secp, &key_agg_cache, agg_nonce, msg);
-
-            // Verify the Boltz's sig.
-            let boltz_partial_sig_verify = musig_session.partial_verify(
-                &secp,
-                &key_agg_cache,
-                boltz_partial_sig,
-                boltz_public_nonce,
-                self.swap_script.sender_pubkey.inner,
-            );
-
-            if !boltz_partial_sig_verify {
-                return Err(Error::Protocol(
-                    "Invalid partial-sig received from Boltz".to_string(),
-                ));
+    /// Claim paying `fee`, built with `build(absolute_fees, destination_outputs)`.
+    ///
+    /// The split of the output to `output_address` by the `privacy` policy depends on the amount
+    /// left after fees, so the claim is built unsplit first. If that amount is to be split, the
+    /// claim is rebuilt with the extra outputs, paying for their size.
+    fn build_claim<B>(&self, fee: Fee, build: B) -> Result<Transaction, Error>
+    where
+        B: Fn(u64, usize) -> Result<Transaction, Error>,
+    {
+        let claim_tx = create_tx_with_fee(fee, |fee| build(fee, 1), |tx| tx.vsize())?;
+        let outputs = self.privacy.split_count(claim_tx.output[0].value.to_sat());
+        if outputs < 2 {
+            return Ok(claim_tx);
+        }
+        match create_tx_with_fee(fee, |fee| build(fee, outputs), |tx| tx.vsize()) {
+            Ok(split_tx) => Ok(split_tx),
+            Err(e) => {
+                log::debug!("Claiming to a single output: {}", e.message());
+                Ok(claim_tx)
             }
-
-            let our_partial_sig =
-                musig_session.partial_sign(&secp, claim_sec_nonce, keys, &key_agg_cache)?;
-
-            let schnorr_sig = musig_session.partial_sig_agg(&[boltz_partial_sig, our_partial_sig]);
-
-            let final_schnorr_sig = Signature {
-                signature: schnorr_sig,
-                sighash_type: TapSighashType::Default,
-            };
-
-            let output_key = self.swap_script.taproot_spendinfo()?.output_key();
-
-            secp.verify_schnorr(&final_schnorr_sig.signature, &msg, &output_key.to_inner())?;
-
-            let mut witness = Witness::new();
-            witness.push(final_schnorr_sig.to_vec());
-
-            claim_tx.input[0].witness = witness;
         }
-
-        Ok(claim_tx)
     }
 
     fn create_claim(
         &self,
-        keys: &Keypair,
+        signer: &dyn SwapSigner,
         preimage: &Preimage,
         absolute_fees: u64,
         is_cooperative: bool,
+        destination_outputs: usize,
     ) -> Result<Transaction, Error> {
         let preimage_bytes = if let Some(value) = preimage.bytes {
             value
@@ -858,85 +1435,159 @@ impl BtcSwapTx {
             ));
         };
 
-        // For claim, we only consider 1 utxo
-        let utxo = self.utxos.first().ok_or(Error::Protocol(
-            "No Bitcoin UTXO detected for this script".to_string(),
-        ))?;
-
-        let txin = TxIn {
-            previous_output: utxo.0,
-            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-            script_sig: ScriptBuf::new(),
-            witness: Witness::new(),
-        };
-
-        let destination_spk = self.output_address.script_pubkey();
-
-        let txout = TxOut {
-            script_pubkey: destination_spk,
-            value: Amount::from_sat(utxo.1.value.to_sat() - absolute_fees),
-        };
-
-        let mut claim_tx = Transaction {
-            version: Version::TWO,
-            lock_time: LockTime::ZERO,
-            input: vec![txin],
-            output: vec![txout],
-        };
+        let mut claim_tx =
+            self.unsigned_claim(absolute_fees, is_cooperative, destination_outputs)?;
 
         if is_cooperative {
-            claim_tx.input[0].witness = Self::stubbed_cooperative_witness();
+            for input in claim_tx.input.iter_mut() {
+                input.witness = Self::stubbed_cooperative_witness();
+            }
         } else {
-            let secp = Secp256k1::new();
-
             // If Non-Cooperative claim use the Script Path spending
-            claim_tx.input[0].sequence = Sequence::ZERO;
-
             let leaf_hash =
                 TapLeafHash::from_script(&self.swap_script.claim_script(), LeafVersion::TapScript);
 
-            let sighash = SighashCache::new(claim_tx.clone()).taproot_script_spend_signature_hash(
-                0,
-                &Prevouts::All(&[&utxo.1]),
-                leaf_hash,
-                TapSighashType::Default,
-            )?;
-
-            let msg = Message::from_digest_slice(sighash.as_byte_array())?;
-
-            let signature = secp.sign_schnorr(&msg, keys);
-
-            let final_sig = Signature {
-                signature,
-                sighash_type: TapSighashType::Default,
-            };
-
             let control_block = self
                 .swap_script
                 .taproot_spendinfo()?
                 .control_block(&(self.swap_script.claim_script(), LeafVersion::TapScript))
-                .expect("Control block calculation failed");
+                .ok_or(Error::Protocol(
+                    "Control block calculation failed".to_string(),
+                ))?;
 
-            let mut witness = Witness::new();
+            let tx_outs: Vec<&TxOut> = self.utxos.iter().map(|(_, out)| out).collect();
+
+            for input_index in 0..claim_tx.input.len() {
+                let sighash = SighashCache::new(claim_tx.clone())
+                    .taproot_script_spend_signature_hash(
+                        input_index,
+                        &Prevouts::All(&tx_outs),
+                        leaf_hash,
+                        TapSighashType::Default,
+                    )?;
+
+                let msg = Message::from_digest_slice(sighash.as_byte_array())?;
 
-            witness.push(final_sig.to_vec());
-            witness.push(preimage.bytes.unwrap());
-            witness.push(self.swap_script.claim_script().as_bytes());
-            witness.push(control_block.serialize());
+                let signature = signer.sign_taproot_script_spend(&msg)?;
 
-            claim_tx.input[0].witness = witness;
+                let final_sig = Signature {
+                    signature,
+                    sighash_type: TapSighashType::Default,
+                };
+
+                let mut witness = Witness::new();
+
+                witness.push(final_sig.to_vec());
+                witness.push(preimage_bytes);
+                witness.push(self.swap_script.claim_script().as_bytes());
+                witness.push(control_block.serialize());
+
+                claim_tx.input[input_index].witness = witness;
+            }
         }
 
         Ok(claim_tx)
     }
 
+    /// Claim paying `absolute_fees`, with inputs and outputs set but without witnesses.
+    /// The amount for `output_address` is split into `destination_outputs` non-round outputs.
+    fn unsigned_claim(
+        &self,
+        absolute_fees: u64,
+        is_cooperative: bool,
+        destination_outputs: usize,
+    ) -> Result<Transaction, Error> {
+        if self.utxos.is_empty() {
+            return Err(Error::Protocol(
+                "No Bitcoin UTXO detected for this script".to_string(),
+            ));
+        }
+
+        // Claim all utxos, so nothing is left behind if the script was paid more than once
+        let inputs: Vec<TxIn> = self
+            .utxos
+            .iter()
+            .map(|(outpoint, _)| TxIn {
+                previous_output: *outpoint,
+                sequence: self.claim_sequence(is_cooperative),
+                script_sig: ScriptBuf::new(),
+                witness: Witness::new(),
+            })
+            .collect();
+        let input_value = self.utxos.iter().map(|(_, txo)| txo.value.to_sat()).sum();
+
+        let destination_spk = self.output_address.script_pubkey();
+        let claimed_value =
+            drain_output_value(input_value, absolute_fees + self.anchor_value(), 0)?;
+        let shares: Vec<f64> = self.claim_splits.iter().map(|(_, share)| *share).collect();
+        let (split_values, output_value) = match shares.is_empty() {
+            true => (vec![], claimed_value),
+            false => split_value(claimed_value, &shares)?,
+        };
+        let dust_limit = destination_spk.minimal_non_dust().to_sat();
+        if output_value < dust_limit {
+            return Err(Error::DustOutput(format!(
+                "Claim output of {} sats is below the dust limit of {} sats",
+                output_value, dust_limit
+            )));
+        }
+
+        let destination_values = match destination_outputs {
+            0 | 1 => vec![output_value],
+            outputs => split_non_round(output_value, dust_limit, outputs).ok_or(
+                Error::DustOutput(format!(
+                    "Claim output of {} sats can't be split into {} non-round outputs",
+                    output_value, outputs
+                )),
+            )?,
+        };
+        let mut outputs: Vec<TxOut> = destination_values
+            .into_iter()
+            .map(|value| TxOut {
+                script_pubkey: destination_spk.clone(),
+                value: Amount::from_sat(value),
+            })
+            .collect();
+        for ((address, _), value) in self.claim_splits.iter().zip(split_values) {
+            let script_pubkey = address.script_pubkey();
+            if value < script_pubkey.minimal_non_dust().to_sat() {
+                return Err(Error::DustOutput(format!(
+                    "Claim output of {} sats to {} is below the dust limit",
+                    value, address
+                )));
+            }
+            outputs.push(TxOut {
+                script_pubkey,
+                value: Amount::from_sat(value),
+            });
+        }
+
+        Ok(Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs,
+            output: [outputs, self.anchor_output(), self.memo_output()?].concat(),
+        })
+    }
+
     /// Sign a refund transaction.
-    /// Errors if called for a Reverse Swap.
+    /// Errors if called for a Reverse Swap, or with [`Error::LocktimeNotReached`] for a
+    /// non-cooperative refund before the refund locktime, if `network_config` is set.
     pub fn sign_refund(
         &self,
         keys: &Keypair,
         fee: Fee,
         is_cooperative: Option<Cooperative>,
+    ) -> Result<Transaction, Error> {
+        self.sign_refund_with_signer(&KeypairSigner::new(*keys), fee, is_cooperative)
+    }
+
+    /// [`Self::sign_refund`] with an external signer.
+    pub fn sign_refund_with_signer(
+        &self,
+        signer: &dyn SwapSigner,
+        fee: Fee,
+        is_cooperative: Option<Cooperative>,
     ) -> Result<Transaction, Error> {
         if self.swap_script.swap_type == SwapType::ReverseSubmarine {
             return Err(Error::Protocol(
@@ -950,122 +1601,230 @@ impl BtcSwapTx {
             ));
         }
 
-        let mut refund_tx = create_tx_with_fee(
+        if let Some(cooperative) = is_cooperative {
+            return self.musig_session(signer, None, fee)?.resume(&cooperative);
+        }
+        self.screen_outputs()?;
+
+        if let Some(network_config) = &self.network_config {
+            self.swap_script.check_refund_locktime(network_config)?;
+        }
+
+        let refund_tx = create_tx_with_fee(
             fee,
-            |fee| self.create_refund(keys, fee, is_cooperative.is_some()),
+            |fee| self.create_refund(signer, fee, false),
             |tx| tx.vsize(),
         )?;
 
-        if let Some(Cooperative {
-            boltz_api, swap_id, ..
-        }) = is_cooperative
-        {
-            // Start the Musig session
-            refund_tx.lock_time = LockTime::ZERO; // No locktime for cooperative spend
+        // Safety net against witness construction regressions in debug builds
+        #[cfg(all(feature = "consensus-verify", debug_assertions))]
+        self.verify_witness(&refund_tx)?;
 
-            for input_index in 0..refund_tx.input.len() {
-                // Step 1: Get the sighash
-                let tx_outs: Vec<&TxOut> = self.utxos.iter().map(|(_, out)| out).collect();
-                let refund_tx_taproot_hash = SighashCache::new(refund_tx.clone())
-                    .taproot_key_spend_signature_hash(
-                        input_index,
-                        &Prevouts::All(&tx_outs),
-                        bitcoin::TapSighashType::Default,
-                    )?;
+        StandardnessPolicy::default().check_bitcoin_tx(&refund_tx)?;
+        self.check_limits(&refund_tx)?;
 
-                let msg = Message::from_digest_slice(refund_tx_taproot_hash.as_byte_array())?;
+        Ok(refund_tx)
+    }
 
-                // Step 2: Get the Public and Secret nonces
-                let mut key_agg_cache = self.swap_script.musig_keyagg_cache();
+    /// Screen the `refund_outputs` of refunds and the `claim_splits` of claims. The output
+    /// address is screened when the transaction is created, these fields can be set afterwards.
+    fn screen_outputs(&self) -> Result<(), Error> {
+        match self.kind {
+            SwapTxKind::Claim => self.claim_splits.iter().try_for_each(|(address, _)| {
+                screen_address(&address.to_string(), AddressPurpose::Claim)
+            }),
+            SwapTxKind::Refund => self.refund_outputs.iter().try_for_each(|(address, _)| {
+                screen_address(&address.to_string(), AddressPurpose::Refund)
+            }),
+        }
+    }
 
-                let tweak = SecretKey::from_slice(
-                    self.swap_script
-                        .taproot_spendinfo()?
-                        .tap_tweak()
-                        .as_byte_array(),
+    /// Start the cooperative signing of this claim or refund with Boltz. `preimage` is required
+    /// for claims. Call [`MusigSigningSession::resume`] until it returns the signed transaction.
+    pub fn musig_session<'a>(
+        &'a self,
+        signer: &'a dyn SwapSigner,
+        preimage: Option<&Preimage>,
+        fee: Fee,
+    ) -> Result<MusigSigningSession<'a>, Error> {
+        self.screen_outputs()?;
+        let tx = match (&self.kind, preimage) {
+            (SwapTxKind::Claim, Some(preimage)) => self.build_claim(fee, |fee, outputs| {
+                self.create_claim(signer, preimage, fee, true, outputs)
+            })?,
+            (SwapTxKind::Claim, None) => {
+                return Err(Error::Protocol(
+                    "Preimage is required to sign a claim".to_string(),
+                ))
+            }
+            (SwapTxKind::Refund, _) => {
+                let mut refund_tx = create_tx_with_fee(
+                    fee,
+                    |fee| self.create_refund(signer, fee, true),
+                    |tx| tx.vsize(),
                 )?;
+                refund_tx.lock_time = LockTime::ZERO; // No locktime for cooperative spend
+                refund_tx
+            }
+        };
+        Ok(MusigSigningSession {
+            swap_tx: self,
+            signer,
+            preimage: preimage.cloned(),
+            tx,
+            signed_inputs: 0,
+            pending_nonce: None,
+        })
+    }
 
-                let secp = Secp256k1::new();
-                let _ = key_agg_cache.pubkey_xonly_tweak_add(&secp, tweak)?;
+    /// Cooperative claim or refund paying `fee`, with placeholder witnesses, for signing the key
+    /// path elsewhere, e.g. on an air-gapped device. Sign the messages of
+    /// [`Self::key_spend_sighashes`] with the swap's [`SwapMusigContext`], then complete the
+    /// transaction with [`Self::finalize_key_spend`].
+    pub fn unsigned_cooperative_tx(&self, fee: Fee) -> Result<Transaction, Error> {
+        self.screen_outputs()?;
+        let stub_witnesses = |mut tx: Transaction| {
+            for input in tx.input.iter_mut() {
+                input.witness = Self::stubbed_cooperative_witness();
+            }
+            tx
+        };
+        let mut tx = match self.kind {
+            SwapTxKind::Claim => self.build_claim(fee, |fee, outputs| {
+                Ok(stub_witnesses(self.unsigned_claim(fee, true, outputs)?))
+            })?,
+            SwapTxKind::Refund => create_tx_with_fee(
+                fee,
+                |fee| Ok(stub_witnesses(self.unsigned_refund(fee, true)?)),
+                |tx| tx.vsize(),
+            )?,
+        };
+        tx.lock_time = LockTime::ZERO; // No locktime for cooperative spend
+        Ok(tx)
+    }
 
-                let session_id = MusigSessionId::new(&mut thread_rng());
+    /// Taproot key path sighash of input `input_index` of `tx`, the message signed with MuSig2.
+    /// `tx` has to spend the utxos of this swap, in order.
+    pub fn key_spend_sighash(
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+    ) -> Result<Message, Error> {
+        let spends_utxos = tx.input.len() == self.utxos.len()
+            && tx
+                .input
+                .iter()
+                .zip(&self.utxos)
+                .all(|(input, (outpoint, _))| input.previous_output == *outpoint);
+        if !spends_utxos {
+            return Err(Error::Protocol(
+                "Transaction does not spend the utxos of the swap".to_string(),
+            ));
+        }
+        let tx_outs: Vec<&TxOut> = self.utxos.iter().map(|(_, out)| out).collect();
+        let sighash = SighashCache::new(tx).taproot_key_spend_signature_hash(
+            input_index,
+            &Prevouts::All(&tx_outs),
+            TapSighashType::Default,
+        )?;
+        Ok(Message::from_digest_slice(sighash.as_byte_array())?)
+    }
 
-                let mut extra_rand = [0u8; 32];
-                OsRng.fill_bytes(&mut extra_rand);
+    /// [`Self::key_spend_sighash`] of every input of `tx`.
+    pub fn key_spend_sighashes(&self, tx: &Transaction) -> Result<Vec<Message>, Error> {
+        (0..tx.input.len())
+            .map(|input_index| self.key_spend_sighash(tx, input_index))
+            .collect()
+    }
 
-                let (sec_nonce, pub_nonce) = key_agg_cache.nonce_gen(
-                    &secp,
-                    session_id,
-                    keys.public_key(),
-                    msg,
-                    Some(extra_rand),
-                )?;
+    /// Complete `tx` with the aggregated key path signature of every input, after checking them
+    /// against the swap output key, and run the checks of a signed claim or refund.
+    pub fn finalize_key_spend(
+        &self,
+        tx: &Transaction,
+        signatures: &[schnorr::Signature],
+    ) -> Result<Transaction, Error> {
+        if signatures.len() != tx.input.len() {
+            return Err(Error::Protocol(format!(
+                "Expected {} signatures, got {}",
+                tx.input.len(),
+                signatures.len()
+            )));
+        }
+        let secp = Secp256k1::new();
+        let output_key = self.swap_script.musig_context()?.output_key();
+        let mut signed_tx = tx.clone();
+        for (input_index, signature) in signatures.iter().enumerate() {
+            let msg = self.key_spend_sighash(tx, input_index)?;
+            secp.verify_schnorr(signature, &msg, &output_key)?;
+            let mut witness = Witness::new();
+            witness.push(
+                Signature {
+                    signature: *signature,
+                    sighash_type: TapSighashType::Default,
+                }
+                .to_vec(),
+            );
+            signed_tx.input[input_index].witness = witness;
+        }
 
-                // Step 7: Get boltz's partial sig
-                let refund_tx_hex = refund_tx.serialize().to_lower_hex_string();
-                let partial_sig_resp = match self.swap_script.swap_type {
-                    SwapType::Chain => boltz_api.get_chain_partial_sig(
-                        &swap_id,
-                        input_index,
-                        &pub_nonce,
-                        &refund_tx_hex,
-                    ),
-                    SwapType::Submarine => boltz_api.get_submarine_partial_sig(
-                        &swap_id,
-                        input_index,
-                        &pub_nonce,
-                        &refund_tx_hex,
-                    ),
-                    _ => Err(Error::Protocol(format!(
-                        "Cannot get partial sig for {:?} Swap",
-                        self.swap_script.swap_type
-                    ))),
-                }?;
-
-                let boltz_public_nonce =
-                    MusigPubNonce::from_slice(&Vec::from_hex(&partial_sig_resp.pub_nonce)?)?;
-
-                let boltz_partial_sig = MusigPartialSignature::from_slice(&Vec::from_hex(
-                    &partial_sig_resp.partial_signature,
-                )?)?;
-
-                // Aggregate Our's and Other's Nonce and start the Musig session.
-                let agg_nonce = MusigAggNonce::new(&secp, &[boltz_public_nonce, pub_nonce]);
-
-                let musig_session = MusigSession::new(&secp, &key_agg_cache, agg_nonce, msg);
-
-                // Verify the Boltz's sig.
-                let boltz_partial_sig_verify = musig_session.partial_verify(
-                    &secp,
-                    &key_agg_cache,
-                    boltz_partial_sig,
-                    boltz_public_nonce,
-                    self.swap_script.receiver_pubkey.inner, //boltz key
-                );
+        StandardnessPolicy::default().check_bitcoin_tx(&signed_tx)?;
+        self.check_limits(&signed_tx)?;
 
-                if !boltz_partial_sig_verify {
-                    return Err(Error::Protocol(
-                        "Invalid partial-sig received from Boltz".to_string(),
-                    ));
-                }
+        Ok(signed_tx)
+    }
+
+    fn create_refund(
+        &self,
+        signer: &dyn SwapSigner,
+        absolute_fees: u64,
+        is_cooperative: bool,
+    ) -> Result<Transaction, Error> {
+        let mut refund_tx = self.unsigned_refund(absolute_fees, is_cooperative)?;
 
-                let our_partial_sig =
-                    musig_session.partial_sign(&secp, sec_nonce, keys, &key_agg_cache)?;
+        let tx_outs: Vec<&TxOut> = self.utxos.iter().map(|(_, out)| out).collect();
 
-                let schnorr_sig =
-                    musig_session.partial_sig_agg(&[boltz_partial_sig, our_partial_sig]);
+        if is_cooperative {
+            for index in 0..refund_tx.input.len() {
+                refund_tx.input[index].witness = Self::stubbed_cooperative_witness();
+            }
+        } else {
+            let leaf_hash =
+                TapLeafHash::from_script(&self.swap_script.refund_script(), LeafVersion::TapScript);
 
-                let final_schnorr_sig = Signature {
-                    signature: schnorr_sig,
-                    sighash_type: TapSighashType::Default,
-                };
+            let control_block = self
+                .swap_script
+                .taproot_spendinfo()?
+                .control_block(&(
+                    self.swap_script.refund_script().clone(),
+                    LeafVersion::TapScript,
+                ))
+                .ok_or(Error::Protocol(
+                    "Control block calculation failed".to_string(),
+                ))?;
+
+            for input_index in 0..refund_tx.input.len() {
+                let sighash = SighashCache::new(refund_tx.clone())
+                    .taproot_script_spend_signature_hash(
+                        input_index,
+                        &Prevouts::All(&tx_outs),
+                        leaf_hash,
+                        TapSighashType::Default,
+                    )?;
+
+                let msg = Message::from_digest_slice(sighash.as_byte_array())?;
 
-                let output_key = self.swap_script.taproot_spendinfo()?.output_key();
+                let signature = signer.sign_taproot_script_spend(&msg)?;
 
-                secp.verify_schnorr(&final_schnorr_sig.signature, &msg, &output_key.to_inner())?;
+                let final_sig = Signature {
+                    signature,
+                    sighash_type: TapSighashType::Default,
+                };
 
                 let mut witness = Witness::new();
-                witness.push(final_schnorr_sig.to_vec());
+                witness.push(final_sig.to_vec());
+                witness.push(self.swap_script.refund_script().as_bytes());
+                witness.push(control_block.serialize());
                 refund_tx.input[input_index].witness = witness;
             }
         }
@@ -1073,9 +1832,10 @@ impl BtcSwapTx {
         Ok(refund_tx)
     }
 
-    fn create_refund(
+    /// Refund paying `absolute_fees`, with inputs, outputs, locktime and sequences set but
+    /// without witnesses.
+    fn unsigned_refund(
         &self,
-        keys: &Keypair,
         absolute_fees: u64,
         is_cooperative: bool,
     ) -> Result<Transaction, Error> {
@@ -1089,11 +1849,37 @@ impl BtcSwapTx {
                 format!("Cannot sign Refund Tx because utxos_amount ({utxos_amount}) <= absolute_fees ({absolute_fees_amount})")
             ));
         }
-        let output_amount: Amount = utxos_amount - absolute_fees_amount;
-        let output: TxOut = TxOut {
-            script_pubkey: self.output_address.script_pubkey(),
-            value: output_amount,
-        };
+        let mut outputs = vec![];
+        for (address, value) in &self.refund_outputs {
+            let script_pubkey = address.script_pubkey();
+            if *value < script_pubkey.minimal_non_dust() {
+                return Err(Error::DustOutput(format!(
+                    "Refund output of {} to {} is below the dust limit",
+                    value, address
+                )));
+            }
+            outputs.push(TxOut {
+                script_pubkey,
+                value: *value,
+            });
+        }
+        outputs.extend(self.anchor_output());
+        let fixed_amount = outputs
+            .iter()
+            .fold(Amount::ZERO, |acc, txo| acc + txo.value);
+        let output_amount = utxos_amount
+            .checked_sub(absolute_fees_amount + fixed_amount)
+            .filter(|amount| *amount > Amount::ZERO)
+            .ok_or(Error::Generic(format!(
+                "Cannot sign Refund Tx because utxos_amount ({utxos_amount}) <= absolute_fees ({absolute_fees_amount}) + refund outputs ({fixed_amount})"
+            )))?;
+        outputs.insert(
+            0,
+            TxOut {
+                script_pubkey: self.output_address.script_pubkey(),
+                value: output_amount,
+            },
+        );
 
         let unsigned_inputs = self
             .utxos
@@ -1111,7 +1897,7 @@ impl BtcSwapTx {
             .refund_script()
             .instructions()
             .filter_map(|i| {
-                let ins = i.unwrap();
+                let ins = i.ok()?;
                 if let Instruction::PushBytes(bytes) = ins {
                     if bytes.len() < 5_usize {
                         Some(LockTime::from_consensus(bytes_to_u32_little_endian(
@@ -1133,65 +1919,26 @@ impl BtcSwapTx {
                 ))
             }
         };
+        // The timelock pushed by relative timelock scripts is a sequence
+        let lock_time = match self.swap_script.relative_timelock {
+            Some(_) => LockTime::ZERO,
+            None => lock_time,
+        };
 
         let mut refund_tx = Transaction {
             version: Version::TWO,
             lock_time,
             input: unsigned_inputs,
-            output: vec![output],
+            output: [outputs, self.memo_output()?].concat(),
         };
 
-        let tx_outs: Vec<&TxOut> = self.utxos.iter().map(|(_, out)| out).collect();
-
-        if is_cooperative {
-            for index in 0..refund_tx.input.len() {
-                refund_tx.input[index].witness = Self::stubbed_cooperative_witness();
-            }
-        } else {
-            let leaf_hash =
-                TapLeafHash::from_script(&self.swap_script.refund_script(), LeafVersion::TapScript);
-
-            let control_block = self
-                .swap_script
-                .taproot_spendinfo()?
-                .control_block(&(
-                    self.swap_script.refund_script().clone(),
-                    LeafVersion::TapScript,
-                ))
-                .ok_or(Error::Protocol(
-                    "Control block calculation failed".to_string(),
-                ))?;
-
+        if !is_cooperative {
             // Input sequence has to be set for all inputs before signing
-            for input_index in 0..refund_tx.input.len() {
-                refund_tx.input[input_index].sequence = Sequence::ZERO;
+            let sequence = self.swap_script.relative_timelock.unwrap_or(Sequence::ZERO);
+            for input in refund_tx.input.iter_mut() {
+                input.sequence = sequence;
             }
-
-            for input_index in 0..refund_tx.input.len() {
-                let sighash = SighashCache::new(refund_tx.clone())
-                    .taproot_script_spend_signature_hash(
-                        input_index,
-                        &Prevouts::All(&tx_outs),
-                        leaf_hash,
-                        TapSighashType::Default,
-                    )?;
-
-                let msg = Message::from_digest_slice(sighash.as_byte_array())?;
-
-                let signature = Secp256k1::new().sign_schnorr(&msg, keys);
-
-                let final_sig = Signature {
-                    signature,
-                    sighash_type: TapSighashType::Default,
-                };
-
-                let mut witness = Witness::new();
-                witness.push(final_sig.to_vec());
-                witness.push(self.swap_script.refund_script().as_bytes());
-                witness.push(control_block.serialize());
-                refund_tx.input[input_index].witness = witness;
-            }
-        }
+        }
 
         Ok(refund_tx)
     }
@@ -1207,40 +1954,2375 @@ impl BtcSwapTx {
     /// Calculate the size of a transaction.
     /// Use this before calling drain to help calculate the absolute fees.
     /// Multiply the size by the fee_rate to get the absolute fees.
-    pub fn size(&self, keys: &Keypair, is_cooperative: bool) -> Result<usize, Error> {
+    /// The keys are not needed anymore, see [`Self::estimate_vsize`].
+    pub fn size(&self, _keys: &Keypair, is_cooperative: bool) -> Result<usize, Error> {
+        self.estimate_vsize(is_cooperative)
+    }
+
+    /// Virtual size of the signed transaction, without keys or signing.
+    /// The unsigned transaction gets the witness of the spending path with placeholder
+    /// signatures, which have the same size as real ones.
+    pub fn estimate_vsize(&self, is_cooperative: bool) -> Result<usize, Error> {
         let dummy_abs_fee = 1;
-        let tx = match self.kind {
+        let mut tx = match self.kind {
+            SwapTxKind::Claim => self.unsigned_claim(dummy_abs_fee, is_cooperative, 1)?,
+            SwapTxKind::Refund => self.unsigned_refund(dummy_abs_fee, is_cooperative)?,
+        };
+        let witness = self.witness_template(is_cooperative)?;
+        for input in tx.input.iter_mut() {
+            input.witness = witness.clone();
+        }
+        Ok(tx.vsize())
+    }
+
+    /// Witness of one input with placeholders for the signature and preimage.
+    fn witness_template(&self, is_cooperative: bool) -> Result<Witness, Error> {
+        if is_cooperative {
+            return Ok(Self::stubbed_cooperative_witness());
+        }
+        let leaf_script = match self.kind {
+            SwapTxKind::Claim => self.swap_script.claim_script(),
+            SwapTxKind::Refund => self.swap_script.refund_script(),
+        };
+        let control_block = self
+            .swap_script
+            .taproot_spendinfo()?
+            .control_block(&(leaf_script.clone(), LeafVersion::TapScript))
+            .ok_or(Error::Protocol(
+                "Control block calculation failed".to_string(),
+            ))?;
+        let mut witness = Witness::new();
+        witness.push([0; 64]);
+        if self.kind == SwapTxKind::Claim {
+            witness.push([0; 32]);
+        }
+        witness.push(leaf_script.as_bytes());
+        witness.push(control_block.serialize());
+        Ok(witness)
+    }
+
+    /// Export the script path spend as an unsigned PSBT, to be signed and finalized by an external
+    /// signer (e.g. a hardware wallet) instead of handing the `Keypair` to this library.
+    ///
+    /// Every input carries the witness utxo, internal key, merkle root and the claim or refund leaf
+    /// with its control block. The origin of our key is added if `key_source` is given.
+    /// Claims also carry the preimage in `hash160_preimages`, it is needed to finalize the witness.
+    pub fn to_psbt(
+        &self,
+        fee: Fee,
+        preimage: Option<&Preimage>,
+        key_source: Option<KeySource>,
+    ) -> Result<Psbt, Error> {
+        // Build the script path spend with a throwaway key, the signatures are stripped below
+        let throwaway_signer = KeypairSigner::new(Keypair::new(&Secp256k1::new(), &mut SecureRng));
+        let mut unsigned_tx = match self.kind {
             SwapTxKind::Claim => {
-                let preimage = Preimage::from_vec([0; 32].to_vec())?;
-                self.create_claim(keys, &preimage, dummy_abs_fee, is_cooperative)?
+                let preimage = preimage.ok_or(Error::Protocol(
+                    "Preimage is required to export a claim".to_string(),
+                ))?;
+                self.build_claim(fee, |fee, outputs| {
+                    self.create_claim(&throwaway_signer, preimage, fee, false, outputs)
+                })?
             }
-            SwapTxKind::Refund => self.create_refund(keys, dummy_abs_fee, is_cooperative)?,
+            SwapTxKind::Refund => create_tx_with_fee(
+                fee,
+                |fee| self.create_refund(&throwaway_signer, fee, false),
+                |tx| tx.vsize(),
+            )?,
         };
-        Ok(tx.vsize())
+        for input in unsigned_tx.input.iter_mut() {
+            input.witness = Witness::new();
+        }
+
+        let (leaf_script, our_pubkey) = match self.kind {
+            SwapTxKind::Claim => (
+                self.swap_script.claim_script(),
+                self.swap_script.receiver_pubkey,
+            ),
+            SwapTxKind::Refund => (
+                self.swap_script.refund_script(),
+                self.swap_script.sender_pubkey,
+            ),
+        };
+        let spend_info = self.swap_script.taproot_spendinfo()?;
+        let control_block = spend_info
+            .control_block(&(leaf_script.clone(), LeafVersion::TapScript))
+            .ok_or(Error::Protocol(
+                "Control block calculation failed".to_string(),
+            ))?;
+        let leaf_hash = TapLeafHash::from_script(&leaf_script, LeafVersion::TapScript);
+
+        let mut psbt =
+            Psbt::from_unsigned_tx(unsigned_tx).map_err(|e| Error::Protocol(e.to_string()))?;
+        // Inputs are built in the order of self.utxos
+        for (input, (_, txout)) in psbt.inputs.iter_mut().zip(self.utxos.iter()) {
+            input.witness_utxo = Some(txout.clone());
+            input.tap_internal_key = Some(spend_info.internal_key());
+            input.tap_merkle_root = spend_info.merkle_root();
+            input.tap_scripts.insert(
+                control_block.clone(),
+                (leaf_script.clone(), LeafVersion::TapScript),
+            );
+            if let Some(key_source) = &key_source {
+                input.tap_key_origins.insert(
+                    our_pubkey.inner.x_only_public_key().0,
+                    (vec![leaf_hash], key_source.clone()),
+                );
+            }
+            if let (SwapTxKind::Claim, Some(preimage)) = (&self.kind, preimage) {
+                if let Some(bytes) = preimage.bytes {
+                    input
+                        .hash160_preimages
+                        .insert(preimage.hash160, bytes.to_vec());
+                }
+            }
+        }
+        Ok(psbt)
+    }
+
+    /// Amount received by `output_address`, when draining the swap utxos with `fee`.
+    /// Everything minus the fee, the `refund_outputs` of refunds and the `claim_splits` of claims
+    /// goes there.
+    /// Fails if the output would be dust.
+    pub fn drain_amount(
+        &self,
+        keys: &Keypair,
+        fee: Fee,
+        is_cooperative: bool,
+    ) -> Result<u64, Error> {
+        let mut input_value: u64 = self.utxos.iter().map(|(_, txo)| txo.value.to_sat()).sum();
+        input_value = input_value.saturating_sub(self.anchor_value());
+        if self.kind == SwapTxKind::Refund {
+            let fixed: u64 = self.refund_outputs.iter().map(|(_, v)| v.to_sat()).sum();
+            input_value = input_value.saturating_sub(fixed);
+        }
+        let absolute_fee = match fee {
+            Fee::Absolute(fee) => fee,
+            Fee::Relative(rate) => (self.size(keys, is_cooperative)? as f64 * rate).ceil() as u64,
+            Fee::Deadline { blocks_remaining } => {
+                return Err(unresolved_deadline(blocks_remaining))
+            }
+        };
+        if self.kind == SwapTxKind::Claim && !self.claim_splits.is_empty() {
+            let shares: Vec<f64> = self.claim_splits.iter().map(|(_, share)| *share).collect();
+            let claimed_value = drain_output_value(input_value, absolute_fee, 0)?;
+            return Ok(split_value(claimed_value, &shares)?.1);
+        }
+        drain_output_value(
+            input_value,
+            absolute_fee,
+            self.output_address
+                .script_pubkey()
+                .minimal_non_dust()
+                .to_sat(),
+        )
+    }
+
+    /// Rebuild a claim or refund with a higher fee, to replace the unconfirmed `original_tx` (RBF).
+    ///
+    /// The replacement spends the same swap utxos as `original_tx` through the same spending path.
+    /// If the original was a cooperative (key path) spend, `is_cooperative` is required and a fresh
+    /// MuSig2 session is run with Boltz, so no nonce is reused. Otherwise it has to be None.
+    /// `preimage` is required for claims.
+    /// Fails if the new fee doesn't exceed the original fee by the incremental relay fee.
+    pub fn bump_fee(
+        &self,
+        keys: &Keypair,
+        original_tx: &Transaction,
+        new_fee: Fee,
+        preimage: Option<&Preimage>,
+        is_cooperative: Option<Cooperative>,
+    ) -> Result<Transaction, Error> {
+        // Spend exactly the inputs of the original, in the same order
+        let utxos = self.spent_utxos(original_tx)?;
+        let original_fee = tx_fee(original_tx, &utxos)?;
+
+        let was_cooperative = original_tx
+            .input
+            .iter()
+            .all(|input| input.witness.len() == 1);
+        if was_cooperative != is_cooperative.is_some() {
+            return Err(Error::Protocol(format!(
+                "Original transaction is a {} spend, the replacement has to use the same path",
+                if was_cooperative {
+                    "cooperative"
+                } else {
+                    "script path"
+                }
+            )));
+        }
+        if !original_tx.is_explicitly_rbf() {
+            log::warn!(
+                "Transaction {} does not signal replaceability, replacing it relies on full RBF",
+                original_tx.compute_txid()
+            );
+        }
+
+        let mut swap_tx = self.clone();
+        swap_tx.utxos = utxos;
+        let replacement = match self.kind {
+            SwapTxKind::Claim => {
+                let preimage = preimage.ok_or(Error::Protocol(
+                    "Preimage is required to bump the fee of a claim".to_string(),
+                ))?;
+                swap_tx.sign_claim(keys, preimage, new_fee, is_cooperative)?
+            }
+            SwapTxKind::Refund => swap_tx.sign_refund(keys, new_fee, is_cooperative)?,
+        };
+
+        let replacement_fee = tx_fee(&replacement, &swap_tx.utxos)?;
+        let min_fee = min_replacement_fee(original_fee, replacement.vsize());
+        if replacement_fee < min_fee {
+            return Err(Error::Protocol(format!(
+                "Replacement fee of {} sats is below the minimum of {} sats",
+                replacement_fee, min_fee
+            )));
+        }
+
+        Ok(replacement)
+    }
+
+    /// Fee to replace the unconfirmed `original_tx` with through [`Self::bump_fee`], if it is no
+    /// longer expected to confirm within `blocks_remaining` blocks, e.g. before the swap timeout.
+    /// The fee pays at least the current [`ElectrumConfig::deadline_fee_rate`] and the minimum
+    /// replacement fee. Returns None if the original is still on time.
+    pub fn deadline_fee_bump(
+        &self,
+        original_tx: &Transaction,
+        network_config: &ElectrumConfig,
+        blocks_remaining: u32,
+    ) -> Result<Option<Fee>, Error> {
+        self.deadline_fee_bump_with(
+            original_tx,
+            blocks_remaining,
+            |fee_rate| {
+                network_config
+                    .estimate_confirmation_time(fee_rate)
+                    .map(|estimate| estimate.blocks)
+            },
+            |blocks_remaining| network_config.deadline_fee_rate(blocks_remaining),
+        )
+    }
+
+    /// [`Self::deadline_fee_bump`] with the confirmation time estimates of `confirmation_blocks`
+    /// and the fee rates of `deadline_fee_rate`.
+    fn deadline_fee_bump_with(
+        &self,
+        original_tx: &Transaction,
+        blocks_remaining: u32,
+        confirmation_blocks: impl Fn(f64) -> Result<u32, Error>,
+        deadline_fee_rate: impl Fn(u32) -> Result<f64, Error>,
+    ) -> Result<Option<Fee>, Error> {
+        let original_fee = tx_fee(original_tx, &self.spent_utxos(original_tx)?)?;
+        let vsize = original_tx.vsize();
+        let original_rate = original_fee as f64 / vsize as f64;
+        let on_time = match confirmation_blocks(original_rate) {
+            Ok(blocks) => blocks <= blocks_remaining,
+            Err(_) => false,
+        };
+        if on_time {
+            return Ok(None);
+        }
+
+        let deadline_rate = deadline_fee_rate(blocks_remaining)?;
+        let fee = ((vsize as f64 * deadline_rate).ceil() as u64)
+            .max(min_replacement_fee(original_fee, vsize));
+        log::info!(
+            "Transaction {} at {:.2} sat/vbyte may miss its deadline of {} blocks, bumping to {} sats",
+            original_tx.compute_txid(),
+            original_rate,
+            blocks_remaining,
+            fee
+        );
+        Ok(Some(Fee::Absolute(fee)))
+    }
+
+    /// Build a child transaction spending the output of the broadcast `claim_tx` to `destination`,
+    /// so that claim and child together pay `package_fee_rate` sat/vbyte (CPFP).
+    ///
+    /// `signer` returns the witness of the single child input, given the unsigned child and the
+    /// claim output it spends. The child pays at least the minimum relay fee on its own.
+    pub fn cpfp_child<S>(
+        &self,
+        claim_tx: &Transaction,
+        destination: &Address,
+        package_fee_rate: f64,
+        signer: S,
+    ) -> Result<Transaction, Error>
+    where
+        S: Fn(&Transaction, &TxOut) -> Result<Witness, Error>,
+    {
+        if self.kind != SwapTxKind::Claim {
+            return Err(Error::Protocol(
+                "Only claim transactions can be bumped with a child".to_string(),
+            ));
+        }
+        screen_address(&destination.to_string(), AddressPurpose::Claim)?;
+        let parent_fee = tx_fee(claim_tx, &self.spent_utxos(claim_tx)?)?;
+        let parent_vsize = claim_tx.vsize();
+
+        let spk = self.output_address.script_pubkey();
+        let (vout, prevout) = claim_tx
+            .output
+            .iter()
+            .enumerate()
+            .find(|(_, output)| output.script_pubkey == spk)
+            .ok_or(Error::Protocol(
+                "Transaction does not pay to the output address".to_string(),
+            ))?;
+
+        let create_child = |fee: u64| -> Result<Transaction, Error> {
+            let output_value = drain_output_value(
+                prevout.value.to_sat(),
+                fee,
+                destination.script_pubkey().minimal_non_dust().to_sat(),
+            )?;
+            let mut child = Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::new(claim_tx.compute_txid(), vout as u32),
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: Witness::new(),
+                }],
+                output: vec![TxOut {
+                    script_pubkey: destination.script_pubkey(),
+                    value: Amount::from_sat(output_value),
+                }],
+            };
+            child.input[0].witness = signer(&child, prevout)?;
+            Ok(child)
+        };
+
+        let child_vsize = create_child(1)?.vsize();
+        let package_fee = ((parent_vsize + child_vsize) as f64 * package_fee_rate).ceil() as u64;
+        // The child has to meet the minimum relay fee of 1 sat/vbyte by itself
+        let child_fee = package_fee
+            .saturating_sub(parent_fee)
+            .max(child_vsize as u64);
+        create_child(child_fee)
+    }
+
+    /// [`Self::cpfp_child`] for an output address that is a P2WPKH or P2TR (BIP-86) address of `keys`.
+    pub fn cpfp_child_with_keys(
+        &self,
+        claim_tx: &Transaction,
+        keys: &Keypair,
+        destination: &Address,
+        package_fee_rate: f64,
+    ) -> Result<Transaction, Error> {
+        let secp = Secp256k1::new();
+        let spk = self.output_address.script_pubkey();
+        let compressed = CompressedPublicKey(keys.public_key());
+
+        if spk == ScriptBuf::new_p2wpkh(&compressed.wpubkey_hash()) {
+            self.cpfp_child(claim_tx, destination, package_fee_rate, |child, prevout| {
+                let sighash = SighashCache::new(child)
+                    .p2wpkh_signature_hash(0, &spk, prevout.value, EcdsaSighashType::All)
+                    .map_err(|e| Error::Protocol(e.to_string()))?;
+                let msg = Message::from_digest_slice(sighash.as_byte_array())?;
+                let signature = bitcoin::ecdsa::Signature {
+                    signature: secp.sign_ecdsa(&msg, &keys.secret_key()),
+                    sighash_type: EcdsaSighashType::All,
+                };
+                Ok(Witness::p2wpkh(&signature, &keys.public_key()))
+            })
+        } else if spk == ScriptBuf::new_p2tr(&secp, keys.x_only_public_key().0, None) {
+            self.cpfp_child(claim_tx, destination, package_fee_rate, |child, prevout| {
+                let sighash = SighashCache::new(child).taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&[prevout]),
+                    TapSighashType::Default,
+                )?;
+                let msg = Message::from_digest_slice(sighash.as_byte_array())?;
+                let tweaked = keys.tap_tweak(&secp, None);
+                let signature = Signature {
+                    signature: secp.sign_schnorr(&msg, &tweaked.to_inner()),
+                    sighash_type: TapSighashType::Default,
+                };
+                Ok(Witness::p2tr_key_spend(&signature))
+            })
+        } else {
+            Err(Error::Protocol(
+                "Output address is not a P2WPKH or P2TR address of the given keys".to_string(),
+            ))
+        }
+    }
+
+    /// Check a signed transaction against the configured [`TxLimits`].
+    fn check_limits(&self, tx: &Transaction) -> Result<(), Error> {
+        let spent = self.spent_utxos(tx)?;
+        let input_value = spent.iter().map(|(_, txo)| txo.value.to_sat()).sum();
+        let spk = self.output_address.script_pubkey();
+        let output_value = tx
+            .output
+            .iter()
+            .filter(|output| output.script_pubkey == spk)
+            .map(|output| output.value.to_sat())
+            .sum();
+        self.limits.check(
+            input_value,
+            tx_fee(tx, &spent)?,
+            output_value,
+            tx.vsize(),
+            spk.minimal_non_dust().to_sat(),
+            BITCOIN_MIN_RELAY_FEE_RATE,
+        )
+    }
+
+    /// The swap utxos spent by `tx`, in input order.
+    fn spent_utxos(&self, tx: &Transaction) -> Result<Vec<(OutPoint, TxOut)>, Error> {
+        tx.input
+            .iter()
+            .map(|input| {
+                self.utxos
+                    .iter()
+                    .find(|(outpoint, _)| *outpoint == input.previous_output)
+                    .cloned()
+                    .ok_or(Error::Protocol(format!(
+                        "Input {} does not spend a swap utxo",
+                        input.previous_output
+                    )))
+            })
+            .collect()
+    }
+
+    /// Point this claim at the lockup address of another swap, so the claim tx directly funds it
+    /// (swap chaining, e.g. LN -> LN rebalancing via an onchain hop).
+    ///
+    /// The claim output pays exactly `lockup_amount` to `lockup_address`, the rest of the swap
+    /// utxos go to fees and the anchor output, if any. Fails if that fee is below
+    /// `min_fee_rate` or above `max_fee_rate` (sat/vbyte), so a lockup amount far below the claimed
+    /// value can't hand the difference to miners. Returns the fee to pass to `sign_claim`.
+    /// Errors if `lockup_address` is not an address of `chain`.
+    pub fn chain_into(
+        &mut self,
+        keys: &Keypair,
+        chain: Chain,
+        lockup_address: &str,
+        lockup_amount: u64,
+        min_fee_rate: f64,
+        max_fee_rate: f64,
+        is_cooperative: bool,
+    ) -> Result<Fee, Error> {
+        if self.kind != SwapTxKind::Claim {
+            return Err(Error::Protocol(
+                "Only claim transactions can be chained into another swap".to_string(),
+            ));
+        }
+        if self.utxos.is_empty() {
+            return Err(Error::Protocol("No utxo to claim".to_string()));
+        }
+        if !self.claim_splits.is_empty() {
+            return Err(Error::Protocol(
+                "Claims with split outputs cannot be chained into another swap".to_string(),
+            ));
+        }
+        let value: u64 = self.utxos.iter().map(|(_, txo)| txo.value.to_sat()).sum();
+        let network = chain.bitcoin_network()?;
+        let output_address = Address::from_str(lockup_address)?;
+        screen_address(lockup_address, AddressPurpose::Claim)?;
+        if !output_address.is_valid_for_network(network) {
+            return Err(Error::Address("Address validation failed".to_string()));
+        }
+        let output_address = output_address.assume_checked();
+
+        let previous_address = std::mem::replace(&mut self.output_address, output_address);
+        let size = match self.size(keys, is_cooperative) {
+            Ok(size) => size,
+            Err(e) => {
+                self.output_address = previous_address;
+                return Err(e);
+            }
+        };
+
+        let min_fee = (size as f64 * min_fee_rate).ceil() as u64;
+        let max_fee = (size as f64 * max_fee_rate).floor() as u64;
+        // The anchor value is taken from the claim output, which has to stay at lockup_amount
+        let spent = lockup_amount + self.anchor_value();
+        let fee = value.saturating_sub(spent);
+        if value < spent || fee < min_fee {
+            self.output_address = previous_address;
+            return Err(Error::Protocol(format!(
+                "Claim of {} sats cannot fund a lockup of {} sats with a fee of at least {} sats",
+                value, lockup_amount, min_fee
+            )));
+        }
+        if fee > max_fee {
+            self.output_address = previous_address;
+            return Err(Error::Protocol(format!(
+                "Claim of {} sats funding a lockup of {} sats would pay a fee of {} sats, above the maximum of {} sats",
+                value, lockup_amount, fee, max_fee
+            )));
+        }
+        Ok(Fee::Absolute(fee))
+    }
+
+    /// [`Self::chain_into`] the lockup of a submarine swap.
+    pub fn chain_into_submarine(
+        &mut self,
+        keys: &Keypair,
+        chain: Chain,
+        submarine: &CreateSubmarineResponse,
+        min_fee_rate: f64,
+        max_fee_rate: f64,
+        is_cooperative: bool,
+    ) -> Result<Fee, Error> {
+        self.chain_into(
+            keys,
+            chain,
+            &submarine.address,
+            submarine.expected_amount,
+            min_fee_rate,
+            max_fee_rate,
+            is_cooperative,
+        )
+    }
+
+    /// [`Self::chain_into`] the user lockup of a chain swap.
+    pub fn chain_into_chain_swap(
+        &mut self,
+        keys: &Keypair,
+        chain: Chain,
+        chain_swap: &CreateChainResponse,
+        min_fee_rate: f64,
+        max_fee_rate: f64,
+        is_cooperative: bool,
+    ) -> Result<Fee, Error> {
+        self.chain_into(
+            keys,
+            chain,
+            &chain_swap.lockup_details.lockup_address,
+            chain_swap.lockup_details.amount,
+            min_fee_rate,
+            max_fee_rate,
+            is_cooperative,
+        )
     }
 
-    /// Broadcast transaction to the network.
+    /// Dry-run check of a signed claim or refund transaction before broadcast.
+    /// Every input must be a valid spend of a swap utxo, on a path matching the tx kind.
+    /// With the `consensus-verify` feature, the tx is also run through libbitcoinconsensus.
+    pub fn verify_witness(&self, signed_tx: &Transaction) -> Result<(), Error> {
+        let prevouts = signed_tx
+            .input
+            .iter()
+            .map(|input| {
+                self.utxos
+                    .iter()
+                    .find(|(outpoint, _)| *outpoint == input.previous_output)
+                    .map(|(_, txout)| txout.clone())
+                    .ok_or(Error::Protocol(format!(
+                        "Input {} does not spend a swap utxo",
+                        input.previous_output
+                    )))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        for input_index in 0..signed_tx.input.len() {
+            let path = self
+                .swap_script
+                .verify_spend(signed_tx, input_index, &prevouts)?;
+            match (&self.kind, path) {
+                (_, SpendPath::KeyPath)
+                | (SwapTxKind::Claim, SpendPath::ClaimLeaf)
+                | (SwapTxKind::Refund, SpendPath::RefundLeaf) => {}
+                (kind, path) => {
+                    return Err(Error::Protocol(format!(
+                        "{:?} transaction spends input {} via {:?}",
+                        kind, input_index, path
+                    )))
+                }
+            }
+        }
+
+        #[cfg(feature = "consensus-verify")]
+        crate::util::consensus::verify_bitcoin_tx(signed_tx, &prevouts)?;
+
+        Ok(())
+    }
+
+    /// Coin control metadata of the output paying to `output_address` in a signed claim or refund tx.
+    pub fn output_metadata(
+        &self,
+        signed_tx: &Transaction,
+        swap_id: &str,
+    ) -> Result<SwapOutput, Error> {
+        let spk = self.output_address.script_pubkey();
+        let (vout, output) = signed_tx
+            .output
+            .iter()
+            .enumerate()
+            .find(|(_, output)| output.script_pubkey == spk)
+            .ok_or(Error::Protocol(
+                "Transaction does not pay to the output address".to_string(),
+            ))?;
+        Ok(SwapOutput {
+            txid: signed_tx.compute_txid().to_string(),
+            vout: vout as u32,
+            address: self.output_address.to_string(),
+            script_type: ScriptType::from_bitcoin_script(&spk),
+            amount: output.value.to_sat(),
+            swap_id: swap_id.to_string(),
+            swap_type: self.swap_script.swap_type,
+            spend_kind: self.kind.clone(),
+        })
+    }
+
+    /// Broadcast transaction to the network, over a dedicated connection using the broadcast
+    /// timeout of `network_config`.
     pub fn broadcast(
         &self,
         signed_tx: &Transaction,
         network_config: &ElectrumConfig,
     ) -> Result<Txid, Error> {
         Ok(network_config
-            .build_client()?
+            .build_broadcast_client()?
             .transaction_broadcast(signed_tx)?)
     }
+
+    /// Broadcast to every configured Electrum server and, if given, through Boltz, concurrently.
+    /// Returns as soon as one of them accepts the transaction.
+    pub fn broadcast_all(
+        &self,
+        signed_tx: &Transaction,
+        network_config: &ElectrumConfig,
+        boltz_api: Option<&BoltzApiClientV2>,
+    ) -> Result<Txid, Error> {
+        let boltz_api = boltz_api.map(|api| (api, network_config.network()));
+        let txid = broadcast_all(
+            &bitcoin::consensus::serialize(signed_tx),
+            network_config,
+            boltz_api,
+        )?;
+        Ok(Txid::from_str(&txid)?)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::BtcSwapScript;
-    use bitcoin::absolute::LockTime;
-    use bitcoin::blockdata::transaction::Transaction;
-    use bitcoin::blockdata::transaction::Txid;
-    use bitcoin::transaction::Version;
-    use bitcoin::{Amount, OutPoint, Script, ScriptBuf, TxIn, TxOut};
-    use electrum_client::GetHistoryRes;
-    use std::str::FromStr;
+/// Cooperative (key path) signing of a claim or refund with Boltz, one MuSig2 session per input.
+/// Started with [`BtcSwapTx::musig_session`].
+///
+/// Requesting Boltz's partial signature can fail for transient reasons, e.g. a timeout or an
+/// HTTP 500. The session keeps the transaction, the inputs signed so far and our pending nonce,
+/// so [`Self::resume`] can simply be called again. The pending nonce is reused for the retry, as
+/// its secret part is only used once a valid partial signature of Boltz was received. A new
+/// nonce is generated if Boltz's partial signature is invalid or our partial signing failed.
+pub struct MusigSigningSession<'a> {
+    swap_tx: &'a BtcSwapTx,
+    signer: &'a dyn SwapSigner,
+    preimage: Option<Preimage>,
+    tx: Transaction,
+    signed_inputs: usize,
+    pending_nonce: Option<MusigPubNonce>,
+}
+
+impl MusigSigningSession<'_> {
+    /// Number of inputs signed so far.
+    pub fn signed_inputs(&self) -> usize {
+        self.signed_inputs
+    }
+
+    /// Sign the remaining inputs, and return the signed transaction once all are signed.
+    /// On error, the session can be resumed again.
+    pub fn resume(&mut self, cooperative: &Cooperative) -> Result<Transaction, Error> {
+        while self.signed_inputs < self.tx.input.len() {
+            let witness = self.sign_input(self.signed_inputs, cooperative)?;
+            self.tx.input[self.signed_inputs].witness = witness;
+            self.signed_inputs += 1;
+        }
+
+        // Safety net against witness construction regressions in debug builds
+        #[cfg(all(feature = "consensus-verify", debug_assertions))]
+        self.swap_tx.verify_witness(&self.tx)?;
+
+        StandardnessPolicy::default().check_bitcoin_tx(&self.tx)?;
+        self.swap_tx.check_limits(&self.tx)?;
+
+        Ok(self.tx.clone())
+    }
+
+    fn sign_input(
+        &mut self,
+        input_index: usize,
+        cooperative: &Cooperative,
+    ) -> Result<Witness, Error> {
+        let swap_script = &self.swap_tx.swap_script;
+        let msg = self.swap_tx.key_spend_sighash(&self.tx, input_index)?;
+
+        let musig = swap_script.musig_context()?;
+        let our_nonce = match self.pending_nonce {
+            Some(nonce) => nonce,
+            None => {
+                let nonce = musig.nonce(self.signer, msg)?;
+                self.pending_nonce = Some(nonce);
+                nonce
+            }
+        };
+
+        let partial_sig_resp = self.request_partial_sig(input_index, &our_nonce, cooperative)?;
+        let boltz_nonce = pub_nonce_from_hex(&partial_sig_resp.pub_nonce)?;
+        let boltz_partial_sig = partial_sig_from_hex(&partial_sig_resp.partial_signature)?;
+
+        // Aggregate both nonces and verify Boltz's partial sig
+        let musig_session = musig.session(our_nonce, boltz_nonce, msg);
+        let boltz_pubkey = match self.swap_tx.kind {
+            SwapTxKind::Claim => swap_script.sender_pubkey.inner,
+            SwapTxKind::Refund => swap_script.receiver_pubkey.inner,
+        };
+        // From here on, our nonce is either used or discarded
+        self.pending_nonce = None;
+        if !musig.verify_partial_sig(&musig_session, boltz_partial_sig, boltz_nonce, boltz_pubkey) {
+            return Err(Error::Protocol(
+                "Invalid partial-sig received from Boltz".to_string(),
+            ));
+        }
+
+        let our_partial_sig = musig.partial_sign(self.signer, &musig_session, &our_nonce)?;
+        let signature = Signature {
+            signature: musig.aggregate(
+                &musig_session,
+                &[boltz_partial_sig, our_partial_sig],
+                &msg,
+            )?,
+            sighash_type: TapSighashType::Default,
+        };
+
+        let mut witness = Witness::new();
+        witness.push(signature.to_vec());
+        Ok(witness)
+    }
+
+    fn request_partial_sig(
+        &self,
+        input_index: usize,
+        our_nonce: &MusigPubNonce,
+        cooperative: &Cooperative,
+    ) -> Result<PartialSig, Error> {
+        let boltz_api = cooperative.boltz_api;
+        let swap_id = &cooperative.swap_id;
+        let tx_hex = self.tx.serialize().to_lower_hex_string();
+        match (&self.swap_tx.kind, self.swap_tx.swap_script.swap_type) {
+            (SwapTxKind::Claim, SwapType::Chain) => {
+                let preimage = self.preimage.as_ref().ok_or(Error::Protocol(
+                    "Preimage is required to sign a claim".to_string(),
+                ))?;
+                match (cooperative.pub_nonce, cooperative.partial_sig) {
+                    (Some(pub_nonce), Some(partial_sig)) => boltz_api.post_chain_claim_tx_details(
+                        swap_id,
+                        preimage,
+                        pub_nonce,
+                        partial_sig,
+                        ToSign {
+                            pub_nonce: our_nonce.serialize().to_lower_hex_string(),
+                            transaction: tx_hex,
+                            index: input_index as u32,
+                        },
+                    ),
+                    _ => Err(Error::Protocol(
+                        "Chain swap claim needs a partial_sig".to_string(),
+                    )),
+                }
+            }
+            (SwapTxKind::Claim, SwapType::ReverseSubmarine) => {
+                let preimage = self.preimage.as_ref().ok_or(Error::Protocol(
+                    "Preimage is required to sign a claim".to_string(),
+                ))?;
+                boltz_api.get_reverse_partial_sig(
+                    swap_id,
+                    input_index,
+                    preimage,
+                    our_nonce,
+                    &tx_hex,
+                )
+            }
+            (SwapTxKind::Refund, SwapType::Chain) => {
+                boltz_api.get_chain_partial_sig(swap_id, input_index, our_nonce, &tx_hex)
+            }
+            (SwapTxKind::Refund, SwapType::Submarine) => {
+                boltz_api.get_submarine_partial_sig(swap_id, input_index, our_nonce, &tx_hex)
+            }
+            (kind, swap_type) => Err(Error::Protocol(format!(
+                "Cannot get partial sig for {:?} of {:?} Swap",
+                kind, swap_type
+            ))),
+        }
+    }
+}
+
+/// A swap refunded by a [`BatchRefundTx`], with the lockup utxos to spend.
+#[derive(Debug, Clone)]
+pub struct BatchRefundInput {
+    pub swap_script: BtcSwapScript,
+    pub utxos: Vec<(OutPoint, TxOut)>,
+}
+
+/// Non-cooperative refund of several expired Submarine or Chain swaps in a single transaction.
+///
+/// Every lockup utxo is spent through the refund leaf of its swap, and the funds are sent to a
+/// single output. The transaction locktime is the latest refund timelock of all swaps, so it
+/// can only be broadcast once every swap has expired.
+#[derive(Debug, Clone)]
+pub struct BatchRefundTx {
+    pub inputs: Vec<BatchRefundInput>,
+    pub output_address: Address,
+    pub limits: TxLimits,
+    /// Used to check the refund timelocks against the chain tip before signing, if set.
+    pub network_config: Option<ElectrumConfig>,
+}
+
+impl BatchRefundTx {
+    /// Batch refund paying to `refund_address`, without any swap yet.
+    pub fn new(refund_address: &str, network_config: &ElectrumConfig) -> Result<Self, Error> {
+        Ok(BatchRefundTx {
+            inputs: vec![],
+            output_address: checked_refund_address(refund_address, network_config)?,
+            limits: TxLimits::default(),
+            network_config: Some(network_config.clone()),
+        })
+    }
+
+    /// Add a swap with its lockup utxos.
+    pub fn add_swap(
+        &mut self,
+        swap_script: BtcSwapScript,
+        utxos: Vec<(OutPoint, TxOut)>,
+    ) -> Result<(), Error> {
+        if swap_script.swap_type == SwapType::ReverseSubmarine {
+            return Err(Error::Protocol(
+                "Refund Txs cannot be constructed for Reverse Submarine Swaps.".to_string(),
+            ));
+        }
+        if utxos.is_empty() {
+            return Err(Error::Protocol(
+                "No Bitcoin UTXO detected for this script".to_string(),
+            ));
+        }
+        if let Some(first) = self.inputs.first() {
+            if !first
+                .swap_script
+                .locktime
+                .is_same_unit(swap_script.locktime)
+            {
+                return Err(Error::Protocol(
+                    "Cannot batch refunds with height and time based locktimes".to_string(),
+                ));
+            }
+        }
+        self.inputs.push(BatchRefundInput { swap_script, utxos });
+        Ok(())
+    }
+
+    /// Fetch the utxos of `swap_script` and add it.
+    pub fn fetch_and_add_swap(
+        &mut self,
+        swap_script: BtcSwapScript,
+        network_config: &ElectrumConfig,
+    ) -> Result<(), Error> {
+        let utxos = swap_script.fetch_utxos(network_config)?;
+        self.add_swap(swap_script, utxos)
+    }
+
+    /// Fetch the utxos of many swaps, keyed by swap id, and add those whose refund timelock
+    /// expired. Swaps that fail are left out instead of failing the whole batch; sign with the
+    /// keys of the succeeded swaps, in order, and retry the retryable ones later.
+    pub fn fetch_and_add_swaps(
+        &mut self,
+        swaps: Vec<(String, BtcSwapScript)>,
+        network_config: &ElectrumConfig,
+    ) -> BatchResult<()> {
+        let mut result = BatchResult::new();
+        for (swap_id, swap_script) in swaps {
+            let added = swap_script
+                .check_refund_locktime(network_config)
+                .and_then(|_| self.fetch_and_add_swap(swap_script, network_config));
+            if let Err(e) = &added {
+                log::warn!(
+                    "Leaving swap {} out of batch refund: {}",
+                    swap_id,
+                    e.message()
+                );
+            }
+            result.push(swap_id, added);
+        }
+        result
+    }
+
+    /// Sign the batch refund. `keys` are the refund keys of the swaps, in the order they were
+    /// added.
+    pub fn sign(&self, keys: &[Keypair], fee: Fee) -> Result<Transaction, Error> {
+        let signers: Vec<KeypairSigner> = keys.iter().map(|k| KeypairSigner::new(*k)).collect();
+        let signers: Vec<&dyn SwapSigner> = signers.iter().map(|s| s as &dyn SwapSigner).collect();
+        self.sign_with_signers(&signers, fee)
+    }
+
+    /// [`Self::sign`] with external signers.
+    pub fn sign_with_signers(
+        &self,
+        signers: &[&dyn SwapSigner],
+        fee: Fee,
+    ) -> Result<Transaction, Error> {
+        if self.inputs.is_empty() {
+            return Err(Error::Protocol("No swaps to refund".to_string()));
+        }
+        if signers.len() != self.inputs.len() {
+            return Err(Error::Protocol(format!(
+                "Expected {} signers, got {}",
+                self.inputs.len(),
+                signers.len()
+            )));
+        }
+        if let Some(network_config) = &self.network_config {
+            for input in &self.inputs {
+                input.swap_script.check_refund_locktime(network_config)?;
+            }
+        }
+
+        let refund_tx =
+            create_tx_with_fee(fee, |fee| self.create_refund(signers, fee), |tx| tx.vsize())?;
+
+        StandardnessPolicy::default().check_bitcoin_tx(&refund_tx)?;
+        let input_value: u64 = self.utxos().map(|(_, txo)| txo.value.to_sat()).sum();
+        let spent: Vec<(OutPoint, TxOut)> = self.utxos().cloned().collect();
+        let spk = self.output_address.script_pubkey();
+        self.limits.check(
+            input_value,
+            tx_fee(&refund_tx, &spent)?,
+            refund_tx.output[0].value.to_sat(),
+            refund_tx.vsize(),
+            spk.minimal_non_dust().to_sat(),
+            BITCOIN_MIN_RELAY_FEE_RATE,
+        )?;
+
+        Ok(refund_tx)
+    }
+
+    /// All utxos spent by the batch, in input order.
+    fn utxos(&self) -> impl Iterator<Item = &(OutPoint, TxOut)> {
+        self.inputs.iter().flat_map(|input| input.utxos.iter())
+    }
+
+    fn create_refund(
+        &self,
+        signers: &[&dyn SwapSigner],
+        absolute_fees: u64,
+    ) -> Result<Transaction, Error> {
+        let utxos_amount = self
+            .utxos()
+            .fold(Amount::ZERO, |acc, (_, txo)| acc + txo.value);
+        let absolute_fees_amount = Amount::from_sat(absolute_fees);
+        let output_amount = utxos_amount
+            .checked_sub(absolute_fees_amount)
+            .filter(|amount| *amount > Amount::ZERO)
+            .ok_or(Error::Generic(format!(
+                "Cannot sign Refund Tx because utxos_amount ({utxos_amount}) <= absolute_fees ({absolute_fees_amount})"
+            )))?;
+
+        let lock_time = self
+            .inputs
+            .iter()
+            .map(|input| input.swap_script.locktime)
+            .max_by_key(|locktime| locktime.to_consensus_u32())
+            .unwrap_or(LockTime::ZERO);
+
+        // Inputs of relative timelock scripts carry the sequence, the others have to enable
+        // the transaction locktime
+        let mut refund_tx = Transaction {
+            version: Version::TWO,
+            lock_time,
+            input: self
+                .inputs
+                .iter()
+                .flat_map(|input| {
+                    let sequence = input
+                        .swap_script
+                        .relative_timelock
+                        .unwrap_or(Sequence::ZERO);
+                    input.utxos.iter().map(move |(outpoint, _)| TxIn {
+                        previous_output: *outpoint,
+                        script_sig: ScriptBuf::new(),
+                        sequence,
+                        witness: Witness::new(),
+                    })
+                })
+                .collect(),
+            output: vec![TxOut {
+                script_pubkey: self.output_address.script_pubkey(),
+                value: output_amount,
+            }],
+        };
+
+        let tx_outs: Vec<&TxOut> = self.utxos().map(|(_, out)| out).collect();
+        let mut input_index = 0;
+        for (input, signer) in self.inputs.iter().zip(signers) {
+            let refund_script = input.swap_script.refund_script();
+            let leaf_hash = TapLeafHash::from_script(&refund_script, LeafVersion::TapScript);
+            let control_block = input
+                .swap_script
+                .taproot_spendinfo()?
+                .control_block(&(refund_script.clone(), LeafVersion::TapScript))
+                .ok_or(Error::Protocol(
+                    "Control block calculation failed".to_string(),
+                ))?;
+
+            for _ in &input.utxos {
+                let sighash = SighashCache::new(refund_tx.clone())
+                    .taproot_script_spend_signature_hash(
+                        input_index,
+                        &Prevouts::All(&tx_outs),
+                        leaf_hash,
+                        TapSighashType::Default,
+                    )?;
+
+                let msg = Message::from_digest_slice(sighash.as_byte_array())?;
+
+                let final_sig = Signature {
+                    signature: signer.sign_taproot_script_spend(&msg)?,
+                    sighash_type: TapSighashType::Default,
+                };
+
+                let mut witness = Witness::new();
+                witness.push(final_sig.to_vec());
+                witness.push(refund_script.as_bytes());
+                witness.push(control_block.serialize());
+                refund_tx.input[input_index].witness = witness;
+                input_index += 1;
+            }
+        }
+
+        Ok(refund_tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::fees::TxLimits;
+    use crate::BtcSwapScript;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::blockdata::transaction::Transaction;
+    use bitcoin::blockdata::transaction::Txid;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, OutPoint, Script, ScriptBuf, TxIn, TxOut};
+    use electrum_client::GetHistoryRes;
+    use std::str::FromStr;
+
+    /// Swap script with a zero hashlock and an absolute refund timelock at height 850_000.
+    fn test_swap_script(
+        swap_type: crate::swaps::boltz::SwapType,
+        sender_pubkey: bitcoin::PublicKey,
+        receiver_pubkey: bitcoin::PublicKey,
+    ) -> BtcSwapScript {
+        use bitcoin::hashes::{hash160, Hash};
+
+        BtcSwapScript {
+            swap_type,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey,
+            locktime: LockTime::from_height(850_000).unwrap(),
+            sender_pubkey,
+            relative_timelock: None,
+        }
+    }
+
+    /// Swap transaction spending `utxos` of `swap_script`, paying back to the swap address on
+    /// mainnet. Other fields are set with struct update syntax.
+    fn test_swap_tx(
+        kind: crate::swaps::boltz::SwapTxKind,
+        swap_script: BtcSwapScript,
+        utxos: Vec<(OutPoint, TxOut)>,
+    ) -> super::BtcSwapTx {
+        let output_address = swap_script
+            .to_address(crate::network::Chain::Bitcoin)
+            .unwrap();
+        super::BtcSwapTx {
+            kind,
+            swap_script,
+            output_address,
+            utxos,
+            signal_rbf: true,
+            memo: None,
+            limits: TxLimits::default(),
+            refund_outputs: vec![],
+            claim_splits: vec![],
+            privacy: super::PrivacyPolicy::default(),
+            network_config: None,
+            anchor: None,
+        }
+    }
+
+    /// Utxo of 50_000 sats paying `address`.
+    fn test_utxo(address: &bitcoin::Address, vout: u32) -> (OutPoint, TxOut) {
+        use bitcoin::hashes::Hash;
+
+        (
+            OutPoint::new(Txid::all_zeros(), vout),
+            TxOut {
+                value: Amount::from_sat(50_000),
+                script_pubkey: address.script_pubkey(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_witness_sizes() {
+        use crate::util::fees::*;
+        use bitcoin::consensus::serialize;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::taproot::LeafVersion;
+        use bitcoin::{PublicKey, Witness};
+
+        let secp = Secp256k1::new();
+        let pubkey = |_| PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key());
+        let mut script = test_swap_script(
+            crate::swaps::boltz::SwapType::ReverseSubmarine,
+            pubkey(()),
+            pubkey(()),
+        );
+
+        let witness_size = |items: &[Vec<u8>]| serialize(&Witness::from_slice(items)).len();
+        let leaf_witness = |script: &BtcSwapScript, leaf: ScriptBuf, preimage: Option<Vec<u8>>| {
+            let control_block = script
+                .taproot_spendinfo()
+                .unwrap()
+                .control_block(&(leaf.clone(), LeafVersion::TapScript))
+                .unwrap();
+            let mut items = vec![vec![0; 64]];
+            items.extend(preimage);
+            items.push(leaf.to_bytes());
+            items.push(control_block.serialize());
+            witness_size(&items)
+        };
+
+        assert_eq!(witness_size(&[vec![0; 64]]), KEY_PATH_WITNESS_SIZE);
+        assert_eq!(
+            leaf_witness(&script, script.claim_script(), Some(vec![0; 32])),
+            CLAIM_LEAF_WITNESS_SIZE
+        );
+        assert_eq!(
+            leaf_witness(&script, script.refund_script(), None),
+            REFUND_LEAF_WITNESS_SIZE
+        );
+
+        script.swap_type = crate::swaps::boltz::SwapType::Submarine;
+        assert_eq!(
+            leaf_witness(&script, script.claim_script(), Some(vec![0; 32])),
+            SUBMARINE_CLAIM_LEAF_WITNESS_SIZE
+        );
+    }
+
+    #[test]
+    fn test_check_swap_tree() {
+        use crate::swaps::boltz::{Leaf, SwapTree, SwapType};
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::opcodes::all::OP_DROP;
+        use bitcoin::opcodes::OP_TRUE;
+        use bitcoin::script::Builder;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::PublicKey;
+
+        let secp = Secp256k1::new();
+        let pubkey = |_| PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key());
+        let script = test_swap_script(SwapType::ReverseSubmarine, pubkey(()), pubkey(()));
+        let leaf = |script: &Script| Leaf {
+            output: script.to_hex_string(),
+            version: 0xc0,
+        };
+        let tree = SwapTree {
+            claim_leaf: leaf(&script.claim_script()),
+            refund_leaf: leaf(&script.refund_script()),
+        };
+        assert!(script.check_swap_tree(&tree).is_ok());
+
+        // Same hashlock and timelock pushes, but the sender key check is dropped
+        let mut tampered = tree.clone();
+        let mut refund = Builder::new()
+            .push_opcode(OP_TRUE)
+            .push_opcode(OP_DROP)
+            .into_script()
+            .to_bytes();
+        refund.extend(script.refund_script().to_bytes());
+        tampered.refund_leaf = leaf(Script::from_bytes(&refund));
+        assert!(script.check_swap_tree(&tampered).is_err());
+
+        let mut tampered = tree.clone();
+        tampered.claim_leaf.version = 0xc2;
+        assert!(script.check_swap_tree(&tampered).is_err());
+
+        let mut other = script.clone();
+        other.swap_type = SwapType::Submarine;
+        assert!(other.check_swap_tree(&tree).is_err());
+    }
+
+    #[test]
+    fn test_regtest_addresses() {
+        use crate::swaps::boltz::SwapType;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::PublicKey;
+
+        let secp = Secp256k1::new();
+        let pubkey = |_| PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key());
+        let script = BtcSwapScript {
+            locktime: LockTime::from_height(200).unwrap(),
+            ..test_swap_script(SwapType::Submarine, pubkey(()), pubkey(()))
+        };
+        let address = script.to_address(Chain::BitcoinRegtest).unwrap();
+        assert!(address.to_string().starts_with("bcrt1p"));
+        assert!(script
+            .to_address(Chain::BitcoinTestnet)
+            .unwrap()
+            .to_string()
+            .starts_with("tb1p"));
+        assert!(script.to_address(Chain::LiquidRegtest).is_err());
+
+        let regtest_config =
+            ElectrumConfig::default(Chain::BitcoinRegtest, Some("localhost:19001".to_string()))
+                .unwrap();
+        assert!(BatchRefundTx::new(&address.to_string(), &regtest_config).is_ok());
+        assert!(
+            BatchRefundTx::new(&address.to_string(), &ElectrumConfig::default_bitcoin()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_musig_context_output_key() {
+        use crate::swaps::boltz::SwapType;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::PublicKey;
+
+        let secp = Secp256k1::new();
+        let pubkey = |_| PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key());
+        let script = test_swap_script(SwapType::ReverseSubmarine, pubkey(()), pubkey(()));
+        assert_eq!(
+            script.musig_context().unwrap().output_key(),
+            script.taproot_spendinfo().unwrap().output_key().to_inner()
+        );
+    }
+
+    #[test]
+    fn test_select_claim_utxo() {
+        use super::select_claim_utxo;
+
+        let txid =
+            Txid::from_str("4d8d2aa2d4d36d6bdd85da80bb23fa7f2bb4c9a8b1e05f3c2b7a4f5e3d9e1c0a")
+                .unwrap();
+        let utxo = |vout, value| {
+            (
+                OutPoint::new(txid, vout),
+                TxOut {
+                    value: Amount::from_sat(value),
+                    script_pubkey: ScriptBuf::new(),
+                },
+            )
+        };
+
+        assert!(select_claim_utxo(&[]).is_none());
+        let utxos = vec![
+            utxo(0, 1_000),
+            utxo(1, 5_000),
+            utxo(2, 5_000),
+            utxo(3, 2_000),
+        ];
+        assert_eq!(select_claim_utxo(&utxos).unwrap().0.vout, 1);
+        let reversed: Vec<_> = utxos.into_iter().rev().collect();
+        assert_eq!(select_claim_utxo(&reversed).unwrap().0.vout, 1);
+    }
+
+    #[test]
+    fn test_chain_into() {
+        use super::Anchor;
+        use crate::error::Error;
+        use crate::network::Chain;
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let script = test_swap_script(
+            SwapType::ReverseSubmarine,
+            PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+            PublicKey::new(keys.public_key()),
+        );
+        let own_address = script.to_address(Chain::Bitcoin).unwrap();
+        let lockup_address = "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr";
+        let mut tx = test_swap_tx(
+            SwapTxKind::Claim,
+            script,
+            vec![(
+                OutPoint::null(),
+                TxOut {
+                    value: Amount::from_sat(100_000),
+                    script_pubkey: own_address.script_pubkey(),
+                },
+            )],
+        );
+
+        assert!(tx
+            .chain_into(
+                &keys,
+                Chain::Bitcoin,
+                lockup_address,
+                99_990,
+                1.0,
+                10.0,
+                false
+            )
+            .is_err());
+        assert_eq!(tx.output_address, own_address);
+
+        assert!(matches!(
+            tx.chain_into(
+                &keys,
+                Chain::Bitcoin,
+                lockup_address,
+                99_000,
+                1.0,
+                10.0,
+                false
+            ),
+            Ok(Fee::Absolute(1_000))
+        ));
+        assert_eq!(
+            tx.output_address,
+            Address::from_str(lockup_address).unwrap().assume_checked()
+        );
+
+        // Lockup addresses of other networks are rejected
+        let testnet_address =
+            Address::p2tr(&secp, keys.x_only_public_key().0, None, Network::Testnet).to_string();
+        assert!(matches!(
+            tx.chain_into(
+                &keys,
+                Chain::Bitcoin,
+                &testnet_address,
+                99_000,
+                1.0,
+                10.0,
+                false
+            ),
+            Err(Error::Address(_))
+        ));
+
+        // The anchor is paid on top of the lockup amount
+        tx.anchor = Some(Anchor::PayToAnchor);
+        assert!(matches!(
+            tx.chain_into(
+                &keys,
+                Chain::Bitcoin,
+                lockup_address,
+                99_000,
+                1.0,
+                10.0,
+                false
+            ),
+            Ok(Fee::Absolute(760))
+        ));
+        assert!(tx
+            .chain_into(
+                &keys,
+                Chain::Bitcoin,
+                lockup_address,
+                99_800,
+                1.0,
+                10.0,
+                false
+            )
+            .is_err());
+
+        // A lockup far below the claimed value would leave the difference to miners
+        assert!(tx
+            .chain_into(
+                &keys,
+                Chain::Bitcoin,
+                lockup_address,
+                90_000,
+                1.0,
+                10.0,
+                false
+            )
+            .is_err());
+        assert!(matches!(
+            tx.chain_into(
+                &keys,
+                Chain::Bitcoin,
+                lockup_address,
+                90_000,
+                1.0,
+                1_000.0,
+                false
+            ),
+            Ok(Fee::Absolute(9_760))
+        ));
+    }
+
+    #[test]
+    fn test_bump_fee() {
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use crate::util::secrets::Preimage;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::PublicKey;
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let preimage = Preimage::new();
+        let script = BtcSwapScript {
+            hashlock: preimage.hash160,
+            ..test_swap_script(
+                SwapType::ReverseSubmarine,
+                PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+                PublicKey::new(keys.public_key()),
+            )
+        };
+        let address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let tx = test_swap_tx(
+            SwapTxKind::Claim,
+            script,
+            vec![(
+                OutPoint::null(),
+                TxOut {
+                    value: Amount::from_sat(100_000),
+                    script_pubkey: address.script_pubkey(),
+                },
+            )],
+        );
+
+        let original = tx
+            .sign_claim(&keys, &preimage, Fee::Absolute(200), None)
+            .unwrap();
+        assert!(tx
+            .bump_fee(&keys, &original, Fee::Absolute(250), Some(&preimage), None)
+            .is_err());
+
+        let replacement = tx
+            .bump_fee(
+                &keys,
+                &original,
+                Fee::Absolute(1_000),
+                Some(&preimage),
+                None,
+            )
+            .unwrap();
+        assert_eq!(replacement.input[0].previous_output, OutPoint::null());
+        assert_eq!(replacement.output[0].value, Amount::from_sat(99_000));
+        assert!(tx
+            .bump_fee(&keys, &original, Fee::Absolute(1_000), None, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_deadline_fee_bump() {
+        use crate::error::Error;
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use crate::util::secrets::Preimage;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::PublicKey;
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let preimage = Preimage::new();
+        let script = BtcSwapScript {
+            hashlock: preimage.hash160,
+            ..test_swap_script(
+                SwapType::ReverseSubmarine,
+                PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+                PublicKey::new(keys.public_key()),
+            )
+        };
+        let address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let tx = test_swap_tx(
+            SwapTxKind::Claim,
+            script,
+            vec![(
+                OutPoint::null(),
+                TxOut {
+                    value: Amount::from_sat(100_000),
+                    script_pubkey: address.script_pubkey(),
+                },
+            )],
+        );
+        let original = tx
+            .sign_claim(&keys, &preimage, Fee::Absolute(200), None)
+            .unwrap();
+        let vsize = original.vsize() as u64;
+        let no_rate = |_| -> Result<f64, Error> { panic!("no bump needed") };
+
+        // Expected within the deadline
+        assert!(matches!(
+            tx.deadline_fee_bump_with(&original, 6, |_| Ok(3), no_rate),
+            Ok(None)
+        ));
+        // Too late, or not expected to confirm at all
+        assert!(matches!(
+            tx.deadline_fee_bump_with(&original, 2, |_| Ok(3), |_| Ok(10.0)),
+            Ok(Some(Fee::Absolute(fee))) if fee == vsize * 10
+        ));
+        let unknown = |_| Err(Error::Generic("no estimate".to_string()));
+        assert!(matches!(
+            tx.deadline_fee_bump_with(&original, 6, unknown, |_| Ok(10.0)),
+            Ok(Some(Fee::Absolute(fee))) if fee == vsize * 10
+        ));
+        // The replacement pays at least the minimum replacement fee
+        assert!(matches!(
+            tx.deadline_fee_bump_with(&original, 2, |_| Ok(3), |_| Ok(1.0)),
+            Ok(Some(Fee::Absolute(fee))) if fee == 200 + vsize
+        ));
+        assert!(tx
+            .deadline_fee_bump_with(
+                &original,
+                0,
+                |_| Ok(3),
+                |_| Err(Error::Protocol(
+                    "Deadline of 0 blocks has already passed".to_string()
+                ))
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_cpfp_child() {
+        use super::BtcSwapTx;
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use crate::util::secrets::Preimage;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let preimage = Preimage::new();
+        let script = BtcSwapScript {
+            hashlock: preimage.hash160,
+            ..test_swap_script(
+                SwapType::ReverseSubmarine,
+                PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+                PublicKey::new(keys.public_key()),
+            )
+        };
+        let swap_address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let wallet_address =
+            Address::p2tr(&secp, keys.x_only_public_key().0, None, Network::Bitcoin);
+        let tx = BtcSwapTx {
+            output_address: wallet_address.clone(),
+            ..test_swap_tx(
+                SwapTxKind::Claim,
+                script,
+                vec![(
+                    OutPoint::null(),
+                    TxOut {
+                        value: Amount::from_sat(100_000),
+                        script_pubkey: swap_address.script_pubkey(),
+                    },
+                )],
+            )
+        };
+
+        let claim = tx
+            .sign_claim(&keys, &preimage, Fee::Absolute(200), None)
+            .unwrap();
+        let child = tx
+            .cpfp_child_with_keys(&claim, &keys, &wallet_address, 10.0)
+            .unwrap();
+        assert_eq!(child.input[0].previous_output.txid, claim.compute_txid());
+
+        let child_fee = 99_800 - child.output[0].value.to_sat();
+        assert!(200 + child_fee >= ((claim.vsize() + child.vsize()) as f64 * 10.0) as u64);
+
+        let other_keys = Keypair::new(&secp, &mut thread_rng());
+        assert!(tx
+            .cpfp_child_with_keys(&claim, &other_keys, &wallet_address, 10.0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_to_psbt() {
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use crate::util::secrets::Preimage;
+        use bitcoin::bip32::{DerivationPath, Fingerprint};
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::PublicKey;
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let preimage = Preimage::new();
+        let script = BtcSwapScript {
+            hashlock: preimage.hash160,
+            ..test_swap_script(
+                SwapType::ReverseSubmarine,
+                PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+                PublicKey::new(keys.public_key()),
+            )
+        };
+        let address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let tx = test_swap_tx(
+            SwapTxKind::Claim,
+            script,
+            vec![(
+                OutPoint::null(),
+                TxOut {
+                    value: Amount::from_sat(100_000),
+                    script_pubkey: address.script_pubkey(),
+                },
+            )],
+        );
+
+        assert!(tx.to_psbt(Fee::Absolute(300), None, None).is_err());
+
+        let key_source = (Fingerprint::from([1, 2, 3, 4]), DerivationPath::master());
+        let psbt = tx
+            .to_psbt(Fee::Absolute(300), Some(&preimage), Some(key_source))
+            .unwrap();
+        assert!(psbt.unsigned_tx.input[0].witness.is_empty());
+        assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(99_700));
+
+        let input = &psbt.inputs[0];
+        assert_eq!(input.witness_utxo, Some(tx.utxos[0].1.clone()));
+        assert_eq!(input.tap_scripts.len(), 1);
+        assert!(input
+            .tap_key_origins
+            .contains_key(&keys.x_only_public_key().0));
+        assert_eq!(
+            input.hash160_preimages.get(&preimage.hash160),
+            Some(&preimage.bytes.unwrap().to_vec())
+        );
+    }
+
+    #[test]
+    fn test_refund_outputs() {
+        use super::{BtcSwapTx, RefundLocktime};
+        use crate::error::Error;
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let script = test_swap_script(
+            SwapType::Submarine,
+            PublicKey::new(keys.public_key()),
+            PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+        );
+        let swap_address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let hot_wallet = Address::p2tr(&secp, keys.x_only_public_key().0, None, Network::Bitcoin);
+        let cold_wallet = Address::p2wpkh(
+            &bitcoin::CompressedPublicKey(keys.public_key()),
+            Network::Bitcoin,
+        );
+        let mut tx = BtcSwapTx {
+            output_address: hot_wallet.clone(),
+            refund_outputs: vec![(cold_wallet.clone(), Amount::from_sat(70_000))],
+            ..test_swap_tx(
+                SwapTxKind::Refund,
+                script,
+                vec![test_utxo(&swap_address, 0), test_utxo(&swap_address, 1)],
+            )
+        };
+
+        let refund = tx.sign_refund(&keys, Fee::Absolute(1_000), None).unwrap();
+        assert_eq!(
+            tx.swap_script.refund_spendable_at(),
+            RefundLocktime::Absolute(850_000)
+        );
+        assert_eq!(refund.lock_time.to_consensus_u32(), 850_000);
+        assert_eq!(refund.input.len(), 2);
+        assert_eq!(refund.output.len(), 2);
+        assert_eq!(refund.output[0].script_pubkey, hot_wallet.script_pubkey());
+        assert_eq!(refund.output[0].value, Amount::from_sat(29_000));
+        assert_eq!(refund.output[1].script_pubkey, cold_wallet.script_pubkey());
+        assert_eq!(refund.output[1].value, Amount::from_sat(70_000));
+        assert_eq!(
+            tx.drain_amount(&keys, Fee::Absolute(1_000), false).unwrap(),
+            29_000
+        );
+
+        tx.refund_outputs = vec![(cold_wallet.clone(), Amount::from_sat(99_500))];
+        assert!(tx.sign_refund(&keys, Fee::Absolute(1_000), None).is_err());
+        tx.refund_outputs = vec![(cold_wallet, Amount::from_sat(100))];
+        assert!(matches!(
+            tx.sign_refund(&keys, Fee::Absolute(1_000), None),
+            Err(Error::DustOutput(_))
+        ));
+    }
+
+    #[test]
+    fn test_anchor_output() {
+        use super::{Anchor, BtcSwapTx};
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let script = test_swap_script(
+            SwapType::Submarine,
+            PublicKey::new(keys.public_key()),
+            PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+        );
+        let swap_address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let wallet = Address::p2tr(&secp, keys.x_only_public_key().0, None, Network::Bitcoin);
+        let mut tx = BtcSwapTx {
+            output_address: wallet.clone(),
+            anchor: Some(Anchor::PayToAnchor),
+            ..test_swap_tx(
+                SwapTxKind::Refund,
+                script,
+                vec![test_utxo(&swap_address, 0)],
+            )
+        };
+
+        assert_eq!(Anchor::PayToAnchor.value(), Amount::from_sat(240));
+        let refund = tx.sign_refund(&keys, Fee::Absolute(1_000), None).unwrap();
+        assert_eq!(refund.output.len(), 2);
+        assert_eq!(refund.output[0].value, Amount::from_sat(48_760));
+        let (outpoint, anchor) = tx.anchor_outpoint(&refund).unwrap();
+        assert_eq!(outpoint, OutPoint::new(refund.compute_txid(), 1));
+        assert_eq!(anchor, Anchor::PayToAnchor.txout());
+        assert_eq!(anchor.script_pubkey.to_hex_string(), "51024e73");
+        assert_eq!(
+            tx.drain_amount(&keys, Fee::Absolute(1_000), false).unwrap(),
+            48_760
+        );
+
+        let anchor_keys = Keypair::new(&secp, &mut thread_rng());
+        let anchor = Anchor::Keyed(anchor_keys.x_only_public_key().0);
+        assert_eq!(anchor.value(), Amount::from_sat(330));
+        tx.anchor = Some(anchor);
+        let refund = tx.sign_refund(&keys, Fee::Absolute(1_000), None).unwrap();
+        assert_eq!(refund.output[0].value, Amount::from_sat(48_670));
+        assert!(tx.anchor_outpoint(&refund).is_some());
+
+        tx.anchor = None;
+        assert!(tx.anchor_outpoint(&refund).is_none());
+    }
+
+    #[test]
+    fn test_estimate_vsize() {
+        use super::BtcSwapTx;
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use crate::util::secrets::Preimage;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let script = test_swap_script(
+            SwapType::Submarine,
+            PublicKey::new(keys.public_key()),
+            PublicKey::new(keys.public_key()),
+        );
+        let swap_address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let wallet = Address::p2tr(&secp, keys.x_only_public_key().0, None, Network::Bitcoin);
+        let mut tx = BtcSwapTx {
+            output_address: wallet,
+            memo: Some(b"refund".to_vec()),
+            ..test_swap_tx(
+                SwapTxKind::Refund,
+                script,
+                vec![test_utxo(&swap_address, 0), test_utxo(&swap_address, 1)],
+            )
+        };
+
+        let refund = tx.sign_refund(&keys, Fee::Absolute(1_000), None).unwrap();
+        assert_eq!(tx.estimate_vsize(false).unwrap(), refund.vsize());
+        assert!(tx.estimate_vsize(true).unwrap() < refund.vsize());
+
+        tx.kind = SwapTxKind::Claim;
+        tx.swap_script.swap_type = SwapType::ReverseSubmarine;
+        let preimage = Preimage::from_vec(vec![0; 32]).unwrap();
+        let claim = tx
+            .sign_claim(&keys, &preimage, Fee::Absolute(1_000), None)
+            .unwrap();
+        assert_eq!(tx.estimate_vsize(false).unwrap(), claim.vsize());
+        assert_eq!(tx.size(&keys, false).unwrap(), claim.vsize());
+    }
+
+    #[test]
+    fn test_lockup_output_key_fuzz() {
+        use super::lockup_output_key;
+        use crate::error::Error;
+        use crate::swaps::boltz::SwapType;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        crate::util::fuzz_lockup_scripts(10_000, |bytes| {
+            let spk = ScriptBuf::from_bytes(bytes);
+            match lockup_output_key(&spk) {
+                Ok(_) => assert!(spk.is_p2tr()),
+                Err(e) => assert!(matches!(e, Error::Protocol(_))),
+            }
+        });
+
+        // A lockup address of another type is rejected instead of panicking
+        let secp = Secp256k1::new();
+        let key = PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key());
+        let script = BtcSwapScript {
+            funding_addrs: Some(Address::p2pkh(key, Network::Bitcoin)),
+            ..test_swap_script(SwapType::Submarine, key, key)
+        };
+        assert!(matches!(
+            script.taproot_spendinfo(),
+            Err(Error::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn test_finalize_key_spend() {
+        use super::BtcSwapTx;
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::swaps::signer::KeypairSigner;
+        use crate::util::fees::Fee;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::PublicKey;
+
+        let secp = Secp256k1::new();
+        let ours = KeypairSigner::new(Keypair::new(&secp, &mut thread_rng()));
+        let boltz = KeypairSigner::new(Keypair::new(&secp, &mut thread_rng()));
+        let script = test_swap_script(
+            SwapType::Submarine,
+            PublicKey::new(ours.public_key()),
+            PublicKey::new(boltz.public_key()),
+        );
+        let swap_address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let refund = test_swap_tx(
+            SwapTxKind::Refund,
+            script.clone(),
+            vec![test_utxo(&swap_address, 0), test_utxo(&swap_address, 1)],
+        );
+
+        // Online: build the transaction and hand the messages to the offline device
+        let tx = refund
+            .unsigned_cooperative_tx(Fee::Absolute(1_000))
+            .unwrap();
+        assert_eq!(tx.lock_time, LockTime::ZERO);
+        assert_eq!(tx.vsize(), refund.estimate_vsize(true).unwrap());
+        let msgs = refund.key_spend_sighashes(&tx).unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_ne!(msgs[0], msgs[1]);
+
+        // Offline: both MuSig2 parties sign every message
+        let musig = script.musig_context().unwrap();
+        let signatures: Vec<_> = msgs
+            .iter()
+            .map(|msg| {
+                let our_nonce = musig.nonce(&ours, *msg).unwrap();
+                let boltz_nonce = musig.nonce(&boltz, *msg).unwrap();
+                let session = musig.session(our_nonce, boltz_nonce, *msg);
+                let partial_sigs = [
+                    musig.partial_sign(&boltz, &session, &boltz_nonce).unwrap(),
+                    musig.partial_sign(&ours, &session, &our_nonce).unwrap(),
+                ];
+                musig.aggregate(&session, &partial_sigs, msg).unwrap()
+            })
+            .collect();
+
+        let signed = refund.finalize_key_spend(&tx, &signatures).unwrap();
+        assert_eq!(signed.compute_txid(), tx.compute_txid());
+        assert!(signed.input.iter().all(|input| input.witness.len() == 1));
+
+        let swapped = [signatures[1], signatures[0]];
+        assert!(refund.finalize_key_spend(&tx, &swapped).is_err());
+        assert!(refund.finalize_key_spend(&tx, &signatures[..1]).is_err());
+        let other = BtcSwapTx {
+            utxos: vec![test_utxo(&swap_address, 2), test_utxo(&swap_address, 1)],
+            ..refund.clone()
+        };
+        assert!(other.key_spend_sighash(&tx, 0).is_err());
+    }
+
+    #[test]
+    fn test_musig_session_resume() {
+        use super::BtcSwapTx;
+        use crate::swaps::boltz::{BoltzApiClientV2, Cooperative, SwapTxKind, SwapType};
+        use crate::swaps::signer::KeypairSigner;
+        use crate::util::fees::Fee;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let script = test_swap_script(
+            SwapType::Submarine,
+            PublicKey::new(keys.public_key()),
+            PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+        );
+        let swap_address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let tx = BtcSwapTx {
+            output_address: Address::p2tr(
+                &secp,
+                keys.x_only_public_key().0,
+                None,
+                Network::Bitcoin,
+            ),
+            ..test_swap_tx(
+                SwapTxKind::Refund,
+                script,
+                vec![test_utxo(&swap_address, 0)],
+            )
+        };
+
+        // Nothing listens there, so every partial sig request fails
+        let boltz_api = BoltzApiClientV2::new("http://127.0.0.1:1/v2");
+        let cooperative = Cooperative {
+            boltz_api: &boltz_api,
+            swap_id: "swap".to_string(),
+            pub_nonce: None,
+            partial_sig: None,
+        };
+        let signer = KeypairSigner::new(keys);
+        let mut session = tx
+            .musig_session(&signer, None, Fee::Absolute(1_000))
+            .unwrap();
+        assert_eq!(session.tx.lock_time, LockTime::ZERO);
+
+        assert!(session.resume(&cooperative).is_err());
+        let nonce = session.pending_nonce.unwrap();
+        assert_eq!(session.signed_inputs(), 0);
+
+        // The retry reuses the nonce, as Boltz never answered
+        assert!(session.resume(&cooperative).is_err());
+        assert_eq!(
+            session.pending_nonce.unwrap().serialize(),
+            nonce.serialize()
+        );
+
+        let mut claim = tx.clone();
+        claim.kind = SwapTxKind::Claim;
+        assert!(claim
+            .musig_session(&signer, None, Fee::Absolute(1_000))
+            .is_err());
+    }
+
+    #[test]
+    fn test_claim_splits() {
+        use super::BtcSwapTx;
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use crate::util::secrets::Preimage;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let preimage = Preimage::new();
+        let script = BtcSwapScript {
+            hashlock: preimage.hash160,
+            ..test_swap_script(
+                SwapType::ReverseSubmarine,
+                PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+                PublicKey::new(keys.public_key()),
+            )
+        };
+        let swap_address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let hot_wallet = Address::p2tr(&secp, keys.x_only_public_key().0, None, Network::Bitcoin);
+        let cold_wallet = Address::p2wpkh(
+            &bitcoin::CompressedPublicKey(keys.public_key()),
+            Network::Bitcoin,
+        );
+        let mut tx = BtcSwapTx {
+            output_address: hot_wallet.clone(),
+            claim_splits: vec![(cold_wallet.clone(), 90.0)],
+            ..test_swap_tx(
+                SwapTxKind::Claim,
+                script,
+                vec![(
+                    OutPoint::null(),
+                    TxOut {
+                        value: Amount::from_sat(100_000),
+                        script_pubkey: swap_address.script_pubkey(),
+                    },
+                )],
+            )
+        };
+
+        assert!(tx.verify_lockup(100_000, 0).is_ok());
+        assert!(tx.verify_lockup(100_500, 500).is_ok());
+        assert!(matches!(
+            tx.verify_lockup(100_501, 500),
+            Err(crate::error::Error::AmountMismatch(_))
+        ));
+
+        let claim = tx
+            .sign_claim(&keys, &preimage, Fee::Absolute(1_000), None)
+            .unwrap();
+        assert_eq!(claim.output.len(), 2);
+        assert_eq!(claim.output[0].script_pubkey, hot_wallet.script_pubkey());
+        assert_eq!(claim.output[0].value, Amount::from_sat(9_900));
+        assert_eq!(claim.output[1].script_pubkey, cold_wallet.script_pubkey());
+        assert_eq!(claim.output[1].value, Amount::from_sat(89_100));
+        assert_eq!(
+            tx.drain_amount(&keys, Fee::Absolute(1_000), false).unwrap(),
+            9_900
+        );
+
+        // The remainder to the destination is dust
+        tx.claim_splits = vec![(cold_wallet.clone(), 99.9)];
+        assert!(matches!(
+            tx.sign_claim(&keys, &preimage, Fee::Absolute(1_000), None),
+            Err(crate::error::Error::DustOutput(_))
+        ));
+
+        tx.claim_splits = vec![(cold_wallet, 100.0)];
+        assert!(tx
+            .sign_claim(&keys, &preimage, Fee::Absolute(1_000), None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_claim_privacy() {
+        use super::{BtcSwapTx, PrivacyPolicy};
+        use crate::swaps::boltz::{SwapTxKind, SwapType};
+        use crate::swaps::privacy::is_round;
+        use crate::util::fees::Fee;
+        use crate::util::secrets::Preimage;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let preimage = Preimage::new();
+        let script = BtcSwapScript {
+            hashlock: preimage.hash160,
+            ..test_swap_script(
+                SwapType::ReverseSubmarine,
+                PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+                PublicKey::new(keys.public_key()),
+            )
+        };
+        let swap_address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let hot_wallet = Address::p2tr(&secp, keys.x_only_public_key().0, None, Network::Bitcoin);
+        let tx = BtcSwapTx {
+            output_address: hot_wallet.clone(),
+            privacy: PrivacyPolicy {
+                avoid_round_amounts: true,
+                max_outputs: 3,
+                ..Default::default()
+            },
+            ..test_swap_tx(
+                SwapTxKind::Claim,
+                script,
+                vec![(
+                    OutPoint::null(),
+                    TxOut {
+                        value: Amount::from_sat(100_000),
+                        script_pubkey: swap_address.script_pubkey(),
+                    },
+                )],
+            )
+        };
+
+        // 99_000 sats left after fees are split
+        for _ in 0..20 {
+            let claim = tx
+                .sign_claim(&keys, &preimage, Fee::Absolute(1_000), None)
+                .unwrap();
+            assert!(claim.output.len() >= 2 && claim.output.len() <= 3);
+            assert!(claim
+                .output
+                .iter()
+                .all(|o| o.script_pubkey == hot_wallet.script_pubkey()
+                    && !is_round(o.value.to_sat())));
+            let claimed: u64 = claim.output.iter().map(|o| o.value.to_sat()).sum();
+            assert_eq!(claimed, 99_000);
+        }
+
+        let claim = tx
+            .sign_claim(&keys, &preimage, Fee::Absolute(1_234), None)
+            .unwrap();
+        assert_eq!(claim.output.len(), 1);
+        assert_eq!(claim.output[0].value, Amount::from_sat(98_766));
+    }
+    #[test]
+    fn test_relative_timelock_refund() {
+        use super::{relative_refund_timelock, RefundLocktime};
+        use crate::error::Error;
+        use crate::swaps::boltz::{SpendPath, SwapTxKind, SwapType};
+        use crate::util::fees::Fee;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{PublicKey, Sequence};
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let absolute = test_swap_script(
+            SwapType::Chain,
+            PublicKey::new(keys.public_key()),
+            PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+        );
+        let sequence = Sequence::from_height(144);
+        let script = BtcSwapScript {
+            locktime: LockTime::ZERO,
+            relative_timelock: Some(sequence),
+            ..absolute.clone()
+        };
+        assert_eq!(
+            relative_refund_timelock(&script.refund_script()),
+            Some(sequence)
+        );
+        assert_eq!(relative_refund_timelock(&absolute.refund_script()), None);
+        assert_eq!(
+            script.refund_spendable_at(),
+            RefundLocktime::Relative(sequence)
+        );
+        assert_eq!(
+            script
+                .refund_spendable_at()
+                .spendable_height(&[850_000, 850_010]),
+            Some(850_153)
+        );
+        assert_eq!(script.refund_spendable_at().spendable_height(&[]), None);
+
+        assert!(matches!(
+            absolute.check_refund_locktime_at(849_999, &[]),
+            Err(Error::LocktimeNotReached {
+                current: 849_999,
+                required: 850_000
+            })
+        ));
+        assert!(absolute.check_refund_locktime_at(850_000, &[]).is_ok());
+        assert!(matches!(
+            script.check_refund_locktime_at(850_152, &[850_000, 850_010]),
+            Err(Error::LocktimeNotReached {
+                current: 850_152,
+                required: 850_153
+            })
+        ));
+        assert!(script
+            .check_refund_locktime_at(850_153, &[850_000, 850_010])
+            .is_ok());
+        assert!(matches!(
+            script.check_refund_locktime_at(900_000, &[]),
+            Err(Error::Protocol(_))
+        ));
+        assert_eq!(
+            RefundLocktime::Relative(Sequence::from_512_second_intervals(10))
+                .spendable_height(&[850_000]),
+            None
+        );
+
+        // Delays up to 16 are encoded as opcodes
+        for delay in [1, 16, 17, 144] {
+            let sequence = Sequence::from_consensus(delay);
+            let script = BtcSwapScript {
+                relative_timelock: Some(sequence),
+                ..script.clone()
+            };
+            assert_eq!(
+                relative_refund_timelock(&script.refund_script()),
+                Some(sequence)
+            );
+        }
+
+        let address = script.to_address(crate::network::Chain::Bitcoin).unwrap();
+        let prevout = TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: address.script_pubkey(),
+        };
+        let tx = test_swap_tx(
+            SwapTxKind::Refund,
+            script.clone(),
+            vec![(OutPoint::null(), prevout.clone())],
+        );
+
+        let mut refund = tx.sign_refund(&keys, Fee::Absolute(1_000), None).unwrap();
+        assert_eq!(refund.lock_time, LockTime::ZERO);
+        assert_eq!(refund.input[0].sequence, sequence);
+        assert_eq!(
+            script.verify_spend(&refund, 0, &[prevout.clone()]).unwrap(),
+            SpendPath::RefundLeaf
+        );
+
+        refund.input[0].sequence = Sequence::from_height(10);
+        assert!(script.verify_spend(&refund, 0, &[prevout]).is_err());
+    }
+
+    #[test]
+    fn test_batch_refund() {
+        use super::BatchRefundTx;
+        use crate::swaps::boltz::{SpendPath, SwapType};
+        use crate::util::fees::Fee;
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{Address, Network, PublicKey};
+
+        let secp = Secp256k1::new();
+        let swap = |keys: &Keypair, locktime| BtcSwapScript {
+            locktime: LockTime::from_height(locktime).unwrap(),
+            ..test_swap_script(
+                SwapType::Submarine,
+                PublicKey::new(keys.public_key()),
+                PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+            )
+        };
+        let utxo = |script: &BtcSwapScript, vout| {
+            test_utxo(
+                &script.to_address(crate::network::Chain::Bitcoin).unwrap(),
+                vout,
+            )
+        };
+
+        let keys = [
+            Keypair::new(&secp, &mut thread_rng()),
+            Keypair::new(&secp, &mut thread_rng()),
+        ];
+        let first = swap(&keys[0], 850_100);
+        let second = swap(&keys[1], 850_000);
+        let address = Address::p2tr(&secp, keys[0].x_only_public_key().0, None, Network::Bitcoin);
+        let mut batch = BatchRefundTx {
+            inputs: vec![],
+            output_address: address.clone(),
+            limits: TxLimits::default(),
+            network_config: None,
+        };
+        batch
+            .add_swap(first.clone(), vec![utxo(&first, 0), utxo(&first, 1)])
+            .unwrap();
+        batch
+            .add_swap(second.clone(), vec![utxo(&second, 2)])
+            .unwrap();
+
+        let reverse = BtcSwapScript {
+            swap_type: SwapType::ReverseSubmarine,
+            ..first.clone()
+        };
+        assert!(batch
+            .clone()
+            .add_swap(reverse.clone(), vec![utxo(&reverse, 3)])
+            .is_err());
+        let timed = BtcSwapScript {
+            locktime: LockTime::from_time(1_700_000_000).unwrap(),
+            ..second.clone()
+        };
+        assert!(batch
+            .clone()
+            .add_swap(timed.clone(), vec![utxo(&timed, 3)])
+            .is_err());
+        assert!(batch.sign(&keys[..1], Fee::Absolute(1_000)).is_err());
+
+        let refund = batch.sign(&keys, Fee::Absolute(1_000)).unwrap();
+        assert_eq!(refund.lock_time.to_consensus_u32(), 850_100);
+        assert_eq!(refund.input.len(), 3);
+        assert_eq!(refund.output.len(), 1);
+        assert_eq!(refund.output[0].script_pubkey, address.script_pubkey());
+        assert_eq!(refund.output[0].value, Amount::from_sat(149_000));
+
+        let prevouts = [utxo(&first, 0).1, utxo(&first, 1).1, utxo(&second, 2).1];
+        for (index, script) in [&first, &first, &second].into_iter().enumerate() {
+            assert_eq!(
+                script.verify_spend(&refund, index, &prevouts).unwrap(),
+                SpendPath::RefundLeaf
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_claim_spend() {
+        use crate::swaps::boltz::{SpendPath, SwapType};
+        use bitcoin::hashes::{hash160, Hash};
+        use bitcoin::key::rand::thread_rng;
+        use bitcoin::secp256k1::{Keypair, Message, Secp256k1};
+        use bitcoin::sighash::{Prevouts, SighashCache};
+        use bitcoin::taproot::{LeafVersion, Signature};
+        use bitcoin::{PublicKey, Sequence, TapLeafHash, TapSighashType, Witness};
+
+        let secp = Secp256k1::new();
+        let receiver_keys = Keypair::new(&secp, &mut thread_rng());
+        let preimage = [7; 32];
+        let script = BtcSwapScript {
+            hashlock: hash160::Hash::hash(&preimage),
+            ..test_swap_script(
+                SwapType::ReverseSubmarine,
+                PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+                PublicKey::new(receiver_keys.public_key()),
+            )
+        };
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: script
+                .to_address(crate::network::Chain::Bitcoin)
+                .unwrap()
+                .script_pubkey(),
+        };
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                sequence: Sequence::MAX,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+
+        let leaf = script.claim_script();
+        let sighash = SighashCache::new(&tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[&prevout]),
+                TapLeafHash::from_script(&leaf, LeafVersion::TapScript),
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let signature = Signature {
+            signature: secp.sign_schnorr(
+                &Message::from_digest_slice(sighash.as_byte_array()).unwrap(),
+                &receiver_keys,
+            ),
+            sighash_type: TapSighashType::Default,
+        };
+        let control_block = script
+            .taproot_spendinfo()
+            .unwrap()
+            .control_block(&(leaf.clone(), LeafVersion::TapScript))
+            .unwrap();
+        let witness = |preimage: &[u8]| {
+            Witness::from_slice(&[
+                signature.to_vec(),
+                preimage.to_vec(),
+                leaf.to_bytes(),
+                control_block.serialize(),
+            ])
+        };
+
+        tx.input[0].witness = witness(&preimage);
+        assert_eq!(
+            script.verify_spend(&tx, 0, &[prevout.clone()]).unwrap(),
+            SpendPath::ClaimLeaf
+        );
+
+        tx.input[0].witness = witness(&[8; 32]);
+        assert!(script.verify_spend(&tx, 0, &[prevout.clone()]).is_err());
+
+        tx.input[0].witness = witness(&preimage);
+        tx.output[0].value = Amount::from_sat(8_000);
+        assert!(script.verify_spend(&tx, 0, &[prevout]).is_err());
+    }
 
     #[test]
     fn test_utxo_fetching() {
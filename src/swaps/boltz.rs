@@ -27,14 +27,21 @@ use serde_json::Value;
 use std::fmt::{Display, Formatter, Write};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{collections::HashMap, fmt::format, net::TcpStream};
 use tungstenite::{connect, http::response, stream::MaybeTlsStream, WebSocket};
 use ureq::json;
 use ureq::{AgentBuilder, TlsConnector};
 
-use crate::{error::Error, network::Chain, util::secrets::Preimage};
+use crate::{
+    error::Error,
+    network::{Chain, OperationTimeouts},
+    util::{backoff::Backoff, secrets::Preimage},
+};
 use crate::{BtcSwapScript, LBtcSwapScript};
 
+use super::status::SwapPhase;
+
 pub const BOLTZ_TESTNET_URL_V2: &str = "https://api.testnet.boltz.exchange/v2";
 pub const BOLTZ_MAINNET_URL_V2: &str = "https://api.boltz.exchange/v2";
 pub const BOLTZ_REGTEST: &str = "http://127.0.0.1:9001/v2";
@@ -187,6 +194,17 @@ impl SubmarineFees {
         self.boltz(invoice_amount_sat) + self.network()
     }
 
+    /// Largest invoice amount payable with a lockup of `lockup_amount` sats, without the limits.
+    pub fn max_invoice_amount(&self, lockup_amount: u64) -> Option<u64> {
+        let available = lockup_amount.checked_sub(self.network())?;
+        let mut invoice_amount = (available as f64 / (1.0 + self.percentage / 100.0)) as u64;
+        // Rounding of the Boltz fee can push the total just above the lockup
+        while invoice_amount > 0 && invoice_amount + self.total(invoice_amount) > lockup_amount {
+            invoice_amount -= 1;
+        }
+        Some(invoice_amount).filter(|amount| *amount > 0)
+    }
+
     pub fn boltz(&self, invoice_amount_sat: u64) -> u64 {
         ((self.percentage / 100.0) * invoice_amount_sat as f64).ceil() as u64
     }
@@ -209,6 +227,28 @@ pub struct ChainPair {
     pub fees: ChainFees,
 }
 
+impl ChainPair {
+    /// Largest user lockup amount out of `lockup_amount` available sats, e.g. the result of
+    /// [`crate::fees::max_lockup_amount`] for the whole wallet balance.
+    /// None if it is below the minimum of the pair, or nothing is left after the Boltz, server
+    /// lockup and claim fees. Capped at the maximum of the pair.
+    pub fn max_user_lock_amount(&self, lockup_amount: u64) -> Option<u64> {
+        let amount = lockup_amount.min(self.limits.maximal);
+        let fees = self.fees.boltz(amount) + self.fees.server() + self.fees.claim_estimate();
+        Some(amount).filter(|amount| *amount >= self.limits.minimal && *amount > fees)
+    }
+
+    /// Amount the server is expected to lock up for a user lockup of `user_lock_amount` sats,
+    /// after the Boltz fee and the server lockup fee. Used to check the quote of zero-amount and
+    /// over- or underpaid swaps. None if the user lockup doesn't cover the fees.
+    pub fn expected_server_lock_amount(&self, user_lock_amount: u64) -> Option<u64> {
+        let amount = (user_lock_amount as f64 * self.rate) as u64;
+        amount
+            .checked_sub(self.fees.boltz(amount) + self.fees.server())
+            .filter(|amount| *amount > 0)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ReversePair {
@@ -235,6 +275,18 @@ pub struct SubmarinePair {
     pub fees: SubmarineFees,
 }
 
+impl SubmarinePair {
+    /// Largest invoice amount that can be paid by locking up `lockup_amount` sats, e.g. the
+    /// result of [`crate::fees::max_lockup_amount`] for the whole wallet balance.
+    /// None if it is below the minimum of the pair. Capped at the maximum of the pair.
+    pub fn max_invoice_amount(&self, lockup_amount: u64) -> Option<u64> {
+        self.fees
+            .max_invoice_amount(lockup_amount)
+            .map(|amount| amount.min(self.limits.maximal))
+            .filter(|amount| *amount >= self.limits.minimal)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetSubmarinePairsResponse {
     #[serde(rename = "BTC")]
@@ -312,18 +364,74 @@ impl GetChainPairsResponse {
 }
 
 /// Reference Documnetation: <https://api.boltz.exchange/swagger>
+///
+/// The client is `Send + Sync` and cheap to clone: clones share the same HTTP connection pool,
+/// so a single client can be used by many threads.
 #[derive(Debug, Clone)]
 pub struct BoltzApiClientV2 {
     base_url: String,
+    agent: ureq::Agent,
+    timeouts: OperationTimeouts,
+    backoff: Backoff,
 }
 
 impl BoltzApiClientV2 {
     pub fn new(base_url: &str) -> Self {
+        Self::with_timeouts(base_url, OperationTimeouts::default())
+    }
+
+    /// Client whose agent uses the `connect` and `read` timeouts. Broadcasts and partial
+    /// signature requests are limited by `broadcast` and `cooperative_sign` instead of `read`.
+    pub fn with_timeouts(base_url: &str, timeouts: OperationTimeouts) -> Self {
+        let builder = AgentBuilder::new()
+            .timeout_connect(timeouts.connect)
+            .timeout_read(timeouts.read);
+        let agent = match native_tls::TlsConnector::new() {
+            // If native_tls is available, use that for TLS
+            // It has better handling of close_notify, which avoids some POST call failures
+            // See https://github.com/SatoshiPortal/boltz-rust/issues/39
+            Ok(tls_connector) => builder.tls_connector(Arc::new(tls_connector)).build(),
+            // If native_tls is not available, fallback to the default (rustls)
+            Err(_) => builder.build(),
+        };
         Self {
             base_url: base_url.to_string(),
+            agent,
+            timeouts,
+            backoff: Backoff::none(),
         }
     }
 
+    /// Use an existing agent for all requests, e.g. one built with the app's own middlewares
+    /// (metrics, logging), proxy or user agent via `ureq::AgentBuilder`.
+    /// The connect and read timeouts of the agent are kept, the per request timeouts of the
+    /// default [`OperationTimeouts`] still apply.
+    pub fn with_agent(base_url: &str, agent: ureq::Agent) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            agent,
+            timeouts: OperationTimeouts::default(),
+            backoff: Backoff::none(),
+        }
+    }
+
+    /// The agent used for all requests.
+    pub fn agent(&self) -> &ureq::Agent {
+        &self.agent
+    }
+
+    pub fn timeouts(&self) -> &OperationTimeouts {
+        &self.timeouts
+    }
+
+    /// Backoff for GET requests failing with a connection error, a 429 or a 5xx status.
+    /// POST requests are never retried, as they are not idempotent. Requests are not retried by
+    /// default, e.g. use [`Backoff::default`] to retry for up to a minute.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
     /// Returns the web socket connection to the boltz server
     pub fn connect_ws(&self) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, Error> {
         let ws_string = self.base_url.clone().replace("http", "ws") + "/ws";
@@ -335,48 +443,47 @@ impl BoltzApiClientV2 {
     /// Make a get request. returns the Response
     fn get(&self, end_point: &str) -> Result<String, Error> {
         let url = format!("{}/{}", self.base_url, end_point);
-        Ok(ureq::get(&url).call()?.into_string()?)
+        let response = self
+            .backoff
+            .retry(None, || self.agent.get(&url).call(), is_transient)?;
+        Ok(response.into_string()?)
     }
 
     /// Make a Post request. Returns the Response
     fn post(&self, end_point: &str, data: impl Serialize) -> Result<String, Error> {
-        let url = format!("{}/{}", self.base_url, end_point);
-        // Ok(ureq::post(&url).send_json(data)?.into_string()?)
+        self.post_with_timeout(end_point, data, None)
+    }
 
-        let response = match native_tls::TlsConnector::new() {
-            // If native_tls is available, use that for TLS
-            // It has better handling of close_notify, which avoids some POST call failures
-            // See https://github.com/SatoshiPortal/boltz-rust/issues/39
-            Ok(tls_connector) => {
-                let response = match AgentBuilder::new()
-                    .tls_connector(Arc::new(tls_connector))
-                    .build()
-                    .request("POST", &url)
-                    .send_json(data)
-                {
-                    Ok(r) => {
-                        log::debug!("POST response: {:#?}", r);
-                        r.into_string()?
-                    }
-                    Err(ureq_err) => {
-                        log::error!("POST error: {:#?}", ureq_err);
-                        let err = match ureq_err {
-                            ureq::Error::Status(_code, err_resp) => {
-                                let e_val: Value = serde_json::from_str(&err_resp.into_string()?)?;
-                                let e_str = e_val.get("error").unwrap_or(&Value::Null).to_string();
-                                Error::HTTP(e_str)
-                            }
-                            ureq::Error::Transport(_) => ureq_err.into(),
-                        };
-                        return Err(err);
+    /// Make a Post request, failing if the whole request takes longer than `timeout`.
+    fn post_with_timeout(
+        &self,
+        end_point: &str,
+        data: impl Serialize,
+        timeout: Option<Duration>,
+    ) -> Result<String, Error> {
+        let url = format!("{}/{}", self.base_url, end_point);
+        let mut request = self.agent.post(&url);
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+        match request.send_json(data) {
+            Ok(r) => {
+                log::debug!("POST response: {:#?}", r);
+                Ok(r.into_string()?)
+            }
+            Err(ureq_err) => {
+                log::error!("POST error: {:#?}", ureq_err);
+                let err = match ureq_err {
+                    ureq::Error::Status(_code, err_resp) => {
+                        let e_val: Value = serde_json::from_str(&err_resp.into_string()?)?;
+                        let e_str = e_val.get("error").unwrap_or(&Value::Null).to_string();
+                        Error::HTTP(e_str)
                     }
+                    ureq::Error::Transport(_) => ureq_err.into(),
                 };
-                response
+                Err(err)
             }
-            // If native_tls is not available, fallback to the default (rustls)
-            Err(_) => ureq::post(&url).send_json(data)?.into_string()?,
-        };
-        Ok(response)
+        }
     }
 
     pub fn get_fee_estimation(&self) -> Result<GetFeeEstimationResponse, Error> {
@@ -466,7 +573,11 @@ impl BoltzApiClientV2 {
             }
         );
         let endpoint = format!("swap/chain/{}/claim", id);
-        Ok(serde_json::from_str(&self.post(&endpoint, data)?)?)
+        Ok(serde_json::from_str(&self.post_with_timeout(
+            &endpoint,
+            data,
+            Some(self.timeouts.cooperative_sign),
+        )?)?)
     }
 
     pub fn get_reverse_tx(&self, id: &str) -> Result<ReverseSwapTxResp, Error> {
@@ -496,6 +607,7 @@ impl BoltzApiClientV2 {
     pub fn get_reverse_partial_sig(
         &self,
         id: &String,
+        input_index: usize,
         preimage: &Preimage,
         pub_nonce: &MusigPubNonce,
         claim_tx_hex: &String,
@@ -505,12 +617,16 @@ impl BoltzApiClientV2 {
                 "preimage": preimage.bytes.expect("expected").to_lower_hex_string(),
                 "pubNonce": pub_nonce.serialize().to_lower_hex_string(),
                 "transaction": claim_tx_hex,
-                "index": 0
+                "index": input_index
             }
         );
 
         let endpoint = format!("swap/reverse/{}/claim", id);
-        Ok(serde_json::from_str(&self.post(&endpoint, data)?)?)
+        Ok(serde_json::from_str(&self.post_with_timeout(
+            &endpoint,
+            data,
+            Some(self.timeouts.cooperative_sign),
+        )?)?)
     }
 
     pub fn get_submarine_partial_sig(
@@ -529,7 +645,11 @@ impl BoltzApiClientV2 {
         );
 
         let endpoint = format!("swap/submarine/{}/refund", id);
-        Ok(serde_json::from_str(&self.post(&endpoint, data)?)?)
+        Ok(serde_json::from_str(&self.post_with_timeout(
+            &endpoint,
+            data,
+            Some(self.timeouts.cooperative_sign),
+        )?)?)
     }
 
     pub fn get_chain_partial_sig(
@@ -548,7 +668,11 @@ impl BoltzApiClientV2 {
         );
 
         let endpoint = format!("swap/chain/{}/refund", id);
-        Ok(serde_json::from_str(&self.post(&endpoint, data)?)?)
+        Ok(serde_json::from_str(&self.post_with_timeout(
+            &endpoint,
+            data,
+            Some(self.timeouts.cooperative_sign),
+        )?)?)
     }
 
     pub fn get_mrh_bip21(&self, invoice: &str) -> Result<MrhResponse, Error> {
@@ -569,7 +693,11 @@ impl BoltzApiClientV2 {
         };
 
         let end_point = format!("chain/{}/transaction", chain);
-        Ok(serde_json::from_str(&self.post(&end_point, data)?)?)
+        Ok(serde_json::from_str(&self.post_with_timeout(
+            &end_point,
+            data,
+            Some(self.timeouts.broadcast),
+        )?)?)
     }
 
     /// Fetch an invoice for the specified BOLT12 offer
@@ -611,11 +739,84 @@ impl BoltzApiClientV2 {
         Ok(())
     }
 
+    /// Accept the quote for the `user_lock_amount` sats locked up for a zero-amount, or over- or
+    /// underpaid, chain swap. The quote is only accepted if Boltz locks up at least the amount
+    /// expected from the `pair` fees, less `tolerance` sats for fee changes since the pair was
+    /// fetched. Returns the accepted server lockup amount.
+    pub fn accept_lockup_quote(
+        &self,
+        swap_id: &str,
+        pair: &ChainPair,
+        user_lock_amount: u64,
+        tolerance: u64,
+    ) -> Result<u64, Error> {
+        let expected =
+            pair.expected_server_lock_amount(user_lock_amount)
+                .ok_or(Error::Protocol(format!(
+                    "Lockup of {} sats does not cover the swap fees",
+                    user_lock_amount
+                )))?;
+        let quote = self.get_quote(swap_id)?;
+        if quote.amount + tolerance < expected {
+            return Err(Error::AmountMismatch(format!(
+                "Quote of {} sats for swap {} is below the expected {} sats",
+                quote.amount, swap_id, expected
+            )));
+        }
+        self.accept_quote(swap_id, quote.amount)?;
+        Ok(quote.amount)
+    }
+
     /// Gets the latest status of the Swap
     pub fn get_swap(&self, swap_id: &str) -> Result<GetSwapResponse, Error> {
         let end_point = format!("swap/{swap_id}");
         Ok(serde_json::from_str(&self.get(&end_point)?)?)
     }
+
+    /// Swaps with a lockup that may still have to be refunded, among those whose refund keys
+    /// were derived from `xpub`, the rescue key of the wallet.
+    pub fn rescue_swaps(&self, xpub: &str) -> Result<Vec<RescuableSwap>, Error> {
+        let req = RescueRequest {
+            xpub: xpub.to_string(),
+        };
+        Ok(serde_json::from_str(&self.post("swap/rescue", req)?)?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RescueRequest {
+    pub xpub: String,
+}
+
+/// Lockup transaction of a [`RescuableSwap`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RescueTransaction {
+    pub id: String,
+    pub vout: u32,
+}
+
+/// A swap returned by [`BoltzApiClientV2::rescue_swaps`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RescuableSwap {
+    pub id: String,
+    /// "submarine" or "chain".
+    #[serde(rename = "type")]
+    pub swap_type: String,
+    pub status: String,
+    /// Currency of the lockup, "BTC" or "L-BTC".
+    pub symbol: String,
+    /// Derivation index of the refund key below the rescue xpub.
+    pub key_index: u32,
+    pub preimage_hash: String,
+    pub timeout_block_height: u32,
+    pub server_public_key: PublicKey,
+    pub blinding_key: Option<String>,
+    pub tree: SwapTree,
+    pub lockup_address: String,
+    /// None if the lockup was not broadcast.
+    pub transaction: Option<RescueTransaction>,
+    pub created_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -635,6 +836,22 @@ pub struct SubmarineClaimTxResponse {
     pub transaction_hash: String,
 }
 
+impl SubmarineClaimTxResponse {
+    /// Ensure the preimage sent by Boltz is the one of the invoice, i.e. the invoice was paid.
+    /// Returns the preimage as proof of payment.
+    pub fn validate_preimage(&self, invoice: &str) -> Result<Preimage, Error> {
+        let preimage = Preimage::from_str(&self.preimage)?;
+        let invoice_preimage = Preimage::from_invoice_str(invoice)?;
+        if preimage.sha256 != invoice_preimage.sha256 {
+            return Err(Error::Protocol(format!(
+                "Preimage hash mismatch. Invoice: {}, Preimage: {}",
+                invoice_preimage.sha256, preimage.sha256
+            )));
+        }
+        Ok(preimage)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MrhResponse {
@@ -683,7 +900,65 @@ pub struct CreateSubmarineResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub blinding_key: Option<String>,
 }
+
+/// Fees implied by a submarine swap, see [`CreateSubmarineResponse::fee_report`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmarineFeeReport {
+    pub invoice_amount: u64,
+    pub expected_amount: u64,
+    /// Fee paid by the user: `expected_amount - invoice_amount`.
+    pub implied_fee: u64,
+    /// Fee quoted by the pair for the invoice amount.
+    pub quoted_fee: u64,
+}
+
 impl CreateSubmarineResponse {
+    /// Reconcile the lockup amount requested by Boltz with the invoice and the pair quote.
+    /// Fails with [`Error::AmountMismatch`] if the implied fee exceeds the quoted fee by more than
+    /// `tolerance` sats. Call before funding the lockup.
+    pub fn fee_report(
+        &self,
+        invoice: &str,
+        pair: &SubmarinePair,
+        tolerance: u64,
+    ) -> Result<SubmarineFeeReport, Error> {
+        let invoice_msat = Bolt11Invoice::from_str(invoice)?
+            .amount_milli_satoshis()
+            .ok_or(Error::Protocol("Invoice has no amount".to_string()))?;
+        let invoice_amount = (invoice_msat + 999) / 1000;
+        let implied_fee =
+            self.expected_amount
+                .checked_sub(invoice_amount)
+                .ok_or(Error::AmountMismatch(format!(
+                    "Expected lockup of {} sats for swap {} is below the invoice amount of {} sats",
+                    self.expected_amount, self.id, invoice_amount
+                )))?;
+        let quoted_fee = pair.fees.total(invoice_amount);
+        if implied_fee > quoted_fee + tolerance {
+            log::warn!(
+                "Swap {} charges {} sats in fees, {} sats were quoted",
+                self.id,
+                implied_fee,
+                quoted_fee
+            );
+            return Err(Error::AmountMismatch(format!(
+                "Implied fee of {} sats for swap {} exceeds the quoted {} sats",
+                implied_fee, self.id, quoted_fee
+            )));
+        }
+        Ok(SubmarineFeeReport {
+            invoice_amount,
+            expected_amount: self.expected_amount,
+            implied_fee,
+            quoted_fee,
+        })
+    }
+
+    /// Payment request for the lockup: a BIP21 URI for BTC, or a liquid URI with asset id for L-BTC.
+    pub fn funding_request(&self, chain: Chain) -> Result<String, Error> {
+        funding_uri(chain, &self.address, self.expected_amount)
+    }
+
     /// Ensure submarine swap redeem script uses the preimage hash used in the invoice
     pub fn validate(
         &self,
@@ -727,21 +1002,57 @@ pub struct Leaf {
     pub version: u8,
 }
 
+/// Websocket channels of the Boltz API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Channel {
+    #[serde(rename = "swap.update")]
+    SwapUpdate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionOp {
+    Subscribe,
+    Unsubscribe,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Subscription {
-    op: String,
-    channel: String,
+    op: SubscriptionOp,
+    channel: Channel,
     args: Vec<String>,
 }
 
 impl Subscription {
     pub fn new(id: &str) -> Self {
+        Self::swap_updates(SubscriptionOp::Subscribe, &[id])
+    }
+
+    /// Stop receiving updates of a swap, e.g. once it reached a final status.
+    pub fn unsubscribe(id: &str) -> Self {
+        Self::swap_updates(SubscriptionOp::Unsubscribe, &[id])
+    }
+
+    /// Subscribe to or unsubscribe from the updates of several swaps in one message.
+    pub fn swap_updates(op: SubscriptionOp, ids: &[&str]) -> Self {
         Self {
-            op: "subscribe".to_string(),
-            channel: "swap.update".to_string(),
-            args: vec![id.to_owned()],
+            op,
+            channel: Channel::SwapUpdate,
+            args: ids.iter().map(|id| id.to_string()).collect(),
         }
     }
+
+    pub fn op(&self) -> SubscriptionOp {
+        self.op
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -780,6 +1091,11 @@ pub struct CreateReverseResponse {
     pub blinding_key: Option<String>,
 }
 impl CreateReverseResponse {
+    /// Payment request for the swap, which is the bolt11 invoice.
+    pub fn funding_request(&self) -> String {
+        self.invoice.clone()
+    }
+
     /// Validate reverse swap response
     /// Ensure reverse swap invoice uses the provided preimage
     /// Ensure reverse swap redeem script matches locally constructured SwapScript
@@ -812,7 +1128,8 @@ impl CreateReverseResponse {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Side {
     Lockup,
     Claim,
@@ -825,6 +1142,8 @@ pub struct ChainSwapDetails {
     pub lockup_address: String,
     pub server_public_key: PublicKey,
     pub timeout_block_height: u32,
+    /// Zero for the lockup of a zero-amount swap, whose amount is set by the user lockup.
+    #[serde(default)]
     pub amount: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub blinding_key: Option<String>,
@@ -836,6 +1155,40 @@ pub struct ChainSwapDetails {
     pub bip21: Option<String>,
 }
 
+/// Whether a failed request may succeed when retried.
+fn is_transient(error: &ureq::Error) -> bool {
+    match error {
+        ureq::Error::Transport(_) => true,
+        ureq::Error::Status(code, _) => *code == 429 || *code >= 500,
+    }
+}
+
+/// Wallet-agnostic URI paying `amount` sats to `address`. A zero amount is left out.
+fn funding_uri(chain: Chain, address: &str, amount: u64) -> Result<String, Error> {
+    let scheme = match chain {
+        Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => "bitcoin",
+        Chain::Liquid => "liquidnetwork",
+        Chain::LiquidTestnet | Chain::LiquidRegtest => "liquidtestnet",
+    };
+
+    let mut params = vec![];
+    if amount > 0 {
+        params.push(format!(
+            "amount={}",
+            bitcoin::Amount::from_sat(amount).to_string_in(bitcoin::Denomination::Bitcoin)
+        ));
+    }
+    if let Some(asset_id) = chain.lbtc_asset_id() {
+        params.push(format!("assetid={}", asset_id));
+    }
+
+    if params.is_empty() {
+        Ok(format!("{}:{}", scheme, address))
+    } else {
+        Ok(format!("{}:{}?{}", scheme, address, params.join("&")))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateChainRequest {
@@ -846,6 +1199,8 @@ pub struct CreateChainRequest {
     pub claim_public_key: Option<PublicKey>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub refund_public_key: Option<PublicKey>,
+    /// None, together with `server_lock_amount`, creates a zero-amount swap: the amounts are set
+    /// by whatever the user locks up, see [`BoltzApiClientV2::accept_lockup_quote`].
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_lock_amount: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -866,6 +1221,16 @@ pub struct CreateChainResponse {
     pub lockup_details: ChainSwapDetails,
 }
 impl CreateChainResponse {
+    /// Payment request for the user lockup on `from_chain`.
+    /// The amount is left out for zero-amount swaps.
+    pub fn funding_request(&self, from_chain: Chain) -> Result<String, Error> {
+        funding_uri(
+            from_chain,
+            &self.lockup_details.lockup_address,
+            self.lockup_details.amount,
+        )
+    }
+
     /// Validate chain swap response
     pub fn validate(
         &self,
@@ -1033,12 +1398,24 @@ pub enum SwapUpdate {
     },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum SwapTxKind {
     Claim,
     Refund,
 }
 
+/// Spend path of a swap input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpendPath {
+    /// Cooperative spend with the aggregated MuSig2 key.
+    KeyPath,
+    /// Script path spend of the claim leaf, revealing the preimage.
+    ClaimLeaf,
+    /// Script path spend of the refund leaf, after the timelock.
+    RefundLeaf,
+}
+
 /// States for a submarine swap.
 ///
 /// See <https://docs.boltz.exchange/v/api/lifecycle#normal-submarine-swaps>
@@ -1369,12 +1746,208 @@ pub struct GetSwapResponse {
     pub status: String,
     pub zero_conf_rejected: Option<bool>,
     pub transaction: Option<TransactionResponse>,
+    /// Reason given by Boltz for failed swaps.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+}
+
+impl GetSwapResponse {
+    /// Coarse phase of the swap, to decide when to poll next.
+    pub fn phase(&self, swap_type: SwapType) -> SwapPhase {
+        SwapPhase::from_status(swap_type, &self.status)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_subscription_messages() {
+        assert_eq!(
+            serde_json::to_string(&Subscription::new("abc")).unwrap(),
+            r#"{"op":"subscribe","channel":"swap.update","args":["abc"]}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Subscription::unsubscribe("abc")).unwrap(),
+            r#"{"op":"unsubscribe","channel":"swap.update","args":["abc"]}"#
+        );
+    }
+
+    #[test]
+    fn test_client_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<BoltzApiClientV2>();
+    }
+
+    #[test]
+    fn test_with_agent() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let body = r#"{"BTC":850000,"L-BTC":3000000}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let agent = AgentBuilder::new()
+            .middleware(move |req: ureq::Request, next: ureq::MiddlewareNext| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                next.handle(req)
+            })
+            .build();
+        let client = BoltzApiClientV2::with_agent(&url, agent);
+
+        assert_eq!(client.get_height().unwrap().btc, 850000);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_max_invoice_amount() {
+        let pair = SubmarinePair {
+            hash: "hash".to_string(),
+            rate: 1.0,
+            limits: PairLimits {
+                maximal: 25_000_000,
+                minimal: 1_000,
+                maximal_zero_conf: 0,
+            },
+            fees: SubmarineFees {
+                percentage: 0.1,
+                miner_fees: 300,
+            },
+        };
+        let invoice_amount = pair.max_invoice_amount(100_300).unwrap();
+        assert!(invoice_amount + pair.fees.total(invoice_amount) <= 100_300);
+        assert!(invoice_amount + 1 + pair.fees.total(invoice_amount + 1) > 100_300);
+        assert_eq!(pair.max_invoice_amount(1_000), None);
+        assert_eq!(pair.max_invoice_amount(100_000_000), Some(25_000_000));
+    }
+
+    #[test]
+    fn test_zero_amount_chain_swap() {
+        let pair = ChainPair {
+            hash: "hash".to_string(),
+            rate: 1.0,
+            limits: PairLimits {
+                maximal: 25_000_000,
+                minimal: 25_000,
+                maximal_zero_conf: 0,
+            },
+            fees: ChainFees {
+                percentage: 0.1,
+                miner_fees: ChainMinerFees {
+                    server: 500,
+                    user: PairMinerFees {
+                        lockup: 300,
+                        claim: 200,
+                    },
+                },
+            },
+        };
+        assert_eq!(pair.expected_server_lock_amount(100_000), Some(99_400));
+        assert_eq!(pair.expected_server_lock_amount(500), None);
+
+        let details: ChainSwapDetails = serde_json::from_value(serde_json::json!({
+            "swapTree": {
+                "claimLeaf": { "version": 192, "output": "" },
+                "refundLeaf": { "version": 192, "output": "" }
+            },
+            "lockupAddress": "bc1q",
+            "serverPublicKey": "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "timeoutBlockHeight": 850000
+        }))
+        .unwrap();
+        assert_eq!(details.amount, 0);
+    }
+
+    #[test]
+    fn test_submarine_fee_report() {
+        let pair = SubmarinePair {
+            hash: "hash".to_string(),
+            rate: 1.0,
+            limits: PairLimits {
+                maximal: 25_000_000,
+                minimal: 100,
+                maximal_zero_conf: 0,
+            },
+            fees: SubmarineFees {
+                percentage: 0.1,
+                miner_fees: 100,
+            },
+        };
+        // 400 sats
+        let invoice = "lnbc4u1pnsywcypp5eamm4c3v42vlyr0asmt55muv02zusjp2dy7j6e3kuz5vv3cuyj6scqpjsp56hujjsj4r76gp9gk6y435rz99682uxjx924a06wwqm0av6ezxepq9q7sqqqqqqqqqqqqqqqqqqqsqqqqqysgqdqqmqz9gxqyjw5qrzjqwryaup9lh50kkranzgcdnn2fgvx390wgj5jd07rwr3vxeje0glcllm8u4a8gvusysqqqqlgqqqqqeqqjqtgxt57vzea9xaygxu806xf7w5872n737ptuc6al0plf3544a2f5y2e42j9qv7gvkqkn9k2yxzmew6rr40z2gyq9nu8atj2yt4dlfm3gpjevcgu";
+        let response = |expected_amount: u64| -> CreateSubmarineResponse {
+            serde_json::from_value(serde_json::json!({
+                "acceptZeroConf": false,
+                "address": "bc1q",
+                "bip21": "bitcoin:bc1q",
+                "claimPublicKey": "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                "expectedAmount": expected_amount,
+                "id": "abc",
+                "swapTree": {
+                    "claimLeaf": { "version": 192, "output": "" },
+                    "refundLeaf": { "version": 192, "output": "" }
+                },
+                "timeoutBlockHeight": 850000
+            }))
+            .unwrap()
+        };
+
+        let report = response(501).fee_report(invoice, &pair, 0).unwrap();
+        assert_eq!(report.invoice_amount, 400);
+        assert_eq!(report.implied_fee, 101);
+        assert_eq!(report.quoted_fee, 101);
+
+        assert!(response(510).fee_report(invoice, &pair, 10).is_ok());
+        assert!(matches!(
+            response(512).fee_report(invoice, &pair, 10),
+            Err(Error::AmountMismatch(_))
+        ));
+        assert!(response(300).fee_report(invoice, &pair, 10).is_err());
+    }
+
+    #[test]
+    fn test_funding_uri() {
+        assert_eq!(
+            funding_uri(Chain::Bitcoin, "bc1qaddress", 150_000).unwrap(),
+            "bitcoin:bc1qaddress?amount=0.0015"
+        );
+        assert_eq!(
+            funding_uri(Chain::BitcoinTestnet, "tb1qaddress", 0).unwrap(),
+            "bitcoin:tb1qaddress"
+        );
+
+        let uri = funding_uri(Chain::Liquid, "lq1qaddress", 25_000).unwrap();
+        let (network, address, amount, asset_id) =
+            crate::swaps::magic_routing::parse_bip21(&uri).unwrap();
+        assert_eq!(network, "liquidnetwork");
+        assert_eq!(address, "lq1qaddress");
+        assert_eq!(amount.to_sat(), 25_000);
+        assert_eq!(
+            asset_id.as_deref(),
+            Some(crate::network::LBTC_MAINNET_ASSET_ID)
+        );
+    }
+
     #[test]
     fn test_get_fee_estimation() {
         let client = BoltzApiClientV2::new(BOLTZ_MAINNET_URL_V2);
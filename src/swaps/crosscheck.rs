@@ -0,0 +1,83 @@
+//! Sanity checks of data reported by Boltz against chain data.
+//!
+//! When the chain client can't find a swap lockup, the lockup transaction is fetched from Boltz
+//! instead. Before spending from it, the reported output is compared with what the chain client
+//! returns for the same txid. Chain data is the source of truth: any discrepancy is an error.
+//! If the chain client can't be queried at all, the Boltz data is used unverified.
+
+use std::fmt::Debug;
+
+use crate::error::Error;
+
+/// Outcome of [`check_boltz_lockup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockupCheck {
+    /// The chain client returned the same output.
+    Match,
+    /// The chain client could not be queried, the Boltz data was not verified.
+    Unverified,
+}
+
+/// Compare a lockup output reported by Boltz with `chain_output`, the output at the same
+/// outpoint as returned by the chain client (None if the transaction has no such output).
+pub fn check_boltz_lockup<T: PartialEq + Debug>(
+    swap_id: &str,
+    txid: &str,
+    vout: u32,
+    boltz_output: &T,
+    chain_output: Result<Option<T>, Error>,
+) -> Result<LockupCheck, Error> {
+    match chain_output {
+        Ok(Some(chain_output)) if chain_output == *boltz_output => Ok(LockupCheck::Match),
+        Ok(chain_output) => {
+            log::error!(
+                "Lockup {}:{} of swap {} reported by Boltz differs from chain data. Boltz: {:?}, chain: {:?}",
+                txid,
+                vout,
+                swap_id,
+                boltz_output,
+                chain_output
+            );
+            Err(Error::Protocol(format!(
+                "Lockup {}:{} of swap {} reported by Boltz does not match chain data",
+                txid, vout, swap_id
+            )))
+        }
+        Err(e) => {
+            log::warn!(
+                "Could not verify lockup {}:{} of swap {} reported by Boltz: {:?}",
+                txid,
+                vout,
+                swap_id,
+                e
+            );
+            Ok(LockupCheck::Unverified)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_boltz_lockup() {
+        assert_eq!(
+            check_boltz_lockup("id", "txid", 0, &1000u64, Ok(Some(1000))).unwrap(),
+            LockupCheck::Match
+        );
+        assert!(check_boltz_lockup("id", "txid", 0, &1000u64, Ok(Some(999))).is_err());
+        assert!(check_boltz_lockup("id", "txid", 0, &1000u64, Ok(None)).is_err());
+        assert_eq!(
+            check_boltz_lockup(
+                "id",
+                "txid",
+                0,
+                &1000u64,
+                Err(Error::Generic("offline".to_string()))
+            )
+            .unwrap(),
+            LockupCheck::Unverified
+        );
+    }
+}
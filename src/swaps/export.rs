@@ -0,0 +1,401 @@
+//! Interchange format for in-flight swaps.
+//!
+//! A [`SwapExport`] carries everything another app built on this crate needs to finish, claim or
+//! refund a pending swap: the Boltz creation response, the swap keys and the preimage.
+//! It is serialized as json:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "id": "<boltz swap id>",
+//!   "swapType": "submarine" | "reversesubmarine" | "chain",
+//!   "chain": "Bitcoin" | "BitcoinTestnet" | "BitcoinRegtest" | "Liquid" | "LiquidTestnet" | "LiquidRegtest",
+//!   "toChain": "<claim chain, chain swaps only>",
+//!   "boltzUrl": "https://api.boltz.exchange/v2",
+//!   "claimKey": "<hex secret key, reverse and chain swaps>",
+//!   "refundKey": "<hex secret key, submarine and chain swaps>",
+//!   "preimage": "<hex preimage, reverse and chain swaps>",
+//!   "invoice": "<bolt11 invoice, submarine swaps>",
+//!   "response": { "<the create swap response returned by Boltz>" },
+//!   "createdAt": 1700000000
+//! }
+//! ```
+//!
+//! `chain` is the onchain side of submarine and reverse swaps, and the lockup side of chain swaps.
+//! The `version` is bumped on every incompatible change, readers reject versions they don't know.
+//! Exports contain private keys and must be handled like a wallet backup.
+
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::{
+    hex::DisplayHex,
+    key::{Keypair, Secp256k1},
+    PublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Error, network::Chain, util::secrets::Preimage};
+
+use super::boltz::{CreateChainResponse, CreateReverseResponse, CreateSubmarineResponse, SwapType};
+
+/// Version of the export format written by this library.
+pub const SWAP_EXPORT_VERSION: u32 = 1;
+
+/// A pending swap, as exchanged between apps.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapExport {
+    pub version: u32,
+    pub id: String,
+    pub swap_type: SwapType,
+    pub chain: Chain,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_chain: Option<Chain>,
+    pub boltz_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refund_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preimage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invoice: Option<String>,
+    pub response: serde_json::Value,
+    /// Unix timestamp of the export.
+    pub created_at: u64,
+}
+
+/// Outcome of comparing an import against the swap already stored under the same id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportCheck {
+    /// No swap with this id is known.
+    New,
+    /// The same swap was already imported, nothing to do.
+    AlreadyImported,
+    /// A different swap is stored under this id. Carries the names of the differing fields.
+    Conflict(Vec<&'static str>),
+}
+
+impl SwapExport {
+    pub fn submarine(
+        chain: Chain,
+        boltz_url: &str,
+        refund_keys: &Keypair,
+        invoice: &str,
+        response: &CreateSubmarineResponse,
+    ) -> Result<Self, Error> {
+        Ok(SwapExport {
+            refund_key: Some(secret_hex(refund_keys)),
+            invoice: Some(invoice.to_string()),
+            ..Self::empty(
+                &response.id,
+                SwapType::Submarine,
+                chain,
+                boltz_url,
+                serde_json::to_value(response)?,
+            )?
+        })
+    }
+
+    pub fn reverse(
+        chain: Chain,
+        boltz_url: &str,
+        claim_keys: &Keypair,
+        preimage: &Preimage,
+        response: &CreateReverseResponse,
+    ) -> Result<Self, Error> {
+        Ok(SwapExport {
+            claim_key: Some(secret_hex(claim_keys)),
+            preimage: Some(preimage_hex(preimage)?),
+            ..Self::empty(
+                &response.id,
+                SwapType::ReverseSubmarine,
+                chain,
+                boltz_url,
+                serde_json::to_value(response)?,
+            )?
+        })
+    }
+
+    pub fn chain(
+        from_chain: Chain,
+        to_chain: Chain,
+        boltz_url: &str,
+        claim_keys: &Keypair,
+        refund_keys: &Keypair,
+        preimage: &Preimage,
+        response: &CreateChainResponse,
+    ) -> Result<Self, Error> {
+        Ok(SwapExport {
+            to_chain: Some(to_chain),
+            claim_key: Some(secret_hex(claim_keys)),
+            refund_key: Some(secret_hex(refund_keys)),
+            preimage: Some(preimage_hex(preimage)?),
+            ..Self::empty(
+                &response.id,
+                SwapType::Chain,
+                from_chain,
+                boltz_url,
+                serde_json::to_value(response)?,
+            )?
+        })
+    }
+
+    fn empty(
+        id: &str,
+        swap_type: SwapType,
+        chain: Chain,
+        boltz_url: &str,
+        response: serde_json::Value,
+    ) -> Result<Self, Error> {
+        Ok(SwapExport {
+            version: SWAP_EXPORT_VERSION,
+            id: id.to_string(),
+            swap_type,
+            chain,
+            to_chain: None,
+            boltz_url: boltz_url.to_string(),
+            claim_key: None,
+            refund_key: None,
+            preimage: None,
+            invoice: None,
+            response,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| Error::Generic(e.to_string()))?
+                .as_secs(),
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse and validate an export. Fails on unknown versions and on swaps that don't match
+    /// the included keys and preimage.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(version) if version == SWAP_EXPORT_VERSION as u64 => {}
+            Some(version) => {
+                return Err(Error::Protocol(format!(
+                    "Unsupported swap export version {}, expected {}",
+                    version, SWAP_EXPORT_VERSION
+                )))
+            }
+            None => {
+                return Err(Error::Protocol(
+                    "Swap export is missing its version".to_string(),
+                ))
+            }
+        }
+        let export: SwapExport = serde_json::from_value(value)?;
+        export.validate()?;
+        Ok(export)
+    }
+
+    /// Check that the Boltz response matches the swap id, keys and preimage of the export,
+    /// by rebuilding the swap scripts and comparing them to the lockup addresses.
+    pub fn validate(&self) -> Result<(), Error> {
+        match self.swap_type {
+            SwapType::Submarine => {
+                let response = self.submarine_response()?;
+                self.check_id(&response.id)?;
+                let invoice = self.invoice.as_ref().ok_or(missing("invoice"))?;
+                response.validate(invoice, &self.refund_public_key()?, self.chain)
+            }
+            SwapType::ReverseSubmarine => {
+                let response = self.reverse_response()?;
+                self.check_id(&response.id)?;
+                response.validate(&self.preimage()?, &self.claim_public_key()?, self.chain)
+            }
+            SwapType::Chain => {
+                let response = self.chain_response()?;
+                self.check_id(&response.id)?;
+                let to_chain = self.to_chain.ok_or(missing("toChain"))?;
+                self.preimage()?;
+                response.validate(
+                    &self.claim_public_key()?,
+                    &self.refund_public_key()?,
+                    self.chain,
+                    to_chain,
+                )
+            }
+        }
+    }
+
+    /// Compare this import to the swap already stored under the same id, if any.
+    pub fn check_import(&self, existing: Option<&SwapExport>) -> ImportCheck {
+        let existing = match existing {
+            Some(existing) if existing.id == self.id => existing,
+            _ => return ImportCheck::New,
+        };
+        let mut differing = vec![];
+        if existing.swap_type != self.swap_type {
+            differing.push("swapType");
+        }
+        if existing.chain != self.chain || existing.to_chain != self.to_chain {
+            differing.push("chain");
+        }
+        if existing.claim_key != self.claim_key {
+            differing.push("claimKey");
+        }
+        if existing.refund_key != self.refund_key {
+            differing.push("refundKey");
+        }
+        if existing.preimage != self.preimage {
+            differing.push("preimage");
+        }
+        if existing.invoice != self.invoice {
+            differing.push("invoice");
+        }
+        if existing.response != self.response {
+            differing.push("response");
+        }
+        if differing.is_empty() {
+            ImportCheck::AlreadyImported
+        } else {
+            ImportCheck::Conflict(differing)
+        }
+    }
+
+    pub fn submarine_response(&self) -> Result<CreateSubmarineResponse, Error> {
+        Ok(serde_json::from_value(self.response.clone())?)
+    }
+
+    pub fn reverse_response(&self) -> Result<CreateReverseResponse, Error> {
+        Ok(serde_json::from_value(self.response.clone())?)
+    }
+
+    pub fn chain_response(&self) -> Result<CreateChainResponse, Error> {
+        Ok(serde_json::from_value(self.response.clone())?)
+    }
+
+    pub fn claim_keys(&self) -> Result<Keypair, Error> {
+        parse_keys(self.claim_key.as_ref().ok_or(missing("claimKey"))?)
+    }
+
+    pub fn refund_keys(&self) -> Result<Keypair, Error> {
+        parse_keys(self.refund_key.as_ref().ok_or(missing("refundKey"))?)
+    }
+
+    pub fn preimage(&self) -> Result<Preimage, Error> {
+        Preimage::from_str(self.preimage.as_ref().ok_or(missing("preimage"))?)
+    }
+
+    fn claim_public_key(&self) -> Result<PublicKey, Error> {
+        Ok(PublicKey::new(self.claim_keys()?.public_key()))
+    }
+
+    fn refund_public_key(&self) -> Result<PublicKey, Error> {
+        Ok(PublicKey::new(self.refund_keys()?.public_key()))
+    }
+
+    fn check_id(&self, response_id: &str) -> Result<(), Error> {
+        if response_id != self.id {
+            return Err(Error::Protocol(format!(
+                "Swap export id {} does not match response id {}",
+                self.id, response_id
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn missing(field: &str) -> Error {
+    Error::Protocol(format!("Swap export is missing {}", field))
+}
+
+fn secret_hex(keys: &Keypair) -> String {
+    keys.secret_bytes().to_lower_hex_string()
+}
+
+fn preimage_hex(preimage: &Preimage) -> Result<String, Error> {
+    Ok(preimage
+        .bytes
+        .ok_or(Error::Protocol("Preimage bytes are required".to_string()))?
+        .to_lower_hex_string())
+}
+
+fn parse_keys(secret: &str) -> Result<Keypair, Error> {
+    Ok(Keypair::from_seckey_str(&Secp256k1::new(), secret)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::key::rand::thread_rng;
+
+    fn export() -> SwapExport {
+        let keys = Keypair::new(&Secp256k1::new(), &mut thread_rng());
+        let preimage = Preimage::new();
+        SwapExport {
+            claim_key: Some(secret_hex(&keys)),
+            preimage: Some(preimage_hex(&preimage).unwrap()),
+            ..SwapExport::empty(
+                "abc",
+                SwapType::ReverseSubmarine,
+                Chain::Liquid,
+                "https://api.boltz.exchange/v2",
+                serde_json::json!({ "id": "abc" }),
+            )
+            .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_versioning() {
+        let mut value = serde_json::to_value(export()).unwrap();
+        value["version"] = serde_json::json!(SWAP_EXPORT_VERSION + 1);
+        let err = SwapExport::from_json(&value.to_string()).unwrap_err();
+        assert!(err.message().contains("Unsupported swap export version"));
+
+        value.as_object_mut().unwrap().remove("version");
+        assert!(SwapExport::from_json(&value.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_keys() {
+        let export = export();
+        let parsed: SwapExport = serde_json::from_str(&export.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, export);
+        assert_eq!(
+            secret_hex(&parsed.claim_keys().unwrap()),
+            export.claim_key.clone().unwrap()
+        );
+        assert!(parsed.refund_keys().is_err());
+        assert_eq!(
+            parsed.preimage().unwrap().bytes,
+            Preimage::from_str(export.preimage.as_ref().unwrap())
+                .unwrap()
+                .bytes
+        );
+    }
+
+    #[test]
+    fn test_check_import() {
+        let export = export();
+        assert_eq!(export.check_import(None), ImportCheck::New);
+        assert_eq!(
+            export.check_import(Some(&export)),
+            ImportCheck::AlreadyImported
+        );
+
+        let mut other = export.clone();
+        other.created_at += 10;
+        assert_eq!(
+            export.check_import(Some(&other)),
+            ImportCheck::AlreadyImported
+        );
+
+        other.preimage = Some(preimage_hex(&Preimage::new()).unwrap());
+        assert_eq!(
+            export.check_import(Some(&other)),
+            ImportCheck::Conflict(vec!["preimage"])
+        );
+
+        other.id = "def".to_string();
+        assert_eq!(export.check_import(Some(&other)), ImportCheck::New);
+    }
+}
@@ -0,0 +1,289 @@
+//! Status history of a swap.
+//!
+//! Boltz only returns the current status of a swap (see [`BoltzApiClientV2::get_swap`]). To be able
+//! to reconstruct what happened to a swap after the fact, apps record every transition they see in
+//! a [`StatusHistory`] and persist it next to the swap, e.g. as json.
+//!
+//! Together with the claim broadcast and confirmation recorded by the app, the history also gives
+//! the latency of each phase of the swap, see [`StatusHistory::latencies`].
+//!
+//! [`BoltzApiClientV2::get_swap`]: super::boltz::BoltzApiClientV2::get_swap
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::util::unix_now;
+
+use super::boltz::{GetSwapResponse, SwapType};
+use super::watcher::WatcherEvent;
+
+/// Where a status transition was observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransitionSource {
+    Websocket,
+    Rest,
+    /// Skipped while disconnected and inferred from the happy path.
+    Synthesized,
+}
+
+/// A single status transition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub status: String,
+    /// Unix timestamp in seconds at which the transition was observed.
+    pub timestamp: u64,
+    pub source: TransitionSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+}
+
+/// Phases of a swap whose latency is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyPhase {
+    /// From swap creation until the lockup transaction is seen.
+    LockupSeen,
+    /// From the lockup transaction being seen until it is confirmed.
+    LockupConfirmed,
+    /// From the lockup confirmation until the claim is broadcast.
+    ClaimBroadcast,
+    /// From the claim broadcast until it is confirmed.
+    ClaimConfirmed,
+}
+
+impl LatencyPhase {
+    pub const ALL: [LatencyPhase; 4] = [
+        LatencyPhase::LockupSeen,
+        LatencyPhase::LockupConfirmed,
+        LatencyPhase::ClaimBroadcast,
+        LatencyPhase::ClaimConfirmed,
+    ];
+
+    /// Label used in metrics and logs.
+    pub fn label(&self) -> &'static str {
+        match self {
+            LatencyPhase::LockupSeen => "lockup_seen",
+            LatencyPhase::LockupConfirmed => "lockup_confirmed",
+            LatencyPhase::ClaimBroadcast => "claim_broadcast",
+            LatencyPhase::ClaimConfirmed => "claim_confirmed",
+        }
+    }
+}
+
+/// All status transitions observed for a swap, oldest first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusHistory {
+    pub swap_id: String,
+    pub swap_type: SwapType,
+    pub transitions: Vec<StatusTransition>,
+    /// Unix timestamp at which the app broadcast its claim, for swaps claimed by the app.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claim_broadcast_at: Option<u64>,
+    /// Unix timestamp at which the app saw its claim confirm.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claim_confirmed_at: Option<u64>,
+}
+
+impl StatusHistory {
+    pub fn new(swap_id: &str, swap_type: SwapType) -> Self {
+        StatusHistory {
+            swap_id: swap_id.to_string(),
+            swap_type,
+            transitions: vec![],
+            claim_broadcast_at: None,
+            claim_confirmed_at: None,
+        }
+    }
+
+    /// The last recorded status.
+    pub fn current(&self) -> Option<&StatusTransition> {
+        self.transitions.last()
+    }
+
+    /// Record a transition. Returns false, and records nothing, if the status did not change.
+    pub fn record(&mut self, transition: StatusTransition) -> bool {
+        if self.current().map(|t| &t.status) == Some(&transition.status) {
+            return false;
+        }
+        self.transitions.push(transition);
+        true
+    }
+
+    /// Record the status returned by `get_swap`.
+    pub fn record_response(&mut self, response: &GetSwapResponse) -> Result<bool, Error> {
+        Ok(self.record(StatusTransition {
+            status: response.status.clone(),
+            timestamp: unix_now()?,
+            source: TransitionSource::Rest,
+            txid: response.transaction.as_ref().map(|tx| tx.id.clone()),
+            failure_reason: response.failure_reason.clone(),
+        }))
+    }
+
+    /// Record a status update of this swap emitted by the [`super::watcher::SwapWatcher`].
+    /// Other events, and updates of other swaps, are ignored.
+    pub fn record_event(&mut self, event: &WatcherEvent) -> Result<bool, Error> {
+        let (update, source) = match event {
+            WatcherEvent::Update(update) => (update, TransitionSource::Websocket),
+            WatcherEvent::Synthesized(update) => (update, TransitionSource::Synthesized),
+            _ => return Ok(false),
+        };
+        if update.id != self.swap_id {
+            return Ok(false);
+        }
+        Ok(self.record(StatusTransition {
+            status: update.status.clone(),
+            timestamp: unix_now()?,
+            source,
+            txid: update.transaction.as_ref().map(|tx| tx.id.clone()),
+            failure_reason: None,
+        }))
+    }
+
+    /// Record that the app broadcast its claim of the swap.
+    pub fn record_claim_broadcast(&mut self) -> Result<(), Error> {
+        self.claim_broadcast_at = Some(unix_now()?);
+        Ok(())
+    }
+
+    /// Record that the claim of the swap confirmed, and log the latency breakdown.
+    pub fn record_claim_confirmed(&mut self) -> Result<(), Error> {
+        self.claim_confirmed_at = Some(unix_now()?);
+        let breakdown: Vec<String> = self
+            .latencies()
+            .iter()
+            .map(|(phase, secs)| format!("{}={}s", phase.label(), secs))
+            .collect();
+        log::info!(
+            "Swap {} completed, latencies: {}",
+            self.swap_id,
+            breakdown.join(" ")
+        );
+        Ok(())
+    }
+
+    /// Seconds spent in each [`LatencyPhase`] that has both its start and end recorded.
+    /// Claims of submarine and chain swaps by Boltz are taken from the status history, claims
+    /// by the app from [`Self::record_claim_broadcast`] and [`Self::record_claim_confirmed`].
+    pub fn latencies(&self) -> Vec<(LatencyPhase, u64)> {
+        let first = |statuses: &[&str]| {
+            self.transitions
+                .iter()
+                .find(|t| statuses.contains(&t.status.as_str()))
+                .map(|t| t.timestamp)
+        };
+        let created = first(&["swap.created"]).or(self.transitions.first().map(|t| t.timestamp));
+        let lockup_seen = first(&["transaction.mempool"]);
+        let lockup_confirmed = first(&["transaction.confirmed"]);
+        let claim_broadcast = self
+            .claim_broadcast_at
+            .or(first(&["transaction.claim.pending"]));
+        let claim_confirmed = self.claim_confirmed_at.or(first(&["transaction.claimed"]));
+
+        let bounds = [
+            (LatencyPhase::LockupSeen, created, lockup_seen),
+            (LatencyPhase::LockupConfirmed, lockup_seen, lockup_confirmed),
+            (
+                LatencyPhase::ClaimBroadcast,
+                lockup_confirmed,
+                claim_broadcast,
+            ),
+            (
+                LatencyPhase::ClaimConfirmed,
+                claim_broadcast,
+                claim_confirmed,
+            ),
+        ];
+        bounds
+            .iter()
+            .filter_map(|(phase, start, end)| {
+                Some((*phase, end.as_ref()?.saturating_sub(*start.as_ref()?)))
+            })
+            .collect()
+    }
+
+    /// Seconds spent in each status, in order. The current status is counted until `now`.
+    pub fn durations(&self, now: u64) -> Vec<(&str, u64)> {
+        self.transitions
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let end = self
+                    .transitions
+                    .get(i + 1)
+                    .map(|next| next.timestamp)
+                    .unwrap_or(now);
+                (t.status.as_str(), end.saturating_sub(t.timestamp))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transition(status: &str, timestamp: u64) -> StatusTransition {
+        StatusTransition {
+            status: status.to_string(),
+            timestamp,
+            source: TransitionSource::Websocket,
+            txid: None,
+            failure_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_status_history() {
+        let mut history = StatusHistory::new("abc", SwapType::Submarine);
+        assert!(history.record(transition("swap.created", 100)));
+        assert!(!history.record(transition("swap.created", 110)));
+        assert!(history.record(transition("invoice.set", 130)));
+        assert_eq!(history.current().unwrap().status, "invoice.set");
+        assert_eq!(
+            history.durations(200),
+            vec![("swap.created", 30), ("invoice.set", 70)]
+        );
+
+        let json = serde_json::to_string(&history).unwrap();
+        assert_eq!(
+            serde_json::from_str::<StatusHistory>(&json).unwrap(),
+            history
+        );
+    }
+
+    #[test]
+    fn test_latencies() {
+        let mut history = StatusHistory::new("abc", SwapType::ReverseSubmarine);
+        history.record(transition("swap.created", 100));
+        history.record(transition("transaction.mempool", 160));
+        assert_eq!(history.latencies(), vec![(LatencyPhase::LockupSeen, 60)]);
+
+        history.record(transition("transaction.confirmed", 760));
+        history.claim_broadcast_at = Some(765);
+        history.claim_confirmed_at = Some(1_365);
+        assert_eq!(
+            history.latencies(),
+            vec![
+                (LatencyPhase::LockupSeen, 60),
+                (LatencyPhase::LockupConfirmed, 600),
+                (LatencyPhase::ClaimBroadcast, 5),
+                (LatencyPhase::ClaimConfirmed, 600),
+            ]
+        );
+
+        let mut submarine = StatusHistory::new("def", SwapType::Submarine);
+        submarine.record(transition("transaction.confirmed", 100));
+        submarine.record(transition("transaction.claim.pending", 130));
+        submarine.record(transition("transaction.claimed", 700));
+        assert_eq!(
+            submarine.latencies(),
+            vec![
+                (LatencyPhase::ClaimBroadcast, 30),
+                (LatencyPhase::ClaimConfirmed, 570)
+            ]
+        );
+    }
+}
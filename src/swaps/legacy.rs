@@ -0,0 +1,462 @@
+//! Recovery of legacy (v1) swaps.
+//!
+//! Before taproot, Boltz swaps locked funds in a P2WSH or P2SH-P2WSH output of an HTLC redeem
+//! script, which older versions of this library and the Boltz web app saved in refund files.
+//! [`LegacySwapScript`] parses those redeem scripts, and [`LegacyRefundTx`] refunds the lockup of
+//! a Submarine swap once its timeout expired. Legacy swaps can't be refunded cooperatively.
+
+use bitcoin::absolute::LockTime;
+use bitcoin::blockdata::script::{Builder, Instruction, PushBytesBuf, ScriptBuf};
+use bitcoin::hashes::{hash160, Hash};
+use bitcoin::opcodes::all::{
+    OP_CHECKSIG, OP_CLTV, OP_DROP, OP_ELSE, OP_ENDIF, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_IF,
+    OP_SIZE,
+};
+use bitcoin::secp256k1::{Keypair, Message, Secp256k1};
+use bitcoin::sighash::SighashCache;
+use bitcoin::transaction::Version;
+use bitcoin::{
+    ecdsa, Address, Amount, EcdsaSighashType, OutPoint, PublicKey, Sequence, Transaction, TxIn,
+    TxOut, Txid, Witness,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::network::{electrum::ElectrumConfig, Chain};
+use crate::util::fees::{create_tx_with_fee, Fee, TxLimits, BITCOIN_MIN_RELAY_FEE_RATE};
+
+use super::bitcoin::{bytes_to_u32_little_endian, checked_refund_address, BtcSwapScript};
+use super::boltz::SwapType;
+
+/// Pre-taproot HTLC of a Boltz swap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacySwapScript {
+    /// Submarine or ReverseSubmarine, the only swap types of v1.
+    pub swap_type: SwapType,
+    pub hashlock: hash160::Hash,
+    /// Key of the claim path.
+    pub receiver_pubkey: PublicKey,
+    /// Key of the refund path.
+    pub sender_pubkey: PublicKey,
+    pub locktime: LockTime,
+}
+
+impl LegacySwapScript {
+    /// Parse a hex encoded v1 redeem script.
+    pub fn from_redeem_script(redeem_script: &str) -> Result<Self, Error> {
+        let script = ScriptBuf::from_hex(redeem_script)?;
+        let instructions: Vec<Instruction> = script
+            .instructions()
+            .collect::<Result<_, _>>()
+            .map_err(|e| Error::Protocol(format!("Invalid redeem script: {}", e)))?;
+        let pushes: Vec<&[u8]> = instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::PushBytes(bytes) => Some(bytes.as_bytes()),
+                Instruction::Op(_) => None,
+            })
+            .collect();
+
+        // Reverse swaps check the preimage size first, which pushes an extra 32
+        let (swap_type, pushes) = match instructions.first() {
+            Some(Instruction::Op(op)) if *op == OP_SIZE => {
+                (SwapType::ReverseSubmarine, pushes.get(1..).unwrap_or(&[]))
+            }
+            _ => (SwapType::Submarine, &pushes[..]),
+        };
+        let (hashlock, receiver_pubkey, locktime, sender_pubkey) = match pushes {
+            [hashlock, receiver_pubkey, locktime, sender_pubkey] => {
+                (hashlock, receiver_pubkey, locktime, sender_pubkey)
+            }
+            _ => return Err(not_legacy_script()),
+        };
+        if locktime.len() > 4 {
+            return Err(not_legacy_script());
+        }
+
+        let parsed = LegacySwapScript {
+            swap_type,
+            hashlock: hash160::Hash::from_slice(hashlock)?,
+            receiver_pubkey: PublicKey::from_slice(receiver_pubkey)
+                .map_err(|e| Error::Protocol(format!("Invalid public key: {}", e)))?,
+            sender_pubkey: PublicKey::from_slice(sender_pubkey)
+                .map_err(|e| Error::Protocol(format!("Invalid public key: {}", e)))?,
+            locktime: LockTime::from_consensus(bytes_to_u32_little_endian(locktime)),
+        };
+        // The pushes were in the right places only if the script is rebuilt exactly
+        if parsed.redeem_script() != script {
+            return Err(not_legacy_script());
+        }
+        Ok(parsed)
+    }
+
+    /// The v1 redeem script.
+    pub fn redeem_script(&self) -> ScriptBuf {
+        match self.swap_type {
+            SwapType::ReverseSubmarine => Builder::new()
+                .push_opcode(OP_SIZE)
+                .push_int(32)
+                .push_opcode(OP_EQUAL)
+                .push_opcode(OP_IF)
+                .push_opcode(OP_HASH160)
+                .push_slice(self.hashlock.to_byte_array())
+                .push_opcode(OP_EQUALVERIFY)
+                .push_key(&self.receiver_pubkey)
+                .push_opcode(OP_ELSE)
+                .push_opcode(OP_DROP),
+            _ => Builder::new()
+                .push_opcode(OP_HASH160)
+                .push_slice(self.hashlock.to_byte_array())
+                .push_opcode(OP_EQUAL)
+                .push_opcode(OP_IF)
+                .push_key(&self.receiver_pubkey)
+                .push_opcode(OP_ELSE),
+        }
+        .push_lock_time(self.locktime)
+        .push_opcode(OP_CLTV)
+        .push_opcode(OP_DROP)
+        .push_key(&self.sender_pubkey)
+        .push_opcode(OP_ENDIF)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
+    }
+
+    /// Native segwit (P2WSH) lockup address.
+    pub fn to_address(&self, chain: Chain) -> Result<Address, Error> {
+        Ok(Address::p2wsh(
+            &self.redeem_script(),
+            chain.bitcoin_network()?,
+        ))
+    }
+
+    /// Nested segwit (P2SH-P2WSH) lockup address, used by early v1 swaps.
+    pub fn to_nested_address(&self, chain: Chain) -> Result<Address, Error> {
+        Ok(Address::p2shwsh(
+            &self.redeem_script(),
+            chain.bitcoin_network()?,
+        ))
+    }
+
+    /// Fetch the unspent lockup utxos, of both the native and the nested address.
+    pub fn fetch_utxos(
+        &self,
+        network_config: &ElectrumConfig,
+    ) -> Result<Vec<(OutPoint, TxOut)>, Error> {
+        let chain = network_config.network();
+        let scripts = [
+            self.to_address(chain)?.script_pubkey(),
+            self.to_nested_address(chain)?.script_pubkey(),
+        ];
+        Ok(BtcSwapScript::get_scripts_utxos(network_config, &scripts)?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
+
+impl BtcSwapScript {
+    /// Parse a hex encoded v1 redeem script, as found in refund files of older versions.
+    /// Legacy swaps are not taproot swaps, so they get their own script type.
+    pub fn from_redeem_script(redeem_script: &str) -> Result<LegacySwapScript, Error> {
+        LegacySwapScript::from_redeem_script(redeem_script)
+    }
+}
+
+fn not_legacy_script() -> Error {
+    Error::Protocol("Not a legacy swap redeem script".to_string())
+}
+
+/// Refund file of a v1 Submarine swap.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyRefundFile {
+    pub id: String,
+    pub currency: String,
+    pub redeem_script: String,
+    /// Hex encoded refund key.
+    pub private_key: String,
+    pub timeout_block_height: u32,
+}
+
+impl LegacyRefundFile {
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn swap_script(&self) -> Result<LegacySwapScript, Error> {
+        LegacySwapScript::from_redeem_script(&self.redeem_script)
+    }
+
+    pub fn keypair(&self) -> Result<Keypair, Error> {
+        Ok(Keypair::from_seckey_str(
+            &Secp256k1::new(),
+            &self.private_key,
+        )?)
+    }
+}
+
+/// Non-cooperative refund of a v1 Submarine swap.
+#[derive(Debug, Clone)]
+pub struct LegacyRefundTx {
+    pub swap_script: LegacySwapScript,
+    pub output_address: Address,
+    pub utxos: Vec<(OutPoint, TxOut)>,
+    pub limits: TxLimits,
+    /// Used to check the refund timelock against the chain tip before signing, if set.
+    pub network_config: Option<ElectrumConfig>,
+}
+
+impl LegacyRefundTx {
+    /// Refund of `swap_script` to `refund_address`, spending all its lockup utxos.
+    pub fn new(
+        swap_script: LegacySwapScript,
+        refund_address: &str,
+        network_config: &ElectrumConfig,
+    ) -> Result<Self, Error> {
+        if swap_script.swap_type == SwapType::ReverseSubmarine {
+            return Err(Error::Protocol(
+                "Refund Txs cannot be constructed for Reverse Submarine Swaps.".to_string(),
+            ));
+        }
+        let output_address = checked_refund_address(refund_address, network_config)?;
+        let utxos = swap_script.fetch_utxos(network_config)?;
+        if utxos.is_empty() {
+            return Err(Error::Protocol(
+                "No Bitcoin UTXO detected for this script".to_string(),
+            ));
+        }
+        Ok(LegacyRefundTx {
+            swap_script,
+            output_address,
+            utxos,
+            limits: TxLimits::default(),
+            network_config: Some(network_config.clone()),
+        })
+    }
+
+    /// Sign the refund with the refund key of the swap.
+    pub fn sign_refund(&self, keys: &Keypair, fee: Fee) -> Result<Transaction, Error> {
+        if PublicKey::new(keys.public_key()) != self.swap_script.sender_pubkey {
+            return Err(Error::Protocol(
+                "Keys do not match the refund key of the swap".to_string(),
+            ));
+        }
+        if let Some(network_config) = &self.network_config {
+            let current = network_config.get_tip_height()?;
+            let required = self.swap_script.locktime.to_consensus_u32();
+            if current < required {
+                return Err(Error::LocktimeNotReached { current, required });
+            }
+        }
+
+        let refund_tx =
+            create_tx_with_fee(fee, |fee| self.create_refund(keys, fee), |tx| tx.vsize())?;
+
+        let input_value: u64 = self.utxos.iter().map(|(_, txo)| txo.value.to_sat()).sum();
+        let output_value = refund_tx.output[0].value.to_sat();
+        self.limits.check(
+            input_value,
+            input_value - output_value,
+            output_value,
+            refund_tx.vsize(),
+            self.output_address
+                .script_pubkey()
+                .minimal_non_dust()
+                .to_sat(),
+            BITCOIN_MIN_RELAY_FEE_RATE,
+        )?;
+        Ok(refund_tx)
+    }
+
+    fn create_refund(&self, keys: &Keypair, absolute_fees: u64) -> Result<Transaction, Error> {
+        let utxos_amount = self
+            .utxos
+            .iter()
+            .fold(Amount::ZERO, |acc, (_, txo)| acc + txo.value);
+        let absolute_fees_amount = Amount::from_sat(absolute_fees);
+        let output_amount = utxos_amount
+            .checked_sub(absolute_fees_amount)
+            .filter(|amount| *amount > Amount::ZERO)
+            .ok_or(Error::Generic(format!(
+                "Cannot sign Refund Tx because utxos_amount ({utxos_amount}) <= absolute_fees ({absolute_fees_amount})"
+            )))?;
+
+        let redeem_script = self.swap_script.redeem_script();
+        // Nested inputs push the P2WSH output script as their P2SH redeem script
+        let nested_script_sig = Builder::new()
+            .push_slice(
+                PushBytesBuf::try_from(
+                    ScriptBuf::new_p2wsh(&redeem_script.wscript_hash()).to_bytes(),
+                )
+                .map_err(|e| Error::Protocol(e.to_string()))?,
+            )
+            .into_script();
+
+        let mut refund_tx = Transaction {
+            version: Version::TWO,
+            lock_time: self.swap_script.locktime,
+            input: self
+                .utxos
+                .iter()
+                .map(|(outpoint, txo)| TxIn {
+                    previous_output: *outpoint,
+                    script_sig: if txo.script_pubkey.is_p2sh() {
+                        nested_script_sig.clone()
+                    } else {
+                        ScriptBuf::new()
+                    },
+                    // Enables the locktime
+                    sequence: Sequence::ZERO,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: vec![TxOut {
+                script_pubkey: self.output_address.script_pubkey(),
+                value: output_amount,
+            }],
+        };
+
+        let secp = Secp256k1::new();
+        for (index, (_, txo)) in self.utxos.iter().enumerate() {
+            let sighash = SighashCache::new(&refund_tx)
+                .p2wsh_signature_hash(index, &redeem_script, txo.value, EcdsaSighashType::All)
+                .map_err(|e| Error::Protocol(e.to_string()))?;
+            let msg = Message::from_digest(sighash.to_byte_array());
+            let signature = ecdsa::Signature {
+                signature: secp.sign_ecdsa(&msg, &keys.secret_key()),
+                sighash_type: EcdsaSighashType::All,
+            };
+
+            // The empty element fails the hashlock, which selects the refund branch
+            let mut witness = Witness::new();
+            witness.push(signature.to_vec());
+            witness.push(Vec::<u8>::new());
+            witness.push(redeem_script.as_bytes());
+            refund_tx.input[index].witness = witness;
+        }
+
+        Ok(refund_tx)
+    }
+
+    pub fn broadcast(
+        &self,
+        signed_tx: &Transaction,
+        network_config: &ElectrumConfig,
+    ) -> Result<Txid, Error> {
+        Ok(network_config
+            .build_broadcast_client()?
+            .transaction_broadcast(signed_tx)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hex::DisplayHex;
+    use bitcoin::key::rand::thread_rng;
+    use bitcoin::Network;
+
+    fn test_script(swap_type: SwapType, refund_keys: &Keypair) -> LegacySwapScript {
+        let secp = Secp256k1::new();
+        LegacySwapScript {
+            swap_type,
+            hashlock: hash160::Hash::hash(&[1; 32]),
+            receiver_pubkey: PublicKey::new(Keypair::new(&secp, &mut thread_rng()).public_key()),
+            sender_pubkey: PublicKey::new(refund_keys.public_key()),
+            locktime: LockTime::from_height(850_000).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_from_redeem_script() {
+        let keys = Keypair::new(&Secp256k1::new(), &mut thread_rng());
+        for swap_type in [SwapType::Submarine, SwapType::ReverseSubmarine] {
+            let script = test_script(swap_type, &keys);
+            let hex = script.redeem_script().to_hex_string();
+            assert_eq!(BtcSwapScript::from_redeem_script(&hex).unwrap(), script);
+        }
+
+        // A taproot leaf is not a v1 redeem script
+        let leaf = Builder::new()
+            .push_x_only_key(&keys.x_only_public_key().0)
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        assert!(LegacySwapScript::from_redeem_script(&leaf.to_hex_string()).is_err());
+        assert!(LegacySwapScript::from_redeem_script("zz").is_err());
+
+        let file = LegacyRefundFile::from_json(&format!(
+            r#"{{"id":"abc","currency":"BTC","redeemScript":"{}","privateKey":"{}","timeoutBlockHeight":850000}}"#,
+            test_script(SwapType::Submarine, &keys)
+                .redeem_script()
+                .to_hex_string(),
+            keys.secret_key().secret_bytes().to_lower_hex_string()
+        ))
+        .unwrap();
+        assert_eq!(file.keypair().unwrap(), keys);
+        assert_eq!(
+            file.swap_script().unwrap().locktime.to_consensus_u32(),
+            file.timeout_block_height
+        );
+    }
+
+    #[test]
+    fn test_legacy_refund() {
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let script = test_script(SwapType::Submarine, &keys);
+        let utxo = |address: Address, vout| {
+            (
+                OutPoint::new(Txid::all_zeros(), vout),
+                TxOut {
+                    value: Amount::from_sat(50_000),
+                    script_pubkey: address.script_pubkey(),
+                },
+            )
+        };
+        let refund = LegacyRefundTx {
+            utxos: vec![
+                utxo(script.to_address(Chain::Bitcoin).unwrap(), 0),
+                utxo(script.to_nested_address(Chain::Bitcoin).unwrap(), 1),
+            ],
+            swap_script: script.clone(),
+            output_address: Address::p2tr(
+                &secp,
+                keys.x_only_public_key().0,
+                None,
+                Network::Bitcoin,
+            ),
+            limits: TxLimits::default(),
+            network_config: None,
+        };
+
+        let other_keys = Keypair::new(&secp, &mut thread_rng());
+        assert!(refund
+            .sign_refund(&other_keys, Fee::Absolute(1_000))
+            .is_err());
+
+        let tx = refund.sign_refund(&keys, Fee::Absolute(1_000)).unwrap();
+        assert_eq!(tx.lock_time, script.locktime);
+        assert_eq!(tx.output[0].value, Amount::from_sat(99_000));
+        assert!(tx.input[0].script_sig.is_empty());
+        assert!(!tx.input[1].script_sig.is_empty());
+
+        let redeem_script = script.redeem_script();
+        for (index, (_, txo)) in refund.utxos.iter().enumerate() {
+            let witness: Vec<&[u8]> = tx.input[index].witness.iter().collect();
+            assert_eq!(witness.len(), 3);
+            assert!(witness[1].is_empty());
+            assert_eq!(witness[2], redeem_script.as_bytes());
+
+            let sighash = SighashCache::new(&tx)
+                .p2wsh_signature_hash(index, &redeem_script, txo.value, EcdsaSighashType::All)
+                .unwrap();
+            let signature = ecdsa::Signature::from_slice(witness[0]).unwrap();
+            secp.verify_ecdsa(
+                &Message::from_digest(sighash.to_byte_array()),
+                &signature.signature,
+                &keys.public_key(),
+            )
+            .unwrap();
+        }
+    }
+}
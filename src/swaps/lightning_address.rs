@@ -0,0 +1,544 @@
+//! Lightning address (LUD-16) server, receiving payments to an onchain or Liquid wallet.
+//!
+//! [`LightningAddressService`] answers the two LNURL-pay requests of a lightning address: the pay
+//! request served at `https://<domain>/.well-known/lnurlp/<username>`, and the callback returning
+//! an invoice for the requested amount. Every invoice is the hold invoice of a new Boltz reverse
+//! swap, which is claimed to the configured address once Boltz locked up the funds.
+//!
+//! Implement [`AddressSwapStore`] on top of the app's swap storage, route both requests to
+//! [`LightningAddressService::handle`] from the app's https server, and call
+//! [`LightningAddressService::claim_pending`] regularly, e.g. on every swap status update.
+//!
+//! The callback is public, so anyone can make the service create swaps. Set [`Limits`] with
+//! [`LightningAddressService::with_limits`] to bound how many invoices it hands out.
+
+use std::sync::Arc;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::Keypair;
+use bitcoin::PublicKey;
+use lightning_invoice::Bolt11Invoice;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::network::{electrum::ElectrumConfig, Chain};
+use crate::util::backoff::Backoff;
+use crate::util::fees::Fee;
+use crate::util::limits::{Limits, LimitsStore};
+use crate::util::secrets::Preimage;
+
+use super::batch::BatchResult;
+use super::bitcoin::{BtcSwapScript, BtcSwapTx};
+use super::boltz::{
+    BoltzApiClientV2, Cooperative, CreateReverseRequest, CreateReverseResponse, RevSwapStates,
+    ReversePair,
+};
+use super::liquid::{LBtcSwapScript, LBtcSwapTx};
+
+/// Path of the pay request of `username`, relative to the domain root.
+pub fn well_known_path(username: &str) -> String {
+    format!("/.well-known/lnurlp/{}", username)
+}
+
+/// Lightning address served by a [`LightningAddressService`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightningAddressConfig {
+    /// User part of the address, before the `@`.
+    pub username: String,
+    /// Domain part of the address, serving the pay request over https.
+    pub domain: String,
+    /// Url of the invoice callback, e.g. `https://<domain>/lnurlp/<username>/callback`.
+    pub callback_url: String,
+    /// Chain the payments are received on.
+    pub chain: Chain,
+    /// Address receiving the claimed funds.
+    pub claim_address: String,
+    /// Smallest amount accepted, in msats. Raised to the Boltz minimum if lower.
+    pub min_sendable: u64,
+    /// Largest amount accepted, in msats. Lowered to the Boltz maximum if higher.
+    pub max_sendable: u64,
+    /// Claim as soon as the Boltz lockup is in the mempool, instead of waiting for a confirmation.
+    pub accept_zero_conf: bool,
+}
+
+impl LightningAddressConfig {
+    /// The `name@domain` address.
+    pub fn address(&self) -> String {
+        format!("{}@{}", self.username, self.domain)
+    }
+
+    /// LUD-06 metadata, committed to by the description hash of every invoice.
+    pub fn metadata(&self) -> String {
+        serde_json::json!([
+            ["text/plain", format!("Payment to {}", self.address())],
+            ["text/identifier", self.address()],
+        ])
+        .to_string()
+    }
+}
+
+/// Response to the pay request (LUD-06).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayRequestResponse {
+    pub callback: String,
+    pub min_sendable: u64,
+    pub max_sendable: u64,
+    pub metadata: String,
+    pub tag: String,
+}
+
+/// Response to the invoice callback (LUD-06).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvoiceResponse {
+    /// The bolt11 invoice.
+    pub pr: String,
+    /// Always empty, kept for wallets that still expect it.
+    pub routes: Vec<serde_json::Value>,
+}
+
+/// LNURL error response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LnurlErrorResponse {
+    pub status: String,
+    pub reason: String,
+}
+
+/// Reverse swap created for a lightning address payment.
+/// Contains the claim keys and preimage, so it must be stored like other swap secrets.
+#[derive(Debug, Clone)]
+pub struct AddressSwap {
+    pub chain: Chain,
+    pub claim_keys: Keypair,
+    pub preimage: Preimage,
+    pub response: CreateReverseResponse,
+    pub claim_address: String,
+}
+
+impl AddressSwap {
+    pub fn swap_id(&self) -> &str {
+        &self.response.id
+    }
+
+    fn claim_public_key(&self) -> PublicKey {
+        PublicKey::new(self.claim_keys.public_key())
+    }
+}
+
+/// Storage of the swaps created by a [`LightningAddressService`].
+pub trait AddressSwapStore {
+    /// Keys for the claim of the next swap, e.g. derived from the wallet seed at a new index.
+    fn next_claim_keys(&self) -> Result<Keypair, Error>;
+    /// Persist a new swap. Called before its invoice is handed out.
+    fn insert_swap(&self, swap: &AddressSwap) -> Result<(), Error>;
+    /// Swaps that were neither claimed nor failed yet.
+    fn pending_swaps(&self) -> Result<Vec<AddressSwap>, Error>;
+    /// Record that a swap is no longer pending, with its claim txid, or None if it failed.
+    fn finish_swap(&self, swap_id: &str, claim_txid: Option<String>) -> Result<(), Error>;
+}
+
+/// LNURL-pay server of a lightning address, backed by Boltz reverse swaps.
+pub struct LightningAddressService {
+    config: LightningAddressConfig,
+    boltz_url: String,
+    boltz_api: BoltzApiClientV2,
+    network_config: ElectrumConfig,
+    store: Arc<dyn AddressSwapStore + Send + Sync>,
+    limits: Option<Limits<Box<dyn LimitsStore + Send + Sync>>>,
+}
+
+impl LightningAddressService {
+    pub fn new(
+        config: LightningAddressConfig,
+        boltz_url: &str,
+        network_config: ElectrumConfig,
+        store: Arc<dyn AddressSwapStore + Send + Sync>,
+    ) -> Self {
+        LightningAddressService {
+            config,
+            boltz_url: boltz_url.to_string(),
+            boltz_api: BoltzApiClientV2::new(boltz_url),
+            network_config,
+            store,
+            limits: None,
+        }
+    }
+
+    /// Count every invoice request against `limits`, rejecting those above them before any
+    /// swap is created.
+    pub fn with_limits(mut self, limits: Limits<Box<dyn LimitsStore + Send + Sync>>) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Backoff of the Boltz GET requests, see [`BoltzApiClientV2::with_backoff`].
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.boltz_api = self.boltz_api.with_backoff(backoff);
+        self
+    }
+
+    pub fn config(&self) -> &LightningAddressConfig {
+        &self.config
+    }
+
+    fn reverse_pair(&self) -> Result<ReversePair, Error> {
+        let pairs = self.boltz_api.get_reverse_pairs()?;
+        let pair = match self.config.chain {
+            Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => {
+                pairs.get_btc_to_btc_pair()
+            }
+            Chain::Liquid | Chain::LiquidTestnet | Chain::LiquidRegtest => {
+                pairs.get_btc_to_lbtc_pair()
+            }
+        };
+        pair.ok_or(Error::Protocol(format!(
+            "No reverse pair for {:?}",
+            self.config.chain
+        )))
+    }
+
+    /// The pay request, with the sendable amounts narrowed down to the current Boltz limits.
+    pub fn pay_request(&self) -> Result<PayRequestResponse, Error> {
+        let limits = self.reverse_pair()?.limits;
+        let min_sendable = self.config.min_sendable.max(limits.minimal * 1000);
+        let max_sendable = self.config.max_sendable.min(limits.maximal * 1000);
+        if min_sendable > max_sendable {
+            return Err(Error::Protocol(format!(
+                "No amount can be received, min {} msats is above max {} msats",
+                min_sendable, max_sendable
+            )));
+        }
+        Ok(PayRequestResponse {
+            callback: self.config.callback_url.clone(),
+            min_sendable,
+            max_sendable,
+            metadata: self.config.metadata(),
+            tag: "payRequest".to_string(),
+        })
+    }
+
+    /// Create a reverse swap for `amount_msat` and return its invoice.
+    /// The swap is persisted before the invoice is returned.
+    /// With [`Self::with_limits`], the request is recorded against the limits even if creating
+    /// the swap fails afterwards.
+    pub fn invoice(&self, amount_msat: u64) -> Result<InvoiceResponse, Error> {
+        if amount_msat % 1000 != 0 {
+            return Err(Error::Protocol(format!(
+                "Amount of {} msats is not a whole number of sats",
+                amount_msat
+            )));
+        }
+        if amount_msat < self.config.min_sendable || amount_msat > self.config.max_sendable {
+            return Err(Error::Protocol(format!(
+                "Amount of {} msats is outside of {}..={} msats",
+                amount_msat, self.config.min_sendable, self.config.max_sendable
+            )));
+        }
+        let preimage = Preimage::try_new()?;
+        if let Some(limits) = &self.limits {
+            limits.check_and_record(&preimage.sha256.to_string(), amount_msat / 1000)?;
+        }
+        let pair = self.reverse_pair()?;
+        pair.limits.within(amount_msat / 1000)?;
+
+        let claim_keys = self.store.next_claim_keys()?;
+        let claim_public_key = PublicKey::new(claim_keys.public_key());
+        let to = match self.config.chain {
+            Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => "BTC",
+            Chain::Liquid | Chain::LiquidTestnet | Chain::LiquidRegtest => "L-BTC",
+        };
+        let response = self.boltz_api.post_reverse_req(CreateReverseRequest {
+            invoice_amount: amount_msat / 1000,
+            from: "BTC".to_string(),
+            to: to.to_string(),
+            preimage_hash: preimage.sha256,
+            claim_public_key,
+            description: None,
+            description_hash: Some(
+                sha256::Hash::hash(self.config.metadata().as_bytes()).to_string(),
+            ),
+            address: None,
+            address_signature: None,
+            referral_id: None,
+            webhook: None,
+        })?;
+        response.validate(&preimage, &claim_public_key, self.config.chain)?;
+        let invoice: Bolt11Invoice = response.invoice.parse()?;
+        if invoice.amount_milli_satoshis() != Some(amount_msat) {
+            return Err(Error::Protocol(format!(
+                "Invoice amount {:?} msats doesn't match the requested {} msats",
+                invoice.amount_milli_satoshis(),
+                amount_msat
+            )));
+        }
+
+        let swap = AddressSwap {
+            chain: self.config.chain,
+            claim_keys,
+            preimage,
+            response,
+            claim_address: self.config.claim_address.clone(),
+        };
+        self.store.insert_swap(&swap)?;
+        log::info!(
+            "Created swap {} for a {} msats payment to {}",
+            swap.swap_id(),
+            amount_msat,
+            self.config.address()
+        );
+
+        Ok(InvoiceResponse {
+            pr: swap.response.invoice.clone(),
+            routes: vec![],
+        })
+    }
+
+    /// Answer a request for `path_and_query`, e.g. `/.well-known/lnurlp/alice`.
+    /// Returns the http status code and the json body. Failures are reported as LNURL errors.
+    pub fn handle(&self, path_and_query: &str) -> (u16, String) {
+        let url =
+            match url::Url::parse("http://localhost").and_then(|base| base.join(path_and_query)) {
+                Ok(url) => url,
+                Err(_) => return (400, lnurl_error("Invalid request")),
+            };
+        let callback_path = url::Url::parse(&self.config.callback_url)
+            .map(|callback| callback.path().to_string())
+            .unwrap_or_default();
+
+        let result = if url.path() == well_known_path(&self.config.username) {
+            self.pay_request()
+                .and_then(|r| Ok(serde_json::to_string(&r)?))
+        } else if url.path() == callback_path {
+            let amount = url
+                .query_pairs()
+                .find(|(key, _)| key == "amount")
+                .and_then(|(_, value)| value.parse::<u64>().ok());
+            match amount {
+                Some(amount) => self
+                    .invoice(amount)
+                    .and_then(|r| Ok(serde_json::to_string(&r)?)),
+                None => return (400, lnurl_error("Missing or invalid amount")),
+            }
+        } else {
+            return (404, lnurl_error("Not found"));
+        };
+
+        match result {
+            Ok(body) => (200, body),
+            Err(e) => {
+                log::warn!("Lightning address request failed: {}", e.message());
+                (200, lnurl_error(&e.message()))
+            }
+        }
+    }
+
+    /// Cooperatively claim a swap to its claim address and broadcast the claim.
+    /// Returns the claim txid.
+    pub fn claim(&self, swap: &AddressSwap, fee: Fee) -> Result<String, Error> {
+        let cooperative = Some(Cooperative {
+            boltz_api: &self.boltz_api,
+            swap_id: swap.swap_id().to_string(),
+            pub_nonce: None,
+            partial_sig: None,
+        });
+        match swap.chain {
+            Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => {
+                let script =
+                    BtcSwapScript::reverse_from_swap_resp(&swap.response, swap.claim_public_key())?;
+                let claim_tx = BtcSwapTx::new_claim(
+                    script,
+                    swap.claim_address.clone(),
+                    &self.network_config,
+                    self.boltz_url.clone(),
+                    swap.swap_id().to_string(),
+                )?;
+                let tx = claim_tx.sign_claim(&swap.claim_keys, &swap.preimage, fee, cooperative)?;
+                Ok(claim_tx.broadcast(&tx, &self.network_config)?.to_string())
+            }
+            Chain::Liquid | Chain::LiquidTestnet | Chain::LiquidRegtest => {
+                let script = LBtcSwapScript::reverse_from_swap_resp(
+                    &swap.response,
+                    swap.claim_public_key(),
+                )?;
+                let claim_tx = LBtcSwapTx::new_claim(
+                    script,
+                    swap.claim_address.clone(),
+                    &self.network_config,
+                    self.boltz_url.clone(),
+                    swap.swap_id().to_string(),
+                )?;
+                let tx = claim_tx.sign_claim(
+                    &swap.claim_keys,
+                    &swap.preimage,
+                    fee,
+                    cooperative,
+                    false,
+                )?;
+                claim_tx.broadcast(&tx, &self.network_config, None)
+            }
+        }
+    }
+
+    /// Claim every pending swap whose lockup is ready, at `fee_rate` sat/vbyte, and finish the
+    /// swaps that failed. Returns the claim txid or error of every swap a claim was attempted for.
+    /// Swaps whose claim failed stay pending and are retried on the next call.
+    pub fn claim_pending(&self, fee_rate: f64) -> Result<BatchResult<String>, Error> {
+        let mut results = BatchResult::new();
+        for swap in self.store.pending_swaps()? {
+            let swap_id = swap.swap_id().to_string();
+            let status = match self.boltz_api.get_swap(&swap_id) {
+                Ok(response) => response.status,
+                Err(e) => {
+                    log::warn!("Failed to get status of swap {}: {}", swap_id, e.message());
+                    continue;
+                }
+            };
+            match status.parse::<RevSwapStates>() {
+                Ok(RevSwapStates::TransactionMempool) if self.config.accept_zero_conf => {}
+                Ok(RevSwapStates::TransactionConfirmed) => {}
+                Ok(
+                    RevSwapStates::InvoiceExpired
+                    | RevSwapStates::SwapExpired
+                    | RevSwapStates::TransactionFailed
+                    | RevSwapStates::TransactionRefunded,
+                ) => {
+                    log::info!("Swap {} failed with status {}", swap_id, status);
+                    self.store.finish_swap(&swap_id, None)?;
+                    continue;
+                }
+                _ => continue,
+            }
+
+            let result = self.claim(&swap, Fee::Relative(fee_rate));
+            match &result {
+                Ok(txid) => {
+                    log::info!("Claimed swap {} in {}", swap_id, txid);
+                    self.store.finish_swap(&swap_id, Some(txid.clone()))?;
+                }
+                Err(e) => log::warn!("Failed to claim swap {}: {}", swap_id, e.message()),
+            }
+            results.push(swap_id, result);
+        }
+        Ok(results)
+    }
+}
+
+fn lnurl_error(reason: &str) -> String {
+    serde_json::to_string(&LnurlErrorResponse {
+        status: "ERROR".to_string(),
+        reason: reason.to_string(),
+    })
+    .expect("serializable error response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::limits::{LimitsConfig, MemoryLimitsStore};
+    use bitcoin::key::rand::thread_rng;
+    use bitcoin::secp256k1::Secp256k1;
+
+    struct NoSwaps;
+
+    impl AddressSwapStore for NoSwaps {
+        fn next_claim_keys(&self) -> Result<Keypair, Error> {
+            Ok(Keypair::new(&Secp256k1::new(), &mut thread_rng()))
+        }
+
+        fn insert_swap(&self, _swap: &AddressSwap) -> Result<(), Error> {
+            Err(Error::Generic("No swaps expected".to_string()))
+        }
+
+        fn pending_swaps(&self) -> Result<Vec<AddressSwap>, Error> {
+            Ok(vec![])
+        }
+
+        fn finish_swap(&self, _swap_id: &str, _claim_txid: Option<String>) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn test_service() -> LightningAddressService {
+        let config = LightningAddressConfig {
+            username: "alice".to_string(),
+            domain: "example.com".to_string(),
+            callback_url: "https://example.com/lnurlp/alice/callback".to_string(),
+            chain: Chain::Bitcoin,
+            claim_address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+            min_sendable: 10_000_000,
+            max_sendable: 1_000_000_000,
+            accept_zero_conf: false,
+        };
+        LightningAddressService::new(
+            config,
+            "http://127.0.0.1:1/v2",
+            ElectrumConfig::default_bitcoin(),
+            Arc::new(NoSwaps),
+        )
+        .with_backoff(Backoff::none())
+    }
+
+    #[test]
+    fn test_metadata() {
+        let service = test_service();
+        assert_eq!(service.config().address(), "alice@example.com");
+        let metadata: Vec<(String, String)> =
+            serde_json::from_str(&service.config().metadata()).unwrap();
+        assert!(metadata.contains(&(
+            "text/identifier".to_string(),
+            "alice@example.com".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_handle() {
+        let service = test_service();
+        let (code, body) = service.handle("/unknown");
+        assert_eq!(code, 404);
+        let error: LnurlErrorResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(error.status, "ERROR");
+
+        let (code, _) = service.handle("/lnurlp/alice/callback");
+        assert_eq!(code, 400);
+
+        // Rejected before any request to Boltz
+        let (code, body) = service.handle("/lnurlp/alice/callback?amount=10000500");
+        assert_eq!(code, 200);
+        assert!(serde_json::from_str::<LnurlErrorResponse>(&body).is_ok());
+        let (_, body) = service.handle("/lnurlp/alice/callback?amount=1000");
+        assert!(serde_json::from_str::<LnurlErrorResponse>(&body).is_ok());
+
+        // Boltz isn't reachable
+        let (code, body) = service.handle(&well_known_path("alice"));
+        assert_eq!(code, 200);
+        assert!(serde_json::from_str::<LnurlErrorResponse>(&body).is_ok());
+    }
+
+    #[test]
+    fn test_invoice_limits() {
+        let store: Box<dyn LimitsStore + Send + Sync> = Box::new(MemoryLimitsStore::default());
+        let limits = Limits::new(
+            LimitsConfig {
+                max_swap_amount: Some(50_000),
+                max_daily_amount: None,
+                max_daily_swaps: Some(1),
+            },
+            store,
+        );
+        let service = test_service().with_limits(limits);
+
+        assert!(matches!(
+            service.invoice(60_000_000),
+            Err(Error::LimitExceeded(_))
+        ));
+        // Counted even though Boltz isn't reachable
+        assert!(!matches!(
+            service.invoice(20_000_000),
+            Err(Error::LimitExceeded(_))
+        ));
+        assert!(matches!(
+            service.invoice(20_000_000),
+            Err(Error::LimitExceeded(_))
+        ));
+    }
+}
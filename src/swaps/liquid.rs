@@ -1,10 +1,14 @@
 use electrum_client::ElectrumApi;
-use std::{hash, str::FromStr};
+use std::{
+    hash,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
 
 use bitcoin::{
-    hashes::{hash160, Hash},
+    hashes::{hash160, sha256, Hash},
     hex::DisplayHex,
-    key::rand::{rngs::OsRng, thread_rng, RngCore},
     script::Script as BitcoinScript,
     secp256k1::Keypair,
     Amount, Witness, XOnlyPublicKey,
@@ -13,11 +17,11 @@ use elements::{
     confidential::{self, Asset, AssetBlindingFactor, Value, ValueBlindingFactor},
     hex::{FromHex, ToHex},
     secp256k1_zkp::{
-        self, MusigAggNonce, MusigKeyAggCache, MusigPartialSignature, MusigPubNonce, MusigSession,
-        MusigSessionId, Secp256k1, SecretKey,
+        self, schnorr, MusigAggNonce, MusigKeyAggCache, MusigPartialSignature, MusigPubNonce,
+        MusigSession, MusigSessionId, Secp256k1, SecretKey,
     },
     sighash::{Prevouts, SighashCache},
-    taproot::{LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo},
+    taproot::{ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo},
     Address, AssetIssuance, BlockHash, LockTime, OutPoint, SchnorrSig, SchnorrSighashType, Script,
     Sequence, Transaction, TxIn, TxInWitness, TxOut, TxOutSecrets, TxOutWitness,
 };
@@ -26,24 +30,43 @@ use elements::encode::serialize;
 use elements::secp256k1_zkp::Message;
 
 use crate::{
-    network::{electrum::ElectrumConfig, Chain},
-    util::{liquid_genesis_hash, secrets::Preimage},
+    network::{
+        broadcast::{boltz_broadcast, broadcast_all},
+        electrum::ElectrumConfig,
+        liquid_chain_from_genesis, Chain,
+    },
+    util::{
+        descriptor::addr_descriptor,
+        entropy::{random_32b, EntropyPurpose, SecureRng},
+        liquid_genesis_hash,
+        screening::{screen_address, AddressPurpose},
+        script_fingerprint,
+        secrets::Preimage,
+        standardness::StandardnessPolicy,
+    },
 };
 
 use crate::error::Error;
 
 use super::boltz::{
-    BoltzApiClientV2, ChainClaimTxResponse, ChainSwapDetails, Cooperative, CreateReverseResponse,
-    CreateSubmarineResponse, Side, SubmarineClaimTxResponse, SwapTxKind, SwapType, ToSign,
+    BoltzApiClientV2, ChainClaimTxResponse, ChainSwapDetails, Cooperative, CreateChainResponse,
+    CreateReverseResponse, CreateSubmarineResponse, PartialSig, Side, SpendPath,
+    SubmarineClaimTxResponse, SwapTree, SwapTxKind, SwapType, ToSign,
+};
+use super::crosscheck::check_boltz_lockup;
+use super::musig::{partial_sig_from_hex, pub_nonce_from_hex, SwapMusigContext};
+use super::output::{ScriptType, SwapOutput};
+use super::signer::KeypairSigner;
+use crate::fees::{
+    create_tx_with_fee, drain_output_value, liquid_discount_vsize, unresolved_deadline, Fee,
+    TxLimits, LIQUID_MIN_RELAY_FEE_RATE, MAX_MEMO_SIZE,
 };
-use crate::fees::{create_tx_with_fee, Fee};
 use elements::bitcoin::PublicKey;
 use elements::secp256k1_zkp::Keypair as ZKKeyPair;
 use elements::{
     address::Address as EAddress,
     opcodes::all::*,
-    script::{Builder as EBuilder, Instruction, Script as EScript},
-    AddressParams,
+    script::{read_scriptint, Builder as EBuilder, Instruction, Script as EScript},
 };
 
 /// Liquid v2 swap script helper.
@@ -54,9 +77,13 @@ pub struct LBtcSwapScript {
     pub funding_addrs: Option<Address>,
     pub hashlock: hash160::Hash,
     pub receiver_pubkey: PublicKey,
+    /// Absolute refund timelock. Zero for scripts with a `relative_timelock`.
     pub locktime: LockTime,
     pub sender_pubkey: PublicKey,
     pub blinding_key: ZKKeyPair,
+    /// Relative refund timelock, for refund leaves using OP_CHECKSEQUENCEVERIFY
+    /// instead of OP_CHECKLOCKTIMEVERIFY.
+    pub relative_timelock: Option<Sequence>,
 }
 
 impl LBtcSwapScript {
@@ -106,8 +133,11 @@ impl LBtcSwapScript {
         let hashlock =
             hashlock.ok_or_else(|| Error::Protocol("No hashlock provided".to_string()))?;
 
-        let locktime =
-            locktime.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?;
+        let relative_timelock = relative_refund_timelock(&refund_script);
+        let locktime = match relative_timelock {
+            Some(_) => LockTime::ZERO,
+            None => locktime.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?,
+        };
 
         let funding_addrs = Address::from_str(&create_swap_response.address)?;
 
@@ -117,7 +147,7 @@ impl LBtcSwapScript {
             .expect("No blinding key provided in CreateSwapResp");
         let blinding_key = ZKKeyPair::from_seckey_str(&Secp256k1::new(), blinding_str)?;
 
-        Ok(Self {
+        let script = Self {
             swap_type: SwapType::Submarine,
             side: None,
             funding_addrs: Some(funding_addrs),
@@ -126,7 +156,11 @@ impl LBtcSwapScript {
             locktime,
             sender_pubkey: our_pubkey,
             blinding_key,
-        })
+            relative_timelock,
+        };
+        script.check_swap_tree(&create_swap_response.swap_tree)?;
+
+        Ok(script)
     }
 
     /// Create the struct for a reverse swap from boltz create response.
@@ -175,8 +209,11 @@ impl LBtcSwapScript {
         let hashlock =
             hashlock.ok_or_else(|| Error::Protocol("No hashlock provided".to_string()))?;
 
-        let locktime =
-            locktime.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?;
+        let relative_timelock = relative_refund_timelock(&refund_script);
+        let locktime = match relative_timelock {
+            Some(_) => LockTime::ZERO,
+            None => locktime.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?,
+        };
 
         let funding_addrs = Address::from_str(&reverse_response.lockup_address)?;
 
@@ -186,7 +223,7 @@ impl LBtcSwapScript {
             .expect("No blinding key provided in CreateSwapResp");
         let blinding_key = ZKKeyPair::from_seckey_str(&Secp256k1::new(), blinding_str)?;
 
-        Ok(Self {
+        let script = Self {
             swap_type: SwapType::ReverseSubmarine,
             side: None,
             funding_addrs: Some(funding_addrs),
@@ -195,7 +232,11 @@ impl LBtcSwapScript {
             locktime,
             sender_pubkey: reverse_response.refund_public_key,
             blinding_key,
-        })
+            relative_timelock,
+        };
+        script.check_swap_tree(&reverse_response.swap_tree)?;
+
+        Ok(script)
     }
 
     /// Create the struct for a chain swap from boltz create response.
@@ -245,8 +286,11 @@ impl LBtcSwapScript {
         let hashlock =
             hashlock.ok_or_else(|| Error::Protocol("No hashlock provided".to_string()))?;
 
-        let locktime =
-            locktime.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?;
+        let relative_timelock = relative_refund_timelock(&refund_script);
+        let locktime = match relative_timelock {
+            Some(_) => LockTime::ZERO,
+            None => locktime.ok_or_else(|| Error::Protocol("No timelock provided".to_string()))?,
+        };
 
         let funding_addrs = Address::from_str(&chain_swap_details.lockup_address)?;
 
@@ -261,7 +305,7 @@ impl LBtcSwapScript {
             .expect("No blinding key provided in ChainSwapDetails");
         let blinding_key = ZKKeyPair::from_seckey_str(&Secp256k1::new(), blinding_str)?;
 
-        Ok(Self {
+        let script = Self {
             swap_type: SwapType::Chain,
             side: Some(side),
             funding_addrs: Some(funding_addrs),
@@ -270,7 +314,38 @@ impl LBtcSwapScript {
             locktime,
             sender_pubkey,
             blinding_key,
-        })
+            relative_timelock,
+        };
+        script.check_swap_tree(&chain_swap_details.swap_tree)?;
+
+        Ok(script)
+    }
+
+    /// Check that the swap tree sent by Boltz is byte for byte the one rebuilt from the parsed
+    /// parameters. The constructors only pick the hashlock and timelock out of the leaves, so any
+    /// extra opcode changing the spending conditions has to be caught here.
+    fn check_swap_tree(&self, swap_tree: &SwapTree) -> Result<(), Error> {
+        let leaves = [
+            ("claim", &swap_tree.claim_leaf, self.claim_script()),
+            ("refund", &swap_tree.refund_leaf, self.refund_script()),
+        ];
+        for (name, leaf, expected) in leaves {
+            if leaf.version != LeafVersion::default().as_u8() {
+                return Err(Error::Protocol(format!(
+                    "Unexpected {} leaf version: {}",
+                    name, leaf.version
+                )));
+            }
+            if Script::from_hex(&leaf.output)? != expected {
+                return Err(Error::Protocol(format!(
+                    "{} leaf does not match the swap parameters. Received: {}, Expected: {}",
+                    name,
+                    leaf.output,
+                    expected.as_bytes().to_lower_hex_string()
+                )));
+            }
+        }
+        Ok(())
     }
 
     fn claim_script(&self) -> Script {
@@ -298,12 +373,19 @@ impl LBtcSwapScript {
 
     fn refund_script(&self) -> Script {
         // Refund scripts are same for all swap types
-        EBuilder::new()
+        let builder = EBuilder::new()
             .push_slice(&self.sender_pubkey.inner.x_only_public_key().0.serialize())
-            .push_opcode(OP_CHECKSIGVERIFY)
-            .push_int(self.locktime.to_consensus_u32().into())
-            .push_opcode(OP_CLTV)
-            .into_script()
+            .push_opcode(OP_CHECKSIGVERIFY);
+        match self.relative_timelock {
+            Some(sequence) => builder
+                .push_int(sequence.to_consensus_u32().into())
+                .push_opcode(OP_CSV)
+                .into_script(),
+            None => builder
+                .push_int(self.locktime.to_consensus_u32().into())
+                .push_opcode(OP_CLTV)
+                .into_script(),
+        }
     }
 
     pub fn musig_keyagg_cache(&self) -> MusigKeyAggCache {
@@ -320,6 +402,12 @@ impl LBtcSwapScript {
         }
     }
 
+    /// MuSig2 context for cooperative key path spends of the swap output.
+    pub fn musig_context(&self) -> Result<SwapMusigContext, Error> {
+        let tweak = self.taproot_spendinfo()?.tap_tweak();
+        SwapMusigContext::new(self.musig_keyagg_cache(), *tweak.as_byte_array())
+    }
+
     /// Internally used to convert struct into a bitcoin::Script type
     fn taproot_spendinfo(&self) -> Result<TaprootSpendInfo, Error> {
         let secp = Secp256k1::new();
@@ -342,20 +430,7 @@ impl LBtcSwapScript {
         // Verify taproot construction
         if let Some(funding_addrs) = &self.funding_addrs {
             let claim_key = taproot_spend_info.output_key();
-
-            let lockup_spk = funding_addrs.script_pubkey();
-
-            let pubkey_instruction = lockup_spk
-                .instructions()
-                .last()
-                .expect("should contain value")
-                .expect("should not fail");
-
-            let lockup_xonly_pubkey_bytes = pubkey_instruction
-                .push_bytes()
-                .expect("pubkey bytes expected");
-
-            let lockup_xonly_pubkey = XOnlyPublicKey::from_slice(lockup_xonly_pubkey_bytes)?;
+            let lockup_xonly_pubkey = lockup_output_key(&funding_addrs.script_pubkey())?;
 
             if lockup_xonly_pubkey != claim_key.into_inner() {
                 return Err(Error::Protocol(format!(
@@ -370,20 +445,130 @@ impl LBtcSwapScript {
         Ok(taproot_spend_info)
     }
 
+    /// Check that input `input_index` of a signed `tx` is a valid spend of this swap script,
+    /// without broadcasting it. `prevouts` are the outputs spent by all inputs of `tx`, in order.
+    ///
+    /// For script path spends, the leaf and control block must match this script, the preimage
+    /// must match the hashlock and the refund timelock must be satisfied. The signature is
+    /// checked against the aggregated key, the receiver key or the sender key respectively.
+    pub fn verify_spend(
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+        prevouts: &[TxOut],
+        genesis_hash: BlockHash,
+    ) -> Result<SpendPath, Error> {
+        let secp = Secp256k1::new();
+        let input = tx.input.get(input_index).ok_or(Error::Protocol(format!(
+            "No input at index {}",
+            input_index
+        )))?;
+        let output_key = self.taproot_spendinfo()?.output_key().into_inner();
+        let witness = &input.witness.script_witness;
+        let mut sighash_cache = SighashCache::new(tx);
+        let prevouts = Prevouts::All(prevouts);
+
+        let signature = SchnorrSig::from_slice(
+            witness
+                .first()
+                .ok_or(Error::Protocol("Empty witness".to_string()))?,
+        )
+        .map_err(|e| Error::Protocol(format!("Invalid signature encoding: {}", e)))?;
+
+        let (path, leaf, pubkey) = match witness.len() {
+            1 => {
+                let sighash = sighash_cache.taproot_key_spend_signature_hash(
+                    input_index,
+                    &prevouts,
+                    signature.hash_ty,
+                    genesis_hash,
+                )?;
+                let msg = Message::from_digest_slice(sighash.as_byte_array())?;
+                secp.verify_schnorr(&signature.sig, &msg, &output_key)
+                    .map_err(|e| Error::Protocol(format!("Invalid key path signature: {}", e)))?;
+                return Ok(SpendPath::KeyPath);
+            }
+            4 => {
+                let preimage = &witness[1];
+                if preimage.len() != 32 || hash160::Hash::hash(preimage) != self.hashlock {
+                    return Err(Error::Protocol(
+                        "Preimage does not match the hashlock".to_string(),
+                    ));
+                }
+                (
+                    SpendPath::ClaimLeaf,
+                    self.claim_script(),
+                    self.receiver_pubkey,
+                )
+            }
+            3 => {
+                if let Some(required) = self.relative_timelock {
+                    if !relative_timelock_satisfied(required, input.sequence) || tx.version < 2 {
+                        return Err(Error::Protocol(format!(
+                            "Relative refund timelock {} not satisfied by input sequence {}",
+                            required.to_consensus_u32(),
+                            input.sequence.to_consensus_u32()
+                        )));
+                    }
+                } else if tx.lock_time.is_block_height() != self.locktime.is_block_height()
+                    || tx.lock_time.to_consensus_u32() < self.locktime.to_consensus_u32()
+                    || !input.sequence.enables_absolute_lock_time()
+                {
+                    return Err(Error::Protocol(format!(
+                        "Refund locktime {} not satisfied by transaction locktime {}",
+                        self.locktime, tx.lock_time
+                    )));
+                }
+                (
+                    SpendPath::RefundLeaf,
+                    self.refund_script(),
+                    self.sender_pubkey,
+                )
+            }
+            n => {
+                return Err(Error::Protocol(format!(
+                    "Unexpected witness with {} elements",
+                    n
+                )))
+            }
+        };
+
+        if witness[witness.len() - 2] != leaf.as_bytes() {
+            return Err(Error::Protocol(format!(
+                "Witness script does not match the {:?} script",
+                path
+            )));
+        }
+        let control_block = ControlBlock::from_slice(&witness[witness.len() - 1])?;
+        if !control_block.verify_taproot_commitment(&secp, &output_key, &leaf) {
+            return Err(Error::Protocol(
+                "Control block does not commit to the swap script".to_string(),
+            ));
+        }
+
+        let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::default());
+        let sighash = sighash_cache.taproot_script_spend_signature_hash(
+            input_index,
+            &prevouts,
+            leaf_hash,
+            signature.hash_ty,
+            genesis_hash,
+        )?;
+        let msg = Message::from_digest_slice(sighash.as_byte_array())?;
+        let pubkey = secp256k1_zkp::XOnlyPublicKey::from_slice(
+            &pubkey.inner.x_only_public_key().0.serialize(),
+        )?;
+        secp.verify_schnorr(&signature.sig, &msg, &pubkey)
+            .map_err(|e| Error::Protocol(format!("Invalid {:?} signature: {}", path, e)))?;
+
+        Ok(path)
+    }
+
     /// Get taproot address for the swap script.
     /// Always returns a confidential address
     pub fn to_address(&self, network: Chain) -> Result<EAddress, Error> {
         let taproot_spend_info = self.taproot_spendinfo()?;
-        let address_params = match network {
-            Chain::Liquid => &AddressParams::LIQUID,
-            Chain::LiquidTestnet => &AddressParams::LIQUID_TESTNET,
-            Chain::LiquidRegtest => &AddressParams::ELEMENTS,
-            _ => {
-                return Err(Error::Address(
-                    "Cannot derive Liquid address for Bitcoin network".to_string(),
-                ))
-            }
-        };
+        let address_params = network.liquid_address_params()?;
 
         Ok(EAddress::p2tr(
             &Secp256k1::new(),
@@ -394,6 +579,26 @@ impl LBtcSwapScript {
         ))
     }
 
+    /// Get the `addr()` output descriptor of the swap script, with checksum.
+    /// Uses the unconfidential address, so the importing wallet can only watch the script.
+    pub fn to_descriptor(&self, network: Chain) -> Result<String, Error> {
+        addr_descriptor(&self.to_address(network)?.to_unconfidential().to_string())
+    }
+
+    /// Stable identifier of the swap script, usable as a deduplication key in persistence
+    /// and to match recovered scripts with Boltz records.
+    pub fn fingerprint(&self) -> sha256::Hash {
+        script_fingerprint(
+            self.swap_type,
+            self.hashlock.as_byte_array(),
+            &self.receiver_pubkey,
+            &self.sender_pubkey,
+            self.locktime.to_consensus_u32(),
+            self.relative_timelock
+                .map(|sequence| sequence.to_consensus_u32()),
+        )
+    }
+
     pub fn validate_address(&self, chain: Chain, address: String) -> Result<(), Error> {
         let to_address = self.to_address(chain)?;
         if to_address.to_string() == address {
@@ -403,35 +608,194 @@ impl LBtcSwapScript {
         }
     }
 
+    /// Unblind an output locked to this swap script, to get its true value and asset.
+    pub fn unblind(&self, txout: &TxOut) -> Result<TxOutSecrets, Error> {
+        unblind_txout(txout, &self.blinding_key.secret_key())
+    }
+
+    /// Verify the confidential proofs of a lockup output and unblind it.
+    /// `input_assets` are the assets of the outputs spent by the lockup tx, see
+    /// [`fetch_input_assets`].
+    pub fn verify_lockup(
+        &self,
+        txout: &TxOut,
+        input_assets: &[Asset],
+    ) -> Result<TxOutSecrets, Error> {
+        verify_confidential_txout(txout, &self.blinding_key.secret_key(), input_assets)
+    }
+
+    /// Confirmed and unconfirmed balance of `asset` held by the swap script, in sats.
+    pub fn get_balance(
+        &self,
+        network_config: &ElectrumConfig,
+        asset: elements::AssetId,
+    ) -> Result<(u64, u64), Error> {
+        let address = self.to_address(network_config.network())?;
+        get_address_balance(
+            network_config,
+            &address,
+            &self.blinding_key.secret_key(),
+            asset,
+        )
+    }
+
+    /// Poll until the script has a utxo known to the Electrum server, e.g. after Boltz
+    /// reported its lockup tx, and return it. Fails once `timeout` has elapsed.
+    /// Transient errors, e.g. a dropped Electrum connection, are retried until then, other
+    /// errors are returned right away.
+    pub fn wait_for_utxo(
+        &self,
+        network_config: &ElectrumConfig,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<(OutPoint, TxOut), Error> {
+        let start = Instant::now();
+        loop {
+            match self.find_utxo(network_config) {
+                Ok(Some(utxo)) => return Ok(utxo),
+                Ok(None) => {}
+                Err(e) if e.is_retryable() => {
+                    log::debug!("Failed to fetch swap utxo: {}", e.message())
+                }
+                Err(e) => return Err(e),
+            }
+            if start.elapsed() + poll_interval > timeout {
+                return Err(Error::Protocol(format!(
+                    "No utxo found for the swap script after {:?}",
+                    timeout
+                )));
+            }
+            thread::sleep(poll_interval);
+        }
+    }
+
     /// Fetch utxo for script from Electrum
     pub fn fetch_utxo(&self, network_config: &ElectrumConfig) -> Result<(OutPoint, TxOut), Error> {
-        let electrum_client = network_config.clone().build_client()?;
+        self.find_utxo(network_config)?.ok_or(Error::Protocol(
+            "Electrum could not find a Liquid UTXO for script".to_string(),
+        ))
+    }
+
+    /// Output paying the script in the most recent tx of its history, None if there is none.
+    fn find_utxo(
+        &self,
+        network_config: &ElectrumConfig,
+    ) -> Result<Option<(OutPoint, TxOut)>, Error> {
         let address = self.to_address(network_config.network())?;
+        let electrum_client = network_config.client()?;
         let history = electrum_client.script_get_history(BitcoinScript::from_bytes(
-            self.to_address(network_config.network())?
-                .to_unconfidential()
-                .script_pubkey()
-                .as_bytes(),
+            address.to_unconfidential().script_pubkey().as_bytes(),
         ))?;
-        if history.is_empty() {
-            return Err(Error::Protocol("No Transaction History".to_string()));
-        }
-        let bitcoin_txid = history.last().expect("txid expected").tx_hash;
-        let raw_tx = electrum_client.transaction_get_raw(&bitcoin_txid)?;
+        let last_txid = match history.last() {
+            Some(last) => last.tx_hash,
+            None => return Ok(None),
+        };
+        let raw_tx = electrum_client.transaction_get_raw(&last_txid)?;
         let tx: Transaction = elements::encode::deserialize(&raw_tx)?;
-        for (vout, output) in tx.clone().output.into_iter().enumerate() {
-            if output.script_pubkey == address.script_pubkey() {
-                let outpoint_0 = OutPoint::new(tx.txid(), vout as u32);
+        let txid = tx.txid();
+        Ok(tx
+            .output
+            .into_iter()
+            .enumerate()
+            .find(|(_, output)| output.script_pubkey == address.script_pubkey())
+            .map(|(vout, output)| (OutPoint::new(txid, vout as u32), output)))
+    }
+
+    /// Fetch the most recent utxo of the script holding `asset`, e.g. the L-BTC policy asset.
+    /// Unlike [`LBtcSwapScript::fetch_utxo`], outputs of other assets sent to the swap address
+    /// are skipped. Outputs are unblinded with the swap blinding key, explicit outputs are
+    /// matched directly.
+    pub fn fetch_asset_utxo(
+        &self,
+        network_config: &ElectrumConfig,
+        asset: elements::AssetId,
+    ) -> Result<(OutPoint, TxOut), Error> {
+        Ok(self.fetch_asset_utxos(network_config, asset)?.remove(0))
+    }
 
-                return Ok((outpoint_0, output));
+    /// All outputs holding `asset` of the most recent tx paying the script such an output,
+    /// by vout. Never empty.
+    pub fn fetch_asset_utxos(
+        &self,
+        network_config: &ElectrumConfig,
+        asset: elements::AssetId,
+    ) -> Result<Vec<(OutPoint, TxOut)>, Error> {
+        let electrum_client = network_config.client()?;
+        let address = self.to_address(network_config.network())?;
+        let history = electrum_client.script_get_history(BitcoinScript::from_bytes(
+            address.to_unconfidential().script_pubkey().as_bytes(),
+        ))?;
+        let raw_txs = electrum_client.batch_transaction_get_raw(
+            &history.iter().rev().map(|h| h.tx_hash).collect::<Vec<_>>(),
+        )?;
+        for raw_tx in raw_txs {
+            let tx: Transaction = elements::encode::deserialize(&raw_tx)?;
+            let utxos = select_asset_outputs(
+                &tx,
+                &address.script_pubkey(),
+                &self.blinding_key.secret_key(),
+                asset,
+            );
+            if !utxos.is_empty() {
+                return Ok(utxos);
             }
         }
-        Err(Error::Protocol(
-            "Electrum could not find a Liquid UTXO for script".to_string(),
-        ))
+        Err(Error::Protocol(format!(
+            "Electrum could not find a Liquid UTXO of asset {} for script",
+            asset
+        )))
     }
 
-    /// Fetch utxo for script from BoltzApi
+    /// Fetch the L-BTC outputs of the lockup tx of the swap from Electrum, or else from the
+    /// lockup tx reported by BoltzApi, by vout. Outputs of other assets sent to the swap address
+    /// are skipped. Never empty.
+    fn fetch_lbtc_utxos(
+        &self,
+        network_config: &ElectrumConfig,
+        boltz_url: &str,
+        swap_id: &str,
+        tx_kind: SwapTxKind,
+    ) -> Result<Vec<(OutPoint, TxOut)>, Error> {
+        let chain = network_config.network();
+        let lbtc = chain.lbtc_asset_id().ok_or(Error::Protocol(format!(
+            "{:?} is not a Liquid chain",
+            chain
+        )))?;
+        if let Ok(utxos) = self.fetch_asset_utxos(network_config, lbtc) {
+            return Ok(utxos);
+        }
+        let utxos = self.fetch_lockup_utxos_boltz(network_config, boltz_url, swap_id, tx_kind)?;
+        let utxos = self.select_asset_utxos(utxos, lbtc);
+        if utxos.is_empty() {
+            return Err(Error::Protocol(format!(
+                "Boltz could not find a Liquid UTXO of asset {} for script",
+                lbtc
+            )));
+        }
+        Ok(utxos)
+    }
+
+    /// Utxos of `utxos` holding `asset`, by vout.
+    fn select_asset_utxos(
+        &self,
+        utxos: Vec<(OutPoint, TxOut)>,
+        asset: elements::AssetId,
+    ) -> Vec<(OutPoint, TxOut)> {
+        let mut utxos: Vec<_> = utxos
+            .into_iter()
+            .filter(|(_, txout)| {
+                self.unblind(txout)
+                    .map(|secrets| secrets.asset == asset)
+                    .unwrap_or(false)
+            })
+            .collect();
+        utxos.sort_by_key(|(outpoint, _)| outpoint.vout);
+        utxos
+    }
+
+    /// Fetch utxo for script from BoltzApi.
+    /// Values are blinded, so if the lockup tx pays the script more than once, the output with
+    /// the lowest vout is used.
     pub fn fetch_lockup_utxo_boltz(
         &self,
         network_config: &ElectrumConfig,
@@ -439,6 +803,23 @@ impl LBtcSwapScript {
         swap_id: &str,
         tx_kind: SwapTxKind,
     ) -> Result<(OutPoint, TxOut), Error> {
+        self.fetch_lockup_utxos_boltz(network_config, boltz_url, swap_id, tx_kind)?
+            .into_iter()
+            .min_by_key(|(outpoint, _)| outpoint.vout)
+            .ok_or(Error::Protocol(
+                "Boltz could not find a Liquid UTXO for script".to_string(),
+            ))
+    }
+
+    /// Fetch all outputs of the lockup tx reported by BoltzApi that pay to the script.
+    /// Every output is cross-checked against chain data when the chain client is available.
+    pub fn fetch_lockup_utxos_boltz(
+        &self,
+        network_config: &ElectrumConfig,
+        boltz_url: &str,
+        swap_id: &str,
+        tx_kind: SwapTxKind,
+    ) -> Result<Vec<(OutPoint, TxOut)>, Error> {
         let boltz_client = BoltzApiClientV2::new(boltz_url);
         let hex = match self.swap_type {
             SwapType::Chain => match tx_kind {
@@ -473,16 +854,26 @@ impl LBtcSwapScript {
         }
         let address = self.to_address(network_config.network())?;
         let tx: Transaction = elements::encode::deserialize(&hex::decode(hex.unwrap())?)?;
-        for (vout, output) in tx.clone().output.into_iter().enumerate() {
+        let txid = tx.txid();
+        let chain_tx = network_config.get_liquid_tx(&txid);
+        let mut utxos = vec![];
+        for (vout, output) in tx.output.into_iter().enumerate() {
             if output.script_pubkey == address.script_pubkey() {
-                let outpoint_0 = OutPoint::new(tx.txid(), vout as u32);
-
-                return Ok((outpoint_0, output));
+                let chain_output = match &chain_tx {
+                    Ok(chain_tx) => Ok(chain_tx.output.get(vout).cloned()),
+                    Err(e) => Err(Error::Generic(format!("{:?}", e))),
+                };
+                check_boltz_lockup(
+                    swap_id,
+                    &txid.to_string(),
+                    vout as u32,
+                    &output,
+                    chain_output,
+                )?;
+                utxos.push((OutPoint::new(txid, vout as u32), output));
             }
         }
-        Err(Error::Protocol(
-            "Boltz could not find a Liquid UTXO for script".to_string(),
-        ))
+        Ok(utxos)
     }
 
     // Get the chain genesis hash. Requires for sighash calculation
@@ -490,13 +881,286 @@ impl LBtcSwapScript {
         &self,
         electrum_config: &ElectrumConfig,
     ) -> Result<elements::BlockHash, Error> {
-        let electrum = electrum_config.build_client()?;
+        let electrum = electrum_config.client()?;
         Ok(elements::BlockHash::from_raw_hash(
             electrum.block_header(0)?.block_hash().into(),
         ))
     }
 }
 
+/// Unblind a Liquid output with the given blinding key, to get its true value and asset.
+/// Explicit outputs are returned as is, with zero blinding factors.
+pub fn unblind_txout(txout: &TxOut, blinding_key: &SecretKey) -> Result<TxOutSecrets, Error> {
+    if let (Some(asset), Some(value)) = (txout.asset.explicit(), txout.value.explicit()) {
+        return Ok(TxOutSecrets::new(
+            asset,
+            AssetBlindingFactor::zero(),
+            value,
+            ValueBlindingFactor::zero(),
+        ));
+    }
+    Ok(txout.unblind(&Secp256k1::new(), *blinding_key)?)
+}
+
+/// First output of `tx` paying to `script_pubkey` that holds `asset`.
+fn select_asset_output(
+    tx: &Transaction,
+    script_pubkey: &Script,
+    blinding_key: &SecretKey,
+    asset: elements::AssetId,
+) -> Option<(OutPoint, TxOut)> {
+    select_asset_outputs(tx, script_pubkey, blinding_key, asset)
+        .into_iter()
+        .next()
+}
+
+/// All outputs of `tx` paying to `script_pubkey` that hold `asset`, by vout.
+fn select_asset_outputs(
+    tx: &Transaction,
+    script_pubkey: &Script,
+    blinding_key: &SecretKey,
+    asset: elements::AssetId,
+) -> Vec<(OutPoint, TxOut)> {
+    let txid = tx.txid();
+    tx.output
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| {
+            output.script_pubkey == *script_pubkey
+                && unblind_txout(output, blinding_key)
+                    .map(|secrets| secrets.asset == asset)
+                    .unwrap_or(false)
+        })
+        .map(|(vout, output)| (OutPoint::new(txid, vout as u32), output.clone()))
+        .collect()
+}
+
+/// The lockup output to refund. Unlike Bitcoin refunds, Liquid refunds spend a single output,
+/// so a lockup tx paying the swap script more than once is rejected instead of leaving the other
+/// outputs behind unnoticed.
+fn single_refund_utxo(mut utxos: Vec<(OutPoint, TxOut)>) -> Result<(OutPoint, TxOut), Error> {
+    match utxos.len() {
+        1 => Ok(utxos.remove(0)),
+        count => Err(Error::Protocol(format!(
+            "Liquid refunds spend a single lockup output, but the swap script holds {} L-BTC \
+             outputs. Refund each with an LBtcSwapTx of its own",
+            count
+        ))),
+    }
+}
+
+/// Assets of the outputs spent by the inputs of transaction `txid`, in input order.
+/// Assets issued by the transaction are not included.
+pub fn fetch_input_assets(
+    network_config: &ElectrumConfig,
+    txid: &elements::Txid,
+) -> Result<Vec<Asset>, Error> {
+    let tx = network_config.get_liquid_tx(txid)?;
+    let prev_txids: Vec<bitcoin::Txid> = tx
+        .input
+        .iter()
+        .map(|input| bitcoin::Txid::from_byte_array(input.previous_output.txid.to_byte_array()))
+        .collect();
+    let raw_prev_txs = network_config
+        .client()?
+        .batch_transaction_get_raw(&prev_txids)?;
+    tx.input
+        .iter()
+        .zip(raw_prev_txs)
+        .map(|(input, raw_prev_tx)| {
+            let prev_tx: Transaction = elements::encode::deserialize(&raw_prev_tx)?;
+            prev_tx
+                .output
+                .get(input.previous_output.vout as usize)
+                .map(|output| output.asset)
+                .ok_or(Error::Protocol(format!(
+                    "Spent output {} not found",
+                    input.previous_output
+                )))
+        })
+        .collect()
+}
+
+/// Confirmed and unconfirmed balance of `asset` held by a Liquid address, in sats.
+///
+/// Outputs are unblinded with `blinding_key`. Outputs that can't be unblinded with it are skipped.
+pub fn get_address_balance(
+    network_config: &ElectrumConfig,
+    address: &EAddress,
+    blinding_key: &SecretKey,
+    asset: elements::AssetId,
+) -> Result<(u64, u64), Error> {
+    let electrum_client = network_config.client()?;
+    let spk = address.to_unconfidential().script_pubkey();
+    let history = electrum_client.script_get_history(BitcoinScript::from_bytes(spk.as_bytes()))?;
+    let raw_txs = electrum_client
+        .batch_transaction_get_raw(&history.iter().map(|h| h.tx_hash).collect::<Vec<_>>())?;
+    let txs = raw_txs
+        .iter()
+        .zip(history.iter())
+        .map(|(raw_tx, h)| {
+            let tx: Transaction = elements::encode::deserialize(raw_tx)?;
+            Ok((tx, h.height > 0))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(address_balance_core(&txs, &spk, blinding_key, asset))
+}
+
+fn address_balance_core(
+    txs: &[(Transaction, bool)],
+    spk: &Script,
+    blinding_key: &SecretKey,
+    asset: elements::AssetId,
+) -> (u64, u64) {
+    let mut confirmed = 0;
+    let mut unconfirmed = 0;
+    for (tx, is_confirmed) in txs {
+        let txid = tx.txid();
+        for (vout, output) in tx.output.iter().enumerate() {
+            if output.script_pubkey != *spk {
+                continue;
+            }
+            let is_spent = txs.iter().any(|(spending_tx, _)| {
+                spending_tx
+                    .input
+                    .iter()
+                    .any(|input| input.previous_output == OutPoint::new(txid, vout as u32))
+            });
+            if is_spent {
+                continue;
+            }
+            match unblind_txout(output, blinding_key) {
+                Ok(secrets) if secrets.asset == asset => match is_confirmed {
+                    true => confirmed += secrets.value,
+                    false => unconfirmed += secrets.value,
+                },
+                Ok(_) => {}
+                Err(e) => log::warn!("Could not unblind output {}:{}: {:?}", txid, vout, e),
+            }
+        }
+    }
+    (confirmed, unconfirmed)
+}
+
+/// Verify the confidential proofs of an output and unblind it.
+///
+/// Checks that the rangeproof is valid for the value commitment, that the surjection proof shows
+/// the asset is one of `input_assets`, the assets of all outputs spent by the transaction, and
+/// that the unblinded asset and value open the output commitments.
+pub fn verify_confidential_txout(
+    txout: &TxOut,
+    blinding_key: &SecretKey,
+    input_assets: &[Asset],
+) -> Result<TxOutSecrets, Error> {
+    let secp = Secp256k1::new();
+    let secrets = unblind_txout(txout, blinding_key)?;
+
+    match (txout.asset, txout.value) {
+        (Asset::Explicit(_), Value::Explicit(_)) => {}
+        (Asset::Confidential(generator), Value::Confidential(commitment)) => {
+            let rangeproof = txout
+                .witness
+                .rangeproof
+                .as_ref()
+                .ok_or(Error::ConfidentialProof("Missing rangeproof".to_string()))?;
+            let range = rangeproof
+                .verify(&secp, commitment, txout.script_pubkey.as_bytes(), generator)
+                .map_err(|e| Error::ConfidentialProof(format!("Invalid rangeproof: {}", e)))?;
+            if !range.contains(&secrets.value) {
+                return Err(Error::ConfidentialProof(format!(
+                    "Unblinded value {} outside of rangeproof range {:?}",
+                    secrets.value, range
+                )));
+            }
+
+            let surjection_proof =
+                txout
+                    .witness
+                    .surjection_proof
+                    .as_ref()
+                    .ok_or(Error::ConfidentialProof(
+                        "Missing surjection proof".to_string(),
+                    ))?;
+            let domain = input_assets
+                .iter()
+                .map(|asset| asset.into_asset_gen(&secp))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(Error::ConfidentialProof(
+                    "Spent output without asset".to_string(),
+                ))?;
+            if !surjection_proof.verify(&secp, generator, &domain) {
+                return Err(Error::ConfidentialProof(
+                    "Invalid surjection proof".to_string(),
+                ));
+            }
+
+            if Asset::new_confidential(&secp, secrets.asset, secrets.asset_bf) != txout.asset {
+                return Err(Error::ConfidentialProof(
+                    "Asset commitment does not match unblinded asset".to_string(),
+                ));
+            }
+
+            if Value::new_confidential(&secp, secrets.value, generator, secrets.value_bf)
+                != txout.value
+            {
+                return Err(Error::ConfidentialProof(
+                    "Value commitment does not match unblinded value".to_string(),
+                ));
+            }
+        }
+        _ => {
+            return Err(Error::ConfidentialProof(
+                "Output mixes explicit and confidential asset/value".to_string(),
+            ))
+        }
+    }
+
+    if secrets.value == 0 {
+        return Err(Error::ConfidentialProof(
+            "Output has zero value".to_string(),
+        ));
+    }
+
+    Ok(secrets)
+}
+
+/// Relative timelock of a refund leaf ending in `<sequence> OP_CHECKSEQUENCEVERIFY`, if any.
+fn relative_refund_timelock(refund_script: &Script) -> Option<Sequence> {
+    let instructions: Vec<Instruction> = refund_script
+        .instructions()
+        .filter_map(Result::ok)
+        .collect();
+    let delay = match instructions.as_slice() {
+        [.., Instruction::PushBytes(bytes), Instruction::Op(op)] if *op == OP_CSV => {
+            read_scriptint(bytes).ok()?
+        }
+        // Small delays are pushed as OP_1..OP_16 instead of data
+        [.., Instruction::Op(delay), Instruction::Op(op)]
+            if *op == OP_CSV
+                && (OP_PUSHNUM_1.into_u8()..=OP_PUSHNUM_16.into_u8())
+                    .contains(&delay.into_u8()) =>
+        {
+            (delay.into_u8() - OP_PUSHNUM_1.into_u8() + 1).into()
+        }
+        _ => return None,
+    };
+    Some(Sequence::from_consensus(u32::try_from(delay).ok()?))
+}
+
+/// Whether an input `sequence` satisfies the relative timelock `required` of an
+/// OP_CHECKSEQUENCEVERIFY (BIP68/BIP112).
+fn relative_timelock_satisfied(required: Sequence, sequence: Sequence) -> bool {
+    const DISABLE_FLAG: u32 = 1 << 31;
+    const TYPE_FLAG: u32 = 1 << 22;
+    const VALUE_MASK: u32 = 0xffff;
+
+    let (required, actual) = (required.to_consensus_u32(), sequence.to_consensus_u32());
+    required & DISABLE_FLAG == 0
+        && actual & DISABLE_FLAG == 0
+        && required & TYPE_FLAG == actual & TYPE_FLAG
+        && required & VALUE_MASK <= actual & VALUE_MASK
+}
+
 fn bytes_to_u32_little_endian(bytes: &[u8]) -> u32 {
     let mut result = 0u32;
     for (i, &byte) in bytes.iter().enumerate() {
@@ -512,8 +1176,18 @@ pub struct LBtcSwapTx {
     pub swap_script: LBtcSwapScript,
     pub output_address: Address,
     pub funding_outpoint: OutPoint,
-    pub funding_utxo: TxOut, // there should only ever be one outpoint in a swap
+    /// The single lockup output spent. Unlike [`BtcSwapTx`](crate::BtcSwapTx), extra outputs
+    /// paying the swap script are not spent along.
+    pub funding_utxo: TxOut,
     pub genesis_hash: BlockHash, // Required to calculate sighash
+    /// Whether claim transactions signal replaceability (BIP-125).
+    /// Defaults to false, as zero-conf acceptors on Liquid usually require final claims.
+    pub signal_rbf: bool,
+    /// Data added as a null data output to claim and refund transactions, e.g. an order reference.
+    /// At most [`MAX_MEMO_SIZE`] bytes. The output is included in size and fee calculations.
+    pub memo: Option<Vec<u8>>,
+    /// Sanity limits checked before a signed claim or refund is returned.
+    pub limits: TxLimits,
 }
 
 impl LBtcSwapTx {
@@ -531,17 +1205,18 @@ impl LBtcSwapTx {
             ));
         }
 
-        let (funding_outpoint, funding_utxo) = match swap_script.fetch_utxo(network_config) {
-            Ok(r) => r,
-            Err(_) => swap_script.fetch_lockup_utxo_boltz(
-                network_config,
-                &boltz_url,
-                &swap_id,
-                SwapTxKind::Claim,
-            )?,
-        };
+        screen_address(&output_address, AddressPurpose::Claim)?;
+
+        // If the lockup tx pays the script more than once, the output with the lowest vout
+        // is claimed
+        let (funding_outpoint, funding_utxo) = swap_script
+            .fetch_lbtc_utxos(network_config, &boltz_url, &swap_id, SwapTxKind::Claim)?
+            .remove(0);
+
+        // Never claim from a malformed confidential output.
+        let input_assets = fetch_input_assets(network_config, &funding_outpoint.txid)?;
+        swap_script.verify_lockup(&funding_utxo, &input_assets)?;
 
-        let electrum = network_config.build_client()?;
         let genesis_hash = liquid_genesis_hash(network_config)?;
 
         Ok(LBtcSwapTx {
@@ -551,10 +1226,15 @@ impl LBtcSwapTx {
             funding_outpoint,
             funding_utxo,
             genesis_hash,
+            signal_rbf: false,
+            memo: None,
+            limits: TxLimits::default(),
         })
     }
 
     /// Construct a RefundTX corresponding to the swap_script. Only works for Submarine and Chain Swaps.
+    /// Refunds spend a single lockup output. Errors if the lockup tx pays the swap script more
+    /// than once, build an `LBtcSwapTx` for each output instead.
     pub fn new_refund(
         swap_script: LBtcSwapScript,
         output_address: &str,
@@ -569,17 +1249,14 @@ impl LBtcSwapTx {
         }
 
         let address = Address::from_str(output_address)?;
-        let (funding_outpoint, funding_utxo) = match swap_script.fetch_utxo(network_config) {
-            Ok(r) => r,
-            Err(_) => swap_script.fetch_lockup_utxo_boltz(
-                network_config,
-                &boltz_url,
-                &swap_id,
-                SwapTxKind::Refund,
-            )?,
-        };
+        screen_address(output_address, AddressPurpose::Refund)?;
+        let (funding_outpoint, funding_utxo) = single_refund_utxo(swap_script.fetch_lbtc_utxos(
+            network_config,
+            &boltz_url,
+            &swap_id,
+            SwapTxKind::Refund,
+        )?)?;
 
-        let electrum = network_config.build_client()?;
         let genesis_hash = liquid_genesis_hash(network_config)?;
 
         Ok(LBtcSwapTx {
@@ -589,9 +1266,48 @@ impl LBtcSwapTx {
             funding_outpoint,
             funding_utxo,
             genesis_hash,
+            signal_rbf: false,
+            memo: None,
+            limits: TxLimits::default(),
         })
     }
 
+    /// Liquid chain of the swap, from the genesis hash.
+    pub fn chain(&self) -> Chain {
+        liquid_chain_from_genesis(&self.genesis_hash.to_string())
+    }
+
+    /// Unblinded value and asset of the lockup utxo, e.g. to show the received amount.
+    pub fn funding_secrets(&self) -> Result<TxOutSecrets, Error> {
+        self.swap_script.unblind(&self.funding_utxo)
+    }
+
+    /// Check that the lockup utxo holds L-BTC, at least `expected_amount` sats less `tolerance`.
+    /// Call before claiming a reverse or chain swap, so the preimage is not revealed for a
+    /// lockup short of the quoted amount, or of another asset.
+    pub fn verify_lockup(&self, expected_amount: u64, tolerance: u64) -> Result<(), Error> {
+        let secrets = self.funding_secrets()?;
+        let chain = self.chain();
+        let lbtc = chain.lbtc_asset_id().ok_or(Error::Protocol(format!(
+            "{:?} is not a Liquid chain",
+            chain
+        )))?;
+        if secrets.asset != lbtc {
+            return Err(Error::Protocol(format!(
+                "Lockup holds asset {} instead of L-BTC {}",
+                secrets.asset, lbtc
+            )));
+        }
+        let locked = secrets.value;
+        if locked + tolerance < expected_amount {
+            return Err(Error::AmountMismatch(format!(
+                "Lockup of {} sats is below the expected {} sats",
+                locked, expected_amount
+            )));
+        }
+        Ok(())
+    }
+
     /// Compute the Musig partial signature.
     /// This is used to cooperatively close a Submarine or Chain Swap.
     pub fn partial_sign(
@@ -600,45 +1316,46 @@ impl LBtcSwapTx {
         pub_nonce: &str,
         transaction_hash: &str,
     ) -> Result<(MusigPartialSignature, MusigPubNonce), Error> {
-        // Step 1: Start with a Musig KeyAgg Cache
-        let secp = Secp256k1::new();
-
-        let pubkeys = [
-            self.swap_script.receiver_pubkey.inner,
-            self.swap_script.sender_pubkey.inner,
-        ];
-
-        let mut key_agg_cache = MusigKeyAggCache::new(&secp, &pubkeys);
-
-        let tweak = SecretKey::from_slice(
-            self.swap_script
-                .taproot_spendinfo()?
-                .tap_tweak()
-                .as_byte_array(),
-        )?;
-
-        let _ = key_agg_cache.pubkey_xonly_tweak_add(&secp, tweak)?;
-
-        let session_id = MusigSessionId::new(&mut thread_rng());
-
+        let signer = KeypairSigner::new(*keys);
+        let musig = self.swap_script.musig_context()?;
         let msg = Message::from_digest_slice(&Vec::from_hex(transaction_hash)?)?;
 
-        // Step 4: Start the Musig2 Signing session
-        let mut extra_rand = [0u8; 32];
-        OsRng.fill_bytes(&mut extra_rand);
+        let gen_pub_nonce = musig.nonce(&signer, msg)?;
+        let boltz_nonce = pub_nonce_from_hex(pub_nonce)?;
+        let musig_session = musig.session(gen_pub_nonce, boltz_nonce, msg);
+        let partial_sig = musig.partial_sign(&signer, &musig_session, &gen_pub_nonce)?;
 
-        let (gen_sec_nonce, gen_pub_nonce) =
-            key_agg_cache.nonce_gen(&secp, session_id, keys.public_key(), msg, Some(extra_rand))?;
-
-        let boltz_nonce = MusigPubNonce::from_slice(&Vec::from_hex(pub_nonce)?)?;
+        Ok((partial_sig, gen_pub_nonce))
+    }
 
-        let agg_nonce = MusigAggNonce::new(&secp, &[boltz_nonce, gen_pub_nonce]);
+    /// Handle the `transaction.claim.pending` status of a Submarine Swap.
+    /// Fetches the claim details from Boltz, checks that the preimage matches the invoice
+    /// and posts our partial signature, so Boltz can claim the lockup cooperatively.
+    /// Returns the preimage as proof of payment.
+    pub fn cooperative_submarine_claim(
+        &self,
+        keys: &Keypair,
+        boltz_api: &BoltzApiClientV2,
+        swap_id: &String,
+        invoice: &str,
+    ) -> Result<Preimage, Error> {
+        if self.swap_script.swap_type != SwapType::Submarine {
+            return Err(Error::Protocol(
+                "Cooperative submarine claim called on a non-submarine swap".to_string(),
+            ));
+        }
 
-        let musig_session = MusigSession::new(&secp, &key_agg_cache, agg_nonce, msg);
+        let claim_tx_response = boltz_api.get_submarine_claim_tx_details(swap_id)?;
+        let preimage = claim_tx_response.validate_preimage(invoice)?;
 
-        let partial_sig = musig_session.partial_sign(&secp, gen_sec_nonce, keys, &key_agg_cache)?;
+        let (partial_sig, pub_nonce) = self.partial_sign(
+            keys,
+            &claim_tx_response.pub_nonce,
+            &claim_tx_response.transaction_hash,
+        )?;
+        boltz_api.post_submarine_claim_tx_details(swap_id, pub_nonce, partial_sig)?;
 
-        Ok((partial_sig, gen_pub_nonce))
+        Ok(preimage)
     }
 
     /// Sign a claim transaction.
@@ -665,128 +1382,85 @@ impl LBtcSwapTx {
             ));
         }
 
-        let mut claim_tx = create_tx_with_fee(
+        if let Some(cooperative) = is_cooperative {
+            return self
+                .musig_session(keys, Some(preimage), fee, is_discount_ct)?
+                .resume(&cooperative);
+        }
+
+        let claim_tx = create_tx_with_fee(
             fee,
-            |fee| self.create_claim(keys, preimage, fee, is_cooperative.is_some()),
+            |fee| self.create_claim(keys, preimage, fee, false),
             |tx| tx_size(&tx, is_discount_ct),
         )?;
 
-        // If its a cooperative claim, compute the Musig2 Aggregate Signature and use Keypath spending
-        if let Some(Cooperative {
-            boltz_api,
-            swap_id,
-            pub_nonce,
-            partial_sig,
-        }) = is_cooperative
-        {
-            let claim_tx_taproot_hash = SighashCache::new(&claim_tx)
-                .taproot_key_spend_signature_hash(
-                    0,
-                    &Prevouts::All(&[&self.funding_utxo]),
-                    SchnorrSighashType::Default,
-                    self.genesis_hash,
-                )?;
-
-            let msg = Message::from_digest_slice(claim_tx_taproot_hash.as_byte_array())?;
+        StandardnessPolicy::default().check_liquid_tx(&claim_tx)?;
+        self.check_limits(&claim_tx, is_discount_ct)?;
 
-            let mut key_agg_cache = self.swap_script.musig_keyagg_cache();
+        Ok(claim_tx)
+    }
 
-            let tweak = SecretKey::from_slice(
-                self.swap_script
-                    .taproot_spendinfo()?
-                    .tap_tweak()
-                    .as_byte_array(),
-            )?;
+    /// Sequence of claim inputs, depending on RBF signaling and spending path.
+    /// Script path claims always use [`Sequence::ZERO`], which already signals replaceability.
+    fn claim_sequence(&self, is_cooperative: bool) -> Sequence {
+        match (self.signal_rbf, is_cooperative) {
+            (_, false) => Sequence::ZERO,
+            (true, true) => Sequence::ENABLE_RBF_NO_LOCKTIME,
+            (false, true) => Sequence::MAX,
+        }
+    }
 
-            let secp = Secp256k1::new();
-            let _ = key_agg_cache.pubkey_xonly_tweak_add(&secp, tweak)?;
+    fn create_claim(
+        &self,
+        keys: &Keypair,
+        preimage: &Preimage,
+        absolute_fees: u64,
+        is_cooperative: bool,
+    ) -> Result<Transaction, Error> {
+        let preimage_bytes = preimage
+            .bytes
+            .ok_or(Error::Protocol("No preimage provided".to_string()))?;
 
-            let session_id = MusigSessionId::new(&mut thread_rng());
+        let mut claim_tx = self.unsigned_claim(absolute_fees, is_cooperative)?;
 
-            let mut extra_rand = [0u8; 32];
-            OsRng.fill_bytes(&mut extra_rand);
+        if is_cooperative {
+            claim_tx.input[0].witness = Self::stubbed_cooperative_witness();
+        } else {
+            // If Non-Cooperative claim use the Script Path spending
+            let claim_script = self.swap_script.claim_script();
+            let leaf_hash = TapLeafHash::from_script(&claim_script, LeafVersion::default());
 
-            let (claim_sec_nonce, claim_pub_nonce) = key_agg_cache.nonce_gen(
-                &secp,
-                session_id,
-                keys.public_key(),
-                msg,
-                Some(extra_rand),
+            let sighash = SighashCache::new(&claim_tx).taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[&self.funding_utxo]),
+                leaf_hash,
+                SchnorrSighashType::Default,
+                self.genesis_hash,
             )?;
 
-            // Step 7: Get boltz's partial sig
-            let claim_tx_hex = serialize(&claim_tx).to_lower_hex_string();
-            let partial_sig_resp = match self.swap_script.swap_type {
-                SwapType::Chain => match (pub_nonce, partial_sig) {
-                    (Some(pub_nonce), Some(partial_sig)) => boltz_api.post_chain_claim_tx_details(
-                        &swap_id,
-                        preimage,
-                        pub_nonce,
-                        partial_sig,
-                        ToSign {
-                            pub_nonce: claim_pub_nonce.serialize().to_lower_hex_string(),
-                            transaction: claim_tx_hex,
-                            index: 0,
-                        },
-                    ),
-                    _ => Err(Error::Protocol(
-                        "Chain swap claim needs a partial_sig".to_string(),
-                    )),
-                },
-                SwapType::ReverseSubmarine => boltz_api.get_reverse_partial_sig(
-                    &swap_id,
-                    preimage,
-                    &claim_pub_nonce,
-                    &claim_tx_hex,
-                ),
-                _ => Err(Error::Protocol(format!(
-                    "Cannot get partial sig for {:?} Swap",
-                    self.swap_script.swap_type
-                ))),
-            }?;
-
-            let boltz_public_nonce =
-                MusigPubNonce::from_slice(&Vec::from_hex(&partial_sig_resp.pub_nonce)?)?;
-
-            let boltz_partial_sig = MusigPartialSignature::from_slice(&Vec::from_hex(
-                &partial_sig_resp.partial_signature,
-            )?)?;
-
-            let agg_nonce = MusigAggNonce::new(&secp, &[boltz_public_nonce, claim_pub_nonce]);
-
-            let musig_session = MusigSession::new(&secp, &key_agg_cache, agg_nonce, msg);
-
-            // Verify the sigs.
-            let boltz_partial_sig_verify = musig_session.partial_verify(
-                &secp,
-                &key_agg_cache,
-                boltz_partial_sig,
-                boltz_public_nonce,
-                self.swap_script.sender_pubkey.inner, //boltz key
-            );
-
-            if (!boltz_partial_sig_verify) {
-                return Err(Error::Taproot(
-                    "Unable to verify Partial Signature".to_string(),
-                ));
-            }
-
-            let our_partial_sig =
-                musig_session.partial_sign(&secp, claim_sec_nonce, keys, &key_agg_cache)?;
+            let msg = Message::from_digest_slice(sighash.as_byte_array())?;
 
-            let schnorr_sig = musig_session.partial_sig_agg(&[boltz_partial_sig, our_partial_sig]);
+            let sig = Secp256k1::new().sign_schnorr(&msg, keys);
 
-            let final_schnorr_sig = SchnorrSig {
-                sig: schnorr_sig,
+            let final_sig = SchnorrSig {
+                sig,
                 hash_ty: SchnorrSighashType::Default,
             };
 
-            let output_key = self.swap_script.taproot_spendinfo()?.output_key();
-
-            secp.verify_schnorr(&final_schnorr_sig.sig, &msg, &output_key.into_inner())?;
+            let control_block = match self
+                .swap_script
+                .taproot_spendinfo()?
+                .control_block(&(claim_script.clone(), LeafVersion::default()))
+            {
+                Some(r) => r,
+                None => return Err(Error::Taproot("Could not create control block".to_string())),
+            };
 
             let mut script_witness = Witness::new();
-            script_witness.push(final_schnorr_sig.to_vec());
+            script_witness.push(final_sig.to_vec());
+            script_witness.push(preimage_bytes);
+            script_witness.push(claim_script.as_bytes());
+            script_witness.push(control_block.serialize());
 
             let witness = TxInWitness {
                 amount_rangeproof: None,
@@ -801,19 +1475,14 @@ impl LBtcSwapTx {
         Ok(claim_tx)
     }
 
-    fn create_claim(
+    /// Claim paying `absolute_fees`, with inputs and blinded outputs set but without witness.
+    fn unsigned_claim(
         &self,
-        keys: &Keypair,
-        preimage: &Preimage,
         absolute_fees: u64,
         is_cooperative: bool,
     ) -> Result<Transaction, Error> {
-        let preimage_bytes = preimage
-            .bytes
-            .ok_or(Error::Protocol("No preimage provided".to_string()))?;
-
         let claim_txin = TxIn {
-            sequence: Sequence::MAX,
+            sequence: self.claim_sequence(is_cooperative),
             previous_output: self.funding_outpoint,
             script_sig: Script::new(),
             witness: TxInWitness::default(),
@@ -823,17 +1492,18 @@ impl LBtcSwapTx {
 
         let secp = Secp256k1::new();
 
-        let unblined_utxo = self
-            .funding_utxo
-            .unblind(&secp, self.swap_script.blinding_key.secret_key())?;
+        let unblined_utxo = self.funding_secrets()?;
         let asset_id = unblined_utxo.asset;
-        let out_abf = AssetBlindingFactor::new(&mut thread_rng());
+        self.chain().check_lbtc_asset(asset_id)?;
+        let out_abf = AssetBlindingFactor::new(&mut SecureRng);
         let exp_asset = Asset::Explicit(asset_id);
 
         let (blinded_asset, asset_surjection_proof) =
-            exp_asset.blind(&mut thread_rng(), &secp, out_abf, &[unblined_utxo])?;
+            exp_asset.blind(&mut SecureRng, &secp, out_abf, &[unblined_utxo])?;
 
-        let output_value = Amount::from_sat(unblined_utxo.value) - Amount::from_sat(absolute_fees);
+        // Confidential outputs have no dust limit
+        let output_value =
+            Amount::from_sat(drain_output_value(unblined_utxo.value, absolute_fees, 0)?);
 
         let final_vbf = ValueBlindingFactor::last(
             &secp,
@@ -855,7 +1525,7 @@ impl LBtcSwapTx {
             asset: asset_id,
             bf: out_abf,
         };
-        let ephemeral_sk = SecretKey::new(&mut thread_rng());
+        let ephemeral_sk = SecretKey::new(&mut SecureRng);
 
         // assuming we always use a blinded address that has an extractable blinding pub
         let blinding_key = self
@@ -884,64 +1554,17 @@ impl LBtcSwapTx {
         };
         let fee_output: TxOut = TxOut::new_fee(absolute_fees, asset_id);
 
-        let mut claim_tx = Transaction {
+        Ok(Transaction {
             version: 2,
             lock_time: LockTime::ZERO,
             input: vec![claim_txin],
-            output: vec![payment_output, fee_output],
-        };
-
-        if is_cooperative {
-            claim_tx.input[0].witness = Self::stubbed_cooperative_witness();
-        } else {
-            // If Non-Cooperative claim use the Script Path spending
-            claim_tx.input[0].sequence = Sequence::ZERO;
-            let claim_script = self.swap_script.claim_script();
-            let leaf_hash = TapLeafHash::from_script(&claim_script, LeafVersion::default());
-
-            let sighash = SighashCache::new(&claim_tx).taproot_script_spend_signature_hash(
-                0,
-                &Prevouts::All(&[&self.funding_utxo]),
-                leaf_hash,
-                SchnorrSighashType::Default,
-                self.genesis_hash,
-            )?;
-
-            let msg = Message::from_digest_slice(sighash.as_byte_array())?;
-
-            let sig = secp.sign_schnorr(&msg, keys);
-
-            let final_sig = SchnorrSig {
-                sig,
-                hash_ty: SchnorrSighashType::Default,
-            };
-
-            let control_block = match self
-                .swap_script
-                .taproot_spendinfo()?
-                .control_block(&(claim_script.clone(), LeafVersion::default()))
-            {
-                Some(r) => r,
-                None => return Err(Error::Taproot("Could not create control block".to_string())),
-            };
-
-            let mut script_witness = Witness::new();
-            script_witness.push(final_sig.to_vec());
-            script_witness.push(preimage.bytes.unwrap()); // checked for none
-            script_witness.push(claim_script.as_bytes());
-            script_witness.push(control_block.serialize());
-
-            let witness = TxInWitness {
-                amount_rangeproof: None,
-                inflation_keys_rangeproof: None,
-                script_witness: script_witness.to_vec(),
-                pegin_witness: vec![],
-            };
-
-            claim_tx.input[0].witness = witness;
-        }
-
-        Ok(claim_tx)
+            output: [
+                vec![payment_output],
+                self.memo_output(asset_id)?,
+                vec![fee_output],
+            ]
+            .concat(),
+        })
     }
 
     /// Sign a refund transaction.
@@ -965,111 +1588,194 @@ impl LBtcSwapTx {
             ));
         }
 
-        let mut refund_tx = create_tx_with_fee(
+        if let Some(cooperative) = is_cooperative {
+            return self
+                .musig_session(keys, None, fee, is_discount_ct)?
+                .resume(&cooperative);
+        }
+
+        let refund_tx = create_tx_with_fee(
             fee,
-            |fee| self.create_refund(keys, fee, is_cooperative.is_some()),
+            |fee| self.create_refund(keys, fee, false),
             |tx| tx_size(&tx, is_discount_ct),
         )?;
 
-        if let Some(Cooperative {
-            boltz_api, swap_id, ..
-        }) = is_cooperative
-        {
-            let secp = Secp256k1::new();
+        StandardnessPolicy::default().check_liquid_tx(&refund_tx)?;
+        self.check_limits(&refund_tx, is_discount_ct)?;
 
-            refund_tx.lock_time = LockTime::ZERO;
+        Ok(refund_tx)
+    }
 
-            let claim_tx_taproot_hash = SighashCache::new(&refund_tx)
-                .taproot_key_spend_signature_hash(
-                    0,
-                    &Prevouts::All(&[&self.funding_utxo]),
-                    SchnorrSighashType::Default,
-                    self.genesis_hash,
+    /// Start the cooperative signing of this claim or refund with Boltz. `preimage` is required
+    /// for claims. Call [`MusigSigningSession::resume`] until it returns the signed transaction.
+    pub fn musig_session(
+        &self,
+        keys: &Keypair,
+        preimage: Option<&Preimage>,
+        fee: Fee,
+        is_discount_ct: bool,
+    ) -> Result<MusigSigningSession<'_>, Error> {
+        let tx = match (&self.kind, self.swap_script.swap_type, preimage) {
+            (SwapTxKind::Claim, SwapType::Submarine, _) => {
+                return Err(Error::Protocol(
+                    "Claim Tx signing is not applicable for Submarine Swaps".to_string(),
+                ))
+            }
+            (SwapTxKind::Refund, SwapType::ReverseSubmarine, _) => {
+                return Err(Error::Protocol(
+                    "Refund Tx signing is not applicable for Reverse Submarine Swaps".to_string(),
+                ))
+            }
+            (SwapTxKind::Claim, _, Some(preimage)) => create_tx_with_fee(
+                fee,
+                |fee| self.create_claim(keys, preimage, fee, true),
+                |tx| tx_size(&tx, is_discount_ct),
+            )?,
+            (SwapTxKind::Claim, _, None) => {
+                return Err(Error::Protocol(
+                    "Preimage is required to sign a claim".to_string(),
+                ))
+            }
+            (SwapTxKind::Refund, _, _) => {
+                let mut refund_tx = create_tx_with_fee(
+                    fee,
+                    |fee| self.create_refund(keys, fee, true),
+                    |tx| tx_size(&tx, is_discount_ct),
                 )?;
+                refund_tx.lock_time = LockTime::ZERO; // No locktime for cooperative spend
+                refund_tx
+            }
+        };
+        Ok(MusigSigningSession {
+            swap_tx: self,
+            signer: KeypairSigner::new(*keys),
+            preimage: preimage.cloned(),
+            tx,
+            is_discount_ct,
+            pending_nonce: None,
+        })
+    }
 
-            let msg = Message::from_digest_slice(claim_tx_taproot_hash.as_byte_array())?;
-
-            let mut key_agg_cache = self.swap_script.musig_keyagg_cache();
+    /// Cooperative claim or refund paying `fee`, with a placeholder witness, for signing the key
+    /// path elsewhere, e.g. on an air-gapped device. No keys are needed to build it.
+    /// Sign the message of [`Self::key_spend_sighash`] with the swap's [`SwapMusigContext`], then
+    /// complete the transaction with [`Self::finalize_key_spend`].
+    pub fn unsigned_cooperative_tx(
+        &self,
+        fee: Fee,
+        is_discount_ct: bool,
+    ) -> Result<Transaction, Error> {
+        let mut tx = create_tx_with_fee(
+            fee,
+            |fee| {
+                let mut tx = match self.kind {
+                    SwapTxKind::Claim => self.unsigned_claim(fee, true)?,
+                    SwapTxKind::Refund => self.unsigned_refund(fee, true)?,
+                };
+                tx.input[0].witness = Self::stubbed_cooperative_witness();
+                Ok(tx)
+            },
+            |tx| tx_size(&tx, is_discount_ct),
+        )?;
+        tx.lock_time = LockTime::ZERO; // No locktime for cooperative spend
+        Ok(tx)
+    }
 
-            let tweak = SecretKey::from_slice(
-                self.swap_script
-                    .taproot_spendinfo()?
-                    .tap_tweak()
-                    .as_byte_array(),
-            )?;
+    /// Taproot key path sighash of `tx`, spending the funding utxo of this swap. This is the
+    /// message signed with MuSig2.
+    pub fn key_spend_sighash(&self, tx: &Transaction) -> Result<Message, Error> {
+        if tx.input.len() != 1 || tx.input[0].previous_output != self.funding_outpoint {
+            return Err(Error::Protocol(
+                "Transaction does not spend the funding utxo of the swap".to_string(),
+            ));
+        }
+        let sighash = SighashCache::new(tx).taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(&[&self.funding_utxo]),
+            SchnorrSighashType::Default,
+            self.genesis_hash,
+        )?;
+        Ok(Message::from_digest_slice(sighash.as_byte_array())?)
+    }
 
-            let _ = key_agg_cache.pubkey_xonly_tweak_add(&secp, tweak)?;
+    /// Complete `tx` with the aggregated key path `signature`, after checking it against the swap
+    /// output key, and run the checks of a signed claim or refund.
+    pub fn finalize_key_spend(
+        &self,
+        tx: &Transaction,
+        signature: schnorr::Signature,
+        is_discount_ct: bool,
+    ) -> Result<Transaction, Error> {
+        let msg = self.key_spend_sighash(tx)?;
+        let output_key = self.swap_script.musig_context()?.output_key();
+        Secp256k1::new().verify_schnorr(&signature, &msg, &output_key)?;
+
+        let mut script_witness = Witness::new();
+        script_witness.push(
+            SchnorrSig {
+                sig: signature,
+                hash_ty: SchnorrSighashType::Default,
+            }
+            .to_vec(),
+        );
+        let mut signed_tx = tx.clone();
+        signed_tx.input[0].witness = TxInWitness {
+            amount_rangeproof: None,
+            inflation_keys_rangeproof: None,
+            script_witness: script_witness.to_vec(),
+            pegin_witness: vec![],
+        };
 
-            let session_id = MusigSessionId::new(&mut thread_rng());
+        StandardnessPolicy::default().check_liquid_tx(&signed_tx)?;
+        self.check_limits(&signed_tx, is_discount_ct)?;
 
-            let mut extra_rand = [0u8; 32];
-            OsRng.fill_bytes(&mut extra_rand);
+        Ok(signed_tx)
+    }
 
-            let (sec_nonce, pub_nonce) = key_agg_cache.nonce_gen(
-                &secp,
-                session_id,
-                keys.public_key(),
-                msg,
-                Some(extra_rand),
-            )?;
+    fn create_refund(
+        &self,
+        keys: &Keypair,
+        absolute_fees: u64,
+        is_cooperative: bool,
+    ) -> Result<Transaction, Error> {
+        let mut refund_tx = self.unsigned_refund(absolute_fees, is_cooperative)?;
 
-            // Step 7: Get boltz's partial sig
-            let refund_tx_hex = serialize(&refund_tx).to_lower_hex_string();
-            let partial_sig_resp = match self.swap_script.swap_type {
-                SwapType::Chain => {
-                    boltz_api.get_chain_partial_sig(&swap_id, 0, &pub_nonce, &refund_tx_hex)
-                }
-                SwapType::Submarine => {
-                    boltz_api.get_submarine_partial_sig(&swap_id, 0, &pub_nonce, &refund_tx_hex)
-                }
-                _ => Err(Error::Protocol(format!(
-                    "Cannot get partial sig for {:?} Swap",
-                    self.swap_script.swap_type
-                ))),
-            }?;
-
-            let boltz_public_nonce =
-                MusigPubNonce::from_slice(&Vec::from_hex(&partial_sig_resp.pub_nonce)?)?;
-
-            let boltz_partial_sig = MusigPartialSignature::from_slice(&Vec::from_hex(
-                &partial_sig_resp.partial_signature,
-            )?)?;
-
-            let agg_nonce = MusigAggNonce::new(&secp, &[boltz_public_nonce, pub_nonce]);
-
-            let musig_session = MusigSession::new(&secp, &key_agg_cache, agg_nonce, msg);
-
-            // Verify the sigs.
-            let boltz_partial_sig_verify = musig_session.partial_verify(
-                &secp,
-                &key_agg_cache,
-                boltz_partial_sig,
-                boltz_public_nonce,
-                self.swap_script.receiver_pubkey.inner, //boltz key
-            );
+        if is_cooperative {
+            refund_tx.input[0].witness = Self::stubbed_cooperative_witness();
+        } else {
+            let refund_script = self.swap_script.refund_script();
+            let leaf_hash = TapLeafHash::from_script(&refund_script, LeafVersion::default());
 
-            if (!boltz_partial_sig_verify) {
-                return Err(Error::Taproot(
-                    "Unable to verify Partial Signature".to_string(),
-                ));
-            }
+            let sighash = SighashCache::new(&refund_tx).taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[&self.funding_utxo]),
+                leaf_hash,
+                SchnorrSighashType::Default,
+                self.genesis_hash,
+            )?;
 
-            let our_partial_sig =
-                musig_session.partial_sign(&secp, sec_nonce, keys, &key_agg_cache)?;
+            let msg = Message::from_digest_slice(sighash.as_byte_array())?;
 
-            let schnorr_sig = musig_session.partial_sig_agg(&[boltz_partial_sig, our_partial_sig]);
+            let sig = Secp256k1::new().sign_schnorr(&msg, keys);
 
-            let final_schnorr_sig = SchnorrSig {
-                sig: schnorr_sig,
+            let final_sig = SchnorrSig {
+                sig,
                 hash_ty: SchnorrSighashType::Default,
             };
 
-            let output_key = self.swap_script.taproot_spendinfo()?.output_key();
-
-            secp.verify_schnorr(&final_schnorr_sig.sig, &msg, &output_key.into_inner())?;
+            let control_block = match self
+                .swap_script
+                .taproot_spendinfo()?
+                .control_block(&(refund_script.clone(), LeafVersion::default()))
+            {
+                Some(r) => r,
+                None => return Err(Error::Taproot("Could not create control block".to_string())),
+            };
 
             let mut script_witness = Witness::new();
-            script_witness.push(final_schnorr_sig.to_vec());
+            script_witness.push(final_sig.to_vec());
+            script_witness.push(refund_script.as_bytes());
+            script_witness.push(control_block.serialize());
 
             let witness = TxInWitness {
                 amount_rangeproof: None,
@@ -1084,13 +1790,13 @@ impl LBtcSwapTx {
         Ok(refund_tx)
     }
 
-    fn create_refund(
+    /// Refund paying `absolute_fees`, with inputs, blinded outputs, locktime and sequence set but
+    /// without witness.
+    fn unsigned_refund(
         &self,
-        keys: &Keypair,
         absolute_fees: u64,
         is_cooperative: bool,
     ) -> Result<Transaction, Error> {
-        // Create unsigned refund transaction
         let refund_txin = TxIn {
             sequence: Sequence::MAX,
             previous_output: self.funding_outpoint,
@@ -1102,17 +1808,18 @@ impl LBtcSwapTx {
 
         let secp = Secp256k1::new();
 
-        let unblined_utxo = self
-            .funding_utxo
-            .unblind(&secp, self.swap_script.blinding_key.secret_key())?;
+        let unblined_utxo = self.funding_secrets()?;
         let asset_id = unblined_utxo.asset;
-        let out_abf = AssetBlindingFactor::new(&mut thread_rng());
+        self.chain().check_lbtc_asset(asset_id)?;
+        let out_abf = AssetBlindingFactor::new(&mut SecureRng);
         let exp_asset = Asset::Explicit(asset_id);
 
         let (blinded_asset, asset_surjection_proof) =
-            exp_asset.blind(&mut thread_rng(), &secp, out_abf, &[unblined_utxo])?;
+            exp_asset.blind(&mut SecureRng, &secp, out_abf, &[unblined_utxo])?;
 
-        let output_value = Amount::from_sat(unblined_utxo.value) - Amount::from_sat(absolute_fees);
+        // Confidential outputs have no dust limit
+        let output_value =
+            Amount::from_sat(drain_output_value(unblined_utxo.value, absolute_fees, 0)?);
 
         let final_vbf = ValueBlindingFactor::last(
             &secp,
@@ -1134,7 +1841,7 @@ impl LBtcSwapTx {
             asset: asset_id,
             bf: out_abf,
         };
-        let ephemeral_sk = SecretKey::new(&mut thread_rng());
+        let ephemeral_sk = SecretKey::new(&mut SecureRng);
 
         // assuming we always use a blinded address that has an extractable blinding pub
         let blinding_key = self
@@ -1168,7 +1875,7 @@ impl LBtcSwapTx {
         let lock_time = match refund_script
             .instructions()
             .filter_map(|i| {
-                let ins = i.unwrap();
+                let ins = i.ok()?;
                 if let Instruction::PushBytes(bytes) = ins {
                     if bytes.len() < 5_usize {
                         Some(LockTime::from_consensus(bytes_to_u32_little_endian(bytes)))
@@ -1189,59 +1896,26 @@ impl LBtcSwapTx {
             }
         };
 
+        // The timelock pushed by relative timelock scripts is a sequence
+        let lock_time = match self.swap_script.relative_timelock {
+            Some(_) => LockTime::ZERO,
+            None => lock_time,
+        };
+
         let mut refund_tx = Transaction {
             version: 2,
             lock_time,
             input: vec![refund_txin],
-            output: vec![fee_output, payment_output],
+            output: [
+                vec![fee_output, payment_output],
+                self.memo_output(asset_id)?,
+            ]
+            .concat(),
         };
 
-        if is_cooperative {
-            refund_tx.input[0].witness = Self::stubbed_cooperative_witness();
-        } else {
-            refund_tx.input[0].sequence = Sequence::ZERO;
-
-            let leaf_hash = TapLeafHash::from_script(&refund_script, LeafVersion::default());
-
-            let sighash = SighashCache::new(&refund_tx).taproot_script_spend_signature_hash(
-                0,
-                &Prevouts::All(&[&self.funding_utxo]),
-                leaf_hash,
-                SchnorrSighashType::Default,
-                self.genesis_hash,
-            )?;
-
-            let msg = Message::from_digest_slice(sighash.as_byte_array())?;
-
-            let sig = secp.sign_schnorr(&msg, keys);
-
-            let final_sig = SchnorrSig {
-                sig,
-                hash_ty: SchnorrSighashType::Default,
-            };
-
-            let control_block = match self
-                .swap_script
-                .taproot_spendinfo()?
-                .control_block(&(refund_script.clone(), LeafVersion::default()))
-            {
-                Some(r) => r,
-                None => return Err(Error::Taproot("Could not create control block".to_string())),
-            };
-
-            let mut script_witness = Witness::new();
-            script_witness.push(final_sig.to_vec());
-            script_witness.push(refund_script.as_bytes());
-            script_witness.push(control_block.serialize());
-
-            let witness = TxInWitness {
-                amount_rangeproof: None,
-                inflation_keys_rangeproof: None,
-                script_witness: script_witness.to_vec(),
-                pegin_witness: vec![],
-            };
-
-            refund_tx.input[0].witness = witness;
+        if !is_cooperative {
+            refund_tx.input[0].sequence =
+                self.swap_script.relative_timelock.unwrap_or(Sequence::ZERO);
         }
 
         Ok(refund_tx)
@@ -1281,48 +1955,413 @@ impl LBtcSwapTx {
         Ok(tx_size(&tx, is_discount_ct))
     }
 
-    /// Broadcast transaction to the network
-    pub fn broadcast(
+    /// Amount received by `output_address`, when draining the swap utxo with `fee`.
+    /// Claims and refunds always send everything minus the fee to the single destination.
+    pub fn drain_amount(
         &self,
-        signed_tx: &Transaction,
-        network_config: &ElectrumConfig,
-        is_lowball: Option<(&BoltzApiClientV2, Chain)>,
-    ) -> Result<String, Error> {
-        if let Some((boltz_api, chain)) = is_lowball {
-            log::info!("Attempting lowball broadcast");
-            let tx_hex = serialize(signed_tx).to_lower_hex_string();
-            let response = boltz_api.broadcast_tx(chain, &tx_hex)?;
-
-            match response.as_object() {
-                None => Err(Error::Protocol("Invalid broadcast reply".to_string())),
-                Some(response_map) => match response_map.get("id") {
-                    None => Err(Error::Protocol(
-                        "No txid found in broadcast reply".to_string(),
-                    )),
-                    Some(txid_val) => match txid_val.as_str() {
-                        None => Err(Error::Protocol("Returned txid is not a string".to_string())),
-                        Some(txid_str) => {
-                            let txid = txid_str.to_string();
-                            log::info!("Broadcasted transaction via Boltz: {txid}");
-                            Ok(txid)
-                        }
-                    },
-                },
+        keys: &Keypair,
+        fee: Fee,
+        is_cooperative: bool,
+        is_discount_ct: bool,
+    ) -> Result<u64, Error> {
+        let absolute_fee = match fee {
+            Fee::Absolute(fee) => fee,
+            Fee::Relative(rate) => {
+                (self.size(keys, is_cooperative, is_discount_ct)? as f64 * rate).ceil() as u64
             }
-        } else {
-            let electrum_client = network_config.build_client()?;
-            let serialized = serialize(signed_tx);
-            Ok(electrum_client
-                .transaction_broadcast_raw(&serialized)?
-                .to_string())
-        }
+            Fee::Deadline { blocks_remaining } => {
+                return Err(unresolved_deadline(blocks_remaining))
+            }
+        };
+        drain_output_value(self.funding_secrets()?.value, absolute_fee, 0)
     }
-}
 
-fn tx_size(tx: &Transaction, is_discount_ct: bool) -> usize {
-    match is_discount_ct {
-        true => tx.discount_vsize(),
-        false => tx.vsize(),
+    /// Check a signed transaction against the configured [`TxLimits`].
+    fn check_limits(&self, tx: &Transaction, is_discount_ct: bool) -> Result<(), Error> {
+        let input_value = self.funding_secrets()?.value;
+        let fee = tx
+            .output
+            .iter()
+            .filter(|output| output.is_fee())
+            .filter_map(|output| output.value.explicit())
+            .sum();
+        // Everything but the fee goes to the output address, the memo output has no value
+        let output_value = input_value.saturating_sub(fee);
+        self.limits.check(
+            input_value,
+            fee,
+            output_value,
+            tx_size(tx, is_discount_ct),
+            0,
+            LIQUID_MIN_RELAY_FEE_RATE,
+        )
+    }
+
+    /// Point this claim at the lockup address of another swap, so the claim tx directly funds it
+    /// (swap chaining, e.g. LN -> LN rebalancing via an onchain hop).
+    ///
+    /// The claim output pays exactly `lockup_amount` to `lockup_address`, the rest of the swap
+    /// utxo goes to fees. Fails if that fee is below `min_fee_rate` or above `max_fee_rate`
+    /// (sat/vbyte), so a lockup amount far below the claimed value can't hand the difference to
+    /// miners. Returns the fee to pass to `sign_claim`.
+    /// Errors if `lockup_address` is for another network than the current claim address.
+    pub fn chain_into(
+        &mut self,
+        keys: &Keypair,
+        lockup_address: &str,
+        lockup_amount: u64,
+        min_fee_rate: f64,
+        max_fee_rate: f64,
+        is_cooperative: bool,
+        is_discount_ct: bool,
+    ) -> Result<Fee, Error> {
+        if self.kind != SwapTxKind::Claim {
+            return Err(Error::Protocol(
+                "Only claim transactions can be chained into another swap".to_string(),
+            ));
+        }
+        let value = self.funding_secrets()?.value;
+        let output_address = Address::from_str(lockup_address)?;
+        screen_address(lockup_address, AddressPurpose::Claim)?;
+        if output_address.params != self.output_address.params {
+            return Err(Error::Address(
+                "Lockup address is for another network".to_string(),
+            ));
+        }
+
+        let previous_address = std::mem::replace(&mut self.output_address, output_address);
+        let size = match self.size(keys, is_cooperative, is_discount_ct) {
+            Ok(size) => size,
+            Err(e) => {
+                self.output_address = previous_address;
+                return Err(e);
+            }
+        };
+
+        let min_fee = (size as f64 * min_fee_rate).ceil() as u64;
+        let max_fee = (size as f64 * max_fee_rate).floor() as u64;
+        let fee = value.saturating_sub(lockup_amount);
+        if value < lockup_amount || fee < min_fee {
+            self.output_address = previous_address;
+            return Err(Error::Protocol(format!(
+                "Claim of {} sats cannot fund a lockup of {} sats with a fee of at least {} sats",
+                value, lockup_amount, min_fee
+            )));
+        }
+        if fee > max_fee {
+            self.output_address = previous_address;
+            return Err(Error::Protocol(format!(
+                "Claim of {} sats funding a lockup of {} sats would pay a fee of {} sats, above the maximum of {} sats",
+                value, lockup_amount, fee, max_fee
+            )));
+        }
+        Ok(Fee::Absolute(fee))
+    }
+
+    /// [`Self::chain_into`] the lockup of a submarine swap.
+    pub fn chain_into_submarine(
+        &mut self,
+        keys: &Keypair,
+        submarine: &CreateSubmarineResponse,
+        min_fee_rate: f64,
+        max_fee_rate: f64,
+        is_cooperative: bool,
+        is_discount_ct: bool,
+    ) -> Result<Fee, Error> {
+        self.chain_into(
+            keys,
+            &submarine.address,
+            submarine.expected_amount,
+            min_fee_rate,
+            max_fee_rate,
+            is_cooperative,
+            is_discount_ct,
+        )
+    }
+
+    /// [`Self::chain_into`] the user lockup of a chain swap.
+    pub fn chain_into_chain_swap(
+        &mut self,
+        keys: &Keypair,
+        chain: &CreateChainResponse,
+        min_fee_rate: f64,
+        max_fee_rate: f64,
+        is_cooperative: bool,
+        is_discount_ct: bool,
+    ) -> Result<Fee, Error> {
+        self.chain_into(
+            keys,
+            &chain.lockup_details.lockup_address,
+            chain.lockup_details.amount,
+            min_fee_rate,
+            max_fee_rate,
+            is_cooperative,
+            is_discount_ct,
+        )
+    }
+
+    /// Dry-run check of a signed claim or refund transaction before broadcast.
+    /// The funding utxo must be spent on a path matching the tx kind.
+    pub fn verify_witness(&self, signed_tx: &Transaction) -> Result<(), Error> {
+        if signed_tx.input.len() != 1 || signed_tx.input[0].previous_output != self.funding_outpoint
+        {
+            return Err(Error::Protocol(
+                "Transaction must only spend the funding utxo".to_string(),
+            ));
+        }
+        let path = self.swap_script.verify_spend(
+            signed_tx,
+            0,
+            &[self.funding_utxo.clone()],
+            self.genesis_hash,
+        )?;
+        match (&self.kind, path) {
+            (_, SpendPath::KeyPath)
+            | (SwapTxKind::Claim, SpendPath::ClaimLeaf)
+            | (SwapTxKind::Refund, SpendPath::RefundLeaf) => Ok(()),
+            (kind, path) => Err(Error::Protocol(format!(
+                "{:?} transaction spends the funding utxo via {:?}",
+                kind, path
+            ))),
+        }
+    }
+
+    /// The explicit, zero value null data output carrying the memo, if any.
+    fn memo_output(&self, asset_id: elements::AssetId) -> Result<Vec<TxOut>, Error> {
+        let memo = match &self.memo {
+            Some(memo) => memo,
+            None => return Ok(vec![]),
+        };
+        if memo.len() > MAX_MEMO_SIZE {
+            return Err(Error::Protocol(format!(
+                "Memo of {} bytes exceeds the standard limit of {} bytes",
+                memo.len(),
+                MAX_MEMO_SIZE
+            )));
+        }
+        Ok(vec![TxOut {
+            asset: Asset::Explicit(asset_id),
+            value: Value::Explicit(0),
+            nonce: confidential::Nonce::Null,
+            script_pubkey: Script::new_op_return(memo),
+            witness: TxOutWitness::default(),
+        }])
+    }
+
+    /// Coin control metadata of the output paying to `output_address` in a signed claim or refund tx.
+    /// The amount is derived from the funding utxo and the explicit fee, as the output is blinded.
+    pub fn output_metadata(
+        &self,
+        signed_tx: &Transaction,
+        swap_id: &str,
+    ) -> Result<SwapOutput, Error> {
+        let spk = self.output_address.script_pubkey();
+        let vout = signed_tx
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == spk)
+            .ok_or(Error::Protocol(
+                "Transaction does not pay to the output address".to_string(),
+            ))?;
+        let fee: u64 = signed_tx
+            .output
+            .iter()
+            .filter(|output| output.is_fee())
+            .filter_map(|output| output.value.explicit())
+            .sum();
+        let amount = self
+            .funding_secrets()?
+            .value
+            .checked_sub(fee)
+            .ok_or(Error::Protocol(
+                "Fee exceeds the funding amount".to_string(),
+            ))?;
+        Ok(SwapOutput {
+            txid: signed_tx.txid().to_string(),
+            vout: vout as u32,
+            address: self.output_address.to_string(),
+            script_type: ScriptType::from_elements_script(&spk),
+            amount,
+            swap_id: swap_id.to_string(),
+            swap_type: self.swap_script.swap_type,
+            spend_kind: self.kind.clone(),
+        })
+    }
+
+    /// Broadcast transaction to the network
+    pub fn broadcast(
+        &self,
+        signed_tx: &Transaction,
+        network_config: &ElectrumConfig,
+        is_lowball: Option<(&BoltzApiClientV2, Chain)>,
+    ) -> Result<String, Error> {
+        if let Some((boltz_api, chain)) = is_lowball {
+            log::info!("Attempting lowball broadcast");
+            let tx_hex = serialize(signed_tx).to_lower_hex_string();
+            let txid = boltz_broadcast(boltz_api, chain, &tx_hex)?;
+            log::info!("Broadcasted transaction via Boltz: {txid}");
+            Ok(txid)
+        } else {
+            let electrum_client = network_config.build_broadcast_client()?;
+            let serialized = serialize(signed_tx);
+            Ok(electrum_client
+                .transaction_broadcast_raw(&serialized)?
+                .to_string())
+        }
+    }
+
+    /// Broadcast to every configured Electrum server and, if given, through Boltz, concurrently.
+    /// Returns as soon as one of them accepts the transaction.
+    pub fn broadcast_all(
+        &self,
+        signed_tx: &Transaction,
+        network_config: &ElectrumConfig,
+        boltz_api: Option<(&BoltzApiClientV2, Chain)>,
+    ) -> Result<String, Error> {
+        broadcast_all(&serialize(signed_tx), network_config, boltz_api)
+    }
+}
+
+/// Cooperative (key path) signing of a claim or refund with Boltz, the Liquid counterpart of
+/// [`crate::swaps::bitcoin::MusigSigningSession`]. Started with [`LBtcSwapTx::musig_session`].
+///
+/// Requesting Boltz's partial signature can fail for transient reasons, e.g. a timeout or an
+/// HTTP 500. [`Self::resume`] can simply be called again, reusing our pending nonce, as its
+/// secret part is only used once a valid partial signature of Boltz was received. A new nonce is
+/// generated if Boltz's partial signature is invalid or our partial signing failed.
+pub struct MusigSigningSession<'a> {
+    swap_tx: &'a LBtcSwapTx,
+    signer: KeypairSigner,
+    preimage: Option<Preimage>,
+    tx: Transaction,
+    is_discount_ct: bool,
+    pending_nonce: Option<MusigPubNonce>,
+}
+
+impl MusigSigningSession<'_> {
+    /// Sign the transaction, and return it once signed. On error, the session can be resumed
+    /// again.
+    pub fn resume(&mut self, cooperative: &Cooperative) -> Result<Transaction, Error> {
+        let swap_script = &self.swap_tx.swap_script;
+        let msg = self.swap_tx.key_spend_sighash(&self.tx)?;
+
+        let musig = swap_script.musig_context()?;
+        let our_nonce = match self.pending_nonce {
+            Some(nonce) => nonce,
+            None => {
+                let nonce = musig.nonce(&self.signer, msg)?;
+                self.pending_nonce = Some(nonce);
+                nonce
+            }
+        };
+
+        let partial_sig_resp = self.request_partial_sig(&our_nonce, cooperative)?;
+        let boltz_nonce = pub_nonce_from_hex(&partial_sig_resp.pub_nonce)?;
+        let boltz_partial_sig = partial_sig_from_hex(&partial_sig_resp.partial_signature)?;
+
+        // Aggregate both nonces and verify Boltz's partial sig
+        let musig_session = musig.session(our_nonce, boltz_nonce, msg);
+        let boltz_pubkey = match self.swap_tx.kind {
+            SwapTxKind::Claim => swap_script.sender_pubkey.inner,
+            SwapTxKind::Refund => swap_script.receiver_pubkey.inner,
+        };
+        // From here on, our nonce is either used or discarded
+        self.pending_nonce = None;
+        if !musig.verify_partial_sig(&musig_session, boltz_partial_sig, boltz_nonce, boltz_pubkey) {
+            return Err(Error::Taproot(
+                "Unable to verify Partial Signature".to_string(),
+            ));
+        }
+
+        let our_partial_sig = musig.partial_sign(&self.signer, &musig_session, &our_nonce)?;
+        let final_schnorr_sig = SchnorrSig {
+            sig: musig.aggregate(&musig_session, &[boltz_partial_sig, our_partial_sig], &msg)?,
+            hash_ty: SchnorrSighashType::Default,
+        };
+
+        let mut script_witness = Witness::new();
+        script_witness.push(final_schnorr_sig.to_vec());
+        let mut signed_tx = self.tx.clone();
+        signed_tx.input[0].witness = TxInWitness {
+            amount_rangeproof: None,
+            inflation_keys_rangeproof: None,
+            script_witness: script_witness.to_vec(),
+            pegin_witness: vec![],
+        };
+
+        StandardnessPolicy::default().check_liquid_tx(&signed_tx)?;
+        self.swap_tx.check_limits(&signed_tx, self.is_discount_ct)?;
+
+        Ok(signed_tx)
+    }
+
+    fn request_partial_sig(
+        &self,
+        our_nonce: &MusigPubNonce,
+        cooperative: &Cooperative,
+    ) -> Result<PartialSig, Error> {
+        let boltz_api = cooperative.boltz_api;
+        let swap_id = &cooperative.swap_id;
+        let tx_hex = serialize(&self.tx).to_lower_hex_string();
+        match (&self.swap_tx.kind, self.swap_tx.swap_script.swap_type) {
+            (SwapTxKind::Claim, SwapType::Chain) => {
+                let preimage = self.preimage.as_ref().ok_or(Error::Protocol(
+                    "Preimage is required to sign a claim".to_string(),
+                ))?;
+                match (cooperative.pub_nonce, cooperative.partial_sig) {
+                    (Some(pub_nonce), Some(partial_sig)) => boltz_api.post_chain_claim_tx_details(
+                        swap_id,
+                        preimage,
+                        pub_nonce,
+                        partial_sig,
+                        ToSign {
+                            pub_nonce: our_nonce.serialize().to_lower_hex_string(),
+                            transaction: tx_hex,
+                            index: 0,
+                        },
+                    ),
+                    _ => Err(Error::Protocol(
+                        "Chain swap claim needs a partial_sig".to_string(),
+                    )),
+                }
+            }
+            (SwapTxKind::Claim, SwapType::ReverseSubmarine) => {
+                let preimage = self.preimage.as_ref().ok_or(Error::Protocol(
+                    "Preimage is required to sign a claim".to_string(),
+                ))?;
+                boltz_api.get_reverse_partial_sig(swap_id, 0, preimage, our_nonce, &tx_hex)
+            }
+            (SwapTxKind::Refund, SwapType::Chain) => {
+                boltz_api.get_chain_partial_sig(swap_id, 0, our_nonce, &tx_hex)
+            }
+            (SwapTxKind::Refund, SwapType::Submarine) => {
+                boltz_api.get_submarine_partial_sig(swap_id, 0, our_nonce, &tx_hex)
+            }
+            (kind, swap_type) => Err(Error::Protocol(format!(
+                "Cannot get partial sig for {:?} of {:?} Swap",
+                kind, swap_type
+            ))),
+        }
+    }
+}
+
+/// Output key of a taproot lockup script. Errors instead of trusting the shape of the script,
+/// as the lockup address comes from Boltz.
+fn lockup_output_key(lockup_spk: &Script) -> Result<XOnlyPublicKey, Error> {
+    if !lockup_spk.is_v1_p2tr() {
+        return Err(Error::Protocol(
+            "Lockup address is not a taproot address".to_string(),
+        ));
+    }
+    // OP_1 OP_PUSHBYTES_32 <output key>
+    XOnlyPublicKey::from_slice(&lockup_spk.as_bytes()[2..])
+        .map_err(|e| Error::Protocol(format!("Invalid lockup output key: {}", e)))
+}
+
+fn tx_size(tx: &Transaction, is_discount_ct: bool) -> usize {
+    match is_discount_ct {
+        true => liquid_discount_vsize(tx),
+        false => tx.vsize(),
     }
 }
 
@@ -1353,6 +2392,777 @@ fn hex_to_bytes(hex_str: &str) -> Result<Vec<u8>, Error> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_unblind_explicit_txout() {
+        let asset = Chain::Liquid.lbtc_asset_id().unwrap();
+        let txout = TxOut::new_fee(1000, asset);
+        let blinding_key = SecretKey::from_slice(&[1; 32]).unwrap();
+
+        let secrets = unblind_txout(&txout, &blinding_key).unwrap();
+        assert_eq!(secrets.value, 1000);
+        assert_eq!(secrets.asset, asset);
+        assert_eq!(secrets.value_bf, ValueBlindingFactor::zero());
+    }
+
+    #[test]
+    fn test_witness_sizes() {
+        use crate::util::fees::*;
+
+        let secp = Secp256k1::new();
+        let mut script = LBtcSwapScript {
+            swap_type: SwapType::ReverseSubmarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(3_000_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+
+        let witness_size = |items: Vec<Vec<u8>>| elements::encode::serialize(&items).len();
+        let leaf_witness = |script: &LBtcSwapScript, leaf: Script, preimage: Option<Vec<u8>>| {
+            let control_block = script
+                .taproot_spendinfo()
+                .unwrap()
+                .control_block(&(leaf.clone(), LeafVersion::default()))
+                .unwrap();
+            let mut items = vec![vec![0; 64]];
+            items.extend(preimage);
+            items.push(leaf.to_bytes());
+            items.push(control_block.serialize());
+            witness_size(items)
+        };
+
+        assert_eq!(witness_size(vec![vec![0; 64]]), KEY_PATH_WITNESS_SIZE);
+        assert_eq!(
+            leaf_witness(&script, script.claim_script(), Some(vec![0; 32])),
+            CLAIM_LEAF_WITNESS_SIZE
+        );
+        assert_eq!(
+            leaf_witness(&script, script.refund_script(), None),
+            REFUND_LEAF_WITNESS_SIZE
+        );
+
+        script.swap_type = SwapType::Submarine;
+        assert_eq!(
+            leaf_witness(&script, script.claim_script(), Some(vec![0; 32])),
+            SUBMARINE_CLAIM_LEAF_WITNESS_SIZE
+        );
+    }
+
+    #[test]
+    fn test_relative_refund_timelock() {
+        let secp = Secp256k1::new();
+        let absolute = LBtcSwapScript {
+            swap_type: SwapType::Chain,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(3_000_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        assert_eq!(relative_refund_timelock(&absolute.refund_script()), None);
+
+        let required = Sequence::from_consensus(144);
+        assert!(relative_timelock_satisfied(required, required));
+        assert!(relative_timelock_satisfied(
+            required,
+            Sequence::from_consensus(200)
+        ));
+        assert!(!relative_timelock_satisfied(
+            required,
+            Sequence::from_consensus(10)
+        ));
+        // Time based sequence, or relative timelock disabled
+        assert!(!relative_timelock_satisfied(
+            required,
+            Sequence::from_consensus(144 | 1 << 22)
+        ));
+        assert!(!relative_timelock_satisfied(required, Sequence::MAX));
+
+        // Delays up to 16 are encoded as opcodes
+        for delay in [1, 16, 17, 144] {
+            let sequence = Sequence::from_consensus(delay);
+            let script = LBtcSwapScript {
+                locktime: LockTime::ZERO,
+                relative_timelock: Some(sequence),
+                ..absolute.clone()
+            };
+            assert_eq!(
+                relative_refund_timelock(&script.refund_script()),
+                Some(sequence)
+            );
+        }
+    }
+
+    /// Swap transaction spending an explicit 100_000 sats L-BTC utxo of `swap_script` on
+    /// regtest, paying back to the swap address.
+    fn test_swap_tx(kind: SwapTxKind, swap_script: LBtcSwapScript) -> LBtcSwapTx {
+        let address = swap_script.to_address(Chain::LiquidRegtest).unwrap();
+        let funding_utxo = TxOut {
+            asset: Asset::Explicit(Chain::LiquidRegtest.lbtc_asset_id().unwrap()),
+            value: Value::Explicit(100_000),
+            nonce: confidential::Nonce::Null,
+            script_pubkey: address.script_pubkey(),
+            witness: TxOutWitness::default(),
+        };
+        LBtcSwapTx {
+            kind,
+            swap_script,
+            output_address: address,
+            funding_outpoint: OutPoint::default(),
+            funding_utxo,
+            genesis_hash: BlockHash::all_zeros(),
+            signal_rbf: false,
+            memo: None,
+            limits: TxLimits::default(),
+        }
+    }
+
+    #[test]
+    fn test_claim_sequence() {
+        let secp = Secp256k1::new();
+        let swap_script = LBtcSwapScript {
+            swap_type: SwapType::ReverseSubmarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(3_000_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        let mut swap_tx = test_swap_tx(SwapTxKind::Claim, swap_script);
+
+        assert_eq!(swap_tx.claim_sequence(true), Sequence::MAX);
+        assert_eq!(swap_tx.claim_sequence(false), Sequence::ZERO);
+
+        swap_tx.signal_rbf = true;
+        assert_eq!(
+            swap_tx.claim_sequence(true),
+            Sequence::ENABLE_RBF_NO_LOCKTIME
+        );
+        assert_eq!(swap_tx.claim_sequence(false), Sequence::ZERO);
+    }
+
+    #[test]
+    fn test_finalize_key_spend() {
+        use crate::swaps::signer::SwapSigner;
+        use bitcoin::key::rand::thread_rng;
+
+        let secp = Secp256k1::new();
+        let ours = KeypairSigner::new(Keypair::new(&secp, &mut thread_rng()));
+        let boltz = KeypairSigner::new(Keypair::new(&secp, &mut thread_rng()));
+        let swap_script = LBtcSwapScript {
+            swap_type: SwapType::Submarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::new(boltz.public_key()),
+            locktime: LockTime::from_height(3_000_000).unwrap(),
+            sender_pubkey: PublicKey::new(ours.public_key()),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        let refund = test_swap_tx(SwapTxKind::Refund, swap_script.clone());
+
+        // Online: build the transaction without keys and hand the message to the offline device
+        let tx = refund
+            .unsigned_cooperative_tx(Fee::Absolute(1_000), false)
+            .unwrap();
+        assert_eq!(tx.lock_time, LockTime::ZERO);
+        let msg = refund.key_spend_sighash(&tx).unwrap();
+
+        // Offline: both MuSig2 parties sign the message
+        let musig = swap_script.musig_context().unwrap();
+        let sign = |msg| {
+            let our_nonce = musig.nonce(&ours, msg).unwrap();
+            let boltz_nonce = musig.nonce(&boltz, msg).unwrap();
+            let session = musig.session(our_nonce, boltz_nonce, msg);
+            let partial_sigs = [
+                musig.partial_sign(&boltz, &session, &boltz_nonce).unwrap(),
+                musig.partial_sign(&ours, &session, &our_nonce).unwrap(),
+            ];
+            musig.aggregate(&session, &partial_sigs, &msg).unwrap()
+        };
+
+        let signed = refund.finalize_key_spend(&tx, sign(msg), false).unwrap();
+        assert_eq!(signed.txid(), tx.txid());
+        assert_eq!(signed.input[0].witness.script_witness.len(), 1);
+        assert_eq!(tx_size(&signed, false), tx_size(&tx, false));
+
+        // A signature of another message, or a transaction not spending the swap, is rejected
+        let other_msg = Message::from_digest_slice(&[1; 32]).unwrap();
+        assert!(refund
+            .finalize_key_spend(&tx, sign(other_msg), false)
+            .is_err());
+
+        let mut unrelated = tx.clone();
+        unrelated.input[0].previous_output.vout = 1;
+        assert!(refund.key_spend_sighash(&unrelated).is_err());
+    }
+
+    #[test]
+    fn test_check_swap_tree() {
+        use super::super::boltz::Leaf;
+
+        let secp = Secp256k1::new();
+        let script = LBtcSwapScript {
+            swap_type: SwapType::Submarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(3_000_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        let leaf = |script: &Script| Leaf {
+            output: script.as_bytes().to_lower_hex_string(),
+            version: 0xc4,
+        };
+        let tree = SwapTree {
+            claim_leaf: leaf(&script.claim_script()),
+            refund_leaf: leaf(&script.refund_script()),
+        };
+        assert!(script.check_swap_tree(&tree).is_ok());
+
+        // Receiver signature check replaced by OP_1, the hashlock push is unchanged
+        let mut tampered = tree.clone();
+        let mut claim = script.claim_script().to_bytes();
+        claim.pop();
+        claim.push(0x51);
+        tampered.claim_leaf = leaf(&Script::from(claim));
+        assert!(script.check_swap_tree(&tampered).is_err());
+
+        let mut tampered = tree.clone();
+        tampered.refund_leaf.version = 0xc0;
+        assert!(script.check_swap_tree(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_address_balance() {
+        let asset = Chain::Liquid.lbtc_asset_id().unwrap();
+        let other_asset = elements::AssetId::from_slice(&[2; 32]).unwrap();
+        let blinding_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let spk = Script::from(vec![0x51]);
+        let explicit = |asset, value| TxOut {
+            asset: Asset::Explicit(asset),
+            value: Value::Explicit(value),
+            nonce: confidential::Nonce::Null,
+            script_pubkey: spk.clone(),
+            witness: TxOutWitness::default(),
+        };
+        let tx = |input: Vec<TxIn>, output: Vec<TxOut>| Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input,
+            output,
+        };
+
+        let funding = tx(
+            vec![],
+            vec![explicit(asset, 1000), explicit(other_asset, 5)],
+        );
+        let pending = tx(vec![], vec![explicit(asset, 300), explicit(asset, 200)]);
+        let spending = tx(
+            vec![TxIn {
+                previous_output: OutPoint::new(pending.txid(), 1),
+                ..Default::default()
+            }],
+            vec![],
+        );
+
+        let txs = vec![(funding, true), (pending, false), (spending, false)];
+        assert_eq!(
+            address_balance_core(&txs, &spk, &blinding_key, asset),
+            (1000, 300)
+        );
+    }
+
+    #[test]
+    fn test_select_asset_output() {
+        let asset = elements::AssetId::from_slice(&[1; 32]).unwrap();
+        let other_asset = elements::AssetId::from_slice(&[2; 32]).unwrap();
+        let blinding_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let spk = Script::from(vec![0x51]);
+        let explicit = |asset, script_pubkey: &Script| TxOut {
+            asset: Asset::Explicit(asset),
+            value: Value::Explicit(1000),
+            nonce: confidential::Nonce::Null,
+            script_pubkey: script_pubkey.clone(),
+            witness: TxOutWitness::default(),
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![
+                explicit(other_asset, &spk),
+                explicit(asset, &Script::from(vec![0x52])),
+                explicit(asset, &spk),
+            ],
+        };
+
+        let (outpoint, _) = select_asset_output(&tx, &spk, &blinding_key, asset).unwrap();
+        assert_eq!(outpoint.vout, 2);
+        let unknown_asset = elements::AssetId::from_slice(&[3; 32]).unwrap();
+        assert!(select_asset_output(&tx, &spk, &blinding_key, unknown_asset).is_none());
+    }
+
+    #[test]
+    fn test_mixed_asset_lockup() {
+        let secp = Secp256k1::new();
+        let keys = Keypair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+        let swap_script = LBtcSwapScript {
+            swap_type: SwapType::Submarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(1_000).unwrap(),
+            sender_pubkey: PublicKey::new(keys.public_key()),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        let refund = test_swap_tx(SwapTxKind::Refund, swap_script.clone());
+        let lbtc = Chain::LiquidRegtest.lbtc_asset_id().unwrap();
+        let other_asset = elements::AssetId::from_slice(&[2; 32]).unwrap();
+        let other_output = TxOut {
+            asset: Asset::Explicit(other_asset),
+            ..refund.funding_utxo.clone()
+        };
+        // Another asset sent to the swap address ahead of the L-BTC lockup
+        let lockup_tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![other_output, refund.funding_utxo.clone()],
+        };
+        let spk = refund.funding_utxo.script_pubkey.clone();
+        let blinding_key = swap_script.blinding_key.secret_key();
+
+        let electrum_utxo = select_asset_output(&lockup_tx, &spk, &blinding_key, lbtc).unwrap();
+        assert_eq!(electrum_utxo.0.vout, 1);
+        let boltz_utxos: Vec<_> = lockup_tx
+            .output
+            .iter()
+            .enumerate()
+            .map(|(vout, txout)| (OutPoint::new(lockup_tx.txid(), vout as u32), txout.clone()))
+            .collect();
+        let boltz_utxo = swap_script
+            .select_asset_utxos(boltz_utxos.clone(), lbtc)
+            .remove(0);
+        assert_eq!(boltz_utxo.0, electrum_utxo.0);
+
+        let (funding_outpoint, funding_utxo) = boltz_utxo;
+        let refund_lbtc = LBtcSwapTx {
+            funding_outpoint,
+            funding_utxo,
+            ..refund.clone()
+        };
+        assert!(refund_lbtc
+            .sign_refund(&keys, Fee::Absolute(1_000), None, false)
+            .is_ok());
+
+        // Spending the first output to the script, as before, fails the L-BTC check
+        let (funding_outpoint, funding_utxo) = boltz_utxos[0].clone();
+        let refund_other = LBtcSwapTx {
+            funding_outpoint,
+            funding_utxo,
+            ..refund
+        };
+        assert!(refund_other
+            .sign_refund(&keys, Fee::Absolute(1_000), None, false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_lockup_amount() {
+        let secp = Secp256k1::new();
+        let swap_script = LBtcSwapScript {
+            swap_type: SwapType::ReverseSubmarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(1_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        let claim = test_swap_tx(SwapTxKind::Claim, swap_script);
+        assert!(claim.verify_lockup(100_000, 0).is_ok());
+        assert!(claim.verify_lockup(100_500, 500).is_ok());
+        assert!(matches!(
+            claim.verify_lockup(100_501, 500),
+            Err(Error::AmountMismatch(_))
+        ));
+
+        // Enough sats of another asset don't pass for L-BTC
+        let other_asset = elements::AssetId::from_slice(&[2; 32]).unwrap();
+        let other = LBtcSwapTx {
+            funding_utxo: TxOut {
+                asset: Asset::Explicit(other_asset),
+                ..claim.funding_utxo.clone()
+            },
+            ..claim
+        };
+        assert!(matches!(
+            other.verify_lockup(100_000, 0),
+            Err(Error::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn test_musig_session_resume() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+        use std::sync::mpsc;
+
+        let secp = Secp256k1::new();
+        let keys = Keypair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+        let boltz_keys = Keypair::from_seckey_slice(&secp, &[3; 32]).unwrap();
+        let swap_script = LBtcSwapScript {
+            swap_type: SwapType::Submarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::new(boltz_keys.public_key()),
+            locktime: LockTime::from_height(1_000).unwrap(),
+            sender_pubkey: PublicKey::new(keys.public_key()),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        let refund = test_swap_tx(SwapTxKind::Refund, swap_script.clone());
+        let mut session = refund
+            .musig_session(&keys, None, Fee::Absolute(1_000), false)
+            .unwrap();
+        assert_eq!(session.tx.lock_time, LockTime::ZERO);
+
+        // Nothing listens there, so the partial sig request fails and the retry reuses the nonce
+        let unreachable_api = BoltzApiClientV2::new("http://127.0.0.1:1/v2");
+        let unreachable = Cooperative {
+            boltz_api: &unreachable_api,
+            swap_id: "swap".to_string(),
+            pub_nonce: None,
+            partial_sig: None,
+        };
+        assert!(session.resume(&unreachable).is_err());
+        let nonce = session.pending_nonce.unwrap();
+        assert!(session.resume(&unreachable).is_err());
+        assert_eq!(
+            session.pending_nonce.unwrap().serialize(),
+            nonce.serialize()
+        );
+
+        // Boltz answering with a well formed partial sig that does not verify
+        let msg = Message::from_digest_slice(&[1; 32]).unwrap();
+        let boltz_nonce = swap_script
+            .musig_context()
+            .unwrap()
+            .nonce(&KeypairSigner::new(boltz_keys), msg)
+            .unwrap();
+        let response = serde_json::json!({
+            "pubNonce": boltz_nonce.serialize().to_lower_hex_string(),
+            "partialSignature": "01".repeat(32),
+        })
+        .to_string();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sent_nonces, nonces) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                sent_nonces
+                    .send(request["pubNonce"].as_str().unwrap().to_string())
+                    .unwrap();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+        let boltz_api = BoltzApiClientV2::new(&url);
+        let cooperative = Cooperative {
+            boltz_api: &boltz_api,
+            swap_id: "swap".to_string(),
+            pub_nonce: None,
+            partial_sig: None,
+        };
+
+        assert!(matches!(
+            session.resume(&cooperative),
+            Err(Error::Taproot(_))
+        ));
+        assert!(session.pending_nonce.is_none());
+        assert!(matches!(
+            session.resume(&cooperative),
+            Err(Error::Taproot(_))
+        ));
+        // The pending nonce is sent once more, then a new one after the invalid partial sig
+        let first = nonces.recv().unwrap();
+        let second = nonces.recv().unwrap();
+        assert_eq!(first, nonce.serialize().to_lower_hex_string());
+        assert_ne!(second, first);
+
+        let mut claim = refund.clone();
+        claim.kind = SwapTxKind::Claim;
+        assert!(claim
+            .musig_session(&keys, None, Fee::Absolute(1_000), false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_wait_for_utxo_errors() {
+        let secp = Secp256k1::new();
+        let swap_script = LBtcSwapScript {
+            swap_type: SwapType::Submarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(1_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+
+        // Nothing listens there, so connecting fails until the timeout
+        let unreachable = ElectrumConfig::new(Chain::LiquidRegtest, "127.0.0.1:1", false, false, 1);
+        let err = swap_script
+            .wait_for_utxo(
+                &unreachable,
+                Duration::from_millis(10),
+                Duration::from_millis(50),
+            )
+            .unwrap_err();
+        assert!(err.message().contains("No utxo found"));
+
+        // A Bitcoin config fails right away, without waiting for the timeout
+        let bitcoin = ElectrumConfig::new(Chain::BitcoinRegtest, "127.0.0.1:1", false, false, 1);
+        let start = Instant::now();
+        assert!(swap_script
+            .wait_for_utxo(&bitcoin, Duration::from_millis(10), Duration::from_secs(60))
+            .is_err());
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_single_refund_utxo() {
+        let secp = Secp256k1::new();
+        let swap_script = LBtcSwapScript {
+            swap_type: SwapType::Submarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(1_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        let refund = test_swap_tx(SwapTxKind::Refund, swap_script);
+        let utxo = (refund.funding_outpoint, refund.funding_utxo.clone());
+        let second = (
+            OutPoint::new(
+                refund.funding_outpoint.txid,
+                refund.funding_outpoint.vout + 1,
+            ),
+            refund.funding_utxo,
+        );
+
+        assert_eq!(single_refund_utxo(vec![utxo.clone()]).unwrap(), utxo);
+        let err = single_refund_utxo(vec![utxo, second]).unwrap_err();
+        assert!(err.message().contains("holds 2 L-BTC outputs"));
+        assert!(single_refund_utxo(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let secp = Secp256k1::new();
+        let script = LBtcSwapScript {
+            swap_type: SwapType::ReverseSubmarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(3_000_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+
+        let mut other = script.clone();
+        other.blinding_key = ZKKeyPair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+        assert_eq!(script.fingerprint(), other.fingerprint());
+
+        other.locktime = LockTime::from_height(3_000_001).unwrap();
+        assert_ne!(script.fingerprint(), other.fingerprint());
+
+        // CSV scripts have a zero locktime, but still differ from a CLTV script with locktime 0
+        other.locktime = LockTime::ZERO;
+        let csv = LBtcSwapScript {
+            relative_timelock: Some(Sequence::from_consensus(144)),
+            ..other.clone()
+        };
+        assert_ne!(csv.fingerprint(), other.fingerprint());
+    }
+
+    #[test]
+    fn test_verify_confidential_txout() {
+        let asset = Chain::Liquid.lbtc_asset_id().unwrap();
+        let blinding_key = SecretKey::from_slice(&[1; 32]).unwrap();
+
+        let txout = TxOut::new_fee(1000, asset);
+        assert_eq!(
+            verify_confidential_txout(&txout, &blinding_key, &[])
+                .unwrap()
+                .value,
+            1000
+        );
+
+        let empty = TxOut::new_fee(0, asset);
+        assert!(matches!(
+            verify_confidential_txout(&empty, &blinding_key, &[]),
+            Err(Error::ConfidentialProof(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_blinded_txout() {
+        let secp = Secp256k1::new();
+        let swap_script = LBtcSwapScript {
+            swap_type: SwapType::ReverseSubmarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(&[0; 32]),
+            receiver_pubkey: PublicKey::from_str(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+            locktime: LockTime::from_height(1_000).unwrap(),
+            sender_pubkey: PublicKey::from_str(
+                "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            )
+            .unwrap(),
+            blinding_key: ZKKeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap(),
+            relative_timelock: None,
+        };
+        // The claim output is blinded to the swap address
+        let swap_tx = test_swap_tx(SwapTxKind::Claim, swap_script.clone());
+        let claim = swap_tx.unsigned_claim(1_000, false).unwrap();
+        let blinded = &claim.output[0];
+        assert!(blinded.asset.is_confidential());
+        let input_assets = [swap_tx.funding_utxo.asset];
+
+        let secrets = swap_script.verify_lockup(blinded, &input_assets).unwrap();
+        assert_eq!(secrets.value, 99_000);
+        assert_eq!(secrets.asset, Chain::LiquidRegtest.lbtc_asset_id().unwrap());
+
+        // The surjection proof doesn't cover other inputs
+        let other_asset = Asset::Explicit(elements::AssetId::from_slice(&[2; 32]).unwrap());
+        assert!(matches!(
+            swap_script.verify_lockup(blinded, &[other_asset]),
+            Err(Error::ConfidentialProof(_))
+        ));
+        assert!(matches!(
+            swap_script.verify_lockup(blinded, &[]),
+            Err(Error::ConfidentialProof(_))
+        ));
+
+        let mut no_proof = blinded.clone();
+        no_proof.witness.surjection_proof = None;
+        assert!(matches!(
+            swap_script.verify_lockup(&no_proof, &input_assets),
+            Err(Error::ConfidentialProof(_))
+        ));
+    }
+
+    #[test]
+    fn test_lockup_output_key_fuzz() {
+        crate::util::fuzz_lockup_scripts(10_000, |bytes| {
+            let spk = Script::from(bytes);
+            match lockup_output_key(&spk) {
+                Ok(_) => assert!(spk.is_v1_p2tr()),
+                Err(e) => assert!(matches!(e, Error::Protocol(_))),
+            }
+        });
+    }
+
     #[test]
     fn test_tx_size() {
         // From https://github.com/ElementsProject/ELIPs/blob/main/elip-0200.mediawiki#test-vectors
@@ -1360,5 +3170,6 @@ mod tests {
 
         assert_eq!(tx_size(&tx, false), 1333);
         assert_eq!(tx_size(&tx, true), 216);
+        assert_eq!(liquid_discount_vsize(&tx), tx.discount_vsize());
     }
 }
@@ -0,0 +1,212 @@
+//! User lockup of chain swaps.
+//!
+//! Boltz decides the exact amount the user has to lock up for a chain swap. [`ChainLockup`] takes
+//! that amount straight from the [`CreateChainResponse`], checks it against the pair limits and
+//! the requested amount, and funds it through the app's [`LockupWallet`].
+
+use crate::error::Error;
+
+use super::boltz::{CreateChainResponse, PairLimits};
+
+/// Wallet used to fund swap lockups.
+pub trait LockupWallet {
+    /// Send exactly `amount` sats to `address`. Returns the txid of the funding transaction.
+    fn send_to_address(&self, address: &str, amount: u64) -> Result<String, Error>;
+}
+
+/// The user lockup of a chain swap, as required by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainLockup {
+    pub swap_id: String,
+    pub address: String,
+    /// Amount to lock up, in sats.
+    pub amount: u64,
+    pub timeout_block_height: u32,
+}
+
+impl ChainLockup {
+    /// Read the user lockup from a chain swap response.
+    ///
+    /// Fails if the amount is outside the pair limits, or differs from `requested_amount`
+    /// (the `user_lock_amount` of the request, if one was set).
+    /// The swap trees are not checked here, but when building the swap scripts from the response.
+    pub fn from_response(
+        response: &CreateChainResponse,
+        limits: &PairLimits,
+        requested_amount: Option<u64>,
+    ) -> Result<Self, Error> {
+        let details = &response.lockup_details;
+        limits.within(details.amount)?;
+        if let Some(requested) = requested_amount {
+            if details.amount != requested {
+                return Err(Error::Protocol(format!(
+                    "Lockup amount {} differs from requested amount {}",
+                    details.amount, requested
+                )));
+            }
+        }
+        Ok(ChainLockup {
+            swap_id: response.id.clone(),
+            address: details.lockup_address.clone(),
+            amount: details.amount,
+            timeout_block_height: details.timeout_block_height,
+        })
+    }
+
+    /// Fund the lockup with the wallet. Returns the txid of the lockup transaction.
+    pub fn fund(&self, wallet: &dyn LockupWallet) -> Result<String, Error> {
+        log::info!(
+            "Locking up {} sats to {} for swap {}",
+            self.amount,
+            self.address,
+            self.swap_id
+        );
+        wallet.send_to_address(&self.address, self.amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct StubWallet {
+        sent: RefCell<Vec<(String, u64)>>,
+    }
+
+    impl LockupWallet for StubWallet {
+        fn send_to_address(&self, address: &str, amount: u64) -> Result<String, Error> {
+            self.sent.borrow_mut().push((address.to_string(), amount));
+            Ok("txid".to_string())
+        }
+    }
+
+    fn lockup(amount: u64) -> ChainLockup {
+        ChainLockup {
+            swap_id: "abc".to_string(),
+            address: "tb1qra2cdypld3hyq3f84630cvj9d0lmzv66vn4k28".to_string(),
+            amount,
+            timeout_block_height: 100,
+        }
+    }
+
+    /// `POST /v2/swap/chain` response of a BTC -> L-BTC swap on regtest.
+    const CHAIN_RESPONSE: &str = r#"{
+        "id": "mNHLLkAGGQpD",
+        "claimDetails": {
+            "serverPublicKey": "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "amount": 99400,
+            "lockupAddress": "el1pqwc0g0xgk2ek5ysxnr0zrnpv9dmqlx7xz9jwj8tkqsv9l8h0h3dlhdcqzrzmexs2tm7sn5xe5d8hv0wncahz9nztnrszmkp8q3hpmy4lm9jt3rw9ra2",
+            "timeoutBlockHeight": 1188,
+            "swapTree": {
+                "claimLeaf": {
+                    "version": 196,
+                    "output": "82012088a914e5dcd8b0d5fd71ba7e7b42c2a5d3b8b03b87d2a38820f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9ac"
+                },
+                "refundLeaf": {
+                    "version": 196,
+                    "output": "2079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ad02a404b1"
+                }
+            },
+            "blindingKey": "5f2a8a4a7d41ad4c16f1f9e1f6a0e1b3c7d3a3f1ad0e8d8a4b5c6d7e8f9a0b1c"
+        },
+        "lockupDetails": {
+            "serverPublicKey": "03c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+            "amount": 100000,
+            "lockupAddress": "bcrt1pkd8c3n7hsezyzfp5ymxcqk9eqgtl9fmjc6gfv3n5wp7w2jlpufsq8ne8fz",
+            "timeoutBlockHeight": 1320,
+            "swapTree": {
+                "claimLeaf": {
+                    "version": 192,
+                    "output": "82012088a914e5dcd8b0d5fd71ba7e7b42c2a5d3b8b03b87d2a38820c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5ac"
+                },
+                "refundLeaf": {
+                    "version": 192,
+                    "output": "20f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9ad022805b1"
+                }
+            },
+            "bip21": "bitcoin:bcrt1pkd8c3n7hsezyzfp5ymxcqk9eqgtl9fmjc6gfv3n5wp7w2jlpufsq8ne8fz?amount=0.001&label=Send%20to%20L-BTC%20address"
+        }
+    }"#;
+
+    fn limits() -> PairLimits {
+        PairLimits {
+            maximal: 10_000_000,
+            minimal: 25_000,
+            maximal_zero_conf: 0,
+        }
+    }
+
+    #[test]
+    fn test_from_response() {
+        let response: CreateChainResponse = serde_json::from_str(CHAIN_RESPONSE).unwrap();
+        let lockup = ChainLockup::from_response(&response, &limits(), Some(100_000)).unwrap();
+        assert_eq!(
+            lockup,
+            ChainLockup {
+                swap_id: "mNHLLkAGGQpD".to_string(),
+                address: "bcrt1pkd8c3n7hsezyzfp5ymxcqk9eqgtl9fmjc6gfv3n5wp7w2jlpufsq8ne8fz"
+                    .to_string(),
+                amount: 100_000,
+                timeout_block_height: 1320,
+            }
+        );
+        assert!(ChainLockup::from_response(&response, &limits(), None).is_ok());
+        assert!(ChainLockup::from_response(&response, &limits(), Some(99_000)).is_err());
+
+        // The same swap in the other direction, L-BTC -> BTC: the user locks up on Liquid.
+        let mut value: serde_json::Value = serde_json::from_str(CHAIN_RESPONSE).unwrap();
+        let claim = value["claimDetails"].take();
+        value["claimDetails"] = value["lockupDetails"].take();
+        value["lockupDetails"] = claim;
+        let reversed: CreateChainResponse = serde_json::from_value(value).unwrap();
+        let lockup = ChainLockup::from_response(&reversed, &limits(), None).unwrap();
+        assert_eq!(lockup.address, response.claim_details.lockup_address);
+        assert_eq!(lockup.amount, 99_400);
+        assert_eq!(lockup.timeout_block_height, 1188);
+        assert!(ChainLockup::from_response(&reversed, &limits(), Some(100_000)).is_err());
+
+        let tight = PairLimits {
+            maximal: 99_999,
+            ..limits()
+        };
+        assert!(ChainLockup::from_response(&response, &tight, None).is_err());
+        assert!(ChainLockup::from_response(&reversed, &tight, None).is_ok());
+    }
+
+    #[test]
+    fn test_chain_response_bad_swap_tree() {
+        // Responses with a missing or malformed swap tree never reach `from_response`
+        let mut missing: serde_json::Value = serde_json::from_str(CHAIN_RESPONSE).unwrap();
+        missing["lockupDetails"]
+            .as_object_mut()
+            .unwrap()
+            .remove("swapTree");
+        assert!(serde_json::from_value::<CreateChainResponse>(missing).is_err());
+
+        let mut malformed: serde_json::Value = serde_json::from_str(CHAIN_RESPONSE).unwrap();
+        malformed["lockupDetails"]["swapTree"] = serde_json::json!({
+            "claimLeaf": { "version": 192, "output": "82012088a914" }
+        });
+        assert!(serde_json::from_value::<CreateChainResponse>(malformed).is_err());
+
+        let mut malformed: serde_json::Value = serde_json::from_str(CHAIN_RESPONSE).unwrap();
+        malformed["claimDetails"]["swapTree"]["refundLeaf"]["version"] = serde_json::json!("c4");
+        assert!(serde_json::from_value::<CreateChainResponse>(malformed).is_err());
+    }
+
+    #[test]
+    fn test_fund_lockup() {
+        let wallet = StubWallet {
+            sent: RefCell::new(vec![]),
+        };
+        lockup(25_000).fund(&wallet).unwrap();
+        assert_eq!(
+            wallet.sent.borrow().as_slice(),
+            &[(
+                "tb1qra2cdypld3hyq3f84630cvj9d0lmzv66vn4k28".to_string(),
+                25_000
+            )]
+        );
+    }
+}
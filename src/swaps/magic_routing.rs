@@ -14,10 +14,6 @@ use lightning_invoice::{Bolt11Invoice, RouteHintHop};
 use super::boltz::BoltzApiClientV2;
 
 const MAGIC_ROUTING_HINT_CONSTANT: u64 = 596385002596073472;
-const LBTC_TESTNET_ASSET_HASH: &str =
-    "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49";
-const LBTC_MAINNET_ASSET_HASH: &str =
-    "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
 
 /// Decodes the provided invoice to find the magic routing hint.
 pub fn find_magic_routing_hint(invoice: &str) -> Result<Option<RouteHintHop>, Error> {
@@ -105,23 +101,12 @@ pub fn check_for_mrh(
         let secp = Secp256k1::new();
         secp.verify_schnorr(&receiver_sig, &msg, &receiver_pubkey.x_only_public_key().0)?;
 
-        match network {
-            Chain::LiquidTestnet => {
-                if assetid != Some(LBTC_TESTNET_ASSET_HASH.to_string()) {
-                    return Err(Error::Protocol(
-                        "Asset Id missmatch in Magic Routing Hint".to_string(),
-                    ));
-                }
-            }
-
-            Chain::Liquid => {
-                if assetid != Some(LBTC_MAINNET_ASSET_HASH.to_string()) {
-                    return Err(Error::Protocol(
-                        "Asset Id missmatch in Magic Routing Hint".to_string(),
-                    ));
-                }
+        if let Some(lbtc) = network.lbtc_asset_id() {
+            if assetid != Some(lbtc.to_string()) {
+                return Err(Error::Protocol(
+                    "Asset Id missmatch in Magic Routing Hint".to_string(),
+                ));
             }
-            _ => (),
         }
 
         Ok(Some((address, amount)))
@@ -1,4 +1,23 @@
+pub mod auto_refund;
+pub mod batch;
 pub mod bitcoin;
 pub mod boltz;
+pub mod crosscheck;
+pub mod export;
+pub mod history;
+pub mod legacy;
+#[cfg(feature = "service")]
+pub mod lightning_address;
 pub mod liquid;
+pub mod lockup;
 pub mod magic_routing;
+pub mod musig;
+pub mod output;
+pub mod persist;
+pub mod privacy;
+pub mod receipt;
+pub mod recovery;
+pub mod signer;
+pub mod status;
+pub mod transcript;
+pub mod watcher;
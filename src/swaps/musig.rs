@@ -0,0 +1,175 @@
+//! MuSig2 primitives of cooperative (key path) swap spends.
+//!
+//! The claim and refund builders run the whole cooperative signing with Boltz. For flows they
+//! don't cover, e.g. signing now and broadcasting later, coordinating several rounds, or signing
+//! on a server, [`SwapMusigContext`] exposes the single steps: nonce generation, session setup,
+//! verification of the counterparty's partial signature, our partial signature and aggregation.
+//!
+//! Get the context of a swap with [`BtcSwapScript::musig_context`] or
+//! [`LBtcSwapScript::musig_context`]. The key aggregation cache is already tweaked with the
+//! taproot tweak of the swap tree, so the aggregated signature is valid for the swap output key.
+//! The messages to sign and the completion of the transaction come from
+//! [`BtcSwapTx::key_spend_sighashes`] and [`BtcSwapTx::finalize_key_spend`], or their Liquid
+//! counterparts.
+//!
+//! [`BtcSwapScript::musig_context`]: super::bitcoin::BtcSwapScript::musig_context
+//! [`LBtcSwapScript::musig_context`]: super::liquid::LBtcSwapScript::musig_context
+//! [`BtcSwapTx::key_spend_sighashes`]: super::bitcoin::BtcSwapTx::key_spend_sighashes
+//! [`BtcSwapTx::finalize_key_spend`]: super::bitcoin::BtcSwapTx::finalize_key_spend
+
+use bitcoin::hex::FromHex;
+use bitcoin::secp256k1::{schnorr, Message, PublicKey, XOnlyPublicKey};
+use elements::secp256k1_zkp::{
+    MusigAggNonce, MusigKeyAggCache, MusigPartialSignature, MusigPubNonce, MusigSession, Secp256k1,
+    SecretKey,
+};
+
+use crate::error::Error;
+
+use super::signer::SwapSigner;
+
+/// Key aggregation of a swap, tweaked for key path spends of its taproot output.
+#[derive(Clone)]
+pub struct SwapMusigContext {
+    key_agg_cache: MusigKeyAggCache,
+    output_key: XOnlyPublicKey,
+}
+
+impl SwapMusigContext {
+    /// Tweak the untweaked `key_agg_cache` of the swap keys with the taproot `tap_tweak`.
+    pub fn new(mut key_agg_cache: MusigKeyAggCache, tap_tweak: [u8; 32]) -> Result<Self, Error> {
+        let tweak = SecretKey::from_slice(&tap_tweak)?;
+        let output_key = key_agg_cache
+            .pubkey_xonly_tweak_add(&Secp256k1::new(), tweak)?
+            .x_only_public_key()
+            .0;
+        Ok(SwapMusigContext {
+            key_agg_cache,
+            output_key,
+        })
+    }
+
+    /// The tweaked key aggregation cache.
+    pub fn key_agg_cache(&self) -> &MusigKeyAggCache {
+        &self.key_agg_cache
+    }
+
+    /// Output key of the swap, which the aggregated signature is valid for.
+    pub fn output_key(&self) -> XOnlyPublicKey {
+        self.output_key
+    }
+
+    /// Our public nonce for signing `msg`. The secret nonce stays with the signer.
+    pub fn nonce(&self, signer: &dyn SwapSigner, msg: Message) -> Result<MusigPubNonce, Error> {
+        signer.musig2_nonce(&self.key_agg_cache, msg)
+    }
+
+    /// Signing session of `msg` with the public nonces of both parties.
+    pub fn session(
+        &self,
+        our_nonce: MusigPubNonce,
+        their_nonce: MusigPubNonce,
+        msg: Message,
+    ) -> MusigSession {
+        let secp = Secp256k1::new();
+        let agg_nonce = MusigAggNonce::new(&secp, &[their_nonce, our_nonce]);
+        MusigSession::new(&secp, &self.key_agg_cache, agg_nonce, msg)
+    }
+
+    /// Whether `partial_sig` is a valid partial signature of `their_pubkey` in `session`.
+    pub fn verify_partial_sig(
+        &self,
+        session: &MusigSession,
+        partial_sig: MusigPartialSignature,
+        their_nonce: MusigPubNonce,
+        their_pubkey: PublicKey,
+    ) -> bool {
+        session.partial_verify(
+            &Secp256k1::new(),
+            &self.key_agg_cache,
+            partial_sig,
+            their_nonce,
+            their_pubkey,
+        )
+    }
+
+    /// Our partial signature in `session`, started with `our_nonce`.
+    /// The signer discards the secret nonce, so this can only be done once per nonce.
+    pub fn partial_sign(
+        &self,
+        signer: &dyn SwapSigner,
+        session: &MusigSession,
+        our_nonce: &MusigPubNonce,
+    ) -> Result<MusigPartialSignature, Error> {
+        signer.musig2_partial_sign(&self.key_agg_cache, session, our_nonce)
+    }
+
+    /// Aggregate the partial signatures of both parties into the key path signature of `msg`,
+    /// and check it against the output key.
+    pub fn aggregate(
+        &self,
+        session: &MusigSession,
+        partial_sigs: &[MusigPartialSignature; 2],
+        msg: &Message,
+    ) -> Result<schnorr::Signature, Error> {
+        let signature = session.partial_sig_agg(partial_sigs);
+        Secp256k1::new().verify_schnorr(&signature, msg, &self.output_key())?;
+        Ok(signature)
+    }
+}
+
+/// Parse a hex encoded public nonce, as sent by Boltz.
+pub fn pub_nonce_from_hex(hex: &str) -> Result<MusigPubNonce, Error> {
+    Ok(MusigPubNonce::from_slice(&Vec::from_hex(hex)?)?)
+}
+
+/// Parse a hex encoded partial signature, as sent by Boltz.
+pub fn partial_sig_from_hex(hex: &str) -> Result<MusigPartialSignature, Error> {
+    Ok(MusigPartialSignature::from_slice(&Vec::from_hex(hex)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swaps::signer::KeypairSigner;
+    use bitcoin::hex::DisplayHex;
+    use bitcoin::key::rand::thread_rng;
+    use bitcoin::secp256k1::Keypair;
+
+    #[test]
+    fn test_musig_context() {
+        let secp = Secp256k1::new();
+        let ours = KeypairSigner::new(Keypair::new(&secp, &mut thread_rng()));
+        let theirs = KeypairSigner::new(Keypair::new(&secp, &mut thread_rng()));
+        let key_agg_cache = MusigKeyAggCache::new(&secp, &[theirs.public_key(), ours.public_key()]);
+        let ctx = SwapMusigContext::new(key_agg_cache.clone(), [1; 32]).unwrap();
+        assert_ne!(ctx.output_key(), key_agg_cache.agg_pk());
+
+        let msg = Message::from_digest([7; 32]);
+        let our_nonce = ctx.nonce(&ours, msg).unwrap();
+        let their_nonce = ctx.nonce(&theirs, msg).unwrap();
+        let session = ctx.session(our_nonce, their_nonce, msg);
+        let their_sig = ctx.partial_sign(&theirs, &session, &their_nonce).unwrap();
+        assert!(ctx.verify_partial_sig(&session, their_sig, their_nonce, theirs.public_key()));
+        assert!(!ctx.verify_partial_sig(&session, their_sig, their_nonce, ours.public_key()));
+
+        let our_sig = ctx.partial_sign(&ours, &session, &our_nonce).unwrap();
+        // The secret nonce was used up
+        assert!(ctx.partial_sign(&ours, &session, &our_nonce).is_err());
+        assert!(ctx.aggregate(&session, &[their_sig, our_sig], &msg).is_ok());
+        assert!(ctx
+            .aggregate(
+                &session,
+                &[their_sig, our_sig],
+                &Message::from_digest([8; 32])
+            )
+            .is_err());
+
+        let hex = our_nonce.serialize().to_lower_hex_string();
+        assert_eq!(
+            pub_nonce_from_hex(&hex).unwrap().serialize(),
+            our_nonce.serialize()
+        );
+        assert!(partial_sig_from_hex("00").is_err());
+    }
+}
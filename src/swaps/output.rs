@@ -0,0 +1,92 @@
+//! Coin control metadata of outputs created by claim and refund transactions.
+//!
+//! Wallets can store [`SwapOutput`] next to their UTXO set, to keep the provenance of swapped funds.
+
+use serde::{Deserialize, Serialize};
+
+use super::boltz::{SwapTxKind, SwapType};
+
+/// Script type of an output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    Other,
+}
+
+impl ScriptType {
+    pub fn from_bitcoin_script(script: &bitcoin::Script) -> Self {
+        if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_p2wpkh() {
+            ScriptType::P2wpkh
+        } else if script.is_p2wsh() {
+            ScriptType::P2wsh
+        } else if script.is_p2tr() {
+            ScriptType::P2tr
+        } else {
+            ScriptType::Other
+        }
+    }
+
+    pub fn from_elements_script(script: &elements::Script) -> Self {
+        if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_v0_p2wpkh() {
+            ScriptType::P2wpkh
+        } else if script.is_v0_p2wsh() {
+            ScriptType::P2wsh
+        } else if script.is_v1_p2tr() {
+            ScriptType::P2tr
+        } else {
+            ScriptType::Other
+        }
+    }
+}
+
+/// An output created by a claim or refund transaction of a swap.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapOutput {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    pub script_type: ScriptType,
+    /// Amount in sats.
+    pub amount: u64,
+    pub swap_id: String,
+    pub swap_type: SwapType,
+    /// Whether the output was created by a claim or a refund.
+    pub spend_kind: SwapTxKind,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Address, Network};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_script_type() {
+        let address =
+            Address::from_str("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr")
+                .unwrap()
+                .require_network(Network::Bitcoin)
+                .unwrap();
+        assert_eq!(
+            ScriptType::from_bitcoin_script(&address.script_pubkey()),
+            ScriptType::P2tr
+        );
+        assert_eq!(
+            ScriptType::from_bitcoin_script(&bitcoin::ScriptBuf::new()),
+            ScriptType::Other
+        );
+    }
+}
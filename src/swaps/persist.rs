@@ -0,0 +1,289 @@
+//! Stable serialization of swap scripts.
+//!
+//! [`BtcSwapScript`] and [`LBtcSwapScript`] implement serde, so apps can persist them with their
+//! in-flight swaps and rebuild claim and refund transactions after a restart, without going back
+//! to the Boltz creation response. Both are serialized to the same json object:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "chain": "bitcoin" | "liquid",
+//!   "swapType": "submarine" | "reversesubmarine" | "chain",
+//!   "side": "lockup" | "claim",
+//!   "fundingAddress": "<address of the lockup output>",
+//!   "hashlock": "<hex hash160 of the preimage>",
+//!   "receiverPubkey": "<hex compressed public key>",
+//!   "senderPubkey": "<hex compressed public key>",
+//!   "locktime": 850000,
+//!   "relativeTimelock": 144,
+//!   "blindingKey": "<hex secret key, Liquid only>"
+//! }
+//! ```
+//!
+//! `side`, `fundingAddress`, `relativeTimelock` and `blindingKey` are left out when not set.
+//! `locktime` and `relativeTimelock` are consensus encoded. The `version` is bumped on every
+//! incompatible change, readers reject versions they don't know. Scripts are only read back as
+//! the type of their `chain`, so a Liquid script can't lose its blinding key by being read as a
+//! Bitcoin script.
+//! Liquid scripts contain the blinding key and must be stored like other swap secrets.
+
+use std::str::FromStr;
+
+use bitcoin::hashes::hash160;
+use bitcoin::PublicKey;
+use elements::secp256k1_zkp::{Keypair as ZKKeyPair, Secp256k1};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::Error;
+use crate::{BtcSwapScript, LBtcSwapScript};
+
+use super::boltz::{Side, SwapType};
+
+/// Version of the swap script format written by this library.
+pub const SWAP_SCRIPT_VERSION: u32 = 1;
+
+/// Chain of a stored swap script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ScriptChain {
+    Bitcoin,
+    Liquid,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredSwapScript {
+    version: u32,
+    chain: ScriptChain,
+    swap_type: SwapType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    side: Option<Side>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    funding_address: Option<String>,
+    hashlock: String,
+    receiver_pubkey: String,
+    sender_pubkey: String,
+    locktime: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    relative_timelock: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blinding_key: Option<String>,
+}
+
+impl StoredSwapScript {
+    fn check(&self, chain: ScriptChain) -> Result<(), Error> {
+        if self.version != SWAP_SCRIPT_VERSION {
+            return Err(Error::Protocol(format!(
+                "Unsupported swap script version {}",
+                self.version
+            )));
+        }
+        if self.chain != chain {
+            return Err(Error::Protocol(format!(
+                "Expected a {:?} swap script, found a {:?} one",
+                chain, self.chain
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl From<&BtcSwapScript> for StoredSwapScript {
+    fn from(script: &BtcSwapScript) -> Self {
+        StoredSwapScript {
+            version: SWAP_SCRIPT_VERSION,
+            chain: ScriptChain::Bitcoin,
+            swap_type: script.swap_type,
+            side: script.side.clone(),
+            funding_address: script.funding_addrs.as_ref().map(|a| a.to_string()),
+            hashlock: script.hashlock.to_string(),
+            receiver_pubkey: script.receiver_pubkey.to_string(),
+            sender_pubkey: script.sender_pubkey.to_string(),
+            locktime: script.locktime.to_consensus_u32(),
+            relative_timelock: script.relative_timelock.map(|s| s.0),
+            blinding_key: None,
+        }
+    }
+}
+
+impl TryFrom<StoredSwapScript> for BtcSwapScript {
+    type Error = Error;
+
+    fn try_from(stored: StoredSwapScript) -> Result<Self, Error> {
+        stored.check(ScriptChain::Bitcoin)?;
+        let funding_addrs = match stored.funding_address {
+            Some(address) => Some(bitcoin::Address::from_str(&address)?.assume_checked()),
+            None => None,
+        };
+        Ok(BtcSwapScript {
+            swap_type: stored.swap_type,
+            side: stored.side,
+            funding_addrs,
+            hashlock: hash160::Hash::from_str(&stored.hashlock)?,
+            receiver_pubkey: PublicKey::from_str(&stored.receiver_pubkey)?,
+            locktime: bitcoin::absolute::LockTime::from_consensus(stored.locktime),
+            sender_pubkey: PublicKey::from_str(&stored.sender_pubkey)?,
+            relative_timelock: stored.relative_timelock.map(bitcoin::Sequence),
+        })
+    }
+}
+
+impl From<&LBtcSwapScript> for StoredSwapScript {
+    fn from(script: &LBtcSwapScript) -> Self {
+        StoredSwapScript {
+            version: SWAP_SCRIPT_VERSION,
+            chain: ScriptChain::Liquid,
+            swap_type: script.swap_type,
+            side: script.side.clone(),
+            funding_address: script.funding_addrs.as_ref().map(|a| a.to_string()),
+            hashlock: script.hashlock.to_string(),
+            receiver_pubkey: script.receiver_pubkey.to_string(),
+            sender_pubkey: script.sender_pubkey.to_string(),
+            locktime: script.locktime.to_consensus_u32(),
+            relative_timelock: script.relative_timelock.map(|s| s.0),
+            blinding_key: Some(script.blinding_key.display_secret().to_string()),
+        }
+    }
+}
+
+impl TryFrom<StoredSwapScript> for LBtcSwapScript {
+    type Error = Error;
+
+    fn try_from(stored: StoredSwapScript) -> Result<Self, Error> {
+        stored.check(ScriptChain::Liquid)?;
+        let funding_addrs = match stored.funding_address {
+            Some(address) => Some(elements::Address::from_str(&address)?),
+            None => None,
+        };
+        let blinding_key = stored.blinding_key.ok_or(Error::Protocol(
+            "Liquid swap script without blinding key".to_string(),
+        ))?;
+        Ok(LBtcSwapScript {
+            swap_type: stored.swap_type,
+            side: stored.side,
+            funding_addrs,
+            hashlock: hash160::Hash::from_str(&stored.hashlock)?,
+            receiver_pubkey: PublicKey::from_str(&stored.receiver_pubkey)?,
+            locktime: elements::LockTime::from_consensus(stored.locktime),
+            sender_pubkey: PublicKey::from_str(&stored.sender_pubkey)?,
+            blinding_key: ZKKeyPair::from_seckey_str(&Secp256k1::new(), &blinding_key)?,
+            relative_timelock: stored.relative_timelock.map(elements::Sequence),
+        })
+    }
+}
+
+impl Serialize for BtcSwapScript {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredSwapScript::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BtcSwapScript {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BtcSwapScript::try_from(StoredSwapScript::deserialize(deserializer)?)
+            .map_err(|e| de::Error::custom(e.message()))
+    }
+}
+
+impl Serialize for LBtcSwapScript {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredSwapScript::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LBtcSwapScript {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        LBtcSwapScript::try_from(StoredSwapScript::deserialize(deserializer)?)
+            .map_err(|e| de::Error::custom(e.message()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::key::rand::thread_rng;
+
+    fn test_btc_script() -> BtcSwapScript {
+        let secp = Secp256k1::new();
+        BtcSwapScript {
+            swap_type: SwapType::Chain,
+            side: Some(Side::Lockup),
+            funding_addrs: None,
+            hashlock: hash160::Hash::hash(b"preimage"),
+            receiver_pubkey: PublicKey::new(ZKKeyPair::new(&secp, &mut thread_rng()).public_key()),
+            locktime: bitcoin::absolute::LockTime::from_consensus(850_000),
+            sender_pubkey: PublicKey::new(ZKKeyPair::new(&secp, &mut thread_rng()).public_key()),
+            relative_timelock: None,
+        }
+    }
+
+    #[test]
+    fn test_swap_script_roundtrip() {
+        let script = test_btc_script();
+        let json = serde_json::to_value(&script).unwrap();
+        assert_eq!(json["version"], SWAP_SCRIPT_VERSION);
+        assert_eq!(json["chain"], "bitcoin");
+        assert_eq!(json["side"], "lockup");
+        assert_eq!(json["locktime"], 850_000);
+        assert!(json.get("blindingKey").is_none());
+        assert_eq!(
+            serde_json::from_value::<BtcSwapScript>(json).unwrap(),
+            script
+        );
+
+        let secp = Secp256k1::new();
+        let script = LBtcSwapScript {
+            swap_type: SwapType::Submarine,
+            side: None,
+            funding_addrs: None,
+            hashlock: script.hashlock,
+            receiver_pubkey: script.receiver_pubkey,
+            locktime: elements::LockTime::ZERO,
+            sender_pubkey: script.sender_pubkey,
+            blinding_key: ZKKeyPair::new(&secp, &mut thread_rng()),
+            relative_timelock: Some(elements::Sequence(144)),
+        };
+        let json = serde_json::to_string(&script).unwrap();
+        assert_eq!(
+            serde_json::from_str::<LBtcSwapScript>(&json).unwrap(),
+            script
+        );
+    }
+
+    #[test]
+    fn test_swap_script_unknown_version() {
+        let mut json = serde_json::to_value(test_btc_script()).unwrap();
+        json["version"] = serde_json::json!(SWAP_SCRIPT_VERSION + 1);
+        assert!(serde_json::from_value::<BtcSwapScript>(json.clone()).is_err());
+        assert!(serde_json::from_value::<LBtcSwapScript>(json).is_err());
+    }
+
+    #[test]
+    fn test_swap_script_wrong_chain() {
+        let btc = test_btc_script();
+        let json = serde_json::to_value(&btc).unwrap();
+        assert!(serde_json::from_value::<LBtcSwapScript>(json).is_err());
+
+        let secp = Secp256k1::new();
+        let liquid = LBtcSwapScript {
+            swap_type: btc.swap_type,
+            side: btc.side.clone(),
+            funding_addrs: None,
+            hashlock: btc.hashlock,
+            receiver_pubkey: btc.receiver_pubkey,
+            locktime: elements::LockTime::from_consensus(850_000),
+            sender_pubkey: btc.sender_pubkey,
+            blinding_key: ZKKeyPair::new(&secp, &mut thread_rng()),
+            relative_timelock: None,
+        };
+        let json = serde_json::to_value(&liquid).unwrap();
+        assert_eq!(json["chain"], "liquid");
+        // Would otherwise parse and silently drop the blinding key
+        assert!(serde_json::from_value::<BtcSwapScript>(json.clone()).is_err());
+
+        let mut json = json;
+        json.as_object_mut().unwrap().remove("chain");
+        assert!(serde_json::from_value::<LBtcSwapScript>(json).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Privacy knobs for claiming.
+//!
+//! Claims broadcast right after the lockup, paying a single round amount, are easy to cluster
+//! by chain analysis. A [`PrivacyPolicy`] randomizes when to claim, and splits round claim
+//! amounts into several non-round outputs. Set it as the `privacy` of a
+//! [`BtcSwapTx`](crate::swaps::bitcoin::BtcSwapTx) to split its claim output.
+
+use std::time::Duration;
+
+use bitcoin::key::rand::Rng;
+
+use crate::util::entropy::SecureRng;
+
+/// Amounts that are a multiple of this are considered round, in sats.
+pub const ROUND_AMOUNT_UNIT: u64 = 1_000;
+
+/// Claim privacy policy. The default policy changes nothing.
+#[derive(Debug, Clone, Default)]
+pub struct PrivacyPolicy {
+    /// Window to pick a random delay from, before claiming once the lockup is seen.
+    /// Keep the upper bound well below the swap timeout.
+    pub claim_delay: Option<(Duration, Duration)>,
+    /// Split round claim amounts into non-round outputs.
+    pub avoid_round_amounts: bool,
+    /// Maximum number of outputs a claim amount is split into.
+    pub max_outputs: usize,
+}
+
+impl PrivacyPolicy {
+    /// Random delay to wait before claiming. Zero if no delay window is set.
+    pub fn claim_delay(&self) -> Duration {
+        match self.claim_delay {
+            Some((min, max)) if max > min => SecureRng.gen_range(min..=max),
+            Some((min, _)) => min,
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Split a claim amount into output amounts, in sats.
+    ///
+    /// Returns `[amount]` unless the amount is round and round amounts should be avoided.
+    /// Otherwise the amount is split into up to `max_outputs` non-round outputs of at least
+    /// `dust_limit` each. If no such split exists, the amount is returned unsplit.
+    pub fn split_amount(&self, amount: u64, dust_limit: u64) -> Vec<u64> {
+        match self.split_count(amount) {
+            outputs if outputs < 2 => vec![amount],
+            outputs => split_non_round(amount, dust_limit, outputs).unwrap_or(vec![amount]),
+        }
+    }
+
+    /// Random number of outputs to split a claim amount into, 1 to keep it unsplit.
+    pub fn split_count(&self, amount: u64) -> usize {
+        if !self.avoid_round_amounts || !is_round(amount) || self.max_outputs < 2 {
+            return 1;
+        }
+        SecureRng.gen_range(2..=self.max_outputs)
+    }
+}
+
+/// Split `amount` into `outputs` random non-round amounts of more than `dust_limit` each, in sats.
+/// None if no such split was found.
+pub fn split_non_round(amount: u64, dust_limit: u64, outputs: usize) -> Option<Vec<u64>> {
+    let mut rng = SecureRng;
+    let outputs = outputs.max(1) as u64;
+    // Leave room for every output to be above dust, and not round
+    let min_output = dust_limit.max(1) + 1;
+    if amount < outputs * (min_output + ROUND_AMOUNT_UNIT) {
+        return None;
+    }
+
+    for _ in 0..100 {
+        let mut remaining = amount;
+        let mut split = vec![];
+        for i in 0..outputs - 1 {
+            let left = outputs - i;
+            // Pick around an equal share, +/-30%
+            let share = remaining / left;
+            let spread = share * 3 / 10;
+            let max = (remaining - (left - 1) * min_output).min(share + spread);
+            let output = rng.gen_range(min_output.max(share - spread)..=max);
+            split.push(output);
+            remaining -= output;
+        }
+        split.push(remaining);
+        if split.iter().all(|o| !is_round(*o) && *o >= min_output) {
+            return Some(split);
+        }
+    }
+    None
+}
+
+/// Whether an amount is a multiple of [`ROUND_AMOUNT_UNIT`].
+pub fn is_round(amount: u64) -> bool {
+    amount % ROUND_AMOUNT_UNIT == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim_delay() {
+        assert_eq!(PrivacyPolicy::default().claim_delay(), Duration::ZERO);
+        let policy = PrivacyPolicy {
+            claim_delay: Some((Duration::from_secs(10), Duration::from_secs(60))),
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let delay = policy.claim_delay();
+            assert!(delay >= Duration::from_secs(10) && delay <= Duration::from_secs(60));
+        }
+    }
+
+    #[test]
+    fn test_split_amount() {
+        assert_eq!(
+            PrivacyPolicy::default().split_amount(100_000, 546),
+            vec![100_000]
+        );
+
+        let policy = PrivacyPolicy {
+            avoid_round_amounts: true,
+            max_outputs: 3,
+            ..Default::default()
+        };
+        assert_eq!(policy.split_amount(100_123, 546), vec![100_123]);
+        assert_eq!(policy.split_amount(2_000, 546), vec![2_000]);
+        for _ in 0..100 {
+            let split = policy.split_amount(1_000_000, 546);
+            assert!(split.len() >= 2 && split.len() <= 3);
+            assert_eq!(split.iter().sum::<u64>(), 1_000_000);
+            assert!(split.iter().all(|o| !is_round(*o) && *o > 546));
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Signed swap receipts.
+//!
+//! Once a swap completed, the app can issue a [`SignedReceipt`]: the swap details signed with the
+//! user's swap key. Since that key is committed to in the swap scripts, a third party can check
+//! the receipt against the onchain transactions to verify that a payment was made via the swap.
+
+use bitcoin::{
+    hashes::{sha256, Hash},
+    hex::{DisplayHex, FromHex},
+    key::{Keypair, Secp256k1},
+    secp256k1::{schnorr::Signature, Message},
+    XOnlyPublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+use super::boltz::SwapType;
+
+/// Details of a completed swap.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapReceipt {
+    pub id: String,
+    pub swap_type: SwapType,
+    /// Lightning invoice paid or received, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invoice: Option<String>,
+    /// Amount sent, in sats.
+    pub amount_sent: u64,
+    /// Amount received, in sats.
+    pub amount_received: u64,
+    /// Hex encoded sha256 hash of the swap preimage.
+    pub preimage_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lockup_txid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_txid: Option<String>,
+    /// Unix timestamp of the completion of the swap.
+    pub timestamp: u64,
+}
+
+impl SwapReceipt {
+    /// Hash of the receipt that gets signed: sha256 of its json serialization.
+    pub fn digest(&self) -> Result<sha256::Hash, Error> {
+        Ok(sha256::Hash::hash(serde_json::to_string(self)?.as_bytes()))
+    }
+
+    /// Sign the receipt with the swap key.
+    pub fn sign(self, keys: &Keypair) -> Result<SignedReceipt, Error> {
+        let msg = Message::from_digest_slice(self.digest()?.as_byte_array())?;
+        let signature = Secp256k1::new().sign_schnorr(&msg, keys);
+        Ok(SignedReceipt {
+            receipt: self,
+            public_key: keys.x_only_public_key().0.serialize().to_lower_hex_string(),
+            signature: signature.serialize().to_lower_hex_string(),
+        })
+    }
+}
+
+/// A receipt with a BIP-340 signature over its digest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedReceipt {
+    pub receipt: SwapReceipt,
+    /// Hex encoded x-only public key of the swap key.
+    pub public_key: String,
+    /// Hex encoded schnorr signature.
+    pub signature: String,
+}
+
+impl SignedReceipt {
+    /// Check the signature against the embedded public key.
+    /// Callers still have to check that this key is the one used in the swap scripts.
+    pub fn verify(&self) -> Result<(), Error> {
+        let msg = Message::from_digest_slice(self.receipt.digest()?.as_byte_array())?;
+        let public_key = XOnlyPublicKey::from_slice(&Vec::from_hex(&self.public_key)?)?;
+        let signature = Signature::from_slice(&Vec::from_hex(&self.signature)?)?;
+        Secp256k1::new().verify_schnorr(&signature, &msg, &public_key)?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::key::rand::thread_rng;
+
+    #[test]
+    fn test_signed_receipt() {
+        let keys = Keypair::new(&Secp256k1::new(), &mut thread_rng());
+        let receipt = SwapReceipt {
+            id: "abc".to_string(),
+            swap_type: SwapType::Submarine,
+            invoice: Some("lnbc1".to_string()),
+            amount_sent: 100_500,
+            amount_received: 100_000,
+            preimage_hash: sha256::Hash::hash(&[0; 32]).to_string(),
+            lockup_txid: Some("00".repeat(32)),
+            claim_txid: None,
+            timestamp: 1_700_000_000,
+        };
+
+        let signed = receipt.sign(&keys).unwrap();
+        signed.verify().unwrap();
+        let parsed = SignedReceipt::from_json(&signed.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, signed);
+        parsed.verify().unwrap();
+
+        let mut tampered = parsed;
+        tampered.receipt.amount_sent = 1;
+        assert!(tampered.verify().is_err());
+    }
+}
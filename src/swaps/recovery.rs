@@ -0,0 +1,823 @@
+//! Overview of funds recoverable through refunds.
+//!
+//! Implement [`RefundStore`] on top of the app's swap storage, then call [`summarize_refundables`]
+//! to get per chain totals for a "Recover funds" screen: what can be refunded right now, what
+//! becomes refundable soon, and what can only be recovered cooperatively with Boltz until then.
+//!
+//! The swaps can then be recovered with a [`RecoveryExecutor`]: it hands batches of swaps of the
+//! same chain to a [`RecoveryAction`] on a bounded number of threads, stops spending once the fee
+//! budget is used up, and records progress in a [`RecoveryCheckpoint`] so an interrupted
+//! recovery can be resumed.
+//!
+//! After a seed restore, [`rescue_refundables`] asks Boltz for the swaps of the wallet's rescue
+//! xpub, so the app doesn't have to scan the chains for every derived refund key. Each
+//! [`RescuedSwap`] rebuilds the swap script to refund, given the refund key derived at its
+//! `key_index`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+
+use bitcoin::PublicKey;
+use electrum_client::ElectrumApi;
+use elements::secp256k1_zkp::{Keypair as ZKKeyPair, Secp256k1};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::network::{electrum::ElectrumConfig, Chain};
+
+use super::batch::BatchResult;
+use super::bitcoin::BtcSwapScript;
+use super::boltz::{
+    BoltzApiClientV2, ChainSwapDetails, CreateSubmarineResponse, RescuableSwap, RescueTransaction,
+    Side, SwapTree, SwapType,
+};
+use super::liquid::{unblind_txout, LBtcSwapScript};
+
+/// A swap with funds locked by the user, which may have to be refunded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefundableSwap {
+    pub swap_id: String,
+    pub chain: Chain,
+    /// Amount locked up, in sats.
+    pub amount: u64,
+    /// Block height from which the refund path can be used without Boltz.
+    pub timeout_block_height: u32,
+}
+
+/// Source of the swaps to summarize.
+pub trait RefundStore {
+    /// All swaps with a lockup that was neither claimed nor refunded yet.
+    fn refundable_swaps(&self) -> Result<Vec<RefundableSwap>, Error>;
+}
+
+/// Number of swaps and total amount in sats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RefundTotal {
+    pub count: u32,
+    pub amount: u64,
+}
+
+impl RefundTotal {
+    fn add(&mut self, amount: u64) {
+        self.count += 1;
+        self.amount += amount;
+    }
+}
+
+/// Refund eligibility of the swaps on one chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefundSummary {
+    pub chain: Chain,
+    pub tip_height: u32,
+    /// Timelock expired, refundable now without Boltz.
+    pub matured: RefundTotal,
+    /// Timelock expires within the requested number of blocks.
+    pub maturing_soon: RefundTotal,
+    /// Timelock expires later, only a cooperative refund is possible until then.
+    pub cooperative_only: RefundTotal,
+}
+
+/// Summarize the refundable swaps of `store`, per chain of `chain_clients`.
+/// Swaps maturing within `within_blocks` blocks are counted as maturing soon.
+/// Swaps on a chain without client are left out.
+pub fn summarize_refundables(
+    store: &dyn RefundStore,
+    chain_clients: &[&ElectrumConfig],
+    within_blocks: u32,
+) -> Result<Vec<RefundSummary>, Error> {
+    let swaps = store.refundable_swaps()?;
+    let mut tips = vec![];
+    for client in chain_clients {
+        tips.push((client.network(), client.get_tip_height()?));
+    }
+    Ok(summarize_at(&swaps, &tips, within_blocks))
+}
+
+/// A swap of a restored wallet found by [`rescue_refundables`], with everything needed to rebuild
+/// its swap script and refund it.
+#[derive(Debug, Clone)]
+pub struct RescuedSwap {
+    pub refundable: RefundableSwap,
+    /// Submarine or Chain.
+    pub swap_type: SwapType,
+    /// Derivation index of the refund key below the rescue xpub.
+    pub key_index: u32,
+    pub server_public_key: PublicKey,
+    pub swap_tree: SwapTree,
+    pub lockup_address: String,
+    /// Blinding key of Liquid lockups.
+    pub blinding_key: Option<String>,
+    pub lockup: RescueTransaction,
+}
+
+impl RescuedSwap {
+    /// Rescued swap from the Boltz listing, with the lockup `amount` read on `chain`.
+    pub fn new(swap: RescuableSwap, chain: Chain, amount: u64) -> Result<Self, Error> {
+        let swap_type = match swap.swap_type.as_str() {
+            "submarine" => SwapType::Submarine,
+            "chain" => SwapType::Chain,
+            other => {
+                return Err(Error::Protocol(format!(
+                    "Swap {} of type {} can't be refunded",
+                    swap.id, other
+                )))
+            }
+        };
+        let lockup = swap.transaction.ok_or(Error::Protocol(format!(
+            "Swap {} has no lockup transaction",
+            swap.id
+        )))?;
+        Ok(RescuedSwap {
+            refundable: RefundableSwap {
+                swap_id: swap.id,
+                chain,
+                amount,
+                timeout_block_height: swap.timeout_block_height,
+            },
+            swap_type,
+            key_index: swap.key_index,
+            server_public_key: swap.server_public_key,
+            swap_tree: swap.tree,
+            lockup_address: swap.lockup_address,
+            blinding_key: swap.blinding_key,
+            lockup,
+        })
+    }
+
+    /// Swap script of a Bitcoin lockup. `refund_pubkey` is the key derived at `key_index` below
+    /// the rescue xpub. Fails if the swap tree doesn't match it.
+    pub fn btc_swap_script(&self, refund_pubkey: PublicKey) -> Result<BtcSwapScript, Error> {
+        match self.swap_type {
+            SwapType::Submarine => {
+                BtcSwapScript::submarine_from_swap_resp(&self.submarine_response(), refund_pubkey)
+            }
+            _ => BtcSwapScript::chain_from_swap_resp(
+                Side::Lockup,
+                self.chain_details(),
+                refund_pubkey,
+            ),
+        }
+    }
+
+    /// Swap script of a Liquid lockup, see [`Self::btc_swap_script`].
+    pub fn lbtc_swap_script(&self, refund_pubkey: PublicKey) -> Result<LBtcSwapScript, Error> {
+        match self.swap_type {
+            SwapType::Submarine => {
+                LBtcSwapScript::submarine_from_swap_resp(&self.submarine_response(), refund_pubkey)
+            }
+            _ => LBtcSwapScript::chain_from_swap_resp(
+                Side::Lockup,
+                self.chain_details(),
+                refund_pubkey,
+            ),
+        }
+    }
+
+    /// Parts of the original create response the submarine script is parsed from.
+    fn submarine_response(&self) -> CreateSubmarineResponse {
+        CreateSubmarineResponse {
+            accept_zero_conf: false,
+            address: self.lockup_address.clone(),
+            bip21: String::new(),
+            claim_public_key: self.server_public_key,
+            expected_amount: self.refundable.amount,
+            id: self.refundable.swap_id.clone(),
+            referral_id: None,
+            swap_tree: self.swap_tree.clone(),
+            timeout_block_height: self.refundable.timeout_block_height as u64,
+            blinding_key: self.blinding_key.clone(),
+        }
+    }
+
+    /// Lockup details of the original chain swap response.
+    fn chain_details(&self) -> ChainSwapDetails {
+        ChainSwapDetails {
+            swap_tree: self.swap_tree.clone(),
+            lockup_address: self.lockup_address.clone(),
+            server_public_key: self.server_public_key,
+            timeout_block_height: self.refundable.timeout_block_height,
+            amount: self.refundable.amount,
+            blinding_key: self.blinding_key.clone(),
+            refund_address: None,
+            claim_address: None,
+            bip21: None,
+        }
+    }
+}
+
+/// Refundable swaps of a restored wallet, as listed by Boltz for its rescue `xpub`.
+///
+/// Lockup amounts are read from the lockup transactions, with the client of `chain_clients` for
+/// the chain of each swap. Swaps without lockup transaction, on a chain without client, or whose
+/// lockup was already claimed or refunded are left out. Swaps whose lockup couldn't be checked
+/// are returned as failed items, so they can be retried.
+pub fn rescue_refundables(
+    boltz_api: &BoltzApiClientV2,
+    xpub: &str,
+    chain_clients: &[&ElectrumConfig],
+) -> Result<BatchResult<RescuedSwap>, Error> {
+    let mut rescued = BatchResult::new();
+    for swap in boltz_api.rescue_swaps(xpub)? {
+        if swap.transaction.is_none() || is_spent_status(&swap.status) {
+            continue;
+        }
+        let client = match chain_clients
+            .iter()
+            .find(|client| is_symbol_of(&swap.symbol, client.network()))
+        {
+            Some(client) => client,
+            None => {
+                log::debug!("No client for swap {} on {}", swap.id, swap.symbol);
+                continue;
+            }
+        };
+        let id = swap.id.clone();
+        match rescue_swap(swap, client) {
+            Ok(Some(swap)) => rescued.push(id, Ok(swap)),
+            Ok(None) => log::debug!("Lockup of swap {} was already spent", id),
+            Err(e) => rescued.push(id, Err(e)),
+        }
+    }
+    Ok(rescued)
+}
+
+/// Whether Boltz's status of a swap means its lockup was claimed or refunded.
+fn is_spent_status(status: &str) -> bool {
+    matches!(status, "transaction.claimed" | "transaction.refunded")
+}
+
+/// Rescue a swap with a lockup transaction. None if the lockup output is spent already.
+fn rescue_swap(swap: RescuableSwap, client: &ElectrumConfig) -> Result<Option<RescuedSwap>, Error> {
+    let transaction = swap.transaction.clone().ok_or(Error::Protocol(format!(
+        "Swap {} has no lockup transaction",
+        swap.id
+    )))?;
+    let (amount, script_pubkey) = lockup_output(&swap, &transaction, client)?;
+    let unspent = client
+        .client()?
+        .script_list_unspent(&script_pubkey)?
+        .iter()
+        .any(|utxo| {
+            utxo.tx_hash.to_string() == transaction.id && utxo.tx_pos == transaction.vout as usize
+        });
+    if !unspent {
+        return Ok(None);
+    }
+    RescuedSwap::new(swap, client.network(), amount).map(Some)
+}
+
+/// Whether Boltz's currency `symbol` is the currency of `chain`.
+fn is_symbol_of(symbol: &str, chain: Chain) -> bool {
+    match chain {
+        Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => symbol == "BTC",
+        Chain::Liquid | Chain::LiquidTestnet | Chain::LiquidRegtest => symbol == "L-BTC",
+    }
+}
+
+/// Value in sats and script pubkey of the lockup output of a rescued swap. Liquid outputs are
+/// unblinded with the blinding key of the swap.
+fn lockup_output(
+    swap: &RescuableSwap,
+    transaction: &RescueTransaction,
+    client: &ElectrumConfig,
+) -> Result<(u64, bitcoin::ScriptBuf), Error> {
+    let missing = || {
+        Error::Protocol(format!(
+            "Lockup output {}:{} of swap {} not found",
+            transaction.id, transaction.vout, swap.id
+        ))
+    };
+    let vout = transaction.vout as usize;
+    match client.network() {
+        Chain::Bitcoin | Chain::BitcoinTestnet | Chain::BitcoinRegtest => {
+            let tx = client.get_bitcoin_tx(&bitcoin::Txid::from_str(&transaction.id)?)?;
+            let txout = tx.output.get(vout).ok_or_else(missing)?;
+            Ok((txout.value.to_sat(), txout.script_pubkey.clone()))
+        }
+        Chain::Liquid | Chain::LiquidTestnet | Chain::LiquidRegtest => {
+            let tx = client.get_liquid_tx(&elements::Txid::from_str(&transaction.id)?)?;
+            let txout = tx.output.get(vout).ok_or_else(missing)?;
+            let script_pubkey = bitcoin::ScriptBuf::from_bytes(txout.script_pubkey.to_bytes());
+            if let Some(value) = txout.value.explicit() {
+                return Ok((value, script_pubkey));
+            }
+            let blinding_key = swap.blinding_key.as_ref().ok_or(Error::Protocol(format!(
+                "No blinding key for confidential lockup of swap {}",
+                swap.id
+            )))?;
+            let blinding_key = ZKKeyPair::from_seckey_str(&Secp256k1::new(), blinding_key)?;
+            let value = unblind_txout(txout, &blinding_key.secret_key())?.value;
+            Ok((value, script_pubkey))
+        }
+    }
+}
+
+/// Summarize swaps against known chain tips.
+pub fn summarize_at(
+    swaps: &[RefundableSwap],
+    tips: &[(Chain, u32)],
+    within_blocks: u32,
+) -> Vec<RefundSummary> {
+    tips.iter()
+        .map(|(chain, tip_height)| {
+            let mut summary = RefundSummary {
+                chain: *chain,
+                tip_height: *tip_height,
+                matured: RefundTotal::default(),
+                maturing_soon: RefundTotal::default(),
+                cooperative_only: RefundTotal::default(),
+            };
+            for swap in swaps.iter().filter(|s| s.chain == *chain) {
+                // A tx with the timeout as locktime can be mined in the next block
+                if swap.timeout_block_height <= *tip_height {
+                    summary.matured.add(swap.amount);
+                } else if swap.timeout_block_height - tip_height <= within_blocks {
+                    summary.maturing_soon.add(swap.amount);
+                } else {
+                    summary.cooperative_only.add(swap.amount);
+                }
+            }
+            summary
+        })
+        .collect()
+}
+
+/// Recovery of a batch of swaps, e.g. by signing and broadcasting a (batched) refund.
+pub trait RecoveryAction: Sync {
+    /// Fee in sats the recovery of `batch` is expected to cost.
+    fn estimate_fee(&self, chain: Chain, batch: &[RefundableSwap]) -> Result<u64, Error>;
+    /// Recover all swaps of `batch`, returning the recovery transaction.
+    fn execute(&self, chain: Chain, batch: &[RefundableSwap]) -> Result<RecoveryTx, Error>;
+}
+
+/// Transaction that recovered a batch of swaps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryTx {
+    pub txid: String,
+    /// Fee actually paid, in sats. Counts against the fee budget instead of the estimate.
+    pub fee: u64,
+}
+
+/// Progress of a recovery, to be persisted by the app between runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryCheckpoint {
+    /// Txid of the recovery transaction per recovered swap id.
+    pub recovered: BTreeMap<String, String>,
+    /// Error message per swap id whose last recovery attempt failed. Retried on the next run.
+    pub failed: BTreeMap<String, String>,
+    /// Fees spent so far, in sats.
+    pub fees_spent: u64,
+}
+
+impl RecoveryCheckpoint {
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// Outcome of a [`RecoveryExecutor::run`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Number of swaps recovered in this run.
+    pub recovered: u32,
+    /// Number of swaps whose recovery failed in this run.
+    pub failed: u32,
+    /// Swap ids whose recovery failed in this run with an error that may go away, e.g. a
+    /// connection problem. Other failures likely need attention before retrying.
+    pub retryable: Vec<String>,
+    /// Swap ids not attempted because the fee budget would have been exceeded.
+    pub over_budget: Vec<String>,
+}
+
+/// Runs [`RecoveryAction`]s for many swaps.
+#[derive(Debug, Clone)]
+pub struct RecoveryExecutor {
+    /// Maximum number of batches processed at the same time.
+    pub concurrency: usize,
+    /// Maximum number of swaps per batch. Batches only contain swaps of the same chain.
+    pub batch_size: usize,
+    /// Maximum total fees of all runs sharing a checkpoint, in sats.
+    pub fee_budget: u64,
+}
+
+impl RecoveryExecutor {
+    /// Recover `swaps`, skipping those already recovered according to `checkpoint`.
+    /// `persist` is called with the updated checkpoint after every batch.
+    ///
+    /// `checkpoint` is updated with the progress made even if a worker fails, e.g. because
+    /// `persist` returned an error, before that error is returned.
+    pub fn run(
+        &self,
+        swaps: &[RefundableSwap],
+        action: &dyn RecoveryAction,
+        checkpoint: &mut RecoveryCheckpoint,
+        persist: &(dyn Fn(&RecoveryCheckpoint) -> Result<(), Error> + Sync),
+    ) -> Result<RecoveryReport, Error> {
+        let pending: Vec<&RefundableSwap> = swaps
+            .iter()
+            .filter(|swap| !checkpoint.recovered.contains_key(&swap.swap_id))
+            .collect();
+        let queue = Mutex::new(self.batches(&pending));
+        let state = Mutex::new((checkpoint.clone(), RecoveryReport::default()));
+
+        let result = thread::scope(|scope| -> Result<(), Error> {
+            let workers: Vec<_> = (0..self.concurrency.max(1))
+                .map(|_| scope.spawn(|| self.work(&queue, &state, action, persist)))
+                .collect();
+            for worker in workers {
+                worker
+                    .join()
+                    .map_err(|_| Error::Generic("Recovery worker panicked".to_string()))??;
+            }
+            Ok(())
+        });
+
+        // Batches recorded before a worker failed were executed, so keep them in any case
+        let (updated, report) = state
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *checkpoint = updated;
+        result?;
+        Ok(report)
+    }
+
+    /// Group swaps into batches of the same chain, in order of first appearance.
+    fn batches(&self, swaps: &[&RefundableSwap]) -> VecDeque<(Chain, Vec<RefundableSwap>)> {
+        let mut per_chain: Vec<(Chain, Vec<RefundableSwap>)> = vec![];
+        for swap in swaps {
+            match per_chain.iter_mut().find(|(chain, _)| *chain == swap.chain) {
+                Some((_, chain_swaps)) => chain_swaps.push((*swap).clone()),
+                None => per_chain.push((swap.chain, vec![(*swap).clone()])),
+            }
+        }
+        per_chain
+            .into_iter()
+            .flat_map(|(chain, chain_swaps)| {
+                chain_swaps
+                    .chunks(self.batch_size.max(1))
+                    .map(|batch| (chain, batch.to_vec()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn work(
+        &self,
+        queue: &Mutex<VecDeque<(Chain, Vec<RefundableSwap>)>>,
+        state: &Mutex<(RecoveryCheckpoint, RecoveryReport)>,
+        action: &dyn RecoveryAction,
+        persist: &(dyn Fn(&RecoveryCheckpoint) -> Result<(), Error> + Sync),
+    ) -> Result<(), Error> {
+        let lock_error = || Error::Generic("Recovery state lock poisoned".to_string());
+        loop {
+            let (chain, batch) = match queue.lock().map_err(|_| lock_error())?.pop_front() {
+                Some(next) => next,
+                None => return Ok(()),
+            };
+
+            let result = action.estimate_fee(chain, &batch).and_then(|fee| {
+                // Reserve the fee before executing, so concurrent batches can't overspend
+                {
+                    let mut state = state.lock().map_err(|_| lock_error())?;
+                    if state.0.fees_spent + fee > self.fee_budget {
+                        let ids = batch.iter().map(|swap| swap.swap_id.clone());
+                        state.1.over_budget.extend(ids);
+                        return Ok(None);
+                    }
+                    state.0.fees_spent += fee;
+                }
+                let result = action.execute(chain, &batch);
+                // Replace the reservation with what was actually paid
+                let mut state = state.lock().map_err(|_| lock_error())?;
+                state.0.fees_spent -= fee;
+                if let Ok(tx) = &result {
+                    state.0.fees_spent += tx.fee;
+                }
+                result.map(Some)
+            });
+
+            let mut state = state.lock().map_err(|_| lock_error())?;
+            let (checkpoint, report) = &mut *state;
+            match result {
+                Ok(None) => continue,
+                Ok(Some(tx)) => {
+                    for swap in &batch {
+                        checkpoint.failed.remove(&swap.swap_id);
+                        checkpoint
+                            .recovered
+                            .insert(swap.swap_id.clone(), tx.txid.clone());
+                        report.recovered += 1;
+                    }
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Recovery of {} swaps on {:?} failed: {}",
+                        batch.len(),
+                        chain,
+                        e.message()
+                    );
+                    for swap in &batch {
+                        checkpoint.failed.insert(swap.swap_id.clone(), e.message());
+                        report.failed += 1;
+                        if e.is_retryable() {
+                            report.retryable.push(swap.swap_id.clone());
+                        }
+                    }
+                }
+            }
+            persist(checkpoint)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct StubAction {
+        executed: AtomicUsize,
+    }
+
+    impl RecoveryAction for StubAction {
+        fn estimate_fee(&self, _chain: Chain, batch: &[RefundableSwap]) -> Result<u64, Error> {
+            Ok(100 * batch.len() as u64)
+        }
+
+        fn execute(&self, chain: Chain, batch: &[RefundableSwap]) -> Result<RecoveryTx, Error> {
+            self.executed.fetch_add(1, Ordering::SeqCst);
+            if batch.iter().any(|swap| swap.swap_id == "bad") {
+                return Err(Error::Generic("broadcast failed".to_string()));
+            }
+            // Cheaper than estimated
+            Ok(RecoveryTx {
+                txid: format!("{:?}-{}", chain, batch[0].swap_id),
+                fee: 90 * batch.len() as u64,
+            })
+        }
+    }
+
+    #[test]
+    fn test_recovery_executor() {
+        let swap = |id: &str, chain| RefundableSwap {
+            swap_id: id.to_string(),
+            chain,
+            amount: 10_000,
+            timeout_block_height: 0,
+        };
+        let swaps = vec![
+            swap("a", Chain::Bitcoin),
+            swap("b", Chain::Liquid),
+            swap("c", Chain::Bitcoin),
+            swap("bad", Chain::Liquid),
+            swap("d", Chain::Bitcoin),
+        ];
+        let executor = RecoveryExecutor {
+            concurrency: 2,
+            batch_size: 2,
+            fee_budget: 500,
+        };
+        let action = StubAction {
+            executed: AtomicUsize::new(0),
+        };
+        let persisted = AtomicUsize::new(0);
+        let persist = |_: &RecoveryCheckpoint| {
+            persisted.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        };
+
+        let mut checkpoint = RecoveryCheckpoint::default();
+        let report = executor
+            .run(&swaps, &action, &mut checkpoint, &persist)
+            .unwrap();
+        assert_eq!(report.failed, 2);
+        // A failed broadcast is not a connection problem
+        assert!(report.retryable.is_empty());
+        // a+c and d on Bitcoin, b+bad on Liquid fails and releases its fee
+        assert_eq!(checkpoint.recovered.len(), 3);
+        assert_eq!(checkpoint.recovered["a"], checkpoint.recovered["c"]);
+        assert_eq!(checkpoint.failed.len(), 2);
+        assert_eq!(checkpoint.fees_spent, 270);
+        assert_eq!(persisted.load(Ordering::SeqCst), 3);
+
+        // Resuming only retries what is not recovered yet, within the remaining budget
+        let checkpoint_json = checkpoint.to_json().unwrap();
+        let mut resumed = RecoveryCheckpoint::from_json(&checkpoint_json).unwrap();
+        let executor = RecoveryExecutor {
+            fee_budget: 400,
+            ..executor
+        };
+        let report = executor
+            .run(&swaps, &action, &mut resumed, &persist)
+            .unwrap();
+        assert_eq!(report.recovered, 0);
+        assert_eq!(report.over_budget, vec!["b".to_string(), "bad".to_string()]);
+    }
+
+    #[test]
+    fn test_recovery_executor_persist_error() {
+        let swap = |id: &str| RefundableSwap {
+            swap_id: id.to_string(),
+            chain: Chain::Bitcoin,
+            amount: 10_000,
+            timeout_block_height: 0,
+        };
+        let swaps = vec![swap("a"), swap("b"), swap("c")];
+        let executor = RecoveryExecutor {
+            concurrency: 1,
+            batch_size: 2,
+            fee_budget: 1_000,
+        };
+        let action = StubAction {
+            executed: AtomicUsize::new(0),
+        };
+        let persist = |_: &RecoveryCheckpoint| Err(Error::Generic("disk full".to_string()));
+
+        let mut checkpoint = RecoveryCheckpoint::default();
+        let err = executor
+            .run(&swaps, &action, &mut checkpoint, &persist)
+            .unwrap_err();
+        assert_eq!(err.message(), "disk full");
+        // The executed batch is kept, so it isn't refunded twice
+        assert_eq!(action.executed.load(Ordering::SeqCst), 1);
+        assert_eq!(checkpoint.recovered.len(), 2);
+        assert_eq!(checkpoint.fees_spent, 180);
+    }
+
+    #[test]
+    fn test_summarize_at() {
+        let swap = |chain, amount, timeout_block_height| RefundableSwap {
+            swap_id: "id".to_string(),
+            chain,
+            amount,
+            timeout_block_height,
+        };
+        let swaps = vec![
+            swap(Chain::Bitcoin, 10_000, 850_000),
+            swap(Chain::Bitcoin, 20_000, 850_100),
+            swap(Chain::Bitcoin, 30_000, 851_000),
+            swap(Chain::Liquid, 40_000, 3_000_000),
+        ];
+
+        let summaries = summarize_at(&swaps, &[(Chain::Bitcoin, 850_000)], 144);
+        assert_eq!(summaries.len(), 1);
+        let btc = &summaries[0];
+        assert_eq!(
+            btc.matured,
+            RefundTotal {
+                count: 1,
+                amount: 10_000
+            }
+        );
+        assert_eq!(
+            btc.maturing_soon,
+            RefundTotal {
+                count: 1,
+                amount: 20_000
+            }
+        );
+        assert_eq!(
+            btc.cooperative_only,
+            RefundTotal {
+                count: 1,
+                amount: 30_000
+            }
+        );
+    }
+
+    #[test]
+    fn test_rescued_swap() {
+        let swap: RescuableSwap = serde_json::from_value(serde_json::json!({
+            "id": "3GSEXXvmb1Hv",
+            "type": "submarine",
+            "status": "transaction.mempool",
+            "symbol": "L-BTC",
+            "keyIndex": 12,
+            "preimageHash": "6b4dd0e6e9a7b4bbc6ce82c2fdd4a95ee3a3b3b04a19a8f2b2d2f1d15b5f5c11",
+            "timeoutBlockHeight": 3_000_000,
+            "serverPublicKey": "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "tree": {
+                "claimLeaf": { "version": 196, "output": "" },
+                "refundLeaf": { "version": 196, "output": "" }
+            },
+            "lockupAddress": "lq1",
+            "transaction": { "id": "00", "vout": 1 },
+            "createdAt": 1_700_000_000
+        }))
+        .unwrap();
+        assert_eq!(swap.key_index, 12);
+        assert_eq!(swap.transaction.unwrap().vout, 1);
+        assert!(swap.blinding_key.is_none());
+
+        assert!(is_symbol_of("BTC", Chain::BitcoinRegtest));
+        assert!(is_symbol_of("L-BTC", Chain::Liquid));
+        assert!(!is_symbol_of("L-BTC", Chain::Bitcoin));
+        assert!(!is_symbol_of("BTC", Chain::LiquidTestnet));
+    }
+
+    #[test]
+    fn test_rescue_refund() {
+        use crate::swaps::bitcoin::BtcSwapTx;
+        use crate::swaps::boltz::SwapTxKind;
+        use crate::swaps::privacy::PrivacyPolicy;
+        use crate::util::fees::{Fee, TxLimits};
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+        use bitcoin::{absolute::LockTime, Address, Amount, OutPoint, TxOut, Txid};
+
+        // Refund key 1 (G), server key 2G, 850_000 timeout
+        let rescuable = |status: &str| -> RescuableSwap {
+            serde_json::from_value(serde_json::json!({
+                "id": "Xu9NtyXJBhfd",
+                "type": "submarine",
+                "status": status,
+                "symbol": "BTC",
+                "keyIndex": 3,
+                "preimageHash": "6b4dd0e6e9a7b4bbc6ce82c2fdd4a95ee3a3b3b04a19a8f2b2d2f1d15b5f5c11",
+                "timeoutBlockHeight": 850_000,
+                "serverPublicKey": "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+                "tree": {
+                    "claimLeaf": {
+                        "version": 192,
+                        "output": "a9141111111111111111111111111111111111111111882\
+                                   0c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5ac"
+                    },
+                    "refundLeaf": {
+                        "version": 192,
+                        "output": "2079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+                                   ad0350f80cb1"
+                    }
+                },
+                "lockupAddress": "bcrt1pyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3qnswg0v",
+                "transaction": {
+                    "id": "8f3a8d1e1c5c0b3c9e9b6c0a7e5e1f6b3c2d4a5b6c7d8e9f0a1b2c3d4e5f6a7b",
+                    "vout": 1
+                },
+                "createdAt": 1_700_000_000
+            }))
+            .unwrap()
+        };
+        assert!(is_spent_status("transaction.claimed"));
+        assert!(is_spent_status("transaction.refunded"));
+        assert!(!is_spent_status("swap.expired"));
+
+        let rescued =
+            RescuedSwap::new(rescuable("swap.expired"), Chain::BitcoinRegtest, 50_000).unwrap();
+        assert_eq!(rescued.key_index, 3);
+        assert_eq!(rescued.refundable.timeout_block_height, 850_000);
+
+        let secp = Secp256k1::new();
+        let mut secret = [0; 32];
+        secret[31] = 1;
+        let keys = Keypair::from_seckey_slice(&secp, &secret).unwrap();
+        let refund_pubkey = PublicKey::new(keys.public_key());
+        // Keys derived at another index don't match the tree
+        let other_pubkey = PublicKey::from_str(
+            "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        )
+        .unwrap();
+        assert!(rescued.btc_swap_script(other_pubkey).is_err());
+
+        let swap_script = rescued.btc_swap_script(refund_pubkey).unwrap();
+        let lockup_address = swap_script.to_address(Chain::BitcoinRegtest).unwrap();
+        let swap_tx = BtcSwapTx {
+            kind: SwapTxKind::Refund,
+            swap_script,
+            output_address: Address::from_str("bcrt1qxvenxvenxvenxvenxvenxvenxvenxvenztev8a")
+                .unwrap()
+                .assume_checked(),
+            utxos: vec![(
+                OutPoint::new(
+                    Txid::from_str(&rescued.lockup.id).unwrap(),
+                    rescued.lockup.vout,
+                ),
+                TxOut {
+                    value: Amount::from_sat(rescued.refundable.amount),
+                    script_pubkey: lockup_address.script_pubkey(),
+                },
+            )],
+            signal_rbf: true,
+            memo: None,
+            limits: TxLimits::default(),
+            refund_outputs: vec![],
+            claim_splits: vec![],
+            privacy: PrivacyPolicy::default(),
+            network_config: None,
+            anchor: None,
+        };
+        let refund = swap_tx
+            .sign_refund(&keys, Fee::Absolute(1_000), None)
+            .unwrap();
+        assert_eq!(refund.lock_time, LockTime::from_height(850_000).unwrap());
+        assert_eq!(refund.output[0].value, Amount::from_sat(49_000));
+
+        let mut reverse = rescuable("swap.expired");
+        reverse.swap_type = "reverse".to_string();
+        assert!(RescuedSwap::new(reverse, Chain::BitcoinRegtest, 50_000).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Signing of swap transactions outside of the library.
+//!
+//! Everything that needs the swap private key goes through a [`SwapSigner`]: script path
+//! signatures and the MuSig2 nonce and partial signature of cooperative spends. Implement it on
+//! top of a hardware wallet, remote signer or HSM and pass it to the `*_with_signer` methods of
+//! [`BtcSwapTx`](super::bitcoin::BtcSwapTx), so the key never enters this crate.
+//! [`KeypairSigner`] is the in-process implementation used by the `Keypair` based methods.
+
+use std::sync::Mutex;
+
+use bitcoin::secp256k1::{schnorr, Keypair, Message, PublicKey, Secp256k1};
+use elements::secp256k1_zkp::{
+    MusigKeyAggCache, MusigPartialSignature, MusigPubNonce, MusigSecNonce, MusigSession,
+    MusigSessionId,
+};
+
+use crate::error::Error;
+use crate::util::entropy::{random_32b, EntropyPurpose, SecureRng};
+
+/// Holder of a swap private key.
+pub trait SwapSigner {
+    /// Public key committed to in the swap script.
+    fn public_key(&self) -> PublicKey;
+
+    /// BIP-340 signature of a taproot script path sighash.
+    fn sign_taproot_script_spend(&self, msg: &Message) -> Result<schnorr::Signature, Error>;
+
+    /// Start a MuSig2 signing session for `msg` and return our public nonce.
+    /// The secret nonce stays with the signer, and must only be used once.
+    fn musig2_nonce(
+        &self,
+        key_agg_cache: &MusigKeyAggCache,
+        msg: Message,
+    ) -> Result<MusigPubNonce, Error>;
+
+    /// Partial signature for the session started with `pub_nonce`.
+    /// The secret nonce of that session has to be discarded afterwards.
+    fn musig2_partial_sign(
+        &self,
+        key_agg_cache: &MusigKeyAggCache,
+        session: &MusigSession,
+        pub_nonce: &MusigPubNonce,
+    ) -> Result<MusigPartialSignature, Error>;
+}
+
+/// [`SwapSigner`] holding the key in memory.
+pub struct KeypairSigner {
+    keys: Keypair,
+    /// Secret nonces of started MuSig2 sessions, removed when used.
+    sec_nonces: Mutex<Vec<(MusigPubNonce, MusigSecNonce)>>,
+}
+
+impl KeypairSigner {
+    pub fn new(keys: Keypair) -> Self {
+        KeypairSigner {
+            keys,
+            sec_nonces: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl SwapSigner for KeypairSigner {
+    fn public_key(&self) -> PublicKey {
+        self.keys.public_key()
+    }
+
+    fn sign_taproot_script_spend(&self, msg: &Message) -> Result<schnorr::Signature, Error> {
+        Ok(Secp256k1::new().sign_schnorr(msg, &self.keys))
+    }
+
+    fn musig2_nonce(
+        &self,
+        key_agg_cache: &MusigKeyAggCache,
+        msg: Message,
+    ) -> Result<MusigPubNonce, Error> {
+        let session_id = MusigSessionId::new(&mut SecureRng);
+        let extra_rand = random_32b(EntropyPurpose::MusigExtraRand)?;
+        let (sec_nonce, pub_nonce) = key_agg_cache.nonce_gen(
+            &Secp256k1::new(),
+            session_id,
+            self.keys.public_key(),
+            msg,
+            Some(extra_rand),
+        )?;
+        self.sec_nonces
+            .lock()
+            .map_err(|_| Error::Generic("Signer nonce lock poisoned".to_string()))?
+            .push((pub_nonce, sec_nonce));
+        Ok(pub_nonce)
+    }
+
+    fn musig2_partial_sign(
+        &self,
+        key_agg_cache: &MusigKeyAggCache,
+        session: &MusigSession,
+        pub_nonce: &MusigPubNonce,
+    ) -> Result<MusigPartialSignature, Error> {
+        let sec_nonce = {
+            let mut sec_nonces = self
+                .sec_nonces
+                .lock()
+                .map_err(|_| Error::Generic("Signer nonce lock poisoned".to_string()))?;
+            let index = sec_nonces
+                .iter()
+                .position(|(nonce, _)| nonce.serialize() == pub_nonce.serialize())
+                .ok_or(Error::Protocol(
+                    "No MuSig2 session started for this nonce".to_string(),
+                ))?;
+            sec_nonces.remove(index).1
+        };
+        Ok(session.partial_sign(&Secp256k1::new(), sec_nonce, &self.keys, key_agg_cache)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::key::rand::thread_rng;
+
+    #[test]
+    fn test_keypair_signer_script_spend() {
+        let secp = Secp256k1::new();
+        let keys = Keypair::new(&secp, &mut thread_rng());
+        let signer = KeypairSigner::new(keys);
+        let msg = Message::from_digest(sha256::Hash::hash(b"sighash").to_byte_array());
+
+        let signature = signer.sign_taproot_script_spend(&msg).unwrap();
+        assert!(secp
+            .verify_schnorr(&signature, &msg, &keys.x_only_public_key().0)
+            .is_ok());
+        assert_eq!(signer.public_key(), keys.public_key());
+    }
+}
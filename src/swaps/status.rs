@@ -0,0 +1,324 @@
+//! Swap status helpers.
+//!
+//! Boltz reports swap status as plain strings (see [`super::boltz::GetSwapResponse`]).
+//! This module maps those into coarse phases, which callers polling `get_swap` can use to
+//! decide how often to poll.
+
+use std::time::Duration;
+
+use super::boltz::SwapType;
+use crate::util::backoff::with_jitter;
+
+/// Coarse phase of a swap, derived from its Boltz status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapPhase {
+    /// Waiting for the user to fund the swap (lockup tx or lightning payment).
+    AwaitingLockup,
+    /// A lockup transaction is in the mempool, waiting for confirmation.
+    AwaitingConfirmation,
+    /// Waiting on Boltz to do something (pay the invoice, lock up funds).
+    AwaitingServer,
+    /// The client is expected to act soon (cooperative claim, claim of server lockup).
+    ActionWindow,
+    /// No more status transitions are expected.
+    Final,
+    /// Status not known to this version of the library.
+    Unknown,
+}
+
+impl SwapPhase {
+    /// Classify a Boltz status string for the given swap type.
+    pub fn from_status(swap_type: SwapType, status: &str) -> Self {
+        match (swap_type, status) {
+            (_, "swap.created") => SwapPhase::AwaitingLockup,
+            (SwapType::Submarine, "invoice.set") => SwapPhase::AwaitingLockup,
+            (SwapType::ReverseSubmarine, "minerfee.paid") => SwapPhase::AwaitingLockup,
+
+            (SwapType::Submarine, "transaction.mempool")
+            | (SwapType::Chain, "transaction.mempool") => SwapPhase::AwaitingConfirmation,
+            (SwapType::Chain, "transaction.zeroconf.rejected") => SwapPhase::AwaitingConfirmation,
+
+            (SwapType::Submarine, "transaction.confirmed")
+            | (SwapType::Submarine, "invoice.pending")
+            | (SwapType::Submarine, "invoice.paid")
+            | (SwapType::Chain, "transaction.confirmed")
+            | (SwapType::ReverseSubmarine, "invoice.expired") => SwapPhase::AwaitingServer,
+
+            (SwapType::Submarine, "transaction.claim.pending")
+            | (SwapType::ReverseSubmarine, "transaction.mempool")
+            | (SwapType::ReverseSubmarine, "transaction.confirmed")
+            | (SwapType::Chain, "transaction.server.mempool")
+            | (SwapType::Chain, "transaction.server.confirmed") => SwapPhase::ActionWindow,
+
+            (_, "swap.expired")
+            | (_, "transaction.failed")
+            | (_, "transaction.refunded")
+            | (_, "transaction.lockupFailed")
+            | (SwapType::Submarine, "transaction.claimed")
+            | (SwapType::Submarine, "invoice.failedToPay")
+            | (SwapType::ReverseSubmarine, "invoice.settled")
+            | (SwapType::Chain, "transaction.claimed") => SwapPhase::Final,
+
+            _ => SwapPhase::Unknown,
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        *self == SwapPhase::Final
+    }
+}
+
+const SUBMARINE_HAPPY_PATH: &[&str] = &[
+    "swap.created",
+    "invoice.set",
+    "transaction.mempool",
+    "transaction.confirmed",
+    "invoice.pending",
+    "invoice.paid",
+    "transaction.claim.pending",
+    "transaction.claimed",
+];
+
+const REVERSE_HAPPY_PATH: &[&str] = &[
+    "swap.created",
+    "transaction.mempool",
+    "transaction.confirmed",
+    "invoice.settled",
+];
+
+const CHAIN_HAPPY_PATH: &[&str] = &[
+    "swap.created",
+    "transaction.mempool",
+    "transaction.confirmed",
+    "transaction.server.mempool",
+    "transaction.server.confirmed",
+    "transaction.claimed",
+];
+
+const SUBMARINE_STATUSES: &[&str] = &[
+    "swap.created",
+    "transaction.mempool",
+    "transaction.confirmed",
+    "invoice.set",
+    "invoice.paid",
+    "invoice.pending",
+    "invoice.failedToPay",
+    "transaction.claimed",
+    "transaction.claim.pending",
+    "transaction.lockupFailed",
+    "swap.expired",
+];
+
+const REVERSE_STATUSES: &[&str] = &[
+    "swap.created",
+    "minerfee.paid",
+    "transaction.mempool",
+    "transaction.confirmed",
+    "invoice.settled",
+    "invoice.expired",
+    "swap.expired",
+    "transaction.failed",
+    "transaction.refunded",
+];
+
+const CHAIN_STATUSES: &[&str] = &[
+    "swap.created",
+    "transaction.zeroconf.rejected",
+    "transaction.mempool",
+    "transaction.confirmed",
+    "transaction.server.mempool",
+    "transaction.server.confirmed",
+    "transaction.claimed",
+    "transaction.lockupFailed",
+    "swap.expired",
+    "transaction.failed",
+    "transaction.refunded",
+];
+
+/// All statuses known for a swap type, matching `SubSwapStates`, `RevSwapStates` and `ChainSwapStates`.
+pub fn known_statuses(swap_type: SwapType) -> &'static [&'static str] {
+    match swap_type {
+        SwapType::Submarine => SUBMARINE_STATUSES,
+        SwapType::ReverseSubmarine => REVERSE_STATUSES,
+        SwapType::Chain => CHAIN_STATUSES,
+    }
+}
+
+/// Ordered statuses of a successful swap.
+pub fn happy_path(swap_type: SwapType) -> &'static [&'static str] {
+    match swap_type {
+        SwapType::Submarine => SUBMARINE_HAPPY_PATH,
+        SwapType::ReverseSubmarine => REVERSE_HAPPY_PATH,
+        SwapType::Chain => CHAIN_HAPPY_PATH,
+    }
+}
+
+/// Statuses of the happy path strictly between `from` and `to`, in order.
+/// These are the transitions missed when a swap jumps from `from` to `to`, e.g. after a reconnect.
+/// Empty if either status is not on the happy path, or `to` does not come after `from`.
+pub fn missed_statuses(swap_type: SwapType, from: &str, to: &str) -> Vec<&'static str> {
+    let path = happy_path(swap_type);
+    let from = path.iter().position(|s| *s == from);
+    let to = path.iter().position(|s| *s == to);
+    match (from, to) {
+        (Some(from), Some(to)) if to > from + 1 => path[from + 1..to].to_vec(),
+        _ => vec![],
+    }
+}
+
+/// Poll interval policy for swaps monitored over REST.
+///
+/// Swaps in an action window are polled fast, swaps sitting in the same phase for longer than
+/// `idle_after` are polled slowly. A random jitter spreads out requests of many swaps.
+#[derive(Debug, Clone)]
+pub struct PollPolicy {
+    pub fast: Duration,
+    pub normal: Duration,
+    pub slow: Duration,
+    /// Time spent in a non-action phase after which a swap is considered idle.
+    pub idle_after: Duration,
+    /// Jitter as a fraction of the interval, e.g. 0.2 for +/-20%. Capped at 0.9, NaN disables it.
+    pub jitter: f64,
+}
+
+impl Default for PollPolicy {
+    fn default() -> Self {
+        PollPolicy {
+            fast: Duration::from_secs(5),
+            normal: Duration::from_secs(30),
+            slow: Duration::from_secs(300),
+            idle_after: Duration::from_secs(600),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl PollPolicy {
+    /// Base interval for a phase, without jitter. Returns None for final phases.
+    pub fn base_interval(&self, phase: SwapPhase, time_in_phase: Duration) -> Option<Duration> {
+        let interval = match phase {
+            SwapPhase::Final => return None,
+            SwapPhase::ActionWindow | SwapPhase::AwaitingServer => self.fast,
+            SwapPhase::AwaitingLockup | SwapPhase::AwaitingConfirmation | SwapPhase::Unknown => {
+                if time_in_phase >= self.idle_after {
+                    self.slow
+                } else {
+                    self.normal
+                }
+            }
+        };
+        Some(interval)
+    }
+
+    /// Interval until the next poll for a phase, with jitter applied.
+    /// Returns None if the swap does not need to be polled anymore.
+    pub fn next_interval(&self, phase: SwapPhase, time_in_phase: Duration) -> Option<Duration> {
+        let base = self.base_interval(phase, time_in_phase)?;
+        Some(with_jitter(base, self.jitter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swaps::boltz::{ChainSwapStates, RevSwapStates, SubSwapStates};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_phase_from_status() {
+        assert_eq!(
+            SwapPhase::from_status(SwapType::Submarine, "invoice.set"),
+            SwapPhase::AwaitingLockup
+        );
+        assert_eq!(
+            SwapPhase::from_status(SwapType::Submarine, "transaction.claim.pending"),
+            SwapPhase::ActionWindow
+        );
+        assert_eq!(
+            SwapPhase::from_status(SwapType::ReverseSubmarine, "transaction.mempool"),
+            SwapPhase::ActionWindow
+        );
+        assert_eq!(
+            SwapPhase::from_status(SwapType::Chain, "transaction.mempool"),
+            SwapPhase::AwaitingConfirmation
+        );
+        assert!(SwapPhase::from_status(SwapType::ReverseSubmarine, "invoice.settled").is_final());
+        assert_eq!(
+            SwapPhase::from_status(SwapType::Chain, "some.new.status"),
+            SwapPhase::Unknown
+        );
+    }
+
+    #[test]
+    fn test_known_statuses() {
+        for status in known_statuses(SwapType::Submarine) {
+            assert!(SubSwapStates::from_str(status).is_ok());
+        }
+        for status in known_statuses(SwapType::ReverseSubmarine) {
+            assert!(RevSwapStates::from_str(status).is_ok());
+        }
+        for status in known_statuses(SwapType::Chain) {
+            assert!(ChainSwapStates::from_str(status).is_ok());
+        }
+        for swap_type in [
+            SwapType::Submarine,
+            SwapType::ReverseSubmarine,
+            SwapType::Chain,
+        ] {
+            for status in happy_path(swap_type) {
+                assert!(known_statuses(swap_type).contains(status));
+            }
+        }
+    }
+
+    #[test]
+    fn test_missed_statuses() {
+        assert_eq!(
+            missed_statuses(
+                SwapType::Submarine,
+                "transaction.mempool",
+                "transaction.claimed"
+            ),
+            vec![
+                "transaction.confirmed",
+                "invoice.pending",
+                "invoice.paid",
+                "transaction.claim.pending"
+            ]
+        );
+        assert!(missed_statuses(
+            SwapType::ReverseSubmarine,
+            "transaction.mempool",
+            "transaction.confirmed"
+        )
+        .is_empty());
+        assert!(missed_statuses(SwapType::Chain, "transaction.claimed", "swap.created").is_empty());
+        assert!(missed_statuses(SwapType::Chain, "swap.created", "swap.expired").is_empty());
+    }
+
+    #[test]
+    fn test_poll_intervals() {
+        let policy = PollPolicy::default();
+        assert_eq!(
+            policy.base_interval(SwapPhase::ActionWindow, Duration::from_secs(3600)),
+            Some(policy.fast)
+        );
+        assert_eq!(
+            policy.base_interval(SwapPhase::AwaitingLockup, Duration::ZERO),
+            Some(policy.normal)
+        );
+        assert_eq!(
+            policy.base_interval(SwapPhase::AwaitingLockup, Duration::from_secs(3600)),
+            Some(policy.slow)
+        );
+        assert_eq!(policy.next_interval(SwapPhase::Final, Duration::ZERO), None);
+
+        for _ in 0..100 {
+            let interval = policy
+                .next_interval(SwapPhase::AwaitingLockup, Duration::ZERO)
+                .unwrap();
+            assert!(interval >= policy.normal.mul_f64(0.8));
+            assert!(interval <= policy.normal.mul_f64(1.2));
+        }
+    }
+}
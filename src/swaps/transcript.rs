@@ -0,0 +1,128 @@
+//! Recorded Boltz status traffic.
+//!
+//! A [`SwapWatcher`] can record everything it receives, websocket messages and REST status
+//! reconciliations, into a [`Transcript`]. Transcripts are stored as json lines with
+//! transaction hex, invoices and preimages blanked out, and can be replayed into a fresh watcher
+//! with [`SwapWatcher::replay`]. That turns a flaky run against testnet into a deterministic
+//! regression test of the status handling.
+//!
+//! [`SwapWatcher`]: super::watcher::SwapWatcher
+//! [`SwapWatcher::replay`]: super::watcher::SwapWatcher::replay
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Fields blanked out of recorded messages.
+const SANITIZED_FIELDS: &[&str] = &["hex", "invoice", "preimage"];
+
+/// A single recorded event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TranscriptEntry {
+    /// A text message received over websocket.
+    Ws { text: String },
+    /// A `get_swap` response, fetched while reconciling.
+    Rest {
+        swap_id: String,
+        response: serde_json::Value,
+    },
+    /// The websocket connection was re-established.
+    Reconnect,
+}
+
+/// Ordered list of recorded events.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    pub fn push_ws(&mut self, text: &str) {
+        let text = match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(mut value) => {
+                sanitize(&mut value);
+                value.to_string()
+            }
+            Err(_) => text.to_string(),
+        };
+        self.entries.push(TranscriptEntry::Ws { text });
+    }
+
+    pub fn push_rest<T: Serialize>(&mut self, swap_id: &str, response: &T) -> Result<(), Error> {
+        let mut response = serde_json::to_value(response)?;
+        sanitize(&mut response);
+        self.entries.push(TranscriptEntry::Rest {
+            swap_id: swap_id.to_string(),
+            response,
+        });
+        Ok(())
+    }
+
+    pub fn push_reconnect(&mut self) {
+        self.entries.push(TranscriptEntry::Reconnect);
+    }
+
+    /// Serialize as json lines, one entry per line.
+    pub fn to_jsonl(&self) -> Result<String, Error> {
+        let mut out = String::new();
+        for entry in &self.entries {
+            out.push_str(&serde_json::to_string(entry)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Parse json lines. Empty lines are skipped.
+    pub fn from_jsonl(jsonl: &str) -> Result<Self, Error> {
+        let entries = jsonl
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Transcript { entries })
+    }
+}
+
+/// Blank out sensitive and bulky fields, keeping the message structure intact.
+fn sanitize(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SANITIZED_FIELDS.contains(&key.as_str()) && value.is_string() {
+                    *value = serde_json::Value::String(String::new());
+                } else {
+                    sanitize(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(sanitize),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_and_roundtrip() {
+        let mut transcript = Transcript::default();
+        transcript.push_ws(
+            r#"{"event":"update","channel":"swap.update","args":[{"id":"abc","status":"transaction.mempool","transaction":{"id":"txid","hex":"0200"}}]}"#,
+        );
+        transcript
+            .push_rest(
+                "abc",
+                &serde_json::json!({ "status": "invoice.settled", "preimage": "secret" }),
+            )
+            .unwrap();
+        transcript.push_reconnect();
+
+        let jsonl = transcript.to_jsonl().unwrap();
+        assert!(!jsonl.contains("0200"));
+        assert!(!jsonl.contains("secret"));
+        assert!(jsonl.contains("txid"));
+        assert_eq!(Transcript::from_jsonl(&jsonl).unwrap(), transcript);
+    }
+}
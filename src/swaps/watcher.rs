@@ -0,0 +1,515 @@
+//! Websocket swap status watcher.
+//!
+//! Wraps the raw Boltz websocket returned by [`BoltzApiClientV2::connect_ws`] and keeps it healthy:
+//! the connection is pinged regularly, and if nothing was received for the stale window, a
+//! [`WatcherEvent::Stale`] event is emitted, the socket is reconnected, all swaps are re-subscribed
+//! and their status is reconciled over REST. So no status transition gets lost on a silent stall.
+//!
+//! Status transitions of the happy path skipped while disconnected are emitted as
+//! [`WatcherEvent::Synthesized`], in order, before the current status.
+//!
+//! The received traffic can be recorded into a [`Transcript`] and replayed later, see
+//! [`super::transcript`].
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::error::Error;
+use crate::util::backoff::{Backoff, CancellationToken};
+
+use super::boltz::{
+    BoltzApiClientV2, GetSwapResponse, RespError, Subscription, SubscriptionOp, SwapType,
+    SwapUpdate, SwapUpdateTxDetails, Update,
+};
+use super::status::missed_statuses;
+use super::transcript::{Transcript, TranscriptEntry};
+
+/// Default time without any message from the server, after which the connection is considered stale.
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Events emitted by the [`SwapWatcher`].
+#[derive(Debug, Clone)]
+pub enum WatcherEvent {
+    /// Subscription acknowledged by the server for the given swap ids.
+    Subscribed(Vec<String>),
+    /// Unsubscription acknowledged by the server, with the swap ids it reported.
+    Unsubscribed(Vec<String>),
+    /// A swap status changed. Duplicate statuses are filtered out.
+    Update(Update),
+    /// A status of the happy path that was skipped, e.g. while disconnected.
+    /// Carries no transaction details, those have to be fetched by the caller if needed.
+    Synthesized(Update),
+    /// The server reported an error for a swap.
+    Error(RespError),
+    /// Nothing was received within the stale window. The watcher reconnects right after.
+    Stale,
+    /// The connection was re-established, swaps re-subscribed and status reconciled.
+    Reconnected,
+}
+
+/// Blocking websocket watcher for a set of swaps.
+pub struct SwapWatcher {
+    boltz_api: BoltzApiClientV2,
+    socket: Option<WebSocket<MaybeTlsStream<TcpStream>>>,
+    swap_ids: Vec<String>,
+    swap_types: HashMap<String, SwapType>,
+    last_status: HashMap<String, String>,
+    /// Swaps whose subscription was acknowledged on the current connection.
+    acknowledged: HashSet<String>,
+    stale_after: Duration,
+    last_activity: Instant,
+    last_ping: Instant,
+    events: VecDeque<WatcherEvent>,
+    transcript: Option<Transcript>,
+    backoff: Backoff,
+    cancel: Option<CancellationToken>,
+}
+
+impl SwapWatcher {
+    /// Create a new watcher. The connection is only opened on the first call to `next_event`.
+    pub fn new(boltz_api: BoltzApiClientV2, stale_after: Duration) -> Self {
+        SwapWatcher {
+            boltz_api,
+            socket: None,
+            swap_ids: vec![],
+            swap_types: HashMap::new(),
+            last_status: HashMap::new(),
+            acknowledged: HashSet::new(),
+            stale_after,
+            last_activity: Instant::now(),
+            last_ping: Instant::now(),
+            events: VecDeque::new(),
+            transcript: None,
+            backoff: Backoff::default(),
+            cancel: None,
+        }
+    }
+
+    /// Backoff between connection attempts, and a token to stop retrying.
+    /// Once the backoff gives up or is cancelled, `next_event` returns the connection error.
+    pub fn with_backoff(mut self, backoff: Backoff, cancel: Option<CancellationToken>) -> Self {
+        self.backoff = backoff;
+        self.cancel = cancel;
+        self
+    }
+
+    /// Create a new watcher, considering the connection stale after the `ws_idle` timeout of
+    /// the client.
+    pub fn with_client_timeouts(boltz_api: BoltzApiClientV2) -> Self {
+        let stale_after = boltz_api.timeouts().ws_idle;
+        Self::new(boltz_api, stale_after)
+    }
+
+    /// Watch a swap. Swaps stay subscribed across reconnects.
+    ///
+    /// `last_status` is the last status known to the caller (e.g. from storage), so transitions
+    /// missed since then are synthesized.
+    pub fn subscribe(
+        &mut self,
+        swap_id: &str,
+        swap_type: SwapType,
+        last_status: Option<&str>,
+    ) -> Result<(), Error> {
+        if !self.swap_ids.iter().any(|id| id == swap_id) {
+            self.swap_ids.push(swap_id.to_string());
+        }
+        self.swap_types.insert(swap_id.to_string(), swap_type);
+        if let Some(status) = last_status {
+            self.last_status
+                .insert(swap_id.to_string(), status.to_string());
+        }
+        if let Some(socket) = self.socket.as_mut() {
+            send_subscription(socket, Subscription::new(swap_id))?;
+        }
+        Ok(())
+    }
+
+    /// Stop watching a swap, e.g. once it reached a final status. The connection is kept open.
+    pub fn unsubscribe(&mut self, swap_id: &str) -> Result<(), Error> {
+        self.swap_ids.retain(|id| id != swap_id);
+        self.swap_types.remove(swap_id);
+        self.last_status.remove(swap_id);
+        self.acknowledged.remove(swap_id);
+        if let Some(socket) = self.socket.as_mut() {
+            send_subscription(socket, Subscription::unsubscribe(swap_id))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the server acknowledged the subscription of a swap on the current connection.
+    pub fn is_acknowledged(&self, swap_id: &str) -> bool {
+        self.acknowledged.contains(swap_id)
+    }
+
+    /// Ids of all watched swaps.
+    pub fn swap_ids(&self) -> &[String] {
+        &self.swap_ids
+    }
+
+    /// Last status seen for a swap, either over websocket or REST.
+    pub fn last_status(&self, swap_id: &str) -> Option<&str> {
+        self.last_status.get(swap_id).map(|s| s.as_str())
+    }
+
+    /// Start recording all received traffic into a transcript.
+    pub fn record_transcript(&mut self) {
+        self.transcript = Some(Transcript::default());
+    }
+
+    /// Stop recording and return the transcript recorded so far.
+    pub fn take_transcript(&mut self) -> Option<Transcript> {
+        self.transcript.take()
+    }
+
+    /// Feed a recorded transcript through the status handling, without any connection.
+    /// Returns all events the watcher would have emitted, in order.
+    pub fn replay(&mut self, transcript: &Transcript) -> Result<Vec<WatcherEvent>, Error> {
+        for entry in &transcript.entries {
+            match entry {
+                TranscriptEntry::Ws { text } => self.handle_text(text),
+                TranscriptEntry::Rest { swap_id, response } => {
+                    let swap: GetSwapResponse = serde_json::from_value(response.clone())?;
+                    self.apply_swap_response(swap_id, swap);
+                }
+                TranscriptEntry::Reconnect => self.events.push_back(WatcherEvent::Reconnected),
+            }
+        }
+        Ok(self.events.drain(..).collect())
+    }
+
+    /// Block until the next event.
+    pub fn next_event(&mut self) -> Result<WatcherEvent, Error> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+
+            let read = match self.socket.as_mut() {
+                Some(socket) => socket.read(),
+                None => {
+                    self.connect_with_backoff()?;
+                    continue;
+                }
+            };
+
+            match read {
+                Ok(message) => {
+                    self.last_activity = Instant::now();
+                    if let Message::Text(text) = message {
+                        if let Some(transcript) = self.transcript.as_mut() {
+                            transcript.push_ws(&text);
+                        }
+                        self.handle_text(&text);
+                    }
+                }
+                Err(tungstenite::Error::Io(e))
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut =>
+                {
+                    self.heartbeat()?;
+                }
+                Err(e) => {
+                    log::warn!("Websocket error, reconnecting: {}", e);
+                    self.reconnect()?;
+                }
+            }
+        }
+    }
+
+    /// Fetch the current status of all watched swaps over REST, and emit an update for each
+    /// swap whose status differs from the last one seen.
+    pub fn reconcile(&mut self) -> Result<(), Error> {
+        for swap_id in self.swap_ids.clone() {
+            let swap = self.boltz_api.get_swap(&swap_id)?;
+            if let Some(transcript) = self.transcript.as_mut() {
+                transcript.push_rest(&swap_id, &swap)?;
+            }
+            self.apply_swap_response(&swap_id, swap);
+        }
+        Ok(())
+    }
+
+    fn apply_swap_response(&mut self, swap_id: &str, swap: GetSwapResponse) {
+        self.record(Update {
+            id: swap_id.to_string(),
+            status: swap.status,
+            transaction: swap.transaction.map(|tx| SwapUpdateTxDetails {
+                id: tx.id,
+                hex: tx.hex,
+            }),
+            zero_conf_rejected: swap.zero_conf_rejected,
+        });
+    }
+
+    fn connect(&mut self) -> Result<(), Error> {
+        let mut socket = self.boltz_api.connect_ws()?;
+        set_read_timeout(&socket, Some(self.tick()))?;
+        self.acknowledged.clear();
+        if !self.swap_ids.is_empty() {
+            let ids: Vec<&str> = self.swap_ids.iter().map(|id| id.as_str()).collect();
+            send_subscription(
+                &mut socket,
+                Subscription::swap_updates(SubscriptionOp::Subscribe, &ids),
+            )?;
+        }
+        self.socket = Some(socket);
+        self.last_activity = Instant::now();
+        self.last_ping = Instant::now();
+        Ok(())
+    }
+
+    fn connect_with_backoff(&mut self) -> Result<(), Error> {
+        let backoff = self.backoff.clone();
+        let cancel = self.cancel.clone();
+        backoff.retry(
+            cancel.as_ref(),
+            || self.connect(),
+            |e| !matches!(e, Error::Url(_)),
+        )
+    }
+
+    fn reconnect(&mut self) -> Result<(), Error> {
+        if let Some(mut socket) = self.socket.take() {
+            let _ = socket.close(None);
+        }
+        self.connect_with_backoff()?;
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.push_reconnect();
+        }
+        self.events.push_back(WatcherEvent::Reconnected);
+        self.reconcile()
+    }
+
+    /// Called whenever a read times out without data.
+    fn heartbeat(&mut self) -> Result<(), Error> {
+        if self.last_activity.elapsed() >= self.stale_after {
+            log::warn!(
+                "No websocket message for {:?}, reconnecting",
+                self.last_activity.elapsed()
+            );
+            self.events.push_back(WatcherEvent::Stale);
+            return self.reconnect();
+        }
+
+        if self.last_ping.elapsed() >= self.stale_after / 2 {
+            self.last_ping = Instant::now();
+            if let Some(socket) = self.socket.as_mut() {
+                if let Err(e) = socket.send(Message::Ping(vec![])) {
+                    log::warn!("Websocket ping failed, reconnecting: {}", e);
+                    return self.reconnect();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read timeout of the socket, so staleness is checked regularly.
+    fn tick(&self) -> Duration {
+        (self.stale_after / 4).max(Duration::from_millis(100))
+    }
+
+    fn handle_text(&mut self, text: &str) {
+        match serde_json::from_str::<SwapUpdate>(text) {
+            Ok(SwapUpdate::Subscription { event, args, .. }) if event == "unsubscribe" => {
+                self.events.push_back(WatcherEvent::Unsubscribed(args))
+            }
+            Ok(SwapUpdate::Subscription { args, .. }) => {
+                self.acknowledged.extend(args.iter().cloned());
+                self.events.push_back(WatcherEvent::Subscribed(args))
+            }
+            Ok(SwapUpdate::Update { args, .. }) => {
+                for update in args {
+                    self.record(update);
+                }
+            }
+            Ok(SwapUpdate::Error { args, .. }) => {
+                for error in args {
+                    self.events.push_back(WatcherEvent::Error(error));
+                }
+            }
+            Err(_) => log::debug!("Ignoring websocket message: {}", text),
+        }
+    }
+
+    fn record(&mut self, update: Update) {
+        if self.last_status.get(&update.id) == Some(&update.status) {
+            return;
+        }
+        if let (Some(previous), Some(swap_type)) = (
+            self.last_status.get(&update.id),
+            self.swap_types.get(&update.id),
+        ) {
+            for status in missed_statuses(*swap_type, previous, &update.status) {
+                log::info!(
+                    "Synthesizing missed status {} for swap {}",
+                    status,
+                    update.id
+                );
+                self.events.push_back(WatcherEvent::Synthesized(Update {
+                    id: update.id.clone(),
+                    status: status.to_string(),
+                    transaction: None,
+                    zero_conf_rejected: None,
+                }));
+            }
+        }
+        self.last_status
+            .insert(update.id.clone(), update.status.clone());
+        self.events.push_back(WatcherEvent::Update(update));
+    }
+}
+
+fn send_subscription(
+    socket: &mut WebSocket<MaybeTlsStream<TcpStream>>,
+    subscription: Subscription,
+) -> Result<(), Error> {
+    let subscription = serde_json::to_string(&subscription)?;
+    socket.send(Message::Text(subscription))?;
+    Ok(())
+}
+
+fn set_read_timeout(
+    socket: &WebSocket<MaybeTlsStream<TcpStream>>,
+    timeout: Option<Duration>,
+) -> Result<(), Error> {
+    match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(timeout)?,
+        MaybeTlsStream::NativeTls(stream) => stream.get_ref().set_read_timeout(timeout)?,
+        _ => log::warn!("Unknown websocket stream, staleness detection disabled"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swaps::boltz::BOLTZ_TESTNET_URL_V2;
+
+    #[test]
+    fn test_duplicate_updates_are_filtered() {
+        let mut watcher = SwapWatcher::new(
+            BoltzApiClientV2::new(BOLTZ_TESTNET_URL_V2),
+            DEFAULT_STALE_AFTER,
+        );
+        let update = r#"{"event":"update","channel":"swap.update","args":[{"id":"abc","status":"swap.created"}]}"#;
+        watcher.subscribe("abc", SwapType::Submarine, None).unwrap();
+        watcher.handle_text(update);
+        watcher.handle_text(update);
+        watcher.handle_text(
+            r#"{"event":"update","channel":"swap.update","args":[{"id":"abc","status":"transaction.mempool"}]}"#,
+        );
+
+        let statuses: Vec<_> = watcher
+            .events
+            .iter()
+            .map(|e| match e {
+                WatcherEvent::Update(u) => u.status.clone(),
+                _ => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(statuses, vec!["swap.created", "transaction.mempool"]);
+        assert_eq!(watcher.last_status("abc"), Some("transaction.mempool"));
+    }
+
+    #[test]
+    fn test_replay_transcript() {
+        let transcript = Transcript::from_jsonl(include_str!(
+            "../../tests/transcripts/submarine_reconnect.jsonl"
+        ))
+        .unwrap();
+        let mut watcher = SwapWatcher::new(
+            BoltzApiClientV2::new(BOLTZ_TESTNET_URL_V2),
+            DEFAULT_STALE_AFTER,
+        );
+        watcher
+            .subscribe("Fc8yzFe4WH6Q", SwapType::Submarine, None)
+            .unwrap();
+
+        let events: Vec<_> = watcher
+            .replay(&transcript)
+            .unwrap()
+            .iter()
+            .map(|e| match e {
+                WatcherEvent::Subscribed(ids) => format!("subscribed {}", ids.join(",")),
+                WatcherEvent::Update(u) => format!("update {}", u.status),
+                WatcherEvent::Synthesized(u) => format!("synthesized {}", u.status),
+                WatcherEvent::Reconnected => "reconnected".to_string(),
+                _ => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "subscribed Fc8yzFe4WH6Q",
+                "update invoice.set",
+                "update transaction.mempool",
+                "reconnected",
+                "synthesized transaction.confirmed",
+                "synthesized invoice.pending",
+                "update invoice.paid",
+                "update transaction.claim.pending",
+                "update transaction.claimed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subscription_acknowledgements() {
+        let mut watcher = SwapWatcher::new(
+            BoltzApiClientV2::new(BOLTZ_TESTNET_URL_V2),
+            DEFAULT_STALE_AFTER,
+        );
+        watcher.subscribe("abc", SwapType::Submarine, None).unwrap();
+        watcher.subscribe("def", SwapType::Chain, None).unwrap();
+        assert!(!watcher.is_acknowledged("abc"));
+
+        watcher
+            .handle_text(r#"{"event":"subscribe","channel":"swap.update","args":["abc","def"]}"#);
+        assert!(watcher.is_acknowledged("abc"));
+
+        watcher.unsubscribe("abc").unwrap();
+        assert!(!watcher.is_acknowledged("abc"));
+        assert_eq!(watcher.swap_ids(), &["def".to_string()]);
+
+        watcher.handle_text(r#"{"event":"unsubscribe","channel":"swap.update","args":["def"]}"#);
+        assert!(matches!(
+            watcher.events.back(),
+            Some(WatcherEvent::Unsubscribed(ids)) if ids == &["def".to_string()]
+        ));
+    }
+
+    #[test]
+    fn test_missed_transitions_are_synthesized() {
+        let mut watcher = SwapWatcher::new(
+            BoltzApiClientV2::new(BOLTZ_TESTNET_URL_V2),
+            DEFAULT_STALE_AFTER,
+        );
+        watcher
+            .subscribe("abc", SwapType::Submarine, Some("invoice.pending"))
+            .unwrap();
+        watcher.handle_text(
+            r#"{"event":"update","channel":"swap.update","args":[{"id":"abc","status":"transaction.claimed"}]}"#,
+        );
+
+        let events: Vec<_> = watcher
+            .events
+            .iter()
+            .map(|e| match e {
+                WatcherEvent::Synthesized(u) => format!("synthesized {}", u.status),
+                WatcherEvent::Update(u) => format!("update {}", u.status),
+                _ => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "synthesized invoice.paid",
+                "synthesized transaction.claim.pending",
+                "update transaction.claimed"
+            ]
+        );
+    }
+}
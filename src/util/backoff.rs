@@ -0,0 +1,280 @@
+//! Exponential backoff for retried network operations.
+//!
+//! [`Backoff::retry`] runs an operation until it succeeds, fails with an error that is not worth
+//! retrying, runs out of time, or is cancelled through a [`CancellationToken`]. The Boltz REST
+//! client and the websocket reconnects of the swap watcher use it, so all retries behave the same.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bitcoin::key::rand::Rng;
+
+use super::entropy::SecureRng;
+
+/// Largest jitter fraction applied, so a jittered interval never drops to zero.
+const MAX_JITTER: f64 = 0.9;
+
+/// Longest uninterrupted sleep between cancellation checks.
+const CANCELLATION_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// `interval` scaled by a random factor in `1 +/- jitter`. A NaN or non-positive `jitter`
+/// leaves it unchanged, and `jitter` is capped at [`MAX_JITTER`].
+pub(crate) fn with_jitter(interval: Duration, jitter: f64) -> Duration {
+    if jitter.is_nan() || jitter <= 0.0 {
+        return interval;
+    }
+    let jitter = jitter.min(MAX_JITTER);
+    let factor = SecureRng.gen_range((1.0 - jitter)..=(1.0 + jitter));
+    interval.mul_f64(factor)
+}
+
+/// Cancels retry loops from another thread. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Exponential backoff policy with jitter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    /// Interval before the first retry.
+    pub initial: Duration,
+    /// Upper bound of the interval, before jitter.
+    pub max_interval: Duration,
+    /// Factor applied to the interval after every retry.
+    pub multiplier: f64,
+    /// Jitter as a fraction of the interval, e.g. 0.2 for +/-20%. Capped at 0.9, NaN disables it.
+    pub jitter: f64,
+    /// Time after the first attempt from which no more retries are started.
+    /// None retries until success or cancellation.
+    pub max_elapsed: Option<Duration>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_millis(500),
+            max_interval: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_elapsed: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl Backoff {
+    /// Policy making a single attempt.
+    pub fn none() -> Self {
+        Backoff {
+            max_elapsed: Some(Duration::ZERO),
+            ..Backoff::default()
+        }
+    }
+
+    /// Interval before retry number `retry` (starting at 0), without jitter.
+    pub fn base_interval(&self, retry: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(retry.min(i32::MAX as u32) as i32);
+        let secs = self.initial.as_secs_f64() * factor;
+        // Also catches an infinite factor, which Duration can't represent
+        if secs >= self.max_interval.as_secs_f64() {
+            self.max_interval
+        } else {
+            Duration::from_secs_f64(secs)
+        }
+    }
+
+    /// Interval before retry number `retry`, with jitter applied.
+    pub fn next_interval(&self, retry: u32) -> Duration {
+        with_jitter(self.base_interval(retry), self.jitter)
+    }
+
+    /// Run `op` until it succeeds. Errors rejected by `is_retryable` are returned right away.
+    /// Once `max_elapsed` is reached or `cancel` is cancelled, the last error is returned.
+    pub fn retry<T, E, F, R>(
+        &self,
+        cancel: Option<&CancellationToken>,
+        mut op: F,
+        is_retryable: R,
+    ) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+        R: Fn(&E) -> bool,
+        E: std::fmt::Debug,
+    {
+        let start = Instant::now();
+        let mut retry = 0;
+        loop {
+            let error = match op() {
+                Ok(value) => return Ok(value),
+                Err(e) if !is_retryable(&e) => return Err(e),
+                Err(e) => e,
+            };
+
+            let interval = self.next_interval(retry);
+            if let Some(max_elapsed) = self.max_elapsed {
+                if start.elapsed() + interval > max_elapsed {
+                    return Err(error);
+                }
+            }
+            log::debug!("Retrying in {:?} after error: {:?}", interval, error);
+            if !sleep_unless_cancelled(interval, cancel) {
+                log::debug!("Retry cancelled");
+                return Err(error);
+            }
+            retry += 1;
+        }
+    }
+}
+
+/// Sleep for `duration`, waking up early if `cancel` is cancelled.
+/// Returns false if cancelled.
+pub(crate) fn sleep_unless_cancelled(
+    duration: Duration,
+    cancel: Option<&CancellationToken>,
+) -> bool {
+    let cancel = match cancel {
+        Some(cancel) => cancel,
+        None => {
+            thread::sleep(duration);
+            return true;
+        }
+    };
+    let deadline = Instant::now() + duration;
+    loop {
+        if cancel.is_cancelled() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep((deadline - now).min(CANCELLATION_CHECK_INTERVAL));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast() -> Backoff {
+        Backoff {
+            initial: Duration::from_millis(1),
+            max_interval: Duration::from_millis(4),
+            jitter: 0.0,
+            ..Backoff::default()
+        }
+    }
+
+    #[test]
+    fn test_intervals() {
+        let backoff = Backoff {
+            jitter: 0.0,
+            ..Backoff::default()
+        };
+        assert_eq!(backoff.base_interval(0), Duration::from_millis(500));
+        assert_eq!(backoff.base_interval(2), Duration::from_secs(2));
+        assert_eq!(backoff.base_interval(20), Duration::from_secs(30));
+        assert_eq!(backoff.base_interval(u32::MAX), Duration::from_secs(30));
+
+        let backoff = Backoff::default();
+        for _ in 0..100 {
+            let interval = backoff.next_interval(1);
+            assert!(interval >= Duration::from_millis(800));
+            assert!(interval <= Duration::from_millis(1200));
+        }
+    }
+
+    #[test]
+    fn test_with_jitter() {
+        let interval = Duration::from_secs(10);
+        assert_eq!(with_jitter(interval, f64::NAN), interval);
+        assert_eq!(with_jitter(interval, -1.0), interval);
+        assert_eq!(with_jitter(interval, 0.0), interval);
+        for jitter in [1.0, f64::INFINITY] {
+            for _ in 0..100 {
+                let jittered = with_jitter(interval, jitter);
+                assert!(jittered >= Duration::from_secs(1));
+                assert!(jittered <= Duration::from_secs(19));
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry() {
+        let mut attempts = 0;
+        let result: Result<u32, &str> = fast().retry(
+            None,
+            || {
+                attempts += 1;
+                if attempts < 3 {
+                    Err("transient")
+                } else {
+                    Ok(attempts)
+                }
+            },
+            |_| true,
+        );
+        assert_eq!(result, Ok(3));
+
+        let mut attempts = 0;
+        let result: Result<(), &str> = fast().retry(
+            None,
+            || {
+                attempts += 1;
+                Err("fatal")
+            },
+            |e| *e != "fatal",
+        );
+        assert_eq!(result, Err("fatal"));
+        assert_eq!(attempts, 1);
+
+        let mut attempts = 0;
+        let result: Result<(), &str> = Backoff::none().retry(
+            None,
+            || {
+                attempts += 1;
+                Err("transient")
+            },
+            |_| true,
+        );
+        assert_eq!(result, Err("transient"));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_retry_cancelled() {
+        let cancel = CancellationToken::new();
+        let backoff = Backoff {
+            initial: Duration::from_secs(60),
+            max_elapsed: None,
+            ..Backoff::default()
+        };
+        let canceller = cancel.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        let start = Instant::now();
+        let result: Result<(), &str> = backoff.retry(Some(&cancel), || Err("down"), |_| true);
+        handle.join().unwrap();
+        assert_eq!(result, Err("down"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
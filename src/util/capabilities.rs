@@ -0,0 +1,67 @@
+//! Runtime report of the features this library was compiled with.
+//!
+//! Apps built for several platforms can check [`capabilities`] instead of mirroring the
+//! crate's cargo features with their own `cfg` attributes.
+
+use crate::network::Chain;
+
+/// Chain backends supported by the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Electrum,
+    /// Boltz's own REST API, used for broadcasting and as fallback for lockup lookups.
+    BoltzApi,
+}
+
+/// Features compiled into this build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// LNURL and lightning address support (`lnurl` feature).
+    pub lnurl: bool,
+    /// Prometheus metrics exporter and lightning address server (`service` feature).
+    pub service: bool,
+    /// Consensus verification of signed transactions with libbitcoinconsensus (`consensus-verify` feature).
+    pub consensus_verify: bool,
+    /// Chains for which swaps can be built, signed and broadcast.
+    pub chains: Vec<Chain>,
+    /// Backends available for every chain in `chains`.
+    pub backends: Vec<Backend>,
+}
+
+impl Capabilities {
+    pub fn supports_chain(&self, chain: Chain) -> bool {
+        self.chains.contains(&chain)
+    }
+}
+
+/// Report the features this build of the library was compiled with.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        lnurl: cfg!(feature = "lnurl"),
+        service: cfg!(feature = "service"),
+        consensus_verify: cfg!(feature = "consensus-verify"),
+        chains: vec![
+            Chain::Bitcoin,
+            Chain::BitcoinTestnet,
+            Chain::BitcoinRegtest,
+            Chain::Liquid,
+            Chain::LiquidTestnet,
+            Chain::LiquidRegtest,
+        ],
+        backends: vec![Backend::Electrum, Backend::BoltzApi],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let caps = capabilities();
+        assert_eq!(caps.lnurl, cfg!(feature = "lnurl"));
+        assert_eq!(caps.service, cfg!(feature = "service"));
+        assert!(caps.supports_chain(Chain::Liquid));
+        assert!(caps.backends.contains(&Backend::Electrum));
+    }
+}
@@ -0,0 +1,48 @@
+//! Script verification of produced transactions with libbitcoinconsensus.
+//!
+//! Only available with the `consensus-verify` feature. There is no Elements equivalent, Liquid
+//! transactions are only checked by [`crate::LBtcSwapScript::verify_spend`].
+
+use bitcoin::consensus::serialize;
+use bitcoin::{Transaction, TxOut};
+
+use crate::error::Error;
+
+/// Run every input of `tx` through libbitcoinconsensus, with all flags including taproot.
+/// `prevouts` are the outputs spent by the inputs of `tx`, in order.
+pub fn verify_bitcoin_tx(tx: &Transaction, prevouts: &[TxOut]) -> Result<(), Error> {
+    if prevouts.len() != tx.input.len() {
+        return Err(Error::Protocol(format!(
+            "Expected {} prevouts, got {}",
+            tx.input.len(),
+            prevouts.len()
+        )));
+    }
+    let tx_bytes = serialize(tx);
+    let utxos: Vec<bitcoinconsensus::Utxo> = prevouts
+        .iter()
+        .map(|prevout| bitcoinconsensus::Utxo {
+            script_pubkey: prevout.script_pubkey.as_bytes().as_ptr(),
+            script_pubkey_len: prevout.script_pubkey.len() as u32,
+            value: prevout.value.to_sat() as i64,
+        })
+        .collect();
+
+    for (input_index, prevout) in prevouts.iter().enumerate() {
+        bitcoinconsensus::verify_with_flags(
+            prevout.script_pubkey.as_bytes(),
+            prevout.value.to_sat(),
+            &tx_bytes,
+            Some(&utxos),
+            input_index,
+            bitcoinconsensus::VERIFY_ALL,
+        )
+        .map_err(|e| {
+            Error::Protocol(format!(
+                "Consensus verification of input {} failed: {:?}",
+                input_index, e
+            ))
+        })?;
+    }
+    Ok(())
+}
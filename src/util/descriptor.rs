@@ -0,0 +1,139 @@
+use serde::Serialize;
+
+use crate::error::Error;
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn poly_mod(mut c: u64, val: u64) -> u64 {
+    let c0 = c >> 35;
+    c = ((c & 0x7ffffffff) << 5) ^ val;
+    if c0 & 1 != 0 {
+        c ^= 0xf5dee51989;
+    }
+    if c0 & 2 != 0 {
+        c ^= 0xa9fdca3312;
+    }
+    if c0 & 4 != 0 {
+        c ^= 0x1bab10e32d;
+    }
+    if c0 & 8 != 0 {
+        c ^= 0x3706b1677a;
+    }
+    if c0 & 16 != 0 {
+        c ^= 0x644d626ffd;
+    }
+    c
+}
+
+/// Compute the BIP-380 checksum of an output descriptor (without the `#`).
+pub fn descriptor_checksum(desc: &str) -> Result<String, Error> {
+    let mut c = 1u64;
+    let mut cls = 0u64;
+    let mut clscount = 0;
+
+    for ch in desc.chars() {
+        let pos = INPUT_CHARSET
+            .find(ch)
+            .ok_or_else(|| Error::Generic(format!("Invalid character in descriptor: {}", ch)))?
+            as u64;
+        c = poly_mod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        clscount += 1;
+        if clscount == 3 {
+            c = poly_mod(c, cls);
+            cls = 0;
+            clscount = 0;
+        }
+    }
+    if clscount > 0 {
+        c = poly_mod(c, cls);
+    }
+    for _ in 0..8 {
+        c = poly_mod(c, 0);
+    }
+    c ^= 1;
+
+    Ok((0..8)
+        .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+        .collect())
+}
+
+/// Append the BIP-380 checksum to a descriptor.
+pub fn with_checksum(desc: &str) -> Result<String, Error> {
+    Ok(format!("{}#{}", desc, descriptor_checksum(desc)?))
+}
+
+/// Build an `addr(<address>)#<checksum>` descriptor, for watch-only imports.
+pub fn addr_descriptor(address: &str) -> Result<String, Error> {
+    with_checksum(&format!("addr({})", address))
+}
+
+/// A single entry of an `importdescriptors`/`importmulti` RPC request.
+///
+/// Serialize a list of these to get the `requests` argument of the RPC.
+/// Bitcoin Core (and EPS, which reads from a Core watch-only wallet) will then track the swap scripts.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportDescriptor {
+    pub desc: String,
+    /// Either a unix timestamp or "now". Should be before the swap creation to catch the lockup.
+    pub timestamp: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Required by `importmulti`, ignored by `importdescriptors`.
+    pub watchonly: bool,
+}
+
+impl ImportDescriptor {
+    pub fn new(desc: String, timestamp: Option<u64>, label: Option<String>) -> Self {
+        let timestamp = match timestamp {
+            Some(t) => serde_json::Value::from(t),
+            None => serde_json::Value::from("now"),
+        };
+        ImportDescriptor {
+            desc,
+            timestamp,
+            label,
+            watchonly: true,
+        }
+    }
+}
+
+/// Build the json argument for `importdescriptors`/`importmulti` from a list of descriptors.
+pub fn import_request(descriptors: &[ImportDescriptor]) -> Result<String, Error> {
+    Ok(serde_json::to_string(descriptors)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descriptor_checksum() {
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(
+            addr_descriptor("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr")
+                .unwrap(),
+            "addr(bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr)#v9jfm3ek"
+        );
+        assert!(descriptor_checksum("raw(dead\u{e9}beef)").is_err());
+    }
+
+    #[test]
+    fn test_import_request() {
+        let desc =
+            addr_descriptor("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr")
+                .unwrap();
+        let request = import_request(&[ImportDescriptor::new(
+            desc,
+            Some(1700000000),
+            Some("swap".to_string()),
+        )])
+        .unwrap();
+        assert_eq!(
+            request,
+            "[{\"desc\":\"addr(bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr)#v9jfm3ek\",\"timestamp\":1700000000,\"label\":\"swap\",\"watchonly\":true}]"
+        );
+    }
+}
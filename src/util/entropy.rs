@@ -0,0 +1,252 @@
+//! Randomness used by the library.
+//!
+//! All random values (preimages, keys, MuSig2 session ids and `extra_rand`, blinding factors,
+//! jitter) are drawn from [`SecureRng`]. It reads the OS rng natively and `getrandom` (backed by
+//! `crypto.getRandomValues`) on wasm. Tests can replace it on the current thread with
+//! `with_rng_override`, to get deterministic output. It is only compiled for tests and with the
+//! `test-utils` feature, as a replayed rng reuses MuSig2 nonces and leaks keys.
+//!
+//! Apps that don't want to rely on a single rng can register an [`EntropySource`] with
+//! [`set_entropy_source`], e.g. backed by a hardware rng. Its output is mixed into preimages and
+//! MuSig2 `extra_rand`, so these are unpredictable as long as either source is. Every use of the
+//! source is logged at debug level with its name and purpose, for auditing.
+
+#[cfg(any(test, feature = "test-utils"))]
+use std::cell::RefCell;
+use std::sync::{Arc, RwLock};
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::rand::{self, CryptoRng, RngCore};
+
+use crate::error::Error;
+
+#[cfg(any(test, feature = "test-utils"))]
+thread_local! {
+    static RNG_OVERRIDE: RefCell<Option<Box<dyn RngCore>>> = RefCell::new(None);
+}
+
+/// Cryptographically secure rng of the library. Reads the platform rng, or the rng set with
+/// `with_rng_override` on the current thread in tests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SecureRng;
+
+impl RngCore for SecureRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Panics if the platform rng fails, use [`RngCore::try_fill_bytes`] to handle it.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Err(e) = self.try_fill_bytes(dest) {
+            panic!("Platform rng failed: {}", e);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        #[cfg(any(test, feature = "test-utils"))]
+        {
+            let overridden = RNG_OVERRIDE.with(|rng| match rng.borrow_mut().as_mut() {
+                Some(rng) => Some(rng.try_fill_bytes(dest)),
+                None => None,
+            });
+            if let Some(result) = overridden {
+                return result;
+            }
+        }
+        platform_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for SecureRng {}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn platform_fill_bytes(dest: &mut [u8]) -> Result<(), rand::Error> {
+    rand::rngs::OsRng.try_fill_bytes(dest)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn platform_fill_bytes(dest: &mut [u8]) -> Result<(), rand::Error> {
+    getrandom::getrandom(dest).map_err(|e| rand::Error::from(e.code()))
+}
+
+/// Run `f` with [`SecureRng`] replaced by `rng` on the current thread, e.g. a seeded rng for
+/// reproducible tests. Other threads are not affected.
+#[cfg(any(test, feature = "test-utils"))]
+pub fn with_rng_override<R, T, F>(rng: R, f: F) -> T
+where
+    R: RngCore + 'static,
+    F: FnOnce() -> T,
+{
+    struct Restore(Option<Box<dyn RngCore>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            RNG_OVERRIDE.with(|rng| *rng.borrow_mut() = previous);
+        }
+    }
+
+    let previous = RNG_OVERRIDE.with(|current| current.borrow_mut().replace(Box::new(rng)));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// What the random bytes are used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropyPurpose {
+    /// Swap preimage.
+    Preimage,
+    /// `extra_rand` input of MuSig2 nonce generation.
+    MusigExtraRand,
+}
+
+/// Entropy mixed into the OS rng output.
+pub trait EntropySource: Send + Sync {
+    /// Name of the source, as shown in the audit log.
+    fn name(&self) -> &str;
+    /// Fill `dest` with random bytes. An error aborts the operation the bytes were requested for.
+    fn fill_bytes(&self, purpose: EntropyPurpose, dest: &mut [u8; 32]) -> Result<(), Error>;
+}
+
+static ENTROPY_SOURCE: RwLock<Option<Arc<dyn EntropySource>>> = RwLock::new(None);
+
+/// Register the source mixed into all secrets generated afterwards, replacing any previous one.
+pub fn set_entropy_source(source: Arc<dyn EntropySource>) {
+    if let Ok(mut current) = ENTROPY_SOURCE.write() {
+        *current = Some(source);
+    }
+}
+
+/// Remove the registered source. Secrets are drawn from the OS rng only afterwards.
+pub fn clear_entropy_source() {
+    if let Ok(mut current) = ENTROPY_SOURCE.write() {
+        *current = None;
+    }
+}
+
+/// 32 random bytes from [`SecureRng`], mixed with the registered source, if any.
+pub(crate) fn random_32b(purpose: EntropyPurpose) -> Result<[u8; 32], Error> {
+    let mut os_bytes = [0u8; 32];
+    SecureRng
+        .try_fill_bytes(&mut os_bytes)
+        .map_err(|e| Error::Generic(format!("Platform rng failed: {}", e)))?;
+
+    let source = ENTROPY_SOURCE
+        .read()
+        .map_err(|_| Error::Generic("Entropy source lock poisoned".to_string()))?
+        .clone();
+    let source = match source {
+        Some(source) => source,
+        None => return Ok(os_bytes),
+    };
+
+    let mut extra_bytes = [0u8; 32];
+    source.fill_bytes(purpose, &mut extra_bytes)?;
+    log::debug!(
+        "Mixed entropy from source {} into {:?}",
+        source.name(),
+        purpose
+    );
+    Ok(mix(&os_bytes, &extra_bytes))
+}
+
+fn mix(os_bytes: &[u8; 32], extra_bytes: &[u8; 32]) -> [u8; 32] {
+    let mut engine = sha256::Hash::engine();
+    engine.input(b"boltz-client/entropy");
+    engine.input(os_bytes);
+    engine.input(extra_bytes);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingSource(AtomicUsize);
+
+    impl EntropySource for CountingSource {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn fill_bytes(&self, _purpose: EntropyPurpose, dest: &mut [u8; 32]) -> Result<(), Error> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            dest.fill(7);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_entropy_source() {
+        let source = Arc::new(CountingSource(AtomicUsize::new(0)));
+        set_entropy_source(source.clone());
+        let a = random_32b(EntropyPurpose::Preimage).unwrap();
+        let b = random_32b(EntropyPurpose::MusigExtraRand).unwrap();
+        assert_ne!(a, b);
+        assert!(source.0.load(Ordering::SeqCst) >= 2);
+        clear_entropy_source();
+    }
+
+    /// Counts up from a seed, so every fill is different but reproducible.
+    struct StepRng(u8);
+
+    impl RngCore for StepRng {
+        fn next_u32(&mut self) -> u32 {
+            let mut bytes = [0u8; 4];
+            self.fill_bytes(&mut bytes);
+            u32::from_le_bytes(bytes)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.next_u32() as u64
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.0 = self.0.wrapping_add(1);
+            dest.fill(self.0);
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rng_override() {
+        let draw = || {
+            let mut bytes = [0u8; 32];
+            SecureRng.fill_bytes(&mut bytes);
+            bytes
+        };
+        let (a, b) = with_rng_override(StepRng(0), || (draw(), draw()));
+        assert_eq!(a, [1; 32]);
+        assert_eq!(b, [2; 32]);
+        assert_eq!(with_rng_override(StepRng(0), draw), a);
+
+        // Restored afterwards, also when nested
+        let inner = with_rng_override(StepRng(0), || {
+            with_rng_override(StepRng(9), draw);
+            draw()
+        });
+        assert_eq!(inner, [1; 32]);
+        assert_ne!(draw(), draw());
+    }
+
+    #[test]
+    fn test_mix() {
+        let a = mix(&[0; 32], &[1; 32]);
+        assert_ne!(a, mix(&[0; 32], &[2; 32]));
+        assert_ne!(a, mix(&[1; 32], &[1; 32]));
+        assert_eq!(a, mix(&[0; 32], &[1; 32]));
+    }
+}
@@ -1,10 +1,192 @@
 use crate::error::Error;
 
+/// Maximum size of a memo attached to claim and refund transactions, in bytes.
+/// Larger OP_RETURN outputs are non-standard and not relayed.
+pub const MAX_MEMO_SIZE: usize = 80;
+
+/// Serialized witness size of a cooperative (key path) swap input, in bytes.
+/// Witness bytes count as one weight unit each. Same on Bitcoin and Liquid.
+pub const KEY_PATH_WITNESS_SIZE: usize = 66;
+/// Serialized witness size of a reverse or chain swap claim through the claim leaf, in bytes.
+pub const CLAIM_LEAF_WITNESS_SIZE: usize = 227;
+/// Serialized witness size of a submarine swap claim through the claim leaf, in bytes.
+pub const SUBMARINE_CLAIM_LEAF_WITNESS_SIZE: usize = 223;
+/// Serialized witness size of a refund through the refund leaf, in bytes.
+/// Assumes a block height locktime below 2^23, which is encoded in 3 bytes.
+pub const REFUND_LEAF_WITNESS_SIZE: usize = 172;
+
+/// Virtual size of a lockup transaction without its inputs, in vbytes:
+/// tx overhead with segwit marker and a single taproot output to the swap, no change.
+pub const LOCKUP_TX_BASE_VSIZE: usize = 54;
+/// Virtual size of a P2WPKH wallet input, in vbytes.
+pub const P2WPKH_INPUT_VSIZE: usize = 68;
+/// Virtual size of a P2TR key path wallet input, in vbytes.
+pub const P2TR_INPUT_VSIZE: usize = 58;
+
+/// Amount that can be locked up when spending all `utxo_values` into a swap lockup without
+/// change, after the lockup miner fee at `fee_rate` sat/vbyte. None if the fee exceeds the funds.
+pub fn max_lockup_amount(utxo_values: &[u64], input_vsize: usize, fee_rate: f64) -> Option<u64> {
+    let vsize = LOCKUP_TX_BASE_VSIZE + utxo_values.len() * input_vsize;
+    let fee = (vsize as f64 * fee_rate).ceil() as u64;
+    utxo_values
+        .iter()
+        .sum::<u64>()
+        .checked_sub(fee)
+        .filter(|amount| *amount > 0)
+}
+
+/// Fee rate a replacement has to pay on top of the fee of the replaced transaction,
+/// in sat/vbyte (BIP-125 rule 4, Bitcoin Core's default incremental relay fee).
+pub const INCREMENTAL_RELAY_FEE_RATE: f64 = 1.0;
+
+/// Minimum absolute fee of a replacement of `replacement_vsize` vbytes, for a transaction
+/// that paid `original_fee`.
+pub fn min_replacement_fee(original_fee: u64, replacement_vsize: usize) -> u64 {
+    original_fee + (replacement_vsize as f64 * INCREMENTAL_RELAY_FEE_RATE).ceil() as u64
+}
+
+/// Virtual size of a Liquid transaction with discounted confidential outputs (ELIP-200), as
+/// used for fee checks by Elements nodes with `-acceptdiscountct`.
+///
+/// Output witnesses (range and surjection proofs) are not counted, and value and nonce
+/// commitments are counted as if they were an explicit value and an empty nonce. Same as
+/// [`elements::Transaction::discount_vsize`].
+pub fn liquid_discount_vsize(tx: &elements::Transaction) -> usize {
+    tx.discount_vsize()
+}
+
+/// Minimum relay fee rate of Bitcoin Core, in sat/vbyte.
+pub const BITCOIN_MIN_RELAY_FEE_RATE: f64 = 1.0;
+/// Minimum relay fee rate of Elements, in sat/vbyte.
+pub const LIQUID_MIN_RELAY_FEE_RATE: f64 = 0.1;
+
+/// Sanity limits of signed claims and refunds, so a wrong fee argument fails instead of
+/// producing an unbroadcastable or wasteful transaction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TxLimits {
+    /// Minimum value paid to the output address, in sats. None uses the chain default:
+    /// the dust limit of the output script on Bitcoin, no limit on Liquid.
+    pub dust_limit: Option<u64>,
+    /// Minimum fee rate, in sat/vbyte. None uses the minimum relay fee rate of the chain.
+    pub min_fee_rate: Option<f64>,
+    /// Maximum share of the spent value going to fees, in percent.
+    pub max_fee_percent: f64,
+}
+
+impl Default for TxLimits {
+    fn default() -> Self {
+        TxLimits {
+            dust_limit: None,
+            min_fee_rate: None,
+            max_fee_percent: 50.0,
+        }
+    }
+}
+
+impl TxLimits {
+    /// Check a transaction of `vsize` spending `input_value` with `fee`, of which `output_value`
+    /// goes to the output address. The chain defaults apply where no limit is configured.
+    pub fn check(
+        &self,
+        input_value: u64,
+        fee: u64,
+        output_value: u64,
+        vsize: usize,
+        default_dust_limit: u64,
+        default_min_fee_rate: f64,
+    ) -> Result<(), Error> {
+        let dust_limit = self.dust_limit.unwrap_or(default_dust_limit);
+        if output_value < dust_limit {
+            return Err(Error::DustOutput(format!(
+                "Output of {} sats is below the dust limit of {} sats",
+                output_value, dust_limit
+            )));
+        }
+
+        let min_fee_rate = self.min_fee_rate.unwrap_or(default_min_fee_rate);
+        if (fee as f64) < vsize as f64 * min_fee_rate {
+            return Err(Error::FeeOutOfRange(format!(
+                "Fee of {} sats for {} vbytes is below the minimum fee rate of {} sat/vbyte",
+                fee, vsize, min_fee_rate
+            )));
+        }
+
+        if fee as f64 > input_value as f64 * self.max_fee_percent / 100.0 {
+            return Err(Error::FeeOutOfRange(format!(
+                "Fee of {} sats is more than {}% of the spent {} sats",
+                fee, self.max_fee_percent, input_value
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Maximum virtual size of a block, in vbytes.
+pub const MAX_BLOCK_VSIZE: u64 = 1_000_000;
+
+/// Number of blocks until a transaction paying `fee_rate` sat/vbyte is expected to confirm,
+/// given a mempool fee histogram of `(fee_rate, vsize)` entries as returned by
+/// `mempool.get_fee_histogram`. Only transactions paying at least as much are mined first,
+/// new arrivals are not accounted for.
+pub fn blocks_from_fee_histogram(histogram: &[(f64, u64)], fee_rate: f64) -> u32 {
+    let vsize_ahead: u64 = histogram
+        .iter()
+        .filter(|(rate, _)| *rate >= fee_rate)
+        .map(|(_, vsize)| *vsize)
+        .sum();
+    (vsize_ahead / MAX_BLOCK_VSIZE) as u32 + 1
+}
+
+/// Split `value` by `shares`, in percent of `value`. Returns the amount of every share, rounded
+/// down, and the remainder. Fails unless all shares are positive and sum to less than 100%.
+pub fn split_value(value: u64, shares: &[f64]) -> Result<(Vec<u64>, u64), Error> {
+    let total: f64 = shares.iter().sum();
+    if shares.iter().any(|share| share.is_nan() || *share <= 0.0) || total >= 100.0 {
+        return Err(Error::Protocol(format!(
+            "Invalid output shares {:?}, they have to be positive and sum to less than 100%",
+            shares
+        )));
+    }
+    let amounts: Vec<u64> = shares
+        .iter()
+        .map(|share| (value as f64 * share / 100.0).floor() as u64)
+        .collect();
+    let remainder = value - amounts.iter().sum::<u64>();
+    Ok((amounts, remainder))
+}
+
 pub enum Fee {
     // In sat/vByte
     Relative(f64),
     // In satoshis
     Absolute(u64),
+    /// Cheapest fee rate expected to confirm within `blocks_remaining` blocks, e.g. before the
+    /// swap timeout. Has to be turned into a relative fee with `ElectrumConfig::resolve_fee`
+    /// before signing.
+    Deadline {
+        blocks_remaining: u32,
+    },
+}
+
+/// Error for a [`Fee::Deadline`] used where a resolved fee is needed.
+pub(crate) fn unresolved_deadline(blocks_remaining: u32) -> Error {
+    Error::Protocol(format!(
+        "Deadline fee of {} blocks has to be resolved with ElectrumConfig::resolve_fee first",
+        blocks_remaining
+    ))
+}
+
+/// Value of the single output of a transaction draining `input_value` to one destination,
+/// after `fee`. Fails if nothing, or only dust below `dust_limit`, would be left.
+pub fn drain_output_value(input_value: u64, fee: u64, dust_limit: u64) -> Result<u64, Error> {
+    match input_value.checked_sub(fee) {
+        Some(value) if value > 0 && value >= dust_limit => Ok(value),
+        _ => Err(Error::Protocol(format!(
+            "Cannot drain {} sats with a fee of {} sats, the output would be below the dust limit of {} sats",
+            input_value, fee, dust_limit
+        ))),
+    }
 }
 
 pub(crate) fn create_tx_with_fee<T, F, S>(
@@ -23,6 +205,7 @@ where
             tx_constructor((vsize as f64 * fee).ceil() as u64)
         }
         Fee::Absolute(fee) => tx_constructor(fee),
+        Fee::Deadline { blocks_remaining } => Err(unresolved_deadline(blocks_remaining)),
     }
 }
 
@@ -34,6 +217,22 @@ mod tests {
         fee: u64,
     }
 
+    #[test]
+    fn test_liquid_discount_vsize_explicit() {
+        let asset = elements::AssetId::from_slice(&[1; 32]).unwrap();
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: elements::LockTime::ZERO,
+            input: vec![],
+            output: vec![
+                elements::TxOut::new_fee(1_000, asset),
+                elements::TxOut::new_fee(100, asset),
+            ],
+        };
+        // Nothing to discount without confidential outputs
+        assert_eq!(liquid_discount_vsize(&tx), tx.vsize());
+    }
+
     #[test]
     fn test_create_tx_with_fee_relative() {
         let fee = 0.1;
@@ -43,6 +242,88 @@ mod tests {
         assert_eq!(tx.fee, 5);
     }
 
+    #[test]
+    fn test_max_lockup_amount() {
+        assert_eq!(
+            max_lockup_amount(&[50_000, 50_000], P2WPKH_INPUT_VSIZE, 2.0),
+            Some(100_000 - 2 * (54 + 2 * 68))
+        );
+        assert_eq!(max_lockup_amount(&[100], P2TR_INPUT_VSIZE, 10.0), None);
+        assert_eq!(max_lockup_amount(&[], P2TR_INPUT_VSIZE, 1.0), None);
+    }
+
+    #[test]
+    fn test_tx_limits() {
+        let limits = TxLimits::default();
+        assert!(limits.check(10_000, 200, 9_800, 150, 330, 1.0).is_ok());
+        assert!(matches!(
+            limits.check(500, 200, 300, 150, 330, 1.0),
+            Err(Error::DustOutput(_))
+        ));
+        assert!(matches!(
+            limits.check(10_000, 100, 9_900, 150, 330, 1.0),
+            Err(Error::FeeOutOfRange(_))
+        ));
+        assert!(matches!(
+            limits.check(10_000, 6_000, 4_000, 150, 330, 1.0),
+            Err(Error::FeeOutOfRange(_))
+        ));
+
+        let custom = TxLimits {
+            dust_limit: Some(0),
+            min_fee_rate: Some(0.1),
+            max_fee_percent: 100.0,
+        };
+        assert!(custom.check(10_000, 9_000, 300, 150, 330, 1.0).is_ok());
+    }
+
+    #[test]
+    fn test_min_replacement_fee() {
+        assert_eq!(min_replacement_fee(1_000, 150), 1_150);
+        assert_eq!(min_replacement_fee(0, 0), 0);
+    }
+
+    #[test]
+    fn test_blocks_from_fee_histogram() {
+        let histogram = [(50.0, 400_000), (20.0, 800_000), (5.0, 2_000_000)];
+        assert_eq!(blocks_from_fee_histogram(&histogram, 100.0), 1);
+        assert_eq!(blocks_from_fee_histogram(&histogram, 20.0), 2);
+        assert_eq!(blocks_from_fee_histogram(&histogram, 1.0), 4);
+        assert_eq!(blocks_from_fee_histogram(&[], 1.0), 1);
+    }
+
+    #[test]
+    fn test_split_value() {
+        assert_eq!(split_value(99_001, &[90.0]).unwrap(), (vec![89_100], 9_901));
+        assert_eq!(
+            split_value(1_000, &[33.3, 33.3]).unwrap(),
+            (vec![333, 333], 334)
+        );
+        assert!(split_value(1_000, &[60.0, 40.0]).is_err());
+        assert!(split_value(1_000, &[0.0]).is_err());
+        assert!(split_value(1_000, &[f64::NAN]).is_err());
+    }
+
+    #[test]
+    fn test_drain_output_value() {
+        assert_eq!(drain_output_value(10_000, 300, 330).unwrap(), 9_700);
+        assert!(drain_output_value(10_000, 9_800, 330).is_err());
+        assert!(drain_output_value(10_000, 10_001, 0).is_err());
+        assert!(drain_output_value(10_000, 10_000, 0).is_err());
+    }
+
+    #[test]
+    fn test_create_tx_with_fee_unresolved_deadline() {
+        let result = create_tx_with_fee(
+            Fee::Deadline {
+                blocks_remaining: 6,
+            },
+            |fee| Ok(StubTx { fee }),
+            |_| 42,
+        );
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_create_tx_with_fee_absolute() {
         let fee = 21;
@@ -0,0 +1,114 @@
+//! BIP-329 wallet labels for swap transactions.
+//!
+//! Wallets can export these next to their own labels, so swap transactions show up with a
+//! meaningful description after importing the wallet into Sparrow or other BIP-329 aware tools.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::swaps::boltz::SwapType;
+use crate::util::swap_type_key;
+
+/// Type of the labelled reference, as defined by BIP-329.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelType {
+    Tx,
+    Addr,
+    Pubkey,
+    Input,
+    Output,
+    Xpub,
+}
+
+/// A single BIP-329 label record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    #[serde(rename = "type")]
+    pub label_type: LabelType,
+    #[serde(rename = "ref")]
+    pub reference: String,
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>,
+}
+
+/// Role of a transaction in a swap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapTxRole {
+    Lockup,
+    Claim,
+    Refund,
+}
+
+impl SwapTxRole {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SwapTxRole::Lockup => "lockup",
+            SwapTxRole::Claim => "claim",
+            SwapTxRole::Refund => "refund",
+        }
+    }
+}
+
+/// Label for a transaction of a swap, e.g. `Boltz reverse swap abc123 claim`.
+pub fn swap_tx_label(swap_id: &str, swap_type: SwapType, kind: SwapTxRole, txid: &str) -> Label {
+    Label {
+        label_type: LabelType::Tx,
+        reference: txid.to_string(),
+        label: format!(
+            "Boltz {} swap {} {}",
+            swap_type_key(swap_type),
+            swap_id,
+            kind.as_str()
+        ),
+        origin: None,
+        spendable: None,
+    }
+}
+
+/// Label for the swap address (lockup address of the HTLC).
+pub fn swap_addr_label(swap_id: &str, swap_type: SwapType, address: &str) -> Label {
+    Label {
+        label_type: LabelType::Addr,
+        reference: address.to_string(),
+        label: format!("Boltz {} swap {}", swap_type_key(swap_type), swap_id),
+        origin: None,
+        spendable: None,
+    }
+}
+
+/// Serialize labels in the BIP-329 JSON Lines export format.
+pub fn to_jsonl(labels: &[Label]) -> Result<String, Error> {
+    let mut out = String::new();
+    for label in labels {
+        out.push_str(&serde_json::to_string(label)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_jsonl() {
+        let labels = vec![
+            swap_tx_label(
+                "abc123",
+                SwapType::ReverseSubmarine,
+                SwapTxRole::Claim,
+                "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd",
+            ),
+            swap_addr_label("abc123", SwapType::Chain, "bc1qexample"),
+        ];
+        assert_eq!(
+            to_jsonl(&labels).unwrap(),
+            "{\"type\":\"tx\",\"ref\":\"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd\",\"label\":\"Boltz reverse swap abc123 claim\"}\n\
+             {\"type\":\"addr\",\"ref\":\"bc1qexample\",\"label\":\"Boltz chain swap abc123\"}\n"
+        );
+    }
+}
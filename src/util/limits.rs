@@ -0,0 +1,263 @@
+//! Optional amount and count ceilings for swaps.
+//!
+//! Apps can enforce per-swap and per-day limits by checking every new swap against [`Limits`]
+//! before creating it. Past swaps are kept in a [`LimitsStore`], so limits survive restarts when
+//! backed by the app's own persistence.
+
+use std::sync::Mutex;
+
+use crate::error::Error;
+use crate::util::unix_now;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Configured ceilings. `None` means unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct LimitsConfig {
+    /// Maximum amount of a single swap, in sats.
+    pub max_swap_amount: Option<u64>,
+    /// Maximum total amount of all swaps in the last 24 hours, in sats.
+    pub max_daily_amount: Option<u64>,
+    /// Maximum number of swaps in the last 24 hours.
+    pub max_daily_swaps: Option<u32>,
+}
+
+/// Persistence of swaps counted against the limits.
+pub trait LimitsStore {
+    /// Record a swap of `amount` sats created at `timestamp` (unix seconds), if `check` accepts
+    /// the total amount and number of swaps recorded at or after `since`. Errors of `check` are
+    /// returned as is.
+    ///
+    /// Reading the usage and recording must happen under one lock or database transaction, so
+    /// concurrent swaps can't all pass the check before any of them is recorded.
+    fn record_checked(
+        &self,
+        swap_id: &str,
+        amount: u64,
+        timestamp: u64,
+        since: u64,
+        check: &dyn Fn(u64, u32) -> Result<(), Error>,
+    ) -> Result<(), Error>;
+    /// Total amount and number of swaps recorded at or after `since` (unix seconds).
+    fn usage_since(&self, since: u64) -> Result<(u64, u32), Error>;
+}
+
+impl<S: LimitsStore + ?Sized> LimitsStore for Box<S> {
+    fn record_checked(
+        &self,
+        swap_id: &str,
+        amount: u64,
+        timestamp: u64,
+        since: u64,
+        check: &dyn Fn(u64, u32) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        (**self).record_checked(swap_id, amount, timestamp, since, check)
+    }
+
+    fn usage_since(&self, since: u64) -> Result<(u64, u32), Error> {
+        (**self).usage_since(since)
+    }
+}
+
+/// Non persistent store, for apps that don't need limits to survive restarts.
+#[derive(Debug, Default)]
+pub struct MemoryLimitsStore {
+    swaps: Mutex<Vec<(String, u64, u64)>>,
+}
+
+impl MemoryLimitsStore {
+    fn usage(swaps: &[(String, u64, u64)], since: u64) -> (u64, u32) {
+        swaps
+            .iter()
+            .filter(|(_, _, timestamp)| *timestamp >= since)
+            .fold((0, 0), |(amount, count), (_, a, _)| {
+                (amount.saturating_add(*a), count.saturating_add(1))
+            })
+    }
+}
+
+impl LimitsStore for MemoryLimitsStore {
+    fn record_checked(
+        &self,
+        swap_id: &str,
+        amount: u64,
+        timestamp: u64,
+        since: u64,
+        check: &dyn Fn(u64, u32) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut swaps = self
+            .swaps
+            .lock()
+            .map_err(|_| Error::Generic("Limits store lock poisoned".to_string()))?;
+        let (daily_amount, daily_swaps) = Self::usage(&swaps, since);
+        check(daily_amount, daily_swaps)?;
+        swaps.push((swap_id.to_string(), amount, timestamp));
+        Ok(())
+    }
+
+    fn usage_since(&self, since: u64) -> Result<(u64, u32), Error> {
+        let swaps = self
+            .swaps
+            .lock()
+            .map_err(|_| Error::Generic("Limits store lock poisoned".to_string()))?;
+        Ok(Self::usage(&swaps, since))
+    }
+}
+
+/// Enforces a [`LimitsConfig`] using the swaps recorded in a [`LimitsStore`].
+pub struct Limits<S: LimitsStore> {
+    config: LimitsConfig,
+    store: S,
+}
+
+impl<S: LimitsStore> Limits<S> {
+    pub fn new(config: LimitsConfig, store: S) -> Self {
+        Limits { config, store }
+    }
+
+    pub fn config(&self) -> &LimitsConfig {
+        &self.config
+    }
+
+    /// Check whether a new swap of `amount` sats at `now` (unix seconds) is within limits.
+    pub fn check_at(&self, amount: u64, now: u64) -> Result<(), Error> {
+        self.check_swap_amount(amount)?;
+        let (daily_amount, daily_swaps) = self.store.usage_since(now.saturating_sub(DAY_SECS))?;
+        self.check_daily(amount, daily_amount, daily_swaps)
+    }
+
+    /// Check limits and record the swap if it is within them. Concurrent calls can't together
+    /// exceed the daily limits, see [`LimitsStore::record_checked`].
+    pub fn check_and_record_at(&self, swap_id: &str, amount: u64, now: u64) -> Result<(), Error> {
+        self.check_swap_amount(amount)?;
+        self.store.record_checked(
+            swap_id,
+            amount,
+            now,
+            now.saturating_sub(DAY_SECS),
+            &|daily_amount, daily_swaps| self.check_daily(amount, daily_amount, daily_swaps),
+        )
+    }
+
+    fn check_swap_amount(&self, amount: u64) -> Result<(), Error> {
+        if let Some(max) = self.config.max_swap_amount {
+            if amount > max {
+                return Err(Error::LimitExceeded(format!(
+                    "Swap amount {} above per swap limit {}",
+                    amount, max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_daily(&self, amount: u64, daily_amount: u64, daily_swaps: u32) -> Result<(), Error> {
+        if let Some(max) = self.config.max_daily_amount {
+            if daily_amount.saturating_add(amount) > max {
+                return Err(Error::LimitExceeded(format!(
+                    "Swap amount {} would exceed daily limit {}, {} already used",
+                    amount, max, daily_amount
+                )));
+            }
+        }
+
+        if let Some(max) = self.config.max_daily_swaps {
+            if daily_swaps.saturating_add(1) > max {
+                return Err(Error::LimitExceeded(format!(
+                    "Daily swap count limit {} reached",
+                    max
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check whether a new swap of `amount` sats is within limits right now.
+    pub fn check(&self, amount: u64) -> Result<(), Error> {
+        self.check_at(amount, unix_now()?)
+    }
+
+    /// Check limits and record the swap right now, if it is within them.
+    pub fn check_and_record(&self, swap_id: &str, amount: u64) -> Result<(), Error> {
+        self.check_and_record_at(swap_id, amount, unix_now()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits::new(
+            LimitsConfig {
+                max_swap_amount: Some(100_000),
+                max_daily_amount: Some(150_000),
+                max_daily_swaps: Some(3),
+            },
+            MemoryLimitsStore::default(),
+        );
+        let now = 1_700_000_000;
+
+        assert!(matches!(
+            limits.check_at(100_001, now),
+            Err(Error::LimitExceeded(_))
+        ));
+
+        limits.check_and_record_at("a", 100_000, now).unwrap();
+        assert!(limits.check_at(50_001, now).is_err());
+        limits.check_and_record_at("b", 25_000, now).unwrap();
+        limits.check_and_record_at("c", 25_000, now).unwrap();
+        assert!(limits.check_at(0, now).is_err());
+
+        // A day later, the window is free again
+        limits
+            .check_and_record_at("d", 100_000, now + DAY_SECS + 1)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_limits_overflow() {
+        let limits = Limits::new(
+            LimitsConfig {
+                max_daily_amount: Some(u64::MAX),
+                ..Default::default()
+            },
+            MemoryLimitsStore::default(),
+        );
+        let now = 1_700_000_000;
+        limits.check_and_record_at("a", u64::MAX, now).unwrap();
+        assert!(matches!(
+            limits.check_and_record_at("b", 1, now),
+            Err(Error::LimitExceeded(_))
+        ));
+        assert_eq!(limits.store.usage_since(0).unwrap(), (u64::MAX, 1));
+    }
+
+    #[test]
+    fn test_limits_concurrent() {
+        let limits = Limits::new(
+            LimitsConfig {
+                max_daily_swaps: Some(5),
+                ..Default::default()
+            },
+            MemoryLimitsStore::default(),
+        );
+        let now = 1_700_000_000;
+        let accepted = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..20)
+                .map(|i| {
+                    let limits = &limits;
+                    scope.spawn(move || limits.check_and_record_at(&i.to_string(), 1_000, now))
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .filter(Result::is_ok)
+                .count()
+        });
+        assert_eq!(accepted, 5);
+    }
+}
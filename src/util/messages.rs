@@ -0,0 +1,68 @@
+//! Stable message keys for user facing strings.
+//!
+//! The crate does not ship translations. Instead every swap status and error kind maps to a
+//! stable key, so wallets can keep their localization files in sync with crate releases.
+
+use crate::error::{Error, ERROR_NAMES};
+use crate::swaps::boltz::SwapType;
+use crate::swaps::status::known_statuses;
+use crate::util::{swap_type_key, SWAP_TYPES};
+
+fn error_key(name: &str) -> String {
+    format!("error.{}", name.to_lowercase().replace('-', "_"))
+}
+
+/// Message key of a swap status, e.g. `status.submarine.invoice.set`.
+/// Returns None for statuses unknown to this version of the crate.
+pub fn status_message_key(swap_type: SwapType, status: &str) -> Option<String> {
+    known_statuses(swap_type)
+        .contains(&status)
+        .then(|| format!("status.{}.{}", swap_type_key(swap_type), status))
+}
+
+/// Message key of an error kind, e.g. `error.electrum`.
+pub fn error_message_key(error: &Error) -> String {
+    error_key(&error.name())
+}
+
+/// All message keys, statuses first then errors.
+pub fn all_message_keys() -> Vec<String> {
+    SWAP_TYPES
+        .iter()
+        .flat_map(|swap_type| {
+            known_statuses(*swap_type)
+                .iter()
+                .map(move |status| format!("status.{}.{}", swap_type_key(*swap_type), status))
+        })
+        .chain(ERROR_NAMES.iter().map(|name| error_key(name)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_keys() {
+        assert_eq!(
+            status_message_key(SwapType::Submarine, "transaction.claim.pending").unwrap(),
+            "status.submarine.transaction.claim.pending"
+        );
+        assert!(
+            status_message_key(SwapType::ReverseSubmarine, "transaction.claim.pending").is_none()
+        );
+        assert_eq!(
+            error_message_key(&Error::Protocol("test".to_string())),
+            "error.protocol"
+        );
+
+        let keys = all_message_keys();
+        assert!(keys.contains(&"status.chain.transaction.server.mempool".to_string()));
+        assert!(keys.contains(&"error.generic".to_string()));
+        assert!(keys.contains(&"error.elements_sighash".to_string()));
+        let mut deduped = keys.clone();
+        deduped.sort();
+        deduped.dedup();
+        assert_eq!(deduped.len(), keys.len());
+    }
+}
@@ -0,0 +1,231 @@
+//! Prometheus exporter for swap metrics.
+//!
+//! Implement [`Metrics`] on top of the app's swap storage, then either render the text
+//! exposition format with [`encode_prometheus`] from an existing http server, or run the
+//! minimal blocking [`serve`] loop on a dedicated thread.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::swaps::boltz::SwapType;
+use crate::swaps::history::LatencyPhase;
+use crate::util::{swap_type_key, SWAP_TYPES};
+
+/// Upper bounds of the refund deadline histogram buckets, in blocks.
+pub const REFUND_DEADLINE_BUCKETS: &[u32] = &[6, 36, 144, 432, 1008, 2016];
+/// Upper bounds of the swap phase latency histogram buckets, in seconds.
+pub const LATENCY_BUCKETS: &[u64] = &[10, 60, 300, 900, 1800, 3600, 7200, 21600, 86400];
+/// How long [`serve`] waits for a client to send its request line before dropping it.
+pub const METRICS_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Source of the exported swap metrics.
+pub trait Metrics {
+    /// Number of swaps per swap type and Boltz status.
+    fn swap_status_counts(&self) -> Vec<(SwapType, String, u64)>;
+    /// Blocks left until the refund timelock expires, for each swap with funds locked up.
+    fn refund_deadlines(&self) -> Vec<u32>;
+    /// Seconds spent per swap and phase, e.g. from [`StatusHistory::latencies`] of recent swaps.
+    ///
+    /// [`StatusHistory::latencies`]: crate::swaps::history::StatusHistory::latencies
+    fn swap_latencies(&self) -> Vec<(SwapType, LatencyPhase, u64)> {
+        vec![]
+    }
+}
+
+/// Render the metrics in the Prometheus text exposition format.
+pub fn encode_prometheus(metrics: &dyn Metrics) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP boltz_swaps Number of swaps per type and status.\n");
+    out.push_str("# TYPE boltz_swaps gauge\n");
+    for (swap_type, status, count) in metrics.swap_status_counts() {
+        out.push_str(&format!(
+            "boltz_swaps{{type=\"{}\",status=\"{}\"}} {}\n",
+            swap_type_key(swap_type),
+            escape_label_value(&status),
+            count
+        ));
+    }
+
+    let deadlines = metrics.refund_deadlines();
+    out.push_str(
+        "# HELP boltz_refund_deadline_blocks Blocks left until the refund timelock of locked swaps.\n",
+    );
+    out.push_str("# TYPE boltz_refund_deadline_blocks histogram\n");
+    for bound in REFUND_DEADLINE_BUCKETS {
+        let count = deadlines.iter().filter(|d| **d <= *bound).count();
+        out.push_str(&format!(
+            "boltz_refund_deadline_blocks_bucket{{le=\"{}\"}} {}\n",
+            bound, count
+        ));
+    }
+    out.push_str(&format!(
+        "boltz_refund_deadline_blocks_bucket{{le=\"+Inf\"}} {}\n",
+        deadlines.len()
+    ));
+    out.push_str(&format!(
+        "boltz_refund_deadline_blocks_sum {}\n",
+        deadlines.iter().map(|d| *d as u64).sum::<u64>()
+    ));
+    out.push_str(&format!(
+        "boltz_refund_deadline_blocks_count {}\n",
+        deadlines.len()
+    ));
+
+    let latencies = metrics.swap_latencies();
+    out.push_str("# HELP boltz_swap_phase_seconds Time spent per swap phase.\n");
+    out.push_str("# TYPE boltz_swap_phase_seconds histogram\n");
+    for swap_type in SWAP_TYPES {
+        for phase in LatencyPhase::ALL {
+            let values: Vec<u64> = latencies
+                .iter()
+                .filter(|(t, p, _)| *t == swap_type && *p == phase)
+                .map(|(_, _, secs)| *secs)
+                .collect();
+            if values.is_empty() {
+                continue;
+            }
+            let labels = format!(
+                "type=\"{}\",phase=\"{}\"",
+                swap_type_key(swap_type),
+                phase.label()
+            );
+            for bound in LATENCY_BUCKETS {
+                let count = values.iter().filter(|v| **v <= *bound).count();
+                out.push_str(&format!(
+                    "boltz_swap_phase_seconds_bucket{{{},le=\"{}\"}} {}\n",
+                    labels, bound, count
+                ));
+            }
+            out.push_str(&format!(
+                "boltz_swap_phase_seconds_bucket{{{},le=\"+Inf\"}} {}\n",
+                labels,
+                values.len()
+            ));
+            out.push_str(&format!(
+                "boltz_swap_phase_seconds_sum{{{}}} {}\n",
+                labels,
+                values.iter().sum::<u64>()
+            ));
+            out.push_str(&format!(
+                "boltz_swap_phase_seconds_count{{{}}} {}\n",
+                labels,
+                values.len()
+            ));
+        }
+    }
+
+    out
+}
+
+/// Escape a label value of the text exposition format. Statuses come from Boltz, so they may
+/// contain anything.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serve `GET /metrics` on the listener. Blocks forever, handling one request at a time.
+/// Clients that don't send a request line within [`METRICS_READ_TIMEOUT`] are dropped.
+pub fn serve(listener: TcpListener, metrics: Arc<dyn Metrics + Send + Sync>) -> Result<(), Error> {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                log::warn!("Metrics connection failed: {}", e);
+                continue;
+            }
+        };
+
+        if let Err(e) = stream.set_read_timeout(Some(METRICS_READ_TIMEOUT)) {
+            log::warn!("Failed to set metrics read timeout: {}", e);
+            continue;
+        }
+        let mut request_line = String::new();
+        if let Err(e) = BufReader::new(&stream).read_line(&mut request_line) {
+            log::warn!("Failed to read metrics request: {}", e);
+            continue;
+        }
+
+        let response = if request_line.starts_with("GET /metrics") {
+            let body = encode_prometheus(metrics.as_ref());
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        };
+
+        if let Err(e) = stream.write_all(response.as_bytes()) {
+            log::warn!("Failed to write metrics response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StubMetrics;
+
+    impl Metrics for StubMetrics {
+        fn swap_status_counts(&self) -> Vec<(SwapType, String, u64)> {
+            vec![
+                (SwapType::Submarine, "invoice.set".to_string(), 2),
+                (SwapType::Chain, "transaction.mempool".to_string(), 1),
+            ]
+        }
+
+        fn refund_deadlines(&self) -> Vec<u32> {
+            vec![5, 100, 5000]
+        }
+
+        fn swap_latencies(&self) -> Vec<(SwapType, LatencyPhase, u64)> {
+            vec![
+                (
+                    SwapType::ReverseSubmarine,
+                    LatencyPhase::LockupConfirmed,
+                    600,
+                ),
+                (
+                    SwapType::ReverseSubmarine,
+                    LatencyPhase::LockupConfirmed,
+                    1200,
+                ),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_encode_prometheus() {
+        let out = encode_prometheus(&StubMetrics);
+        assert!(out.contains("boltz_swaps{type=\"submarine\",status=\"invoice.set\"} 2\n"));
+        assert!(out.contains("boltz_swaps{type=\"chain\",status=\"transaction.mempool\"} 1\n"));
+        assert!(out.contains("boltz_refund_deadline_blocks_bucket{le=\"6\"} 1\n"));
+        assert!(out.contains("boltz_refund_deadline_blocks_bucket{le=\"144\"} 2\n"));
+        assert!(out.contains("boltz_refund_deadline_blocks_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("boltz_refund_deadline_blocks_sum 5105\n"));
+        assert!(out.contains("boltz_refund_deadline_blocks_count 3\n"));
+        assert!(out.contains(
+            "boltz_swap_phase_seconds_bucket{type=\"reverse\",phase=\"lockup_confirmed\",le=\"900\"} 1\n"
+        ));
+        assert!(out.contains(
+            "boltz_swap_phase_seconds_sum{type=\"reverse\",phase=\"lockup_confirmed\"} 1800\n"
+        ));
+        assert!(!out.contains("phase=\"lockup_seen\""));
+    }
+
+    #[test]
+    fn test_escape_label_value() {
+        assert_eq!(escape_label_value("invoice.set"), "invoice.set");
+        assert_eq!(escape_label_value("a\\b\"c\nd"), "a\\\\b\\\"c\\nd");
+    }
+}
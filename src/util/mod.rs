@@ -1,20 +1,63 @@
-use std::{env, str::FromStr, sync::Once};
+use std::{
+    env,
+    str::FromStr,
+    sync::Once,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use bitcoin::amount;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
 use electrum_client::ElectrumApi;
 use elements::{encode::Decodable, hex::ToHex};
 use lightning_invoice::{Bolt11Invoice, RouteHintHop};
 
-use crate::{error::Error, network::electrum::ElectrumConfig};
+use crate::{error::Error, network::electrum::ElectrumConfig, swaps::boltz::SwapType};
 
+pub mod backoff;
+pub mod capabilities;
+#[cfg(feature = "consensus-verify")]
+pub mod consensus;
+pub mod descriptor;
 pub mod ec;
+pub mod entropy;
 pub mod fees;
+pub mod labels;
+pub mod limits;
 #[cfg(feature = "lnurl")]
 pub mod lnurl;
+pub mod messages;
+#[cfg(feature = "service")]
+pub mod metrics;
+pub mod screening;
 pub mod secrets;
+pub mod standardness;
+
+/// All swap types, e.g. to export a value per type.
+pub(crate) const SWAP_TYPES: [SwapType; 3] = [
+    SwapType::Submarine,
+    SwapType::ReverseSubmarine,
+    SwapType::Chain,
+];
+
+/// Short lowercase name of a swap type, as used in metric labels, message keys and wallet labels.
+pub(crate) fn swap_type_key(swap_type: SwapType) -> &'static str {
+    match swap_type {
+        SwapType::Submarine => "submarine",
+        SwapType::ReverseSubmarine => "reverse",
+        SwapType::Chain => "chain",
+    }
+}
+
+/// Current unix time, in seconds.
+pub(crate) fn unix_now() -> Result<u64, Error> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| Error::Generic(e.to_string()))?
+        .as_secs())
+}
 
 pub fn liquid_genesis_hash(electrum_config: &ElectrumConfig) -> Result<elements::BlockHash, Error> {
-    let electrum = electrum_config.build_client()?;
+    let electrum = electrum_config.client()?;
     // println!("ELECTRUM NETWORK: {:?}", electrum_config.network());
 
     let response = electrum.block_header_raw(0)?;
@@ -27,6 +70,49 @@ pub fn liquid_genesis_hash(electrum_config: &ElectrumConfig) -> Result<elements:
     ))
 }
 
+/// Stable hash of the parameters of a swap script.
+/// Only the fields committed to by the taproot tree are included, so equal scripts always
+/// get equal fingerprints, regardless of how they were built or serialized.
+pub(crate) fn script_fingerprint(
+    swap_type: SwapType,
+    hashlock: &[u8],
+    receiver_pubkey: &bitcoin::PublicKey,
+    sender_pubkey: &bitcoin::PublicKey,
+    locktime: u32,
+    relative_timelock: Option<u32>,
+) -> sha256::Hash {
+    let mut engine = sha256::Hash::engine();
+    engine.input(swap_type_key(swap_type).as_bytes());
+    engine.input(hashlock);
+    engine.input(&receiver_pubkey.inner.x_only_public_key().0.serialize());
+    engine.input(&sender_pubkey.inner.x_only_public_key().0.serialize());
+    engine.input(&locktime.to_le_bytes());
+    // Only hashed when set, so absolute timelock scripts keep their fingerprints
+    if let Some(sequence) = relative_timelock {
+        engine.input(b"csv");
+        engine.input(&sequence.to_le_bytes());
+    }
+    sha256::Hash::from_engine(engine)
+}
+
+/// Random scripts for fuzzing script parsers, half of them starting like a taproot output
+/// (`OP_1 OP_PUSHBYTES_32`) with a random key and length.
+#[cfg(test)]
+pub(crate) fn fuzz_lockup_scripts(iterations: usize, mut check: impl FnMut(Vec<u8>)) {
+    use bitcoin::key::rand::{thread_rng, Rng};
+
+    let mut rng = thread_rng();
+    for _ in 0..iterations {
+        let len = rng.gen_range(0..80);
+        let mut bytes: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+        if bytes.len() >= 2 && rng.gen_bool(0.5) {
+            bytes[0] = 0x51;
+            bytes[1] = 0x20;
+        }
+        check(bytes);
+    }
+}
+
 /// Setup function that will only run once, even if called multiple times.
 pub fn setup_logger() {
     Once::new().call_once(|| {
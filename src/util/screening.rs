@@ -0,0 +1,85 @@
+//! Address screening hook.
+//!
+//! Integrators with compliance requirements can register an [`AddressScreen`] with
+//! [`set_address_screen`]. It is called with every destination and refund address before a
+//! claim or refund transaction is constructed, and can reject it. Without a registered screen,
+//! all addresses are accepted.
+
+use std::sync::{Arc, RwLock};
+
+use crate::error::Error;
+
+/// What a screened address is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressPurpose {
+    /// Destination of a claim.
+    Claim,
+    /// Destination of a refund.
+    Refund,
+}
+
+/// Compliance check of addresses funds are sent to.
+pub trait AddressScreen: Send + Sync {
+    /// Return an error to prevent sending funds to `address`.
+    /// [`Error::AddressRejected`] is the expected error for rejected addresses.
+    fn check(&self, address: &str, purpose: AddressPurpose) -> Result<(), Error>;
+}
+
+static ADDRESS_SCREEN: RwLock<Option<Arc<dyn AddressScreen>>> = RwLock::new(None);
+
+/// Register the screen used for all transactions constructed afterwards, replacing any previous one.
+pub fn set_address_screen(screen: Arc<dyn AddressScreen>) {
+    if let Ok(mut current) = ADDRESS_SCREEN.write() {
+        *current = Some(screen);
+    }
+}
+
+/// Remove the registered screen.
+pub fn clear_address_screen() {
+    if let Ok(mut current) = ADDRESS_SCREEN.write() {
+        *current = None;
+    }
+}
+
+/// Check an address with the registered screen, if any.
+pub(crate) fn screen_address(address: &str, purpose: AddressPurpose) -> Result<(), Error> {
+    let screen = ADDRESS_SCREEN
+        .read()
+        .map_err(|_| Error::Generic("Address screen lock poisoned".to_string()))?
+        .clone();
+    match screen {
+        Some(screen) => screen.check(address, purpose),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Blacklist(Vec<String>);
+
+    impl AddressScreen for Blacklist {
+        fn check(&self, address: &str, _purpose: AddressPurpose) -> Result<(), Error> {
+            match self.0.iter().any(|a| a == address) {
+                true => Err(Error::AddressRejected(address.to_string())),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_address_screen() {
+        assert!(screen_address("bad", AddressPurpose::Claim).is_ok());
+
+        set_address_screen(Arc::new(Blacklist(vec!["bad".to_string()])));
+        assert!(matches!(
+            screen_address("bad", AddressPurpose::Refund),
+            Err(Error::AddressRejected(_))
+        ));
+        assert!(screen_address("good", AddressPurpose::Claim).is_ok());
+
+        clear_address_screen();
+        assert!(screen_address("bad", AddressPurpose::Claim).is_ok());
+    }
+}
@@ -8,7 +8,6 @@ use std::str::FromStr;
 use bip39::Mnemonic;
 use bitcoin::bip32::{DerivationPath, Fingerprint, Xpriv};
 use bitcoin::hex::{DisplayHex, FromHex};
-use bitcoin::key::rand::{rngs::OsRng, RngCore};
 use bitcoin::secp256k1::hashes::{hash160, ripemd160, sha256, Hash};
 use bitcoin::secp256k1::{Keypair, Secp256k1};
 use elements::secp256k1_zkp::{Keypair as ZKKeyPair, Secp256k1 as ZKSecp256k1};
@@ -19,6 +18,8 @@ use serde_json;
 use crate::error::Error;
 use crate::network::Chain;
 
+use super::entropy::{random_32b, EntropyPurpose};
+
 const SUBMARINE_SWAP_ACCOUNT: u32 = 21;
 const REVERSE_SWAP_ACCOUNT: u32 = 42;
 const CHAIN_SWAP_ACCOUNT: u32 = 84;
@@ -184,13 +185,6 @@ impl Display for DerivationPurpose {
     }
 }
 
-/// Internally used rng to generate secure 32 byte preimages
-fn rng_32b() -> [u8; 32] {
-    let mut bytes = [0u8; 32];
-    OsRng.fill_bytes(&mut bytes);
-    bytes
-}
-
 /// Helper to work with Preimage & Hashes required for swap scripts.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Preimage {
@@ -216,16 +210,23 @@ impl Default for Preimage {
 
 impl Preimage {
     /// Creates a new random preimage
+    /// Panics if the registered [`EntropySource`](super::entropy::EntropySource) fails,
+    /// use [`Preimage::try_new`] to handle that.
     pub fn new() -> Preimage {
-        let preimage = rng_32b();
+        Self::try_new().expect("Entropy source failed to generate a preimage")
+    }
+
+    /// Creates a new random preimage, mixing in the registered entropy source, if any.
+    pub fn try_new() -> Result<Preimage, Error> {
+        let preimage = random_32b(EntropyPurpose::Preimage)?;
         let sha256 = sha256::Hash::hash(&preimage);
         let hash160 = hash160::Hash::hash(&preimage);
 
-        Preimage {
+        Ok(Preimage {
             sha256,
             hash160,
             bytes: Some(preimage),
-        }
+        })
     }
 
     /// Creates a struct from a preimage vector.
@@ -277,6 +278,42 @@ impl Preimage {
     }
 }
 
+/// Serialized form of [`Preimage`]: the hex preimage if known, and its hex sha256 hash.
+#[derive(Serialize, Deserialize)]
+struct StoredPreimage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    preimage: Option<String>,
+    sha256: String,
+}
+
+impl Serialize for Preimage {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredPreimage {
+            preimage: self.to_string(),
+            sha256: self.sha256.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Preimage {
+    /// Hashes are recomputed from the preimage when present, and checked against `sha256`.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredPreimage::deserialize(deserializer)?;
+        let preimage = match stored.preimage {
+            Some(preimage) => Preimage::from_str(&preimage),
+            None => Preimage::from_sha256_str(&stored.sha256),
+        }
+        .map_err(|e| serde::de::Error::custom(e.message()))?;
+        if preimage.sha256.to_string() != stored.sha256 {
+            return Err(serde::de::Error::custom(
+                "Preimage does not match its sha256",
+            ));
+        }
+        Ok(preimage)
+    }
+}
+
 /// Boltz standard JSON refund swap file. Can be used to create a file that can be uploaded to boltz.exchange
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RefundSwapFile {
@@ -309,8 +346,29 @@ impl RefundSwapFile {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::entropy::SecureRng;
+    use bitcoin::key::rand::RngCore;
     use elements::pset::serialize::Serialize;
 
+    #[test]
+    fn test_preimage_serde() {
+        let preimage = Preimage::new();
+        let json = serde_json::to_string(&preimage).unwrap();
+        assert_eq!(serde_json::from_str::<Preimage>(&json).unwrap(), preimage);
+
+        let hash_only = Preimage::from_sha256_str(&preimage.sha256.to_string()).unwrap();
+        let json = serde_json::to_string(&hash_only).unwrap();
+        assert!(!json.contains("\"preimage\""));
+        assert_eq!(serde_json::from_str::<Preimage>(&json).unwrap(), hash_only);
+
+        let other = Preimage::new();
+        let mismatched = serde_json::json!({
+            "preimage": preimage.to_string().unwrap(),
+            "sha256": other.sha256.to_string(),
+        });
+        assert!(serde_json::from_value::<Preimage>(mismatched).is_err());
+    }
+
     #[test]
     fn test_derivation() {
         let mnemonic: &str = "bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon bacon";
@@ -353,7 +411,7 @@ mod tests {
     #[test]
     fn test_preimage_from_vec_invalid_length() {
         let mut bytes = [0u8; 33];
-        OsRng.fill_bytes(&mut bytes);
+        SecureRng.fill_bytes(&mut bytes);
         assert_eq!(
             Preimage::from_vec(Vec::from(bytes))
                 .err()
@@ -0,0 +1,274 @@
+//! Standardness checks of produced transactions.
+//!
+//! Nodes reject non-standard transactions with opaque errors, and not all of them are caught by
+//! script verification. Claim and refund transactions are checked against the default
+//! [`StandardnessPolicy`] when signed. Apps relaying through nodes with other policies can run
+//! their own policy on signed transactions before broadcast.
+
+use std::fmt::{Display, Formatter};
+
+use crate::error::Error;
+
+/// Tag of the optional annex, the last witness element of a taproot input.
+const ANNEX_TAG: u8 = 0x50;
+
+/// Limits checked by [`StandardnessPolicy::check_bitcoin_tx`] and
+/// [`StandardnessPolicy::check_liquid_tx`]. Defaults match Bitcoin Core relay policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandardnessPolicy {
+    /// Maximum transaction weight, in weight units.
+    pub max_tx_weight: usize,
+    /// Minimum size of the transaction without witness, in bytes.
+    pub min_non_witness_size: usize,
+    /// Maximum scriptSig size, in bytes.
+    pub max_script_sig_size: usize,
+    /// Maximum size of a tapscript stack element (excluding script and control block), in bytes.
+    pub max_tapscript_stack_item_size: usize,
+    /// Maximum size of an OP_RETURN output script, in bytes.
+    pub max_null_data_size: usize,
+    /// Whether taproot inputs may have an annex. Nodes currently don't relay them.
+    pub allow_annex: bool,
+}
+
+impl Default for StandardnessPolicy {
+    fn default() -> Self {
+        StandardnessPolicy {
+            max_tx_weight: 400_000,
+            min_non_witness_size: 65,
+            max_script_sig_size: 1650,
+            max_tapscript_stack_item_size: 80,
+            max_null_data_size: 83,
+            allow_annex: false,
+        }
+    }
+}
+
+/// A reason for a transaction to be non-standard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StandardnessViolation {
+    TxWeight(usize),
+    TxTooSmall(usize),
+    ScriptSigSize { input: usize, size: usize },
+    Annex { input: usize },
+    StackItemSize { input: usize, size: usize },
+    NullDataSize { output: usize, size: usize },
+    MultipleNullData,
+    Dust { output: usize, value: u64 },
+}
+
+impl Display for StandardnessViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TxWeight(weight) => write!(f, "Transaction weight {} is too large", weight),
+            Self::TxTooSmall(size) => write!(f, "Transaction size {} is too small", size),
+            Self::ScriptSigSize { input, size } => {
+                write!(f, "Input {} has a scriptSig of {} bytes", input, size)
+            }
+            Self::Annex { input } => write!(f, "Input {} has an annex", input),
+            Self::StackItemSize { input, size } => {
+                write!(f, "Input {} has a witness item of {} bytes", input, size)
+            }
+            Self::NullDataSize { output, size } => {
+                write!(
+                    f,
+                    "Output {} has a null data script of {} bytes",
+                    output, size
+                )
+            }
+            Self::MultipleNullData => write!(f, "More than one null data output"),
+            Self::Dust { output, value } => {
+                write!(f, "Output {} of {} sats is dust", output, value)
+            }
+        }
+    }
+}
+
+impl StandardnessPolicy {
+    /// All standardness violations of a Bitcoin transaction.
+    /// Taproot inputs are assumed, which is the case for all swap transactions.
+    pub fn bitcoin_violations(&self, tx: &bitcoin::Transaction) -> Vec<StandardnessViolation> {
+        let mut violations = vec![];
+
+        let weight = tx.weight().to_wu() as usize;
+        if weight > self.max_tx_weight {
+            violations.push(StandardnessViolation::TxWeight(weight));
+        }
+        let base_size = tx.base_size();
+        if base_size < self.min_non_witness_size {
+            violations.push(StandardnessViolation::TxTooSmall(base_size));
+        }
+
+        for (i, input) in tx.input.iter().enumerate() {
+            let script_sig_size = input.script_sig.len();
+            if script_sig_size > self.max_script_sig_size {
+                violations.push(StandardnessViolation::ScriptSigSize {
+                    input: i,
+                    size: script_sig_size,
+                });
+            }
+            let stack: Vec<&[u8]> = input.witness.iter().collect();
+            violations.extend(self.taproot_witness_violations(i, &stack));
+        }
+
+        let mut null_data_outputs = 0;
+        for (i, output) in tx.output.iter().enumerate() {
+            if output.script_pubkey.is_op_return() {
+                null_data_outputs += 1;
+                if output.script_pubkey.len() > self.max_null_data_size {
+                    violations.push(StandardnessViolation::NullDataSize {
+                        output: i,
+                        size: output.script_pubkey.len(),
+                    });
+                }
+            } else if output.value < output.script_pubkey.minimal_non_dust() {
+                violations.push(StandardnessViolation::Dust {
+                    output: i,
+                    value: output.value.to_sat(),
+                });
+            }
+        }
+        if null_data_outputs > 1 {
+            violations.push(StandardnessViolation::MultipleNullData);
+        }
+
+        violations
+    }
+
+    /// All standardness violations of a Liquid transaction.
+    /// Output values are blinded, so only the weight, witness and null data limits are checked.
+    pub fn liquid_violations(&self, tx: &elements::Transaction) -> Vec<StandardnessViolation> {
+        let mut violations = vec![];
+
+        let weight = tx.weight();
+        if weight > self.max_tx_weight {
+            violations.push(StandardnessViolation::TxWeight(weight));
+        }
+
+        for (i, input) in tx.input.iter().enumerate() {
+            let stack: Vec<&[u8]> = input
+                .witness
+                .script_witness
+                .iter()
+                .map(|item| item.as_slice())
+                .collect();
+            violations.extend(self.taproot_witness_violations(i, &stack));
+        }
+
+        for (i, output) in tx.output.iter().enumerate() {
+            if output.script_pubkey.is_op_return()
+                && output.script_pubkey.len() > self.max_null_data_size
+            {
+                violations.push(StandardnessViolation::NullDataSize {
+                    output: i,
+                    size: output.script_pubkey.len(),
+                });
+            }
+        }
+
+        violations
+    }
+
+    /// Fails with [`Error::NonStandard`] listing all violations, if any.
+    pub fn check_bitcoin_tx(&self, tx: &bitcoin::Transaction) -> Result<(), Error> {
+        to_result(self.bitcoin_violations(tx))
+    }
+
+    /// Fails with [`Error::NonStandard`] listing all violations, if any.
+    pub fn check_liquid_tx(&self, tx: &elements::Transaction) -> Result<(), Error> {
+        to_result(self.liquid_violations(tx))
+    }
+
+    fn taproot_witness_violations(
+        &self,
+        input: usize,
+        stack: &[&[u8]],
+    ) -> Vec<StandardnessViolation> {
+        let mut violations = vec![];
+        let mut stack = stack;
+        if stack.len() >= 2 && stack.last().and_then(|item| item.first()) == Some(&ANNEX_TAG) {
+            if !self.allow_annex {
+                violations.push(StandardnessViolation::Annex { input });
+            }
+            stack = &stack[..stack.len() - 1];
+        }
+        // Script path spend: the last two elements are the script and the control block
+        if stack.len() >= 2 {
+            for item in &stack[..stack.len() - 2] {
+                if item.len() > self.max_tapscript_stack_item_size {
+                    violations.push(StandardnessViolation::StackItemSize {
+                        input,
+                        size: item.len(),
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+fn to_result(violations: Vec<StandardnessViolation>) -> Result<(), Error> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(Error::NonStandard(
+        violations
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute::LockTime, transaction::Version, Amount, ScriptBuf, Transaction, TxIn, TxOut,
+        Witness,
+    };
+
+    #[test]
+    fn test_bitcoin_violations() {
+        let policy = StandardnessPolicy::default();
+        let p2tr = ScriptBuf::from_bytes([vec![0x51, 0x20], vec![1; 32]].concat());
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                witness: Witness::from_slice(&[
+                    vec![0; 64],
+                    vec![0; 32],
+                    vec![0x51],
+                    vec![0xc0; 33],
+                ]),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: p2tr.clone(),
+            }],
+        };
+        assert!(policy.check_bitcoin_tx(&tx).is_ok());
+
+        tx.input[0].witness = Witness::from_slice(&[vec![0; 64], vec![ANNEX_TAG, 1]]);
+        tx.output[0].value = Amount::from_sat(100);
+        assert_eq!(
+            policy.bitcoin_violations(&tx),
+            vec![
+                StandardnessViolation::Annex { input: 0 },
+                StandardnessViolation::Dust {
+                    output: 0,
+                    value: 100
+                }
+            ]
+        );
+
+        tx.input[0].witness =
+            Witness::from_slice(&[vec![0; 64], vec![0; 81], vec![0x51], vec![0xc0; 33]]);
+        tx.output[0].value = Amount::from_sat(10_000);
+        assert!(matches!(
+            policy.check_bitcoin_tx(&tx),
+            Err(Error::NonStandard(_))
+        ));
+    }
+}
@@ -143,33 +143,19 @@ fn bitcoin_v2_submarine() {
                         )
                         .expect("Funding UTXO not found");
 
-                        let claim_tx_response = boltz_api_v2
-                            .get_submarine_claim_tx_details(swap_id)
-                            .unwrap();
-
-                        log::debug!("Received claim tx details : {:?}", claim_tx_response);
-
-                        // Check that boltz have the correct preimage.
-                        // At this stage the client should verify that LN invoice has been paid.
-                        let preimage = Vec::from_hex(&claim_tx_response.preimage).unwrap();
-                        let preimage_hash = sha256::Hash::hash(&preimage);
-                        let invoice = Bolt11Invoice::from_str(&create_swap_req.invoice).unwrap();
-                        let invoice_payment_hash = invoice.payment_hash();
-                        assert!(invoice_payment_hash.to_string() == preimage_hash.to_string());
-                        log::info!("Correct Hash preimage received from Boltz.");
-
-                        // Compute and send Musig2 partial sig
-                        let (partial_sig, pub_nonce) = swap_tx
-                            .partial_sign(
+                        // Boltz has to prove the invoice was paid, by revealing the preimage.
+                        let preimage = swap_tx
+                            .cooperative_submarine_claim(
                                 &our_keys,
-                                &claim_tx_response.pub_nonce,
-                                &claim_tx_response.transaction_hash,
+                                &boltz_api_v2,
+                                swap_id,
+                                &create_swap_req.invoice,
                             )
                             .unwrap();
-                        boltz_api_v2
-                            .post_submarine_claim_tx_details(swap_id, pub_nonce, partial_sig)
-                            .unwrap();
-                        log::info!("Successfully Sent partial signature");
+                        log::info!(
+                            "Successfully Sent partial signature, preimage: {:?}",
+                            preimage.to_string()
+                        );
                     }
 
                     if update.status == "transaction.claimed" {
@@ -341,7 +327,13 @@ fn bitcoin_v2_reverse() {
                     if update.status == "transaction.mempool" {
                         log::info!("Boltz broadcasted funding tx");
 
-                        std::thread::sleep(Duration::from_secs(15));
+                        swap_script
+                            .wait_for_utxos(
+                                &ElectrumConfig::default_bitcoin(),
+                                Duration::from_secs(3),
+                                Duration::from_secs(60),
+                            )
+                            .unwrap();
 
                         let claim_tx = BtcSwapTx::new_claim(
                             swap_script.clone(),
@@ -493,7 +485,13 @@ fn bitcoin_v2_reverse_script_path() {
                     if update.status == "transaction.mempool" {
                         log::info!("Boltz broadcasted funding tx");
 
-                        std::thread::sleep(Duration::from_secs(15));
+                        swap_script
+                            .wait_for_utxos(
+                                &ElectrumConfig::default_bitcoin(),
+                                Duration::from_secs(3),
+                                Duration::from_secs(60),
+                            )
+                            .unwrap();
 
                         let claim_tx = BtcSwapTx::new_claim(
                             swap_script.clone(),
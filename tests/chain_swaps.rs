@@ -149,7 +149,13 @@ fn bitcoin_liquid_v2_chain() {
                     if update.status == "transaction.server.confirmed" {
                         log::info!("Server lockup tx is confirmed!");
 
-                        std::thread::sleep(Duration::from_secs(10));
+                        claim_script
+                            .wait_for_utxo(
+                                &ElectrumConfig::default_liquid(),
+                                Duration::from_secs(1),
+                                Duration::from_secs(60),
+                            )
+                            .unwrap();
                         log::info!("Claiming!");
 
                         let claim_tx = LBtcSwapTx::new_claim(
@@ -417,7 +423,13 @@ fn liquid_bitcoin_v2_chain() {
                     if update.status == "transaction.server.confirmed" {
                         log::info!("Server lockup tx is confirmed!");
 
-                        std::thread::sleep(Duration::from_secs(10));
+                        claim_script
+                            .wait_for_utxos(
+                                &ElectrumConfig::default_bitcoin(),
+                                Duration::from_secs(3),
+                                Duration::from_secs(60),
+                            )
+                            .unwrap();
                         log::info!("Claiming!");
 
                         let claim_tx = BtcSwapTx::new_claim(
@@ -6,8 +6,9 @@ use bitcoin::{Amount, OutPoint, TxOut};
 use bitcoind::bitcoincore_rpc::json::{AddressType, ScanTxOutRequest};
 use bitcoind::bitcoincore_rpc::RpcApi;
 use boltz_client::boltz::{SwapTxKind, SwapType};
-use boltz_client::fees::Fee;
+use boltz_client::fees::{Fee, TxLimits};
 use boltz_client::network::Chain;
+use boltz_client::swaps::privacy::PrivacyPolicy;
 use boltz_client::util::secrets::Preimage;
 use boltz_client::{BtcSwapScript, BtcSwapTx, LBtcSwapScript, LBtcSwapTx};
 use elements::Address;
@@ -51,6 +52,7 @@ fn prepare_btc_claim() -> (
             compressed: true,
             inner: sender_keypair.public_key(),
         },
+        relative_timelock: None,
     };
 
     // Send coin the swapscript address and confirm tx
@@ -95,6 +97,14 @@ fn prepare_btc_claim() -> (
         swap_script,
         output_address: refund_addrs,
         utxos: utxos.clone(),
+        signal_rbf: true,
+        memo: None,
+        limits: TxLimits::default(),
+        refund_outputs: vec![],
+        claim_splits: vec![],
+        privacy: PrivacyPolicy::default(),
+        network_config: None,
+        anchor: None,
     };
 
     (
@@ -224,6 +234,7 @@ fn prepare_btc_refund() -> (
             compressed: true,
             inner: sender_keypair.public_key(),
         },
+        relative_timelock: None,
     };
 
     // Send coin the swapscript address and confirm tx
@@ -266,6 +277,14 @@ fn prepare_btc_refund() -> (
         swap_script,
         output_address: refund_addrs,
         utxos: utxos.clone(),
+        signal_rbf: true,
+        memo: None,
+        limits: TxLimits::default(),
+        refund_outputs: vec![],
+        claim_splits: vec![],
+        privacy: PrivacyPolicy::default(),
+        network_config: None,
+        anchor: None,
     };
 
     (test_framework, scan_request, swap_tx, sender_keypair, utxos)
@@ -403,6 +422,7 @@ fn prepare_lbtc_claim() -> (
             inner: sender_keypair.public_key(),
         },
         blinding_key: blinding_keypair,
+        relative_timelock: None,
     };
 
     // Send coin the swapscript address and confirm tx
@@ -424,6 +444,9 @@ fn prepare_lbtc_claim() -> (
         funding_outpoint: utxo.0,
         funding_utxo: utxo.1.clone(),
         genesis_hash,
+        signal_rbf: false,
+        memo: None,
+        limits: TxLimits::default(),
     };
 
     (
@@ -553,6 +576,7 @@ fn prepare_lbtc_refund() -> (
             inner: sender_keypair.public_key(),
         },
         blinding_key: blinding_keypair,
+        relative_timelock: None,
     };
 
     // Send coin the swapscript address and confirm tx
@@ -574,6 +598,9 @@ fn prepare_lbtc_refund() -> (
         funding_outpoint: utxo.0,
         funding_utxo: utxo.1.clone(),
         genesis_hash,
+        signal_rbf: false,
+        memo: None,
+        limits: TxLimits::default(),
     };
 
     (